@@ -0,0 +1,166 @@
+//! `Cache-Control` parsing and freshness calculation, following [RFC 9111](https://www.rfc-editor.org/rfc/rfc9111).
+use std::time::{Duration, SystemTime};
+
+use salvo_core::http::header::{CACHE_CONTROL, EXPIRES, PRAGMA};
+use salvo_core::http::headers::{Date, Expires, HeaderMapExt};
+use salvo_core::http::{HeaderMap, StatusCode};
+
+/// `Cache-Control` directives understood by a shared cache.
+///
+/// Qualified forms like `private="set-cookie"` are treated as their unqualified form.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct CacheControl {
+    /// `no-store` directive.
+    pub no_store: bool,
+    /// `no-cache` directive, also set by `Pragma: no-cache` when `Cache-Control` is absent.
+    pub no_cache: bool,
+    /// `private` directive.
+    pub private: bool,
+    /// `public` directive.
+    pub public: bool,
+    /// `must-revalidate` or `proxy-revalidate` directive.
+    pub must_revalidate: bool,
+    /// `max-age` directive.
+    pub max_age: Option<Duration>,
+    /// `s-maxage` directive.
+    pub s_maxage: Option<Duration>,
+    /// `stale-while-revalidate` directive from [RFC 5861](https://www.rfc-editor.org/rfc/rfc5861).
+    pub stale_while_revalidate: Option<Duration>,
+    /// `stale-if-error` directive from [RFC 5861](https://www.rfc-editor.org/rfc/rfc5861).
+    pub stale_if_error: Option<Duration>,
+}
+impl CacheControl {
+    /// Parse directives from all `Cache-Control` headers, unknown directives are ignored.
+    pub fn from_headers(headers: &HeaderMap) -> Self {
+        let mut cc = Self::default();
+        let mut found = false;
+        let directives = headers
+            .get_all(CACHE_CONTROL)
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .flat_map(|value| value.split(','));
+        for directive in directives {
+            found = true;
+            let (name, value) = match directive.split_once('=') {
+                Some((name, value)) => (name.trim(), Some(value.trim().trim_matches('"'))),
+                None => (directive.trim(), None),
+            };
+            let seconds = || {
+                value
+                    .and_then(|v| v.parse::<u64>().ok())
+                    .map(Duration::from_secs)
+            };
+            match &*name.to_ascii_lowercase() {
+                "no-store" => cc.no_store = true,
+                "no-cache" => cc.no_cache = true,
+                "private" => cc.private = true,
+                "public" => cc.public = true,
+                "must-revalidate" | "proxy-revalidate" => cc.must_revalidate = true,
+                "max-age" => cc.max_age = seconds(),
+                "s-maxage" => cc.s_maxage = seconds(),
+                "stale-while-revalidate" => cc.stale_while_revalidate = seconds(),
+                "stale-if-error" => cc.stale_if_error = seconds(),
+                _ => {}
+            }
+        }
+        if !found {
+            cc.no_cache = headers
+                .get_all(PRAGMA)
+                .iter()
+                .filter_map(|value| value.to_str().ok())
+                .any(|value| {
+                    value
+                        .split(',')
+                        .any(|v| v.trim().eq_ignore_ascii_case("no-cache"))
+                });
+        }
+        cc
+    }
+
+    /// Explicit freshness lifetime of a response for a shared cache.
+    ///
+    /// Uses `s-maxage`, then `max-age`, then `Expires` minus `Date` (or `now` if `Date` is absent).
+    pub fn freshness(&self, headers: &HeaderMap, now: SystemTime) -> Option<Duration> {
+        if let Some(age) = self.s_maxage.or(self.max_age) {
+            return Some(age);
+        }
+        if let Some(expires) = headers.typed_get::<Expires>() {
+            let date = headers
+                .typed_get::<Date>()
+                .map(SystemTime::from)
+                .unwrap_or(now);
+            return Some(
+                SystemTime::from(expires)
+                    .duration_since(date)
+                    .unwrap_or_default(),
+            );
+        }
+        // An invalid `Expires` value means already expired.
+        headers.contains_key(EXPIRES).then_some(Duration::ZERO)
+    }
+}
+
+/// Check if the status code is cacheable by default, without explicit freshness information.
+///
+/// See [RFC 9110 section 15.1](https://www.rfc-editor.org/rfc/rfc9110#section-15.1).
+pub fn is_heuristically_cacheable(status: StatusCode) -> bool {
+    matches!(
+        status.as_u16(),
+        200 | 203 | 204 | 300 | 301 | 308 | 404 | 405 | 410 | 414 | 501
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use salvo_core::http::header::{HeaderValue, DATE};
+
+    use super::*;
+
+    #[test]
+    fn test_parse_cache_control() {
+        let mut headers = HeaderMap::new();
+        headers.append(
+            CACHE_CONTROL,
+            HeaderValue::from_static("public, max-age=60"),
+        );
+        headers.append(
+            CACHE_CONTROL,
+            HeaderValue::from_static(
+                "s-maxage=\"120\", stale-while-revalidate=30, stale-if-error=600, private=\"x\"",
+            ),
+        );
+        let cc = CacheControl::from_headers(&headers);
+        assert!(cc.public && cc.private && !cc.no_store);
+        assert_eq!(cc.max_age, Some(Duration::from_secs(60)));
+        assert_eq!(cc.stale_while_revalidate, Some(Duration::from_secs(30)));
+        assert_eq!(cc.stale_if_error, Some(Duration::from_secs(600)));
+        assert_eq!(
+            cc.freshness(&headers, SystemTime::now()),
+            Some(Duration::from_secs(120))
+        );
+
+        let mut headers = HeaderMap::new();
+        headers.insert(PRAGMA, HeaderValue::from_static("no-cache"));
+        headers.insert(
+            DATE,
+            HeaderValue::from_static("Sun, 06 Nov 1994 08:49:37 GMT"),
+        );
+        headers.insert(
+            EXPIRES,
+            HeaderValue::from_static("Sun, 06 Nov 1994 08:59:37 GMT"),
+        );
+        let cc = CacheControl::from_headers(&headers);
+        assert!(cc.no_cache);
+        assert_eq!(
+            cc.freshness(&headers, SystemTime::now()),
+            Some(Duration::from_secs(600))
+        );
+
+        headers.insert(EXPIRES, HeaderValue::from_static("0"));
+        assert_eq!(
+            cc.freshness(&headers, SystemTime::now()),
+            Some(Duration::ZERO)
+        );
+    }
+}
@@ -0,0 +1,434 @@
+//! File system store module.
+use std::borrow::Borrow;
+use std::collections::{BTreeMap, HashMap};
+use std::future::Future;
+use std::hash::Hash;
+use std::io::{ErrorKind, Result as IoResult};
+use std::path::{Path, PathBuf};
+use std::pin::Pin;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::task::{Context, Poll};
+use std::time::SystemTime;
+
+use tokio::fs::File;
+use tokio::io::{AsyncRead, AsyncWriteExt, ReadBuf};
+
+use super::{BodyChunks, CacheStore, CachedBody, CachedEntry};
+
+const BODIES_DIR: &str = "bodies";
+const BODY_EXT: &str = "body";
+const TEMP_EXT: &str = "tmp";
+
+/// A body file of a [`DiskStore`].
+///
+/// The file is removed when the last handle is dropped, so bodies which are still being sent are not removed
+/// when their entry is evicted.
+#[derive(Clone, Debug)]
+pub struct CachedFile {
+    inner: Arc<FileHandle>,
+}
+
+#[derive(Debug)]
+struct FileHandle {
+    path: PathBuf,
+    size: u64,
+}
+
+impl CachedFile {
+    /// Get the path of the file.
+    pub fn path(&self) -> &Path {
+        &self.inner.path
+    }
+
+    /// Get the size of the file in bytes.
+    pub fn size(&self) -> u64 {
+        self.inner.size
+    }
+
+    /// Open the file, the returned reader keeps the file until it is dropped.
+    pub(crate) fn open(&self) -> IoResult<CachedFileReader> {
+        let file = std::fs::File::open(self.path())?;
+        Ok(CachedFileReader {
+            file: File::from_std(file),
+            _handle: self.clone(),
+        })
+    }
+}
+
+impl Drop for FileHandle {
+    fn drop(&mut self) {
+        let path = std::mem::take(&mut self.path);
+        let remove = move || {
+            if let Err(e) = std::fs::remove_file(&path) {
+                if e.kind() != ErrorKind::NotFound {
+                    tracing::warn!(error = ?e, path = ?path, "remove cached body failed");
+                }
+            }
+        };
+        match tokio::runtime::Handle::try_current() {
+            Ok(runtime) => {
+                runtime.spawn_blocking(remove);
+            }
+            Err(_) => remove(),
+        }
+    }
+}
+
+/// Reader of a [`CachedFile`].
+pub(crate) struct CachedFileReader {
+    file: File,
+    _handle: CachedFile,
+}
+
+impl AsyncRead for CachedFileReader {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<IoResult<()>> {
+        Pin::new(&mut self.file).poll_read(cx, buf)
+    }
+}
+
+/// A store which keeps response bodies in files and evicts least recently used entries when the total size
+/// of bodies exceeds the max size.
+///
+/// Entries metadata is kept in memory, so entries do not survive restarts. Bodies are kept in the `bodies`
+/// subdirectory of the store directory, body files left there by a previous process are removed when the
+/// store is created, so it should not be shared with other stores.
+///
+/// Cloning a `DiskStore` is cheap, clones share the same entries.
+#[derive(Clone)]
+pub struct DiskStore<K> {
+    inner: Arc<Inner<K>>,
+}
+
+struct Inner<K> {
+    root: PathBuf,
+    bodies: PathBuf,
+    max_size: u64,
+    next_id: AtomicU64,
+    state: Mutex<State<K>>,
+}
+
+struct State<K> {
+    slots: HashMap<K, Slot>,
+    lru: BTreeMap<u64, K>,
+    tick: u64,
+    size: u64,
+}
+
+struct Slot {
+    entry: CachedEntry,
+    size: u64,
+    tick: u64,
+}
+
+impl<K> DiskStore<K>
+where
+    K: Hash + Eq + Send + Sync + Clone + 'static,
+{
+    /// Create a new `DiskStore` in the directory, with the max total size of bodies in bytes.
+    ///
+    /// The directory is created if it does not exist.
+    pub fn new(root: impl Into<PathBuf>, max_size: u64) -> IoResult<Self> {
+        let root = root.into();
+        let bodies = root.join(BODIES_DIR);
+        std::fs::create_dir_all(&bodies)?;
+        for item in std::fs::read_dir(&bodies)? {
+            let path = item?.path();
+            let ext = path.extension().and_then(|ext| ext.to_str());
+            if ext == Some(BODY_EXT) || ext == Some(TEMP_EXT) {
+                std::fs::remove_file(&path)?;
+            }
+        }
+        Ok(Self {
+            inner: Arc::new(Inner {
+                root,
+                bodies,
+                max_size,
+                next_id: AtomicU64::new(0),
+                state: Mutex::new(State {
+                    slots: HashMap::new(),
+                    lru: BTreeMap::new(),
+                    tick: 0,
+                    size: 0,
+                }),
+            }),
+        })
+    }
+
+    /// Get the directory of the store.
+    pub fn root(&self) -> &Path {
+        &self.inner.root
+    }
+
+    /// Get the total size of stored bodies in bytes.
+    pub fn size(&self) -> u64 {
+        self.state().size
+    }
+
+    fn state(&self) -> MutexGuard<'_, State<K>> {
+        self.inner.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Write the body to a new file, bodies already in files are kept.
+    ///
+    /// Returns the size of the file.
+    async fn write_body(&self, body: &mut CachedBody) -> IoResult<u64> {
+        let file = match body {
+            CachedBody::File(file) => return Ok(file.size()),
+            CachedBody::Once(bytes) => {
+                let bytes = bytes.clone();
+                self.write_file(|mut file| async move {
+                    file.write_all(&bytes).await?;
+                    Ok((file, bytes.len() as u64))
+                })
+                .await?
+            }
+            CachedBody::Chunks(chunks) => {
+                let chunks = chunks.clone();
+                self.write_file(|mut file| async move {
+                    let mut size = 0;
+                    for chunk in &chunks {
+                        size += chunk.len() as u64;
+                        file.write_all(chunk).await?;
+                    }
+                    Ok((file, size))
+                })
+                .await?
+            }
+            _ => self.write_file(|file| async { Ok((file, 0)) }).await?,
+        };
+        let size = file.size();
+        *body = CachedBody::File(file);
+        Ok(size)
+    }
+
+    /// Create a new body file written by `write`, which returns the file with the number of bytes written.
+    ///
+    /// The file is written to a temporary path and renamed when it is complete.
+    async fn write_file<F, Fut>(&self, write: F) -> IoResult<CachedFile>
+    where
+        F: FnOnce(File) -> Fut,
+        Fut: Future<Output = IoResult<(File, u64)>>,
+    {
+        let id = self.inner.next_id.fetch_add(1, Ordering::Relaxed);
+        let temp = self.inner.bodies.join(format!("{id}.{TEMP_EXT}"));
+        let path = self.inner.bodies.join(format!("{id}.{BODY_EXT}"));
+        let file = File::create(&temp).await?;
+        let written = async {
+            let (mut file, size) = write(file).await?;
+            file.flush().await?;
+            IoResult::Ok(size)
+        }
+        .await;
+        let size = match written {
+            Ok(size) => size,
+            Err(e) => {
+                let _ = tokio::fs::remove_file(&temp).await;
+                return Err(e);
+            }
+        };
+        if let Err(e) = tokio::fs::rename(&temp, &path).await {
+            let _ = tokio::fs::remove_file(&temp).await;
+            return Err(e);
+        }
+        Ok(CachedFile {
+            inner: Arc::new(FileHandle { path, size }),
+        })
+    }
+}
+
+impl<K: Hash + Eq + Clone> State<K> {
+    fn touch(&mut self, key: &K) {
+        self.tick += 1;
+        let tick = self.tick;
+        if let Some(slot) = self.slots.get_mut(key) {
+            self.lru.remove(&slot.tick);
+            slot.tick = tick;
+            self.lru.insert(tick, key.clone());
+        }
+    }
+
+    fn remove<Q>(&mut self, key: &Q) -> Option<Slot>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let slot = self.slots.remove(key)?;
+        self.lru.remove(&slot.tick);
+        self.size -= slot.size;
+        Some(slot)
+    }
+}
+
+fn is_expired(entry: &CachedEntry, now: SystemTime) -> bool {
+    entry
+        .time_to_live()
+        .and_then(|ttl| entry.stored_at.checked_add(ttl))
+        .is_some_and(|expires| expires <= now)
+}
+
+impl<K> CacheStore for DiskStore<K>
+where
+    K: Hash + Eq + Send + Sync + Clone + 'static,
+{
+    type Error = std::io::Error;
+    type Key = K;
+
+    async fn load_entry<Q>(&self, key: &Q) -> Option<CachedEntry>
+    where
+        Self::Key: Borrow<Q>,
+        Q: Hash + Eq + Sync,
+    {
+        let mut state = self.state();
+        let (key, slot) = state.slots.get_key_value(key)?;
+        if is_expired(&slot.entry, SystemTime::now()) {
+            let key = key.clone();
+            state.remove::<K>(&key);
+            return None;
+        }
+        let (key, entry) = (key.clone(), slot.entry.clone());
+        state.touch(&key);
+        Some(entry)
+    }
+
+    async fn save_entry(&self, key: Self::Key, mut entry: CachedEntry) -> Result<(), Self::Error> {
+        // Files written by this call are removed when the entry is dropped on error. Files kept from the
+        // previous entry are shared with it, so they are removed once both are dropped.
+        let mut size = self.write_body(&mut entry.body).await?;
+        for variant in &mut entry.variants {
+            size += self.write_body(&mut variant.body).await?;
+        }
+        if size > self.inner.max_size {
+            return Ok(());
+        }
+
+        // Evicted entries are dropped after the lock is released.
+        let mut removed = Vec::new();
+        {
+            let mut state = self.state();
+            removed.extend(state.remove(&key));
+            while state.size + size > self.inner.max_size {
+                let Some((_, oldest)) = state.lru.pop_first() else {
+                    break;
+                };
+                removed.extend(state.remove(&oldest));
+            }
+            state.tick += 1;
+            let tick = state.tick;
+            state.size += size;
+            state.lru.insert(tick, key.clone());
+            state.slots.insert(key, Slot { entry, size, tick });
+        }
+        drop(removed);
+        Ok(())
+    }
+
+    async fn write_stream(&self, chunks: &mut BodyChunks) -> Result<CachedBody, Self::Error> {
+        let file = self
+            .write_file(|mut file| async move {
+                let mut size = 0;
+                while let Some(chunk) = chunks.next().await {
+                    size += chunk.len() as u64;
+                    file.write_all(&chunk).await?;
+                }
+                Ok((file, size))
+            })
+            .await?;
+        Ok(CachedBody::File(file))
+    }
+
+    async fn delete_entry<Q>(&self, key: &Q) -> Result<(), Self::Error>
+    where
+        Self::Key: Borrow<Q>,
+        Q: Hash + Eq + Sync,
+    {
+        let slot = self.state().remove(key);
+        drop(slot);
+        Ok(())
+    }
+
+    async fn purge_tag(&self, tag: &str) -> Result<(), Self::Error> {
+        let mut removed = Vec::new();
+        {
+            let mut state = self.state();
+            let keys = state
+                .slots
+                .iter()
+                .filter(|(_, slot)| slot.entry.has_tag(tag))
+                .map(|(key, _)| key.clone())
+                .collect::<Vec<_>>();
+            for key in keys {
+                removed.extend(state.remove(&key));
+            }
+        }
+        drop(removed);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bytes::Bytes;
+    use salvo_core::http::HeaderMap;
+
+    use super::*;
+
+    fn entry(body: &'static str) -> CachedEntry {
+        CachedEntry::new(
+            None,
+            HeaderMap::new(),
+            CachedBody::Once(Bytes::from_static(body.as_bytes())),
+        )
+    }
+
+    #[tokio::test]
+    async fn test_disk_store() {
+        let root = std::env::temp_dir().join(format!("salvo-cache-{}", std::process::id()));
+        let store = DiskStore::new(&root, 10).unwrap();
+        store.save_entry("a", entry("aaaa")).await.unwrap();
+        store.save_entry("b", entry("bbbb")).await.unwrap();
+        assert_eq!(store.size(), 8);
+
+        let loaded = store.load_entry(&"a").await.unwrap();
+        let CachedBody::File(file) = loaded.body() else {
+            panic!("body is not in file");
+        };
+        assert!(file.path().starts_with(root.join(BODIES_DIR)));
+        assert_eq!(tokio::fs::read(file.path()).await.unwrap(), b"aaaa");
+
+        // `b` is the least recently used entry, its file is kept while it is used.
+        let evicted = store.load_entry(&"b").await.unwrap();
+        store.load_entry(&"a").await.unwrap();
+        store.save_entry("c", entry("cccc")).await.unwrap();
+        assert!(store.load_entry(&"b").await.is_none());
+        let CachedBody::File(file) = evicted.body() else {
+            panic!("body is not in file");
+        };
+        let path = file.path().to_owned();
+        assert_eq!(tokio::fs::read(&path).await.unwrap(), b"bbbb");
+        drop(evicted);
+        tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+        assert!(!path.exists());
+        assert!(store.load_entry(&"a").await.is_some());
+        assert_eq!(store.size(), 8);
+
+        // Larger than the max size.
+        store.save_entry("d", entry("ddddddddddd")).await.unwrap();
+        assert!(store.load_entry(&"d").await.is_none());
+
+        let mut tagged = entry("tt");
+        tagged.tags.push("tag".into());
+        store.save_entry("t", tagged).await.unwrap();
+        store.purge_tag("tag").await.unwrap();
+        assert!(store.load_entry(&"t").await.is_none());
+        store.delete_entry(&"a").await.unwrap();
+        assert!(store.load_entry(&"a").await.is_none());
+        assert_eq!(store.size(), 4);
+
+        std::fs::remove_dir_all(root).unwrap();
+    }
+}
@@ -7,6 +7,7 @@
 //! or you can use the default [`RequestIssuer`].
 //!
 //! The default cache store is [`MokaStore`], which is a wrapper of [`moka`].
+//! [`DiskStore`] keeps response bodies in files, which is useful for large responses.
 //! You can define your own cache store by implementing [`CacheStore`].
 //!
 //! Example: [cache-simple](https://github.com/salvo-rs/salvo/tree/main/examples/cache-simple)
@@ -16,19 +17,39 @@
 #![cfg_attr(docsrs, feature(doc_cfg))]
 
 use std::borrow::Borrow;
-use std::collections::VecDeque;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{HashMap, VecDeque};
 use std::error::Error as StdError;
 use std::future::Future;
-use std::hash::Hash;
+use std::hash::{Hash, Hasher};
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant, SystemTime};
 
 use bytes::Bytes;
 use salvo_core::handler::Skipper;
-use salvo_core::http::{HeaderMap, ResBody, StatusCode};
-use salvo_core::{async_trait, Depot, Error, FlowCtrl, Handler, Request, Response};
+use salvo_core::http::body::{Body, Frame, SizeHint};
+use salvo_core::http::header::{
+    AGE, AUTHORIZATION, CACHE_CONTROL, CONTENT_LOCATION, DATE, ETAG, EXPIRES, SET_COOKIE, VARY,
+};
+use salvo_core::http::headers::{ETag, HeaderMapExt, IfModifiedSince, IfNoneMatch, LastModified};
+use salvo_core::http::{HeaderMap, HeaderName, HeaderValue, ResBody, StatusCode};
+use salvo_core::{async_trait, BoxedError, Depot, Error, FlowCtrl, Handler, Request, Response};
+use tokio::sync::{mpsc, watch};
+
+const X_CACHE: HeaderName = HeaderName::from_static("x-cache");
+const CACHE_STATUS: HeaderName = HeaderName::from_static("cache-status");
 
 mod skipper;
 pub use skipper::MethodSkipper;
 
+pub mod cache_control;
+pub use cache_control::{is_heuristically_cacheable, CacheControl};
+
+mod purger;
+pub use purger::CachePurger;
+
 #[macro_use]
 mod cfg;
 
@@ -39,6 +60,13 @@ cfg_feature! {
     pub use moka_store::{MokaStore};
 }
 
+cfg_feature! {
+    #![feature = "disk-store"]
+
+    pub mod disk_store;
+    pub use disk_store::{CachedFile, DiskStore};
+}
+
 /// Issuer
 pub trait CacheIssuer: Send + Sync + 'static {
     /// The key is used to identify the rate limit.
@@ -146,6 +174,9 @@ impl CacheIssuer for RequestIssuer {
 }
 
 /// Store cache.
+///
+/// [`Cache`] clones the store to save entries of streamed bodies in the background, so cloning should be cheap
+/// and clones should share entries.
 pub trait CacheStore: Send + Sync + 'static {
     /// Error type for CacheStore.
     type Error: StdError + Sync + Send + 'static;
@@ -162,12 +193,96 @@ pub trait CacheStore: Send + Sync + 'static {
         key: Self::Key,
         data: CachedEntry,
     ) -> impl Future<Output = Result<(), Self::Error>> + Send;
+    /// Delete the cache item from the store, including all its variants.
+    fn delete_entry<Q>(&self, key: &Q) -> impl Future<Output = Result<(), Self::Error>> + Send
+    where
+        Self::Key: Borrow<Q>,
+        Q: Hash + Eq + Sync;
+    /// Delete all cache items which have the tag, or which have a variant with the tag.
+    fn purge_tag(&self, tag: &str) -> impl Future<Output = Result<(), Self::Error>> + Send;
+    /// Write a streamed body while it is sent, the returned body is stored with its entry if the stream is
+    /// complete.
+    ///
+    /// The default implementation collects the chunks in memory, stores keeping bodies elsewhere can override it
+    /// to write them as they come.
+    fn write_stream(
+        &self,
+        chunks: &mut BodyChunks,
+    ) -> impl Future<Output = Result<CachedBody, Self::Error>> + Send {
+        async move {
+            let mut collected = VecDeque::new();
+            while let Some(chunk) = chunks.next().await {
+                collected.push_back(chunk);
+            }
+            Ok(CachedBody::Chunks(collected))
+        }
+    }
+}
+
+/// Chunks of a streamed body, received by [`CacheStore::write_stream`] while the body is sent.
+#[derive(Debug)]
+pub struct BodyChunks {
+    rx: mpsc::UnboundedReceiver<StreamChunk>,
+    complete: bool,
+}
+#[derive(Debug)]
+enum StreamChunk {
+    Data(Bytes),
+    End,
+}
+impl BodyChunks {
+    /// Receive the next chunk, returns `None` when the body ends or is aborted.
+    pub async fn next(&mut self) -> Option<Bytes> {
+        if self.complete {
+            return None;
+        }
+        match self.rx.recv().await? {
+            StreamChunk::Data(chunk) => Some(chunk),
+            StreamChunk::End => {
+                self.complete = true;
+                None
+            }
+        }
+    }
+    /// Check if the whole body was received, it is `false` if the body failed, was dropped before its end or
+    /// was larger than [`Cache::max_stream_size`].
+    pub fn is_complete(&self) -> bool {
+        self.complete
+    }
+}
+
+/// Key for cache tags in depot.
+pub const CACHE_TAGS_KEY: &str = "::salvo::cache::tags";
+
+/// Extension for Depot.
+pub trait CacheDepotExt {
+    /// Attach a tag (also known as surrogate key) to the response, used to purge it with [`CacheStore::purge_tag`].
+    fn add_cache_tag(&mut self, tag: impl Into<String>) -> &mut Self;
+    /// Get tags attached to the response.
+    fn cache_tags(&self) -> Option<&Vec<String>>;
+}
+
+impl CacheDepotExt for Depot {
+    #[inline]
+    fn add_cache_tag(&mut self, tag: impl Into<String>) -> &mut Self {
+        match self.get_mut::<Vec<String>>(CACHE_TAGS_KEY) {
+            Ok(tags) => tags.push(tag.into()),
+            Err(_) => {
+                self.insert(CACHE_TAGS_KEY, vec![tag.into()]);
+            }
+        }
+        self
+    }
+    #[inline]
+    fn cache_tags(&self) -> Option<&Vec<String>> {
+        self.get::<Vec<String>>(CACHE_TAGS_KEY).ok()
+    }
 }
 
 /// `CachedBody` is used to save response body to `CachedStore`.
 ///
 /// [`ResBody`] has Stream type, which is not `Send + Sync`, so we need to convert it to `CachedBody`.
-/// If response's body is ['ResBody::Stream`], it will not be cached.
+/// Streaming bodies are passed to [`CacheStore::write_stream`] by [`Cache`] while they are sent.
 #[derive(Clone, Debug)]
 #[non_exhaustive]
 pub enum CachedBody {
@@ -177,6 +292,21 @@ pub enum CachedBody {
     Once(Bytes),
     /// Chunks body.
     Chunks(VecDeque<Bytes>),
+    /// Body stored in a file by [`DiskStore`].
+    #[cfg(feature = "disk-store")]
+    File(CachedFile),
+}
+impl CachedBody {
+    /// Get the size of the body in bytes.
+    pub fn size(&self) -> Option<u64> {
+        match self {
+            Self::None => Some(0),
+            Self::Once(bytes) => Some(bytes.len() as u64),
+            Self::Chunks(chunks) => Some(chunks.iter().map(|chunk| chunk.len() as u64).sum()),
+            #[cfg(feature = "disk-store")]
+            Self::File(file) => Some(file.size()),
+        }
+    }
 }
 impl TryFrom<&ResBody> for CachedBody {
     type Error = Error;
@@ -195,6 +325,14 @@ impl From<CachedBody> for ResBody {
             CachedBody::None => Self::None,
             CachedBody::Once(bytes) => Self::Once(bytes),
             CachedBody::Chunks(chunks) => Self::Chunks(chunks),
+            #[cfg(feature = "disk-store")]
+            CachedBody::File(file) => match file.open() {
+                Ok(reader) => Self::stream(tokio_util::io::ReaderStream::new(reader)),
+                Err(e) => {
+                    tracing::error!(error = ?e, path = ?file.path(), "open cached body failed");
+                    Self::Error(salvo_core::http::StatusError::internal_server_error())
+                }
+            },
         }
     }
 }
@@ -208,9 +346,25 @@ pub struct CachedEntry {
     /// Response headers.
     pub headers: HeaderMap,
     /// Response body.
-    ///
-    /// *Notice: If the response's body is streaming, it will be ignored an not cached.
     pub body: CachedBody,
+    /// Time when the response was stored.
+    pub stored_at: SystemTime,
+    /// Age of the response when it was stored, from its `Age` header.
+    pub initial_age: Duration,
+    /// Freshness lifetime, `None` means the entry is fresh until the store evicts it.
+    pub freshness: Option<Duration>,
+    /// How long the entry can be served stale while one request refreshes it.
+    pub stale_while_revalidate: Duration,
+    /// How long the entry can be served stale when the handler fails with a server error.
+    pub stale_if_error: Duration,
+    /// How long the handler took to produce the response, used by probabilistic early refresh.
+    pub compute_time: Duration,
+    /// Tags attached by the handler with [`CacheDepotExt::add_cache_tag`].
+    pub tags: Vec<String>,
+    /// Request headers selected by the response's `Vary` header, with their values when the entry was stored.
+    pub vary: Vec<(HeaderName, Option<HeaderValue>)>,
+    /// Other variants stored under the same key, selected by different `Vary` request header values.
+    pub variants: Vec<CachedEntry>,
 }
 impl CachedEntry {
     /// Create a new `CachedEntry`.
@@ -219,6 +373,15 @@ impl CachedEntry {
             status,
             headers,
             body,
+            stored_at: SystemTime::now(),
+            initial_age: Duration::ZERO,
+            freshness: None,
+            stale_while_revalidate: Duration::ZERO,
+            stale_if_error: Duration::ZERO,
+            compute_time: Duration::ZERO,
+            tags: Vec::new(),
+            vary: Vec::new(),
+            variants: Vec::new(),
         }
     }
 
@@ -233,15 +396,110 @@ impl CachedEntry {
     }
 
     /// Get the response body.
-    ///
-    /// *Notice: If the response's body is streaming, it will be ignored an not cached.
     pub fn body(&self) -> &CachedBody {
         &self.body
     }
+
+    /// Get the current age of the entry.
+    pub fn age(&self, now: SystemTime) -> Duration {
+        self.initial_age + now.duration_since(self.stored_at).unwrap_or_default()
+    }
+
+    /// Check if the entry is fresh.
+    pub fn is_fresh(&self, now: SystemTime) -> bool {
+        match self.freshness {
+            Some(freshness) => self.age(now) < freshness,
+            None => true,
+        }
+    }
+
+    /// Get how long the entry has been stale, `Duration::ZERO` if it is fresh.
+    pub fn staleness(&self, now: SystemTime) -> Duration {
+        self.freshness
+            .map(|freshness| self.age(now).saturating_sub(freshness))
+            .unwrap_or_default()
+    }
+
+    /// How long the store should keep the entry and its variants, `None` means no limit.
+    pub fn time_to_live(&self) -> Option<Duration> {
+        let own = self.freshness.map(|freshness| {
+            let stale = self.stale_while_revalidate.max(self.stale_if_error);
+            (freshness + stale).saturating_sub(self.initial_age)
+        });
+        self.variants.iter().fold(own, |ttl, variant| {
+            ttl.zip(variant.time_to_live()).map(|(a, b)| a.max(b))
+        })
+    }
+
+    /// Check if the entry was stored for a request with the same `Vary` header values.
+    pub fn matches(&self, headers: &HeaderMap) -> bool {
+        self.vary
+            .iter()
+            .all(|(name, value)| joined_values(headers, name).as_ref() == value.as_ref())
+    }
+
+    /// Check if the entry or one of its variants has the tag.
+    pub fn has_tag(&self, tag: &str) -> bool {
+        self.tags.iter().any(|t| t == tag)
+            || self.variants.iter().any(|variant| variant.has_tag(tag))
+    }
+
+    fn vary_names(&self) -> impl Iterator<Item = &HeaderName> {
+        self.vary.iter().map(|(name, _)| name)
+    }
+}
+
+fn joined_values(headers: &HeaderMap, name: &HeaderName) -> Option<HeaderValue> {
+    let values = headers
+        .get_all(name)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .collect::<Vec<_>>();
+    if values.is_empty() {
+        None
+    } else {
+        HeaderValue::from_str(&values.join(", ")).ok()
+    }
+}
+
+/// Header which tells how a response was served by the cache.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum StatusHeader {
+    /// No header.
+    #[default]
+    None,
+    /// `X-Cache` header with `HIT`, `STALE` or `MISS`.
+    XCache,
+    /// [RFC 9211](https://www.rfc-editor.org/rfc/rfc9211) `Cache-Status` header with the cache name,
+    /// such as `salvo; hit; ttl=42` or `salvo; fwd=uri-miss; fwd-status=200; stored`.
+    CacheStatus(String),
+}
+
+/// How a response was served.
+enum Outcome {
+    /// Served from the cache, `ttl` is negative for stale entries.
+    Hit { ttl: i64, stale: bool },
+    /// Forwarded to the handler, `fwd` is the reason defined by RFC 9211.
+    Miss { fwd: &'static str, stored: bool },
 }
 
 /// Cache middleware.
 ///
+/// Responses are cached following [RFC 9111](https://www.rfc-editor.org/rfc/rfc9111) for a shared cache:
+///
+/// * Responses with `no-store`, `no-cache`, `private` or `Set-Cookie` are not stored, and neither are responses
+///   to requests with `Authorization` unless they are marked `public`, `s-maxage` or `must-revalidate`.
+/// * The freshness lifetime comes from `s-maxage`, `max-age` or `Expires`, and falls back to
+///   `default_ttl`. Without it, entries are fresh until the store evicts them.
+/// * `Vary` request headers select one of several variants stored under the same key.
+/// * A stale entry is served to concurrent requests within its `stale-while-revalidate` window while one
+///   request refreshes it, and is served when the handler fails within its `stale-if-error` window.
+/// * Conditional requests matching the cached `ETag` or `Last-Modified` are answered with `304 Not Modified`.
+/// * Responses served from the cache carry an `Age` header, and all responses can carry a [`StatusHeader`].
+/// * Handlers can attach tags to responses with [`CacheDepotExt`], which can be purged with
+///   [`CacheStore::purge_tag`] or a [`CachePurger`].
+///
 /// # Example
 ///
 /// ```
@@ -264,6 +522,22 @@ pub struct Cache<S, I> {
     pub issuer: I,
     /// Skipper.
     pub skipper: Box<dyn Skipper>,
+    /// Freshness lifetime of responses without explicit freshness information.
+    pub default_ttl: Option<Duration>,
+    /// Max number of variants stored under one key.
+    pub max_variants: usize,
+    /// How long concurrent requests for the same key wait for the request which runs the handler.
+    pub coalesce_timeout: Option<Duration>,
+    /// The `beta` parameter of probabilistic early refresh.
+    pub early_refresh: Option<f64>,
+    /// Header which tells how the response was served.
+    pub status_header: StatusHeader,
+    /// Max size of streaming bodies to cache, larger bodies are sent without being cached.
+    ///
+    /// Streaming bodies are written to the store while they are sent, and their entry is stored in the
+    /// background once they are completely sent.
+    pub max_stream_size: usize,
+    in_flight: Arc<InFlight>,
 }
 
 impl<S, I> Cache<S, I> {
@@ -275,6 +549,13 @@ impl<S, I> Cache<S, I> {
             store,
             issuer,
             skipper: Box::new(skipper),
+            default_ttl: None,
+            max_variants: 8,
+            coalesce_timeout: None,
+            early_refresh: None,
+            status_header: StatusHeader::default(),
+            max_stream_size: 8 * 1024 * 1024,
+            in_flight: Arc::new(Mutex::new(HashMap::new())),
         }
     }
     /// Sets skipper and returns new `Cache`.
@@ -283,12 +564,305 @@ impl<S, I> Cache<S, I> {
         self.skipper = Box::new(skipper);
         self
     }
+    /// Sets freshness lifetime of responses without `Cache-Control` or `Expires` freshness information.
+    #[inline]
+    pub fn default_ttl(mut self, ttl: Duration) -> Self {
+        self.default_ttl = Some(ttl);
+        self
+    }
+    /// Sets max number of variants stored under one key, the default is 8.
+    #[inline]
+    pub fn max_variants(mut self, max_variants: usize) -> Self {
+        self.max_variants = max_variants.max(1);
+        self
+    }
+    /// Enables request coalescing.
+    ///
+    /// When an entry is missing or expired, only one request per key runs the handler, and concurrent requests
+    /// for the same key wait up to `timeout` for it and are served from the stored result. Requests which can
+    /// not be served from the result, for example because it was not storable, run the handler themselves.
+    #[inline]
+    pub fn coalesce(mut self, timeout: Duration) -> Self {
+        self.coalesce_timeout = Some(timeout);
+        self
+    }
+    /// Enables probabilistic early refresh, which avoids many requests missing at once when a hot entry expires.
+    ///
+    /// A fresh entry is refreshed by one request before it expires with a probability which grows as it gets
+    /// closer to expiring and with the time its handler took, see
+    /// [Optimal Probabilistic Cache Stampede Prevention](https://cseweb.ucsd.edu/~avattani/papers/cache_stampede.pdf).
+    /// `1.0` is a good default for `beta`, larger values refresh earlier.
+    #[inline]
+    pub fn early_refresh(mut self, beta: f64) -> Self {
+        self.early_refresh = Some(beta);
+        self
+    }
+
+    /// Sets the header which tells how the response was served, the default is [`StatusHeader::None`].
+    #[inline]
+    pub fn status_header(mut self, header: StatusHeader) -> Self {
+        self.status_header = header;
+        self
+    }
+
+    /// Sets max size of streaming bodies to cache, the default is 8 MiB.
+    #[inline]
+    pub fn max_stream_size(mut self, size: usize) -> Self {
+        self.max_stream_size = size;
+        self
+    }
+
+    fn write_status(&self, res: &mut Response, outcome: Outcome) {
+        let (name, value) = match (&self.status_header, outcome) {
+            (StatusHeader::None, _) => return,
+            (StatusHeader::XCache, Outcome::Hit { stale, .. }) => {
+                (X_CACHE, if stale { "STALE" } else { "HIT" }.to_owned())
+            }
+            (StatusHeader::XCache, Outcome::Miss { .. }) => (X_CACHE, "MISS".to_owned()),
+            (StatusHeader::CacheStatus(cache), Outcome::Hit { ttl, .. }) => {
+                (CACHE_STATUS, format!("{cache}; hit; ttl={ttl}"))
+            }
+            (StatusHeader::CacheStatus(cache), Outcome::Miss { fwd, stored }) => {
+                let status = res.status_code.unwrap_or(StatusCode::OK).as_u16();
+                let stored = if stored { "; stored" } else { "" };
+                (
+                    CACHE_STATUS,
+                    format!("{cache}; fwd={fwd}; fwd-status={status}{stored}"),
+                )
+            }
+        };
+        if let Ok(value) = HeaderValue::from_str(&value) {
+            res.headers_mut().insert(name, value);
+        }
+    }
+
+    /// Write the entry to the response and the status header.
+    fn serve(&self, req: &Request, res: &mut Response, entry: CachedEntry, now: SystemTime) {
+        let stale = !entry.is_fresh(now);
+        let ttl = match entry.freshness {
+            Some(freshness) => freshness.as_secs() as i64 - entry.age(now).as_secs() as i64,
+            None => i64::MAX,
+        };
+        serve_entry(req, res, entry, now);
+        self.write_status(res, Outcome::Hit { ttl, stale });
+    }
+
+    /// Build an entry without body from the response, returns `None` if the response is not storable.
+    fn storable_entry(
+        &self,
+        req: &Request,
+        depot: &Depot,
+        res: &Response,
+        now: SystemTime,
+    ) -> Option<CachedEntry> {
+        if res.body.is_error() {
+            return None;
+        }
+        let headers = res.headers();
+        let cc = CacheControl::from_headers(headers);
+        if cc.no_store || cc.no_cache || cc.private || headers.contains_key(SET_COOKIE) {
+            return None;
+        }
+        if req.headers().contains_key(AUTHORIZATION)
+            && !cc.public
+            && !cc.must_revalidate
+            && cc.s_maxage.is_none()
+        {
+            return None;
+        }
+        let vary = headers
+            .get_all(VARY)
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .flat_map(|value| value.split(','))
+            .map(str::trim)
+            .filter(|name| !name.is_empty())
+            .collect::<Vec<_>>();
+        if vary.contains(&"*") {
+            return None;
+        }
+        let vary = vary
+            .into_iter()
+            .filter_map(|name| HeaderName::from_bytes(name.as_bytes()).ok())
+            .map(|name| {
+                let value = joined_values(req.headers(), &name);
+                (name, value)
+            })
+            .collect();
+
+        let status = res.status_code.unwrap_or(StatusCode::OK);
+        let explicit = cc.freshness(headers, now);
+        if explicit.is_none() && !cc.public && !is_heuristically_cacheable(status) {
+            return None;
+        }
+        let freshness = explicit.or(self.default_ttl);
+        let (stale_while_revalidate, stale_if_error) = if cc.must_revalidate {
+            (Duration::ZERO, Duration::ZERO)
+        } else {
+            (
+                cc.stale_while_revalidate.unwrap_or_default(),
+                cc.stale_if_error.unwrap_or_default(),
+            )
+        };
+        if freshness.is_some_and(|f| f.is_zero())
+            && stale_while_revalidate.is_zero()
+            && stale_if_error.is_zero()
+        {
+            return None;
+        }
+        let initial_age = headers
+            .get(AGE)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.parse::<u64>().ok())
+            .map(Duration::from_secs)
+            .unwrap_or_default();
+        let mut entry = CachedEntry::new(res.status_code, headers.clone(), CachedBody::None);
+        entry.stored_at = now;
+        entry.initial_age = initial_age;
+        entry.freshness = freshness;
+        entry.stale_while_revalidate = stale_while_revalidate;
+        entry.stale_if_error = stale_if_error;
+        entry.vary = vary;
+        entry.tags = depot.cache_tags().cloned().unwrap_or_default();
+        Some(entry)
+    }
+
+    /// Add other still useful variants of the previous entry to the new entry.
+    fn merge_variants(
+        &self,
+        entry: &mut CachedEntry,
+        previous: Option<CachedEntry>,
+        now: SystemTime,
+    ) {
+        let Some(mut previous) = previous else {
+            return;
+        };
+        let mut variants = std::mem::take(&mut previous.variants);
+        variants.insert(0, previous);
+        entry.variants = variants
+            .into_iter()
+            .filter(|variant| {
+                variant.vary_names().eq(entry.vary_names())
+                    && variant.vary != entry.vary
+                    && variant.staleness(now)
+                        <= variant.stale_while_revalidate.max(variant.stale_if_error)
+            })
+            .take(self.max_variants - 1)
+            .collect();
+    }
+
+    /// Check if the fresh entry should be refreshed early.
+    fn refresh_early(&self, entry: &CachedEntry, now: SystemTime) -> bool {
+        let (Some(beta), Some(freshness)) = (self.early_refresh, entry.freshness) else {
+            return false;
+        };
+        if entry.compute_time.is_zero() {
+            return false;
+        }
+        let gap = entry.compute_time.as_secs_f64() * beta * -fastrand::f64().ln();
+        entry.age(now).as_secs_f64() + gap >= freshness.as_secs_f64()
+    }
+
+    /// Start running the handler for the key, or get a receiver to wait for the request already running it.
+    fn begin_flight<K: Hash>(&self, key: &K) -> Result<FlightGuard, watch::Receiver<()>> {
+        let mut hasher = DefaultHasher::new();
+        key.hash(&mut hasher);
+        let hash = hasher.finish();
+        let mut in_flight = self.in_flight.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(rx) = in_flight.get(&hash) {
+            return Err(rx.clone());
+        }
+        let (tx, rx) = watch::channel(());
+        in_flight.insert(hash, rx);
+        Ok(FlightGuard {
+            in_flight: self.in_flight.clone(),
+            hash,
+            _tx: tx,
+        })
+    }
+}
+
+/// Removes the key from in-flight keys when dropped, which wakes waiting requests.
+type InFlight = Mutex<HashMap<u64, watch::Receiver<()>>>;
+
+struct FlightGuard {
+    in_flight: Arc<InFlight>,
+    hash: u64,
+    _tx: watch::Sender<()>,
+}
+impl Drop for FlightGuard {
+    fn drop(&mut self) {
+        self.in_flight
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .remove(&self.hash);
+    }
+}
+
+fn find_variant(entry: Option<&CachedEntry>, headers: &HeaderMap) -> Option<CachedEntry> {
+    entry
+        .into_iter()
+        .flat_map(|entry| std::iter::once(entry).chain(entry.variants.iter()))
+        .find(|entry| entry.matches(headers))
+        .cloned()
+}
+
+/// Write the entry to the response, answering conditional requests with `304 Not Modified`.
+fn serve_entry(req: &Request, res: &mut Response, entry: CachedEntry, now: SystemTime) {
+    let age = entry.age(now);
+    let CachedEntry {
+        status,
+        mut headers,
+        body,
+        ..
+    } = entry;
+    headers.insert(AGE, HeaderValue::from(age.as_secs()));
+    let not_modified = match (
+        req.headers().typed_get::<IfNoneMatch>(),
+        headers.typed_get::<ETag>(),
+    ) {
+        (Some(if_none_match), Some(etag)) => !if_none_match.precondition_passes(&etag),
+        (Some(_), None) => false,
+        (None, _) => match (
+            req.headers().typed_get::<IfModifiedSince>(),
+            headers.typed_get::<LastModified>(),
+        ) {
+            (Some(since), Some(last_modified)) => !since.is_modified(last_modified.into()),
+            _ => false,
+        },
+    };
+    if not_modified && status.unwrap_or(StatusCode::OK) == StatusCode::OK {
+        // https://www.rfc-editor.org/rfc/rfc9110#section-15.4.5
+        let mut kept = HeaderMap::new();
+        for name in [
+            CACHE_CONTROL,
+            CONTENT_LOCATION,
+            DATE,
+            ETAG,
+            EXPIRES,
+            VARY,
+            AGE,
+        ] {
+            for value in headers.get_all(&name) {
+                kept.append(name.clone(), value.clone());
+            }
+        }
+        res.status_code(StatusCode::NOT_MODIFIED);
+        *res.headers_mut() = kept;
+        *res.body_mut() = ResBody::None;
+        return;
+    }
+    if let Some(status) = status {
+        res.status_code(status);
+    }
+    *res.headers_mut() = headers;
+    *res.body_mut() = body.into();
 }
 
 #[async_trait]
 impl<S, I> Handler for Cache<S, I>
 where
-    S: CacheStore<Key = I::Key>,
+    S: CacheStore<Key = I::Key> + Clone,
     I: CacheIssuer,
 {
     async fn handle(
@@ -301,48 +875,245 @@ where
         if self.skipper.skipped(req, depot) {
             return;
         }
+        let req_cc = CacheControl::from_headers(req.headers());
+        if req_cc.no_store {
+            return;
+        }
         let key = match self.issuer.issue(req, depot).await {
             Some(key) => key,
             None => {
                 return;
             }
         };
-        let cache = match self.store.load_entry(&key).await {
-            Some(cache) => cache,
+        let now = SystemTime::now();
+        let mut previous = self.store.load_entry(&key).await;
+        let cached = find_variant(previous.as_ref(), req.headers()).filter(|_| !req_cc.no_cache);
+
+        let mut fwd = match (&previous, req_cc.no_cache) {
+            (_, true) => "request",
+            (Some(_), false) => "vary-miss",
+            (None, false) => "uri-miss",
+        };
+        let mut stale = None;
+        let mut flight = None;
+        match cached {
+            Some(entry) if entry.is_fresh(now) => {
+                match self
+                    .refresh_early(&entry, now)
+                    .then(|| self.begin_flight(&key))
+                {
+                    Some(Ok(guard)) => {
+                        fwd = "stale";
+                        flight = Some(guard);
+                    }
+                    _ => {
+                        self.serve(req, res, entry, now);
+                        ctrl.skip_rest();
+                        return;
+                    }
+                }
+            }
+            Some(entry) => {
+                match self.begin_flight(&key) {
+                    Ok(guard) => flight = Some(guard),
+                    Err(_) if entry.staleness(now) <= entry.stale_while_revalidate => {
+                        self.serve(req, res, entry, now);
+                        ctrl.skip_rest();
+                        return;
+                    }
+                    Err(rx) => {
+                        if let Some(timeout) = self.coalesce_timeout {
+                            previous = self.join_flight(rx, timeout, &key).await;
+                            if self.serve_fresh(req, res, previous.as_ref()) {
+                                ctrl.skip_rest();
+                                return;
+                            }
+                        }
+                    }
+                }
+                fwd = "stale";
+                stale = Some(entry);
+            }
             None => {
-                ctrl.call_next(req, depot, res).await;
-                if !res.body.is_stream() && !res.body.is_error() {
-                    let headers = res.headers().clone();
-                    let body = TryInto::<CachedBody>::try_into(&res.body);
-                    match body {
-                        Ok(body) => {
-                            let cached_data = CachedEntry::new(res.status_code, headers, body);
-                            if let Err(e) = self.store.save_entry(key, cached_data).await {
-                                tracing::error!(error = ?e, "cache failed");
+                if let (Some(timeout), false) = (self.coalesce_timeout, req_cc.no_cache) {
+                    match self.begin_flight(&key) {
+                        Ok(guard) => flight = Some(guard),
+                        Err(rx) => {
+                            previous = self.join_flight(rx, timeout, &key).await;
+                            if self.serve_fresh(req, res, previous.as_ref()) {
+                                ctrl.skip_rest();
+                                return;
                             }
                         }
-                        Err(e) => tracing::error!(error = ?e, "cache failed"),
                     }
                 }
+            }
+        }
+
+        let started = Instant::now();
+        ctrl.call_next(req, depot, res).await;
+        let now = SystemTime::now();
+        if let Some(entry) = stale {
+            let failed =
+                res.status_code.is_some_and(|s| s.is_server_error()) || res.body.is_error();
+            if failed && entry.staleness(now) <= entry.stale_if_error {
+                self.serve(req, res, entry, now);
                 return;
             }
-        };
-        let CachedEntry {
-            status,
-            headers,
-            body,
-        } = cache;
-        if let Some(status) = status {
-            res.status_code(status);
         }
-        *res.headers_mut() = headers;
-        *res.body_mut() = body.into();
-        ctrl.skip_rest();
+        let mut stored = false;
+        if let Some(mut entry) = self.storable_entry(req, depot, res, now) {
+            entry.compute_time = started.elapsed();
+            self.merge_variants(&mut entry, previous, now);
+            match CachedBody::try_from(&res.body) {
+                Ok(body) => {
+                    entry.body = body;
+                    match self.store.save_entry(key, entry).await {
+                        Ok(()) => stored = true,
+                        Err(e) => tracing::error!(error = ?e, "cache failed"),
+                    }
+                }
+                Err(_) => {
+                    // Streaming body is written while it is sent, and its entry is stored once it is complete.
+                    let (tx, rx) = mpsc::unbounded_channel();
+                    let tee = CacheTee {
+                        inner: res.take_body(),
+                        size: 0,
+                        max_size: self.max_stream_size,
+                        tx: Some(tx),
+                    };
+                    res.replace_body(ResBody::Boxed(Box::pin(tee)));
+                    let mut chunks = BodyChunks {
+                        rx,
+                        complete: false,
+                    };
+                    let store = self.store.clone();
+                    let flight = flight.take();
+                    tokio::spawn(async move {
+                        // Waiting requests load the entry once the flight guard is dropped.
+                        let _flight = flight;
+                        let body = store.write_stream(&mut chunks).await;
+                        if !chunks.is_complete() {
+                            return;
+                        }
+                        match body {
+                            Ok(body) => {
+                                entry.body = body;
+                                if let Err(e) = store.save_entry(key, entry).await {
+                                    tracing::error!(error = ?e, "cache failed");
+                                }
+                            }
+                            Err(e) => tracing::error!(error = ?e, "cache failed"),
+                        }
+                    });
+                }
+            }
+        }
+        self.write_status(res, Outcome::Miss { fwd, stored });
+        drop(flight);
+    }
+}
+
+/// Body wrapper which sends the chunks of a streaming body to [`CacheStore::write_stream`].
+///
+/// The stream is aborted if the body fails, is dropped before its end or is larger than `max_size`.
+struct CacheTee {
+    inner: ResBody,
+    size: usize,
+    max_size: usize,
+    tx: Option<mpsc::UnboundedSender<StreamChunk>>,
+}
+impl CacheTee {
+    fn finish(&mut self) {
+        if let Some(tx) = self.tx.take() {
+            let _ = tx.send(StreamChunk::End);
+        }
+    }
+}
+impl Drop for CacheTee {
+    fn drop(&mut self) {
+        // Bodies which end with their last data frame are not always polled again.
+        if self.inner.is_end_stream() {
+            self.finish();
+        }
+    }
+}
+impl Body for CacheTee {
+    type Data = Bytes;
+    type Error = BoxedError;
+
+    fn poll_frame(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Frame<Self::Data>, Self::Error>>> {
+        let this = self.get_mut();
+        match Pin::new(&mut this.inner).poll_frame(cx) {
+            Poll::Ready(Some(Ok(frame))) => {
+                if let (Some(data), Some(tx)) = (frame.data_ref(), &this.tx) {
+                    this.size += data.len();
+                    if this.size > this.max_size
+                        || tx.send(StreamChunk::Data(data.clone())).is_err()
+                    {
+                        this.tx = None;
+                    }
+                }
+                Poll::Ready(Some(Ok(frame)))
+            }
+            Poll::Ready(Some(Err(e))) => {
+                this.tx = None;
+                Poll::Ready(Some(Err(e.into())))
+            }
+            Poll::Ready(None) => {
+                this.finish();
+                Poll::Ready(None)
+            }
+            Poll::Pending => Poll::Pending,
+        }
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.inner.is_end_stream()
+    }
+
+    fn size_hint(&self) -> SizeHint {
+        self.inner.size_hint()
+    }
+}
+
+impl<S, I> Cache<S, I>
+where
+    S: CacheStore<Key = I::Key>,
+    I: CacheIssuer,
+{
+    /// Wait up to `timeout` for the request running the handler, then load the entry it stored.
+    async fn join_flight(
+        &self,
+        mut rx: watch::Receiver<()>,
+        timeout: Duration,
+        key: &I::Key,
+    ) -> Option<CachedEntry> {
+        // `changed` fails when the running request drops its `FlightGuard`.
+        let _ = tokio::time::timeout(timeout, async { while rx.changed().await.is_ok() {} }).await;
+        self.store.load_entry(key).await
+    }
+
+    /// Serve the fresh variant matching the request, returns `false` if there is none.
+    fn serve_fresh(&self, req: &Request, res: &mut Response, entry: Option<&CachedEntry>) -> bool {
+        let now = SystemTime::now();
+        match find_variant(entry, req.headers()).filter(|entry| entry.is_fresh(now)) {
+            Some(entry) => {
+                self.serve(req, res, entry, now);
+                true
+            }
+            None => false,
+        }
     }
 }
 
 #[cfg(test)]
 mod tests {
+    use std::sync::Arc;
+
     use super::*;
     use salvo_core::prelude::*;
     use salvo_core::test::{ResponseExt, TestClient};
@@ -390,4 +1161,294 @@ mod tests {
 
         assert_ne!(content0, content2);
     }
+
+    #[handler]
+    async fn controlled(req: &mut Request, res: &mut Response) {
+        let cache_control = req.query::<String>("cc").unwrap_or_default();
+        if !cache_control.is_empty() {
+            res.add_header(CACHE_CONTROL, cache_control, true).unwrap();
+        }
+        res.add_header(VARY, "accept-language", true).unwrap();
+        res.add_header(ETAG, "\"v1\"", true).unwrap();
+        let lang = req.header::<String>("accept-language").unwrap_or_default();
+        if req.query::<bool>("fail").unwrap_or_default() {
+            res.status_code(StatusCode::SERVICE_UNAVAILABLE);
+        }
+        res.render(format!("{lang} {}", OffsetDateTime::now_utc()));
+    }
+
+    async fn fetch(service: &Service, url: &str, lang: &str) -> (StatusCode, String) {
+        let mut res = TestClient::get(url)
+            .add_header("accept-language", lang, true)
+            .send(service)
+            .await;
+        (res.status_code.unwrap(), res.take_string().await.unwrap())
+    }
+
+    #[tokio::test]
+    async fn test_cache_control() {
+        let cache = Cache::new(MokaStore::new(100), RequestIssuer::new().use_query(false));
+        let service = Service::new(Router::new().hoop(cache).goal(controlled));
+
+        let url = "http://127.0.0.1:5801/?cc=no-store";
+        let (_, content0) = fetch(&service, url, "en").await;
+        let (_, content1) = fetch(&service, url, "en").await;
+        assert_ne!(content0, content1);
+
+        let url = "http://127.0.0.1:5801/?cc=max-age=60";
+        let (_, en) = fetch(&service, url, "en").await;
+        let (_, fr) = fetch(&service, url, "fr").await;
+        assert_ne!(en, fr);
+        assert_eq!(fetch(&service, url, "en").await.1, en);
+        assert_eq!(fetch(&service, url, "fr").await.1, fr);
+
+        let res = TestClient::get(url)
+            .add_header("accept-language", "en", true)
+            .add_header("if-none-match", "\"v1\"", true)
+            .send(&service)
+            .await;
+        assert_eq!(res.status_code.unwrap(), StatusCode::NOT_MODIFIED);
+        assert!(res.headers().contains_key(AGE));
+
+        let res = TestClient::get(url)
+            .add_header("accept-language", "en", true)
+            .add_header("cache-control", "no-cache", true)
+            .send(&service)
+            .await;
+        assert_eq!(res.status_code.unwrap(), StatusCode::OK);
+        assert!(!res.headers().contains_key(AGE));
+    }
+
+    #[tokio::test]
+    async fn test_cache_stale_if_error() {
+        let cache = Cache::new(MokaStore::new(100), RequestIssuer::new().use_query(false));
+        let service = Service::new(Router::new().hoop(cache).goal(controlled));
+
+        let url = "http://127.0.0.1:5801/?cc=max-age=1,stale-if-error=60";
+        let (_, content0) = fetch(&service, url, "en").await;
+        tokio::time::sleep(Duration::from_millis(1100)).await;
+
+        let url = "http://127.0.0.1:5801/?cc=max-age=1,stale-if-error=60&fail=true";
+        let (status, content1) = fetch(&service, url, "en").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(content0, content1);
+
+        let url = "http://127.0.0.1:5801/?cc=max-age=60";
+        let (_, content2) = fetch(&service, url, "en").await;
+        assert_ne!(content0, content2);
+    }
+
+    static CALLS: std::sync::atomic::AtomicUsize = std::sync::atomic::AtomicUsize::new(0);
+
+    #[handler]
+    async fn slow(res: &mut Response) {
+        CALLS.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        res.add_header(CACHE_CONTROL, "max-age=60", true).unwrap();
+        res.render(format!("{}", OffsetDateTime::now_utc()));
+    }
+
+    #[tokio::test]
+    async fn test_cache_coalesce() {
+        use std::sync::atomic::Ordering;
+
+        let cache =
+            Cache::new(MokaStore::new(100), RequestIssuer::new()).coalesce(Duration::from_secs(5));
+        let service = Arc::new(Service::new(Router::new().hoop(cache).goal(slow)));
+        let tasks = (0..5).map(|_| {
+            let service = service.clone();
+            tokio::spawn(async move {
+                TestClient::get("http://127.0.0.1:5801/?coalesce")
+                    .send(&*service)
+                    .await
+                    .take_string()
+                    .await
+                    .unwrap()
+            })
+        });
+        let before = CALLS.load(Ordering::SeqCst);
+        let mut contents = Vec::new();
+        for task in tasks.collect::<Vec<_>>() {
+            contents.push(task.await.unwrap());
+        }
+        assert_eq!(CALLS.load(Ordering::SeqCst) - before, 1);
+        assert!(contents.windows(2).all(|w| w[0] == w[1]));
+
+        let cache = Cache::new(MokaStore::new(100), RequestIssuer::new()).early_refresh(1e9);
+        let service = Service::new(Router::new().hoop(cache).goal(slow));
+        let url = "http://127.0.0.1:5801/?early";
+        let content0 = TestClient::get(url)
+            .send(&service)
+            .await
+            .take_string()
+            .await
+            .unwrap();
+        let content1 = TestClient::get(url)
+            .send(&service)
+            .await
+            .take_string()
+            .await
+            .unwrap();
+        assert_ne!(content0, content1);
+    }
+
+    #[handler]
+    async fn tagged(req: &mut Request, depot: &mut Depot) -> String {
+        if let Some(tag) = req.query::<String>("tag") {
+            depot.add_cache_tag(tag);
+        }
+        format!("{}", OffsetDateTime::now_utc())
+    }
+
+    #[tokio::test]
+    async fn test_cache_purge() {
+        let store = MokaStore::new(100);
+        let router = Router::new()
+            .push(Router::with_path("purge").delete(CachePurger::new(store.clone())))
+            .push(
+                Router::with_path("{**rest}")
+                    .hoop(
+                        Cache::new(
+                            store,
+                            RequestIssuer::new().use_scheme(false).use_authority(false),
+                        )
+                        .status_header(StatusHeader::CacheStatus("salvo".into())),
+                    )
+                    .goal(tagged),
+            );
+        let service = Service::new(router);
+
+        let url = "http://127.0.0.1:5801/article?tag=article-1";
+        let mut res = TestClient::get(url).send(&service).await;
+        assert_eq!(
+            res.headers().get(CACHE_STATUS).unwrap(),
+            "salvo; fwd=uri-miss; fwd-status=200; stored"
+        );
+        let content0 = res.take_string().await.unwrap();
+        let res = TestClient::get(url).send(&service).await;
+        assert!(res.headers()[CACHE_STATUS]
+            .to_str()
+            .unwrap()
+            .starts_with("salvo; hit; ttl="));
+
+        let res = TestClient::delete("http://127.0.0.1:5801/purge?tag=article-1")
+            .send(&service)
+            .await;
+        assert_eq!(res.status_code.unwrap(), StatusCode::NO_CONTENT);
+        let content1 = TestClient::get(url)
+            .send(&service)
+            .await
+            .take_string()
+            .await
+            .unwrap();
+        assert_ne!(content0, content1);
+
+        let res = TestClient::delete("http://127.0.0.1:5801/purge?key=/article?tag=article-1|GET")
+            .send(&service)
+            .await;
+        assert_eq!(res.status_code.unwrap(), StatusCode::NO_CONTENT);
+        let content2 = TestClient::get(url)
+            .send(&service)
+            .await
+            .take_string()
+            .await
+            .unwrap();
+        assert_ne!(content1, content2);
+
+        let res = TestClient::delete("http://127.0.0.1:5801/purge")
+            .send(&service)
+            .await;
+        assert_eq!(res.status_code.unwrap(), StatusCode::BAD_REQUEST);
+    }
+
+    #[handler]
+    async fn report(req: &mut Request, res: &mut Response) {
+        let fail = req.query::<bool>("fail").unwrap_or_default();
+        let now = OffsetDateTime::now_utc().to_string();
+        let chunks: Vec<Result<String, std::io::Error>> = if fail {
+            vec![Ok(now), Err(std::io::Error::other("broken"))]
+        } else {
+            vec![Ok("report ".to_owned()), Ok(now)]
+        };
+        res.add_header(CACHE_CONTROL, "max-age=60", true).unwrap();
+        res.stream(tokio_stream::iter(chunks));
+    }
+
+    #[cfg(feature = "disk-store")]
+    #[tokio::test]
+    async fn test_cache_stream() {
+        let root = std::env::temp_dir().join(format!("salvo-cache-stream-{}", std::process::id()));
+        let store = DiskStore::new(&root, 1024 * 1024).unwrap();
+        let cache = Cache::new(store.clone(), RequestIssuer::new())
+            .status_header(StatusHeader::CacheStatus("salvo".into()));
+        let service = Service::new(Router::new().hoop(cache).goal(report));
+
+        let url = "http://127.0.0.1:5801/?report";
+        let content0 = TestClient::get(url)
+            .send(&service)
+            .await
+            .take_string()
+            .await
+            .unwrap();
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        // The body is stored without waiting for another request.
+        assert!(store.size() > 0);
+        let mut res = TestClient::get(url).send(&service).await;
+        assert!(res.headers()[CACHE_STATUS]
+            .to_str()
+            .unwrap()
+            .contains("hit"));
+        assert_eq!(res.take_string().await.unwrap(), content0);
+
+        let url = "http://127.0.0.1:5801/?report&fail=true";
+        let _ = TestClient::get(url)
+            .send(&service)
+            .await
+            .take_string()
+            .await;
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        let res = TestClient::get(url).send(&service).await;
+        assert!(res.headers()[CACHE_STATUS]
+            .to_str()
+            .unwrap()
+            .contains("uri-miss"));
+
+        std::fs::remove_dir_all(root).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_cache_stream_in_memory() {
+        let store = MokaStore::new(100);
+        let issuer = |req: &mut Request, _: &Depot| Some(req.uri().to_string());
+        let cache = Cache::new(store.clone(), issuer).max_stream_size(64);
+        let service = Service::new(Router::new().hoop(cache).goal(report));
+
+        let url = "http://127.0.0.1:5801/?report";
+        let content = TestClient::get(url)
+            .send(&service)
+            .await
+            .take_string()
+            .await
+            .unwrap();
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        let entry = store.load_entry(&url.to_owned()).await.unwrap();
+        let CachedBody::Chunks(chunks) = entry.body() else {
+            panic!("body is not collected");
+        };
+        let body = chunks
+            .iter()
+            .flat_map(|chunk| chunk.to_vec())
+            .collect::<Vec<_>>();
+        assert_eq!(body, content.as_bytes());
+
+        // Failed bodies are not stored.
+        let url = "http://127.0.0.1:5801/?report&fail=true";
+        let _ = TestClient::get(url)
+            .send(&service)
+            .await
+            .take_string()
+            .await;
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert!(store.load_entry(&url.to_owned()).await.is_none());
+    }
 }
@@ -3,11 +3,12 @@ use std::borrow::Borrow;
 use std::convert::Infallible;
 use std::hash::Hash;
 use std::sync::Arc;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
 use moka::future::Cache as MokaCache;
 use moka::future::CacheBuilder as MokaCacheBuilder;
 use moka::notification::RemovalCause;
+use moka::Expiry;
 
 use super::{CacheStore, CachedEntry};
 
@@ -49,7 +50,7 @@ where
     /// Sets the time to live of the cache.
     ///
     /// A cached entry will be expired after the specified duration past from
-    /// `insert`.
+    /// `insert`, or earlier if its own [`CachedEntry::time_to_live`] is shorter.
     ///
     /// # Panics
     ///
@@ -90,7 +91,33 @@ where
         }
     }
 }
+/// Expire entries after their own time to live, which is derived from response headers.
+struct EntryExpiry;
+impl<K> Expiry<K, CachedEntry> for EntryExpiry {
+    fn expire_after_create(
+        &self,
+        _key: &K,
+        value: &CachedEntry,
+        _created_at: Instant,
+    ) -> Option<Duration> {
+        value.time_to_live()
+    }
+
+    fn expire_after_update(
+        &self,
+        _key: &K,
+        value: &CachedEntry,
+        _updated_at: Instant,
+        _duration_until_expiry: Option<Duration>,
+    ) -> Option<Duration> {
+        value.time_to_live()
+    }
+}
+
 /// A simple in-memory store for rate limiter.
+///
+/// Cloning a `MokaStore` is cheap, clones share the same entries.
+#[derive(Clone)]
 pub struct MokaStore<K> {
     inner: MokaCache<K, CachedEntry>,
 }
@@ -101,14 +128,20 @@ where
     /// Create a new `MokaStore`.
     pub fn new(max_capacity: u64) -> Self {
         Self {
-            inner: MokaCache::new(max_capacity),
+            inner: MokaCache::builder()
+                .max_capacity(max_capacity)
+                .expire_after(EntryExpiry)
+                .support_invalidation_closures()
+                .build(),
         }
     }
 
     /// Returns a [`Builder`], which can builds a `MokaStore`
     pub fn builder() -> Builder<K> {
         Builder {
-            inner: MokaCache::builder(),
+            inner: MokaCache::builder()
+                .expire_after(EntryExpiry)
+                .support_invalidation_closures(),
         }
     }
 }
@@ -132,4 +165,24 @@ where
         self.inner.insert(key, entry).await;
         Ok(())
     }
+
+    async fn delete_entry<Q>(&self, key: &Q) -> Result<(), Self::Error>
+    where
+        Self::Key: Borrow<Q>,
+        Q: Hash + Eq + Sync,
+    {
+        self.inner.invalidate(key).await;
+        Ok(())
+    }
+
+    async fn purge_tag(&self, tag: &str) -> Result<(), Self::Error> {
+        let tag = tag.to_owned();
+        if let Err(e) = self
+            .inner
+            .invalidate_entries_if(move |_, entry| entry.has_tag(&tag))
+        {
+            tracing::error!(error = ?e, "purge cache tag failed");
+        }
+        Ok(())
+    }
 }
@@ -0,0 +1,77 @@
+use std::str::FromStr;
+
+use salvo_core::http::StatusCode;
+use salvo_core::{async_trait, Depot, FlowCtrl, Handler, Request, Response};
+
+use crate::CacheStore;
+
+/// Admin handler to purge cached entries by key or by tag.
+///
+/// The key is read from the `key` query parameter and the tag from the `tag` query parameter. It responds
+/// `204 No Content` when entries are purged and `400 Bad Request` when neither is given.
+///
+/// This handler does not authenticate requests, it should be mounted behind an authentication middleware.
+///
+/// # Example
+///
+/// ```
+/// use salvo_core::Router;
+/// use salvo_cache::{Cache, CachePurger, MokaStore, RequestIssuer};
+///
+/// let store = MokaStore::new(1000);
+/// let router = Router::new()
+///     .push(Router::with_path("admin/cache").delete(CachePurger::new(store.clone())))
+///     .push(Router::with_path("articles").hoop(Cache::new(store, RequestIssuer::default())));
+/// ```
+#[derive(Clone, Debug)]
+pub struct CachePurger<S> {
+    store: S,
+}
+impl<S> CachePurger<S> {
+    /// Create a new `CachePurger` with the store, which should share entries with the store of the [`Cache`](crate::Cache).
+    #[inline]
+    pub fn new(store: S) -> Self {
+        Self { store }
+    }
+}
+
+#[async_trait]
+impl<S> Handler for CachePurger<S>
+where
+    S: CacheStore,
+    S::Key: FromStr + Sync,
+{
+    async fn handle(
+        &self,
+        req: &mut Request,
+        _depot: &mut Depot,
+        res: &mut Response,
+        _ctrl: &mut FlowCtrl,
+    ) {
+        let key = req.query::<String>("key");
+        let tag = req.query::<String>("tag");
+        if key.is_none() && tag.is_none() {
+            res.status_code(StatusCode::BAD_REQUEST);
+            return;
+        }
+        if let Some(key) = key {
+            let Ok(key) = key.parse::<S::Key>() else {
+                res.status_code(StatusCode::BAD_REQUEST);
+                return;
+            };
+            if let Err(e) = self.store.delete_entry(&key).await {
+                tracing::error!(error = ?e, "delete cache entry failed");
+                res.status_code(StatusCode::INTERNAL_SERVER_ERROR);
+                return;
+            }
+        }
+        if let Some(tag) = tag {
+            if let Err(e) = self.store.purge_tag(&tag).await {
+                tracing::error!(error = ?e, "purge cache tag failed");
+                res.status_code(StatusCode::INTERNAL_SERVER_ERROR);
+                return;
+            }
+        }
+        res.status_code(StatusCode::NO_CONTENT);
+    }
+}
@@ -84,6 +84,12 @@ pub trait Filter: Debug + Send + Sync + 'static {
         }
     }
 
+    /// Returns this filter as a [`MethodFilter`] if it is one, so that the method can be inspected.
+    #[inline]
+    fn as_method_filter(&self) -> Option<&MethodFilter> {
+        None
+    }
+
     /// Filter `Request` and returns false or true.
     async fn filter(&self, req: &mut Request, path: &mut PathState) -> bool;
 }
@@ -184,6 +190,14 @@ mod tests {
         assert!(patch() == MethodFilter(Method::PATCH));
         assert!(put() == MethodFilter(Method::PUT));
         assert!(delete() == MethodFilter(Method::DELETE));
+
+        let filter: Box<dyn Filter> = Box::new(put());
+        assert_eq!(
+            filter.as_method_filter().map(MethodFilter::method),
+            Some(&Method::PUT)
+        );
+        let filter: Box<dyn Filter> = Box::new(FnFilter(|_: &mut Request, _: &mut PathState| true));
+        assert!(filter.as_method_filter().is_none());
     }
 
     #[tokio::test]
@@ -13,10 +13,21 @@ impl MethodFilter {
     pub fn new(method: Method) -> Self {
         Self(method)
     }
+
+    /// Get the method to filter.
+    #[inline]
+    pub fn method(&self) -> &Method {
+        &self.0
+    }
 }
 
 #[async_trait]
 impl Filter for MethodFilter {
+    #[inline]
+    fn as_method_filter(&self) -> Option<&MethodFilter> {
+        Some(self)
+    }
+
     #[inline]
     async fn filter(&self, req: &mut Request, _state: &mut PathState) -> bool {
         req.method() == self.0
@@ -0,0 +1,229 @@
+//! Middleware for API key authentication.
+//!
+//! The API key can be read from request headers, query parameters or the `Authorization` header with a custom
+//! scheme. Sources are checked in order and the first present key is passed to the [`ApiKeyValidator`].
+//!
+//! # Example
+//!
+//! ```no_run
+//! use salvo_core::prelude::*;
+//! use salvo_extra::api_key::{ApiKeyAuth, ApiKeySource, ApiKeyValidator};
+//!
+//! struct Validator;
+//! impl ApiKeyValidator for Validator {
+//!     async fn validate(&self, key: &str, _depot: &mut Depot) -> bool {
+//!         key == "secret-key"
+//!     }
+//! }
+//!
+//! #[handler]
+//! async fn hello() -> &'static str {
+//!     "Hello"
+//! }
+//!
+//! #[tokio::main]
+//! async fn main() {
+//!     let auth_handler = ApiKeyAuth::new(Validator).add_source(ApiKeySource::query("api_key"));
+//!     let router = Router::with_hoop(auth_handler).goal(hello);
+//!
+//!     let acceptor = TcpListener::new("0.0.0.0:5800").bind().await;
+//!     Server::new(acceptor).serve(router).await;
+//! }
+//! ```
+use std::future::Future;
+
+use salvo_core::http::header::{HeaderName, AUTHORIZATION};
+use salvo_core::http::{Request, Response, StatusCode};
+use salvo_core::{async_trait, Depot, FlowCtrl, Handler};
+
+/// key used when insert into depot.
+pub const API_KEY_KEY: &str = "::salvo::api_key::key";
+
+/// ApiKeyValidator
+pub trait ApiKeyValidator: Send + Sync {
+    /// Validate is that the API key is right.
+    fn validate(&self, key: &str, depot: &mut Depot) -> impl Future<Output = bool> + Send;
+}
+
+/// ApiKeyDepotExt
+pub trait ApiKeyDepotExt {
+    /// Get the validated API key reference.
+    fn api_key(&self) -> Option<&str>;
+}
+
+impl ApiKeyDepotExt for Depot {
+    fn api_key(&self) -> Option<&str> {
+        self.get::<String>(API_KEY_KEY).map(|v| &**v).ok()
+    }
+}
+
+/// Where to find the API key in request.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ApiKeySource {
+    /// Read from a request header, such as `x-api-key`.
+    Header(HeaderName),
+    /// Read from a query parameter.
+    Query(String),
+    /// Read from the `Authorization` header with the given scheme, such as `ApiKey <key>`.
+    Authorization(String),
+}
+impl ApiKeySource {
+    /// Create a header source.
+    #[inline]
+    pub fn header(name: HeaderName) -> Self {
+        Self::Header(name)
+    }
+    /// Create a query parameter source.
+    #[inline]
+    pub fn query(name: impl Into<String>) -> Self {
+        Self::Query(name.into())
+    }
+    /// Create an `Authorization` header source with the given scheme.
+    #[inline]
+    pub fn authorization(scheme: impl Into<String>) -> Self {
+        Self::Authorization(scheme.into())
+    }
+
+    /// Read the API key from request.
+    pub fn extract(&self, req: &Request) -> Option<String> {
+        let key = match self {
+            Self::Header(name) => req
+                .headers()
+                .get(name)
+                .and_then(|value| value.to_str().ok())
+                .map(|value| value.trim().to_owned()),
+            Self::Query(name) => req.queries().get(name).cloned(),
+            Self::Authorization(scheme) => req
+                .headers()
+                .get(AUTHORIZATION)
+                .and_then(|value| value.to_str().ok())
+                .and_then(|value| value.split_once(' '))
+                .filter(|(s, _)| s.eq_ignore_ascii_case(scheme))
+                .map(|(_, key)| key.trim().to_owned()),
+        };
+        key.filter(|key| !key.is_empty())
+    }
+}
+
+/// ApiKeyAuth
+pub struct ApiKeyAuth<V: ApiKeyValidator> {
+    sources: Vec<ApiKeySource>,
+    validator: V,
+}
+
+impl<V> ApiKeyAuth<V>
+where
+    V: ApiKeyValidator,
+{
+    /// Create new `ApiKeyAuth`, the default source is the `x-api-key` header.
+    #[inline]
+    pub fn new(validator: V) -> Self {
+        ApiKeyAuth {
+            sources: vec![ApiKeySource::Header(HeaderName::from_static("x-api-key"))],
+            validator,
+        }
+    }
+
+    /// Set the sources of API key, replacing the default ones.
+    #[inline]
+    pub fn sources(mut self, sources: impl Into<Vec<ApiKeySource>>) -> Self {
+        self.sources = sources.into();
+        self
+    }
+
+    /// Add a source of API key.
+    #[inline]
+    pub fn add_source(mut self, source: ApiKeySource) -> Self {
+        self.sources.push(source);
+        self
+    }
+
+    /// Get the sources of API key.
+    #[inline]
+    pub fn sources_mut(&mut self) -> &mut Vec<ApiKeySource> {
+        &mut self.sources
+    }
+}
+
+#[async_trait]
+impl<V> Handler for ApiKeyAuth<V>
+where
+    V: ApiKeyValidator + 'static,
+{
+    async fn handle(&self, req: &mut Request, depot: &mut Depot, res: &mut Response, ctrl: &mut FlowCtrl) {
+        if let Some(key) = self.sources.iter().find_map(|source| source.extract(req)) {
+            if self.validator.validate(&key, depot).await {
+                depot.insert(API_KEY_KEY, key);
+                ctrl.call_next(req, depot, res).await;
+                return;
+            }
+        }
+        res.status_code(StatusCode::UNAUTHORIZED);
+        ctrl.skip_rest();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use salvo_core::prelude::*;
+    use salvo_core::test::{ResponseExt, TestClient};
+
+    use super::*;
+
+    #[handler]
+    async fn hello(depot: &mut Depot) -> String {
+        format!("Hello {}", depot.api_key().unwrap_or_default())
+    }
+
+    struct Validator;
+    impl ApiKeyValidator for Validator {
+        async fn validate(&self, key: &str, _depot: &mut Depot) -> bool {
+            key == "secret"
+        }
+    }
+
+    #[tokio::test]
+    async fn test_api_key_auth() {
+        let auth_handler = ApiKeyAuth::new(Validator)
+            .add_source(ApiKeySource::query("api_key"))
+            .add_source(ApiKeySource::authorization("ApiKey"));
+        let router = Router::with_hoop(auth_handler).goal(hello);
+        let service = Service::new(router);
+
+        let content = TestClient::get("http://127.0.0.1:5800/")
+            .add_header("x-api-key", "secret", true)
+            .send(&service)
+            .await
+            .take_string()
+            .await
+            .unwrap();
+        assert_eq!(content, "Hello secret");
+
+        let content = TestClient::get("http://127.0.0.1:5800/?api_key=secret")
+            .send(&service)
+            .await
+            .take_string()
+            .await
+            .unwrap();
+        assert_eq!(content, "Hello secret");
+
+        let content = TestClient::get("http://127.0.0.1:5800/")
+            .add_header("authorization", "ApiKey secret", true)
+            .send(&service)
+            .await
+            .take_string()
+            .await
+            .unwrap();
+        assert_eq!(content, "Hello secret");
+
+        let res = TestClient::get("http://127.0.0.1:5800/")
+            .add_header("x-api-key", "wrong", true)
+            .send(&service)
+            .await;
+        assert_eq!(res.status_code, Some(StatusCode::UNAUTHORIZED));
+
+        let res = TestClient::get("http://127.0.0.1:5800/").send(&service).await;
+        assert_eq!(res.status_code, Some(StatusCode::UNAUTHORIZED));
+    }
+}
@@ -0,0 +1,648 @@
+//! Middleware for capturing request and response bodies, used for debugging and audit logging.
+//!
+//! [`BodyCapture`] tees the request and response bodies into bounded buffers while they are streamed, so the
+//! handler and client still see the original data. `Once`, `Chunks`, streaming and channel bodies are all
+//! supported. When the response body is finished, or dropped because the client went away, the captured
+//! [`CapturedExchange`] is redacted and handed to a [`CaptureSink`].
+//!
+//! The request body is only captured as far as the handler reads it.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use salvo_core::prelude::*;
+//! use salvo_extra::body_capture::{BodyCapture, TracingSink};
+//!
+//! #[handler]
+//! async fn hello() -> &'static str {
+//!     "Hello World"
+//! }
+//!
+//! #[tokio::main]
+//! async fn main() {
+//!     let capture = BodyCapture::new(TracingSink)
+//!         .redact_header("x-api-key")
+//!         .redact_field("password")
+//!         .sample_rate(0.1);
+//!     let router = Router::with_path("partner").hoop(capture).post(hello);
+//!
+//!     let acceptor = TcpListener::new("0.0.0.0:5800").bind().await;
+//!     Server::new(acceptor).serve(router).await;
+//! }
+//! ```
+use std::fmt::{self, Debug, Formatter};
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::path::Path;
+use std::pin::Pin;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
+
+use salvo_core::http::body::{Body, Frame, SizeHint};
+use salvo_core::http::header::{HeaderName, HeaderValue, CONTENT_TYPE};
+use salvo_core::http::uri::Uri;
+use salvo_core::http::{HeaderMap, Method, ReqBody, Request, ResBody, Response, StatusCode};
+use salvo_core::hyper::body::Bytes;
+use salvo_core::{async_trait, BoxedError, Depot, Error, FlowCtrl, Handler};
+use serde_json::Value;
+use tokio::sync::mpsc;
+
+/// Replacement of redacted header values and JSON fields.
+pub const REDACTED: &str = "[REDACTED]";
+
+/// A captured request or response body.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct CapturedBody {
+    /// Captured data, at most the configured max size.
+    pub data: Bytes,
+    /// Total size of data seen, including data not kept because of the size cap.
+    pub size: u64,
+    /// Data was cut because it exceeded the max size.
+    pub truncated: bool,
+    /// The end of the body was reached.
+    pub complete: bool,
+}
+impl CapturedBody {
+    /// Get the data as text, invalid UTF-8 sequences are replaced.
+    pub fn text(&self) -> std::borrow::Cow<'_, str> {
+        String::from_utf8_lossy(&self.data)
+    }
+}
+
+/// A captured request and response pair.
+#[derive(Clone, Debug)]
+#[non_exhaustive]
+pub struct CapturedExchange {
+    /// Request method.
+    pub method: Method,
+    /// Request uri.
+    pub uri: Uri,
+    /// Remote address of the request.
+    pub remote_addr: String,
+    /// Request headers, redacted.
+    pub request_headers: HeaderMap,
+    /// Request body, redacted.
+    pub request_body: CapturedBody,
+    /// Response status code.
+    pub status: StatusCode,
+    /// Response headers, redacted.
+    pub response_headers: HeaderMap,
+    /// Response body, redacted.
+    pub response_body: CapturedBody,
+    /// Time from receiving the request to the end of the response body.
+    pub duration: Duration,
+}
+impl CapturedExchange {
+    /// Convert to a JSON value, bodies are written as text.
+    pub fn to_json(&self) -> Value {
+        fn headers(headers: &HeaderMap) -> Value {
+            headers
+                .iter()
+                .map(|(name, value)| {
+                    let value = String::from_utf8_lossy(value.as_bytes()).into_owned();
+                    (name.as_str().to_owned(), Value::String(value))
+                })
+                .collect::<serde_json::Map<_, _>>()
+                .into()
+        }
+        fn body(body: &CapturedBody) -> Value {
+            serde_json::json!({
+                "data": body.text(),
+                "size": body.size,
+                "truncated": body.truncated,
+                "complete": body.complete,
+            })
+        }
+        serde_json::json!({
+            "method": self.method.as_str(),
+            "uri": self.uri.to_string(),
+            "remote_addr": self.remote_addr,
+            "request_headers": headers(&self.request_headers),
+            "request_body": body(&self.request_body),
+            "status": self.status.as_u16(),
+            "response_headers": headers(&self.response_headers),
+            "response_body": body(&self.response_body),
+            "duration_ms": self.duration.as_millis() as u64,
+        })
+    }
+}
+
+/// Receiver of captured exchanges.
+///
+/// `record` is called from the response body when it finishes, so it should not block for long.
+pub trait CaptureSink: Send + Sync + 'static {
+    /// Record a captured exchange.
+    fn record(&self, exchange: CapturedExchange);
+}
+impl<F> CaptureSink for F
+where
+    F: Fn(CapturedExchange) + Send + Sync + 'static,
+{
+    fn record(&self, exchange: CapturedExchange) {
+        self(exchange)
+    }
+}
+
+/// Sink which logs captured exchanges with `tracing` at info level.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct TracingSink;
+impl CaptureSink for TracingSink {
+    fn record(&self, exchange: CapturedExchange) {
+        tracing::info!(
+            method = %exchange.method,
+            uri = %exchange.uri,
+            status = %exchange.status,
+            duration = ?exchange.duration,
+            request_body = %exchange.request_body.text(),
+            response_body = %exchange.response_body.text(),
+            "captured exchange"
+        );
+    }
+}
+
+/// Sink which appends captured exchanges to a file as JSON lines.
+#[derive(Debug)]
+pub struct FileSink {
+    file: Mutex<File>,
+}
+impl FileSink {
+    /// Open the file in append mode, it is created if it does not exist.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, Error> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        Ok(Self { file: Mutex::new(file) })
+    }
+}
+impl CaptureSink for FileSink {
+    fn record(&self, exchange: CapturedExchange) {
+        let mut line = exchange.to_json().to_string();
+        line.push('\n');
+        let mut file = self.file.lock().unwrap_or_else(|e| e.into_inner());
+        if let Err(e) = file.write_all(line.as_bytes()) {
+            tracing::error!(error = ?e, "write captured exchange failed");
+        }
+    }
+}
+
+/// Sink which sends captured exchanges to a channel, exchanges are dropped when the channel is full.
+#[derive(Clone, Debug)]
+pub struct ChannelSink {
+    tx: mpsc::Sender<CapturedExchange>,
+}
+impl ChannelSink {
+    /// Create a new sink and the receiver of captured exchanges.
+    pub fn new(buffer: usize) -> (Self, mpsc::Receiver<CapturedExchange>) {
+        let (tx, rx) = mpsc::channel(buffer);
+        (Self { tx }, rx)
+    }
+}
+impl CaptureSink for ChannelSink {
+    fn record(&self, exchange: CapturedExchange) {
+        if self.tx.try_send(exchange).is_err() {
+            tracing::warn!("capture channel is full or closed, exchange dropped");
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+struct Redaction {
+    headers: Vec<HeaderName>,
+    fields: Vec<String>,
+}
+impl Redaction {
+    fn headers(&self, headers: &HeaderMap) -> HeaderMap {
+        let mut headers = headers.clone();
+        for name in &self.headers {
+            if headers.contains_key(name) {
+                headers.insert(name.clone(), HeaderValue::from_static(REDACTED));
+            }
+        }
+        headers
+    }
+
+    /// Redact JSON fields. Unparsable JSON bodies, for example truncated ones, are dropped entirely.
+    fn body(&self, headers: &HeaderMap, mut body: CapturedBody) -> CapturedBody {
+        let is_json = headers
+            .get(CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .is_some_and(|value| value.contains("json"));
+        if self.fields.is_empty() || !is_json || body.data.is_empty() {
+            return body;
+        }
+        body.data = match serde_json::from_slice::<Value>(&body.data) {
+            Ok(mut value) => {
+                self.value(&mut value);
+                Bytes::from(value.to_string())
+            }
+            Err(_) => Bytes::from_static(REDACTED.as_bytes()),
+        };
+        body
+    }
+
+    fn value(&self, value: &mut Value) {
+        match value {
+            Value::Object(map) => {
+                for (key, value) in map.iter_mut() {
+                    if self.fields.iter().any(|field| field.eq_ignore_ascii_case(key)) {
+                        *value = Value::String(REDACTED.to_owned());
+                    } else {
+                        self.value(value);
+                    }
+                }
+            }
+            Value::Array(items) => items.iter_mut().for_each(|item| self.value(item)),
+            _ => {}
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Buffer {
+    data: Vec<u8>,
+    limit: usize,
+    size: u64,
+    truncated: bool,
+    complete: bool,
+}
+impl Buffer {
+    fn new(limit: usize) -> Self {
+        Self {
+            data: Vec::new(),
+            limit,
+            size: 0,
+            truncated: false,
+            complete: false,
+        }
+    }
+    fn push(&mut self, chunk: &[u8]) {
+        self.size += chunk.len() as u64;
+        let room = self.limit.saturating_sub(self.data.len());
+        if chunk.len() > room {
+            self.truncated = true;
+        }
+        self.data.extend_from_slice(&chunk[..chunk.len().min(room)]);
+    }
+    fn to_body(&self) -> CapturedBody {
+        CapturedBody {
+            data: Bytes::copy_from_slice(&self.data),
+            size: self.size,
+            truncated: self.truncated,
+            complete: self.complete,
+        }
+    }
+}
+
+type SharedBuffer = Arc<Mutex<Buffer>>;
+
+fn lock(buffer: &SharedBuffer) -> std::sync::MutexGuard<'_, Buffer> {
+    buffer.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Body wrapper which copies data frames into a buffer.
+struct TeeBody<B> {
+    inner: B,
+    buffer: SharedBuffer,
+    // Records the exchange when the body is dropped.
+    _pending: Option<Pending>,
+}
+impl<B> Body for TeeBody<B>
+where
+    B: Body<Data = Bytes> + Unpin,
+    B::Error: Into<BoxedError>,
+{
+    type Data = Bytes;
+    type Error = BoxedError;
+
+    fn poll_frame(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Result<Frame<Self::Data>, Self::Error>>> {
+        match Pin::new(&mut self.inner).poll_frame(cx) {
+            Poll::Ready(Some(Ok(frame))) => {
+                if let Some(data) = frame.data_ref() {
+                    lock(&self.buffer).push(data);
+                }
+                Poll::Ready(Some(Ok(frame)))
+            }
+            Poll::Ready(Some(Err(e))) => Poll::Ready(Some(Err(e.into()))),
+            Poll::Ready(None) => {
+                lock(&self.buffer).complete = true;
+                Poll::Ready(None)
+            }
+            Poll::Pending => Poll::Pending,
+        }
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.inner.is_end_stream()
+    }
+
+    fn size_hint(&self) -> SizeHint {
+        self.inner.size_hint()
+    }
+}
+
+/// An exchange waiting for its response body to finish.
+struct Pending {
+    method: Method,
+    uri: Uri,
+    remote_addr: String,
+    request_headers: HeaderMap,
+    request_buffer: SharedBuffer,
+    status: StatusCode,
+    response_headers: HeaderMap,
+    response_buffer: SharedBuffer,
+    started: Instant,
+    redaction: Arc<Redaction>,
+    sink: Arc<dyn CaptureSink>,
+}
+impl Pending {
+    fn finish(&self) {
+        let request_body = lock(&self.request_buffer).to_body();
+        let response_body = lock(&self.response_buffer).to_body();
+        let exchange = CapturedExchange {
+            method: self.method.clone(),
+            uri: self.uri.clone(),
+            remote_addr: self.remote_addr.clone(),
+            request_headers: self.redaction.headers(&self.request_headers),
+            request_body: self.redaction.body(&self.request_headers, request_body),
+            status: self.status,
+            response_headers: self.redaction.headers(&self.response_headers),
+            response_body: self.redaction.body(&self.response_headers, response_body),
+            duration: self.started.elapsed(),
+        };
+        self.sink.record(exchange);
+    }
+}
+impl Drop for Pending {
+    fn drop(&mut self) {
+        self.finish();
+    }
+}
+
+/// Middleware for capturing request and response bodies.
+///
+/// View [module level documentation](index.html) for more details.
+pub struct BodyCapture {
+    sink: Arc<dyn CaptureSink>,
+    redaction: Arc<Redaction>,
+    max_size: usize,
+    sample_rate: f64,
+    #[allow(clippy::type_complexity)]
+    when: Option<Box<dyn Fn(&Request, &Depot) -> bool + Send + Sync>>,
+}
+impl Debug for BodyCapture {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        f.debug_struct("BodyCapture")
+            .field("redaction", &self.redaction)
+            .field("max_size", &self.max_size)
+            .field("sample_rate", &self.sample_rate)
+            .finish()
+    }
+}
+impl BodyCapture {
+    /// Create a new `BodyCapture` with the sink.
+    ///
+    /// By default every request is captured, and at most 64 KiB of each body is kept.
+    pub fn new(sink: impl CaptureSink) -> Self {
+        Self {
+            sink: Arc::new(sink),
+            redaction: Arc::new(Redaction::default()),
+            max_size: 64 * 1024,
+            sample_rate: 1.0,
+            when: None,
+        }
+    }
+
+    /// Set the max size of each captured body, the rest of the body is passed through without being kept.
+    pub fn max_size(mut self, size: usize) -> Self {
+        self.max_size = size;
+        self
+    }
+
+    /// Set the fraction of requests to capture, between `0.0` and `1.0`.
+    pub fn sample_rate(mut self, rate: f64) -> Self {
+        self.sample_rate = rate.clamp(0.0, 1.0);
+        self
+    }
+
+    /// Only capture requests for which the predicate returns `true`, for example from specific clients.
+    pub fn when(mut self, predicate: impl Fn(&Request, &Depot) -> bool + Send + Sync + 'static) -> Self {
+        self.when = Some(Box::new(predicate));
+        self
+    }
+
+    /// Replace the value of the header in captured requests and responses.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `name` is not a valid header name.
+    pub fn redact_header(mut self, name: impl AsRef<str>) -> Self {
+        let name = HeaderName::from_str(name.as_ref()).expect("Invalid header name.");
+        Arc::make_mut(&mut self.redaction).headers.push(name);
+        self
+    }
+
+    /// Replace the value of the field, at any depth, in captured JSON bodies.
+    pub fn redact_field(mut self, name: impl Into<String>) -> Self {
+        Arc::make_mut(&mut self.redaction).fields.push(name.into());
+        self
+    }
+
+    fn sampled(&self) -> bool {
+        self.sample_rate >= 1.0 || (self.sample_rate > 0.0 && rand::random::<f64>() < self.sample_rate)
+    }
+}
+
+#[async_trait]
+impl Handler for BodyCapture {
+    async fn handle(&self, req: &mut Request, depot: &mut Depot, res: &mut Response, ctrl: &mut FlowCtrl) {
+        if !self.sampled() || self.when.as_ref().is_some_and(|when| !when(req, depot)) {
+            return;
+        }
+        let started = Instant::now();
+
+        let request_buffer = Arc::new(Mutex::new(Buffer::new(self.max_size)));
+        match req.take_body() {
+            ReqBody::None => {
+                lock(&request_buffer).complete = true;
+            }
+            ReqBody::Once(bytes) => {
+                let mut buffer = lock(&request_buffer);
+                buffer.push(&bytes);
+                buffer.complete = true;
+                drop(buffer);
+                req.replace_body(ReqBody::Once(bytes));
+            }
+            body => {
+                req.replace_body(ReqBody::Boxed {
+                    inner: Box::pin(TeeBody {
+                        inner: body,
+                        buffer: request_buffer.clone(),
+                        _pending: None,
+                    }),
+                    fusewire: None,
+                });
+            }
+        }
+
+        ctrl.call_next(req, depot, res).await;
+
+        let status = res.status_code.unwrap_or(match &res.body {
+            ResBody::None => StatusCode::NOT_FOUND,
+            ResBody::Error(e) => e.code,
+            _ => StatusCode::OK,
+        });
+        let response_buffer = Arc::new(Mutex::new(Buffer::new(self.max_size)));
+        let pending = Pending {
+            method: req.method().clone(),
+            uri: req.uri().clone(),
+            remote_addr: req.remote_addr().to_string(),
+            request_headers: req.headers().clone(),
+            request_buffer,
+            status,
+            response_headers: res.headers().clone(),
+            response_buffer: response_buffer.clone(),
+            started,
+            redaction: self.redaction.clone(),
+            sink: self.sink.clone(),
+        };
+        match res.take_body() {
+            body @ (ResBody::None | ResBody::Error(_)) => {
+                lock(&response_buffer).complete = true;
+                res.replace_body(body);
+            }
+            ResBody::Once(bytes) => {
+                let mut buffer = lock(&response_buffer);
+                buffer.push(&bytes);
+                buffer.complete = true;
+                drop(buffer);
+                res.replace_body(ResBody::Once(bytes));
+            }
+            ResBody::Chunks(chunks) => {
+                let mut buffer = lock(&response_buffer);
+                chunks.iter().for_each(|chunk| buffer.push(chunk));
+                buffer.complete = true;
+                drop(buffer);
+                res.replace_body(ResBody::Chunks(chunks));
+            }
+            body => {
+                res.replace_body(ResBody::Boxed(Box::pin(TeeBody {
+                    inner: body,
+                    buffer: response_buffer,
+                    _pending: Some(pending),
+                })));
+                return;
+            }
+        }
+        // The exchange is recorded when `pending` is dropped.
+        drop(pending);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use salvo_core::prelude::*;
+    use salvo_core::test::{ResponseExt, TestClient};
+
+    use super::*;
+
+    #[handler]
+    async fn echo(req: &mut Request, res: &mut Response) {
+        let body = req.payload().await.unwrap().clone();
+        res.add_header(CONTENT_TYPE, "application/json", true).unwrap();
+        res.add_header("x-secret", "top", true).unwrap();
+        res.body(body);
+    }
+
+    #[handler]
+    async fn streaming(res: &mut Response) {
+        let chunks: Vec<Result<_, std::io::Error>> = vec![Ok("hello "), Ok("world"), Ok("!")];
+        res.stream(tokio_stream::iter(chunks));
+    }
+
+    #[handler]
+    async fn channel(res: &mut Response) {
+        let mut tx = res.channel();
+        tokio::spawn(async move {
+            tx.send_data("from ").await.unwrap();
+            tx.send_data("channel").await.unwrap();
+        });
+    }
+
+    #[tokio::test]
+    async fn test_body_capture() {
+        let (sink, mut rx) = ChannelSink::new(8);
+        let capture = BodyCapture::new(sink)
+            .redact_header("x-secret")
+            .redact_header("authorization")
+            .redact_field("password");
+        let router = Router::new()
+            .hoop(capture)
+            .push(Router::with_path("echo").post(echo))
+            .push(Router::with_path("stream").get(streaming))
+            .push(Router::with_path("channel").get(channel));
+        let service = Service::new(router);
+
+        let content = TestClient::post("http://127.0.0.1:5800/echo")
+            .add_header("authorization", "Bearer abc", true)
+            .json(&serde_json::json!({"user": {"name": "a", "password": "p"}}))
+            .send(&service)
+            .await
+            .take_string()
+            .await
+            .unwrap();
+        assert!(content.contains("\"password\":\"p\""));
+        let exchange = rx.recv().await.unwrap();
+        assert_eq!(exchange.status, StatusCode::OK);
+        assert_eq!(exchange.request_headers["authorization"], REDACTED);
+        assert_eq!(exchange.response_headers["x-secret"], REDACTED);
+        assert!(exchange.request_body.complete);
+        assert_eq!(exchange.request_body.text(), r#"{"user":{"name":"a","password":"[REDACTED]"}}"#);
+        assert_eq!(exchange.response_body.text(), r#"{"user":{"name":"a","password":"[REDACTED]"}}"#);
+
+        let content = TestClient::get("http://127.0.0.1:5800/stream")
+            .send(&service)
+            .await
+            .take_string()
+            .await
+            .unwrap();
+        assert_eq!(content, "hello world!");
+        let exchange = rx.recv().await.unwrap();
+        assert_eq!(exchange.response_body.text(), "hello world!");
+        assert!(exchange.response_body.complete);
+
+        let content = TestClient::get("http://127.0.0.1:5800/channel")
+            .send(&service)
+            .await
+            .take_string()
+            .await
+            .unwrap();
+        assert_eq!(content, "from channel");
+        let exchange = rx.recv().await.unwrap();
+        assert_eq!(exchange.response_body.text(), "from channel");
+    }
+
+    #[tokio::test]
+    async fn test_body_capture_limits() {
+        let (sink, mut rx) = ChannelSink::new(8);
+        let capture = BodyCapture::new(sink)
+            .max_size(5)
+            .when(|req, _| req.uri().path() != "/skip");
+        let router = Router::new().hoop(capture).push(Router::with_path("{*path}").get(streaming));
+        let service = Service::new(router);
+
+        TestClient::get("http://127.0.0.1:5800/skip").send(&service).await;
+        let mut res = TestClient::get("http://127.0.0.1:5800/take").send(&service).await;
+        assert_eq!(res.take_string().await.unwrap(), "hello world!");
+        let exchange = rx.recv().await.unwrap();
+        assert_eq!(exchange.uri.path(), "/take");
+        assert_eq!(exchange.response_body.text(), "hello");
+        assert_eq!(exchange.response_body.size, 12);
+        assert!(exchange.response_body.truncated);
+
+        let (sink, mut rx) = ChannelSink::new(8);
+        let router = Router::new().hoop(BodyCapture::new(sink).sample_rate(0.0)).get(streaming);
+        TestClient::get("http://127.0.0.1:5800/").send(router).await;
+        assert!(rx.try_recv().is_err());
+    }
+}
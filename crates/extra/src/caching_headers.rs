@@ -1,209 +1,538 @@
-//! Middleware for etag and last-modified-since headers.
-//!
-//! This crate provides three handlers: [`ETag`], [`Modified`], and [`CachingHeaders`].
-//! Unless you are sure that you _don't_ want either etag or last-modified
-//! behavior, please use the combined [`CachingHeaders`] handler.
-
-use etag::EntityTag;
-use salvo_core::http::header::{ETAG, IF_NONE_MATCH};
-use salvo_core::http::headers::{self, HeaderMapExt};
-use salvo_core::http::{ResBody, StatusCode};
-use salvo_core::{async_trait, Depot, FlowCtrl, Handler, Request, Response};
-
-/// Etag and If-None-Match header handler
-///
-/// Salvo handler that provides an outbound [`etag
-/// header`](https://developer.mozilla.org/en-US/docs/Web/HTTP/Headers/ETag)
-/// after other handlers have been run, and if the request includes an
-/// [`if-none-match`](https://developer.mozilla.org/en-US/docs/Web/HTTP/Headers/If-None-Match)
-/// header, compares these values and sends a
-/// [`304 not modified`](https://developer.mozilla.org/en-US/docs/Web/HTTP/Status/304) status,
-/// omitting the response body.
-///
-/// ## Streamed bodies
-///
-/// **Note**: This handler does not currently provide an etag trailer for
-/// streamed bodies, but may do so in the future.
-///
-/// ## Strong vs weak comparison
-///
-/// Etags can be compared using a strong method or a weak
-/// method. By default, this handler allows weak comparison. To change
-/// this setting, construct your handler with `Etag::new().strong()`.
-/// See [`etag::EntityTag`](https://docs.rs/etag/3.0.0/etag/struct.EntityTag.html#comparison)
-/// for further documentation.
-#[derive(Default, Clone, Copy, Debug)]
-pub struct ETag {
-    strong: bool,
-}
-
-impl ETag {
-    /// constructs a new Etag handler
-    pub fn new() -> Self {
-        Self::default()
-    }
-
-    /// Configures this handler to use strong content-based etag comparison only. See
-    /// [`etag::EntityTag`](https://docs.rs/etag/3.0.0/etag/struct.EntityTag.html#comparison)
-    /// for further documentation on the differences between strong
-    /// and weak etag comparison.
-    pub fn strong(mut self) -> Self {
-        self.strong = true;
-        self
-    }
-}
-
-#[async_trait]
-impl Handler for ETag {
-    async fn handle(&self, req: &mut Request, depot: &mut Depot, res: &mut Response, ctrl: &mut FlowCtrl) {
-        ctrl.call_next(req, depot, res).await;
-        if ctrl.is_ceased() {
-            return;
-        }
-
-        let if_none_match = req
-            .headers()
-            .get(IF_NONE_MATCH)
-            .and_then(|etag| etag.to_str().ok())
-            .and_then(|etag| etag.parse::<EntityTag>().ok());
-
-        let etag = req
-            .headers()
-            .get(ETAG)
-            .and_then(|etag| etag.to_str().ok())
-            .and_then(|etag| etag.parse().ok())
-            .or_else(|| {
-                let etag = match &res.body {
-                    ResBody::Once(bytes) => Some(EntityTag::from_data(bytes)),
-                    ResBody::Chunks(bytes) => {
-                        let tags = bytes
-                            .iter()
-                            .map(|item| EntityTag::from_data(item).tag().to_owned())
-                            .collect::<Vec<_>>()
-                            .concat();
-                        Some(EntityTag::from_data(tags.as_bytes()))
-                    }
-                    ResBody::Stream(_) => {
-                        tracing::debug!("etag not supported for streaming body");
-                        None
-                    }
-                    ResBody::None => {
-                        tracing::debug!("etag not supported for empty body");
-                        None
-                    }
-                    _ => None,
-                };
-
-                if let Some(etag) = &etag {
-                    match etag.to_string().parse::<headers::ETag>() {
-                        Ok(etag) => res.headers_mut().typed_insert(etag),
-                        Err(e) => {
-                            tracing::error!(error = ?e, "failed to parse etag");
-                        }
-                    }
-                }
-                etag
-            });
-
-        if let (Some(etag), Some(if_none_match)) = (etag, if_none_match) {
-            let eq = if self.strong {
-                etag.strong_eq(&if_none_match)
-            } else {
-                etag.weak_eq(&if_none_match)
-            };
-
-            if eq {
-                res.body(ResBody::None);
-                res.status_code(StatusCode::NOT_MODIFIED);
-            }
-        }
-    }
-}
-
-/// # A handler for the `Last-Modified` and `If-Modified-Since` header interaction.
-///
-/// This handler does not set a `Last-Modified` header on its own, but
-/// relies on other handlers doing so.
-#[derive(Clone, Debug, Copy, Default)]
-pub struct Modified {
-    _private: (),
-}
-
-impl Modified {
-    /// Constructs a new Modified handler
-    pub fn new() -> Self {
-        Self { _private: () }
-    }
-}
-
-#[async_trait]
-impl Handler for Modified {
-    async fn handle(&self, req: &mut Request, depot: &mut Depot, res: &mut Response, ctrl: &mut FlowCtrl) {
-        ctrl.call_next(req, depot, res).await;
-        if ctrl.is_ceased() {
-            return;
-        }
-
-        if let (Some(if_modified_since), Some(last_modified)) = (
-            req.headers().typed_get::<headers::IfModifiedSince>(),
-            res.headers().typed_get::<headers::LastModified>(),
-        ) {
-            if !if_modified_since.is_modified(last_modified.into()) {
-                res.body(ResBody::None);
-                res.status_code(StatusCode::NOT_MODIFIED);
-            }
-        }
-    }
-}
-
-/// A combined handler that provides both [`ETag`] and [`Modified`] behavior.
-#[derive(Clone, Debug, Copy, Default)]
-pub struct CachingHeaders(Modified, ETag);
-
-impl CachingHeaders {
-    /// Constructs a new combination modified and etag handler
-    pub fn new() -> Self {
-        Self::default()
-    }
-}
-
-#[async_trait]
-impl Handler for CachingHeaders {
-    async fn handle(&self, req: &mut Request, depot: &mut Depot, res: &mut Response, ctrl: &mut FlowCtrl) {
-        self.0.handle(req, depot, res, ctrl).await;
-        if res.status_code != Some(StatusCode::NOT_MODIFIED) {
-            self.1.handle(req, depot, res, ctrl).await;
-        }
-    }
-}
-
-#[cfg(test)]
-mod tests {
-    use salvo_core::http::header::*;
-    use salvo_core::prelude::*;
-    use salvo_core::test::TestClient;
-
-    use super::*;
-
-    #[handler]
-    async fn hello() -> &'static str {
-        "Hello World"
-    }
-
-    #[tokio::test]
-    async fn test_affix() {
-        let router = Router::with_hoop(CachingHeaders::new()).get(hello);
-        let service = Service::new(router);
-
-        let respone = TestClient::get("http://127.0.0.1:5800/").send(&service).await;
-        assert_eq!(respone.status_code, Some(StatusCode::OK));
-
-        let etag = respone.headers().get(ETAG).unwrap();
-        let respone = TestClient::get("http://127.0.0.1:5800/")
-            .add_header(IF_NONE_MATCH, etag, true)
-            .send(&service)
-            .await;
-        assert_eq!(respone.status_code, Some(StatusCode::NOT_MODIFIED));
-        assert!(respone.body.is_none());
-    }
-}
+//! Middleware for etag and last-modified-since headers.
+//!
+//! This crate provides three handlers: [`ETag`], [`Modified`], and [`CachingHeaders`].
+//! Unless you are sure that you _don't_ want either etag or last-modified
+//! behavior, please use the combined [`CachingHeaders`] handler.
+//!
+//! Handlers running before these middlewares can supply the validators of the current version of a
+//! resource with [`CachingHeadersDepotExt`], so that `304 Not Modified` is answered before the expensive
+//! handlers run.
+//!
+//! Only `GET` and `HEAD` requests are answered with `304 Not Modified`. Conditional requests with other
+//! methods are passed through, use [`Preconditions`](crate::precondition::Preconditions) to answer them with
+//! `412 Precondition Failed`.
+
+use std::collections::VecDeque;
+use std::io::Error as IoError;
+use std::time::SystemTime;
+
+use etag::EntityTag;
+use futures_util::stream::{self, StreamExt, TryStreamExt};
+use salvo_core::http::body::{BytesFrame, Frame};
+use salvo_core::http::header::{ETAG, IF_NONE_MATCH};
+use salvo_core::http::headers::{self, HeaderMapExt};
+use salvo_core::http::{Method, ResBody, StatusCode};
+use salvo_core::hyper::body::Bytes;
+use salvo_core::{async_trait, Depot, FlowCtrl, Handler, Request, Response};
+
+/// Key for the etag supplied by handlers in depot.
+pub const ETAG_KEY: &str = "::salvo::caching_headers::etag";
+/// Key for the last modified time supplied by handlers in depot.
+pub const LAST_MODIFIED_KEY: &str = "::salvo::caching_headers::last_modified";
+
+/// Extension for Depot.
+pub trait CachingHeadersDepotExt {
+    /// Set the etag of the current version of the resource, such as a hash of its version number.
+    fn set_etag(&mut self, etag: EntityTag) -> &mut Self;
+    /// Get the etag set by [`set_etag`](CachingHeadersDepotExt::set_etag).
+    fn etag(&self) -> Option<&EntityTag>;
+    /// Set the last modified time of the current version of the resource.
+    fn set_last_modified(&mut self, last_modified: SystemTime) -> &mut Self;
+    /// Get the time set by [`set_last_modified`](CachingHeadersDepotExt::set_last_modified).
+    fn last_modified(&self) -> Option<SystemTime>;
+}
+
+impl CachingHeadersDepotExt for Depot {
+    #[inline]
+    fn set_etag(&mut self, etag: EntityTag) -> &mut Self {
+        self.insert(ETAG_KEY, etag)
+    }
+    #[inline]
+    fn etag(&self) -> Option<&EntityTag> {
+        self.get::<EntityTag>(ETAG_KEY).ok()
+    }
+    #[inline]
+    fn set_last_modified(&mut self, last_modified: SystemTime) -> &mut Self {
+        self.insert(LAST_MODIFIED_KEY, last_modified)
+    }
+    #[inline]
+    fn last_modified(&self) -> Option<SystemTime> {
+        self.get::<SystemTime>(LAST_MODIFIED_KEY).ok().copied()
+    }
+}
+
+/// `304 Not Modified` is only allowed for `GET` and `HEAD` requests.
+fn is_cacheable(req: &Request) -> bool {
+    matches!(*req.method(), Method::GET | Method::HEAD)
+}
+
+fn not_modified(res: &mut Response) {
+    res.body(ResBody::None);
+    res.status_code(StatusCode::NOT_MODIFIED);
+}
+
+/// Etag and If-None-Match header handler
+///
+/// Salvo handler that provides an outbound [`etag
+/// header`](https://developer.mozilla.org/en-US/docs/Web/HTTP/Headers/ETag)
+/// after other handlers have been run, and if the request includes an
+/// [`if-none-match`](https://developer.mozilla.org/en-US/docs/Web/HTTP/Headers/If-None-Match)
+/// header, compares these values and sends a
+/// [`304 not modified`](https://developer.mozilla.org/en-US/docs/Web/HTTP/Status/304) status,
+/// omitting the response body.
+///
+/// The etag is taken from the response `ETag` header, then from the [`Depot`] (see
+/// [`CachingHeadersDepotExt`]), and is computed from the body otherwise.
+///
+/// ## Streamed bodies
+///
+/// Streamed bodies are not hashed by default. With [`ETag::stream_limit`], the body is hashed
+/// incrementally while it is read, and buffered up to the limit so that the etag header can be sent
+/// before the body. Bodies larger than the limit are sent as they are, without an etag.
+///
+/// ## Strong vs weak comparison
+///
+/// Etags can be compared using a strong method or a weak
+/// method. By default, this handler allows weak comparison. To change
+/// this setting, construct your handler with `Etag::new().strong()`.
+/// See [`etag::EntityTag`](https://docs.rs/etag/3.0.0/etag/struct.EntityTag.html#comparison)
+/// for further documentation.
+///
+/// Etags computed from the body are strong by default. Use `Etag::new().weak_tags()` when the body may
+/// be transformed afterwards, for example compressed on the fly.
+#[derive(Default, Clone, Copy, Debug)]
+pub struct ETag {
+    strong: bool,
+    weak_tags: bool,
+    stream_limit: usize,
+}
+
+impl ETag {
+    /// constructs a new Etag handler
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Configures this handler to use strong content-based etag comparison only. See
+    /// [`etag::EntityTag`](https://docs.rs/etag/3.0.0/etag/struct.EntityTag.html#comparison)
+    /// for further documentation on the differences between strong
+    /// and weak etag comparison.
+    pub fn strong(mut self) -> Self {
+        self.strong = true;
+        self
+    }
+
+    /// Configures this handler to compute weak etags from the body.
+    pub fn weak_tags(mut self) -> Self {
+        self.weak_tags = true;
+        self
+    }
+
+    /// Configures this handler to compute etags of streamed bodies up to `limit` bytes, default is 0
+    /// which disables it.
+    pub fn stream_limit(mut self, limit: usize) -> Self {
+        self.stream_limit = limit;
+        self
+    }
+
+    /// Check if the `If-None-Match` header of the request matches the etag.
+    fn is_matched(&self, req: &Request, etag: &EntityTag) -> bool {
+        req.headers()
+            .get_all(IF_NONE_MATCH)
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .flat_map(|value| value.split(','))
+            .any(|value| {
+                let value = value.trim();
+                value == "*"
+                    || value.parse::<EntityTag>().is_ok_and(|other| {
+                        if self.strong {
+                            etag.strong_eq(&other)
+                        } else {
+                            etag.weak_eq(&other)
+                        }
+                    })
+            })
+    }
+
+    /// Answer `304 Not Modified` with the etag supplied in the depot, before running other handlers.
+    fn precheck(&self, req: &Request, depot: &Depot, res: &mut Response) -> bool {
+        let Some(etag) = depot.etag() else {
+            return false;
+        };
+        if !is_cacheable(req) || !self.is_matched(req, etag) {
+            return false;
+        }
+        insert_etag(res, etag);
+        not_modified(res);
+        true
+    }
+
+    async fn compute(&self, res: &mut Response) -> Option<EntityTag> {
+        let tag = match &res.body {
+            ResBody::Once(bytes) => EntityTag::from_data(bytes),
+            ResBody::Chunks(bytes) => {
+                let tags = bytes
+                    .iter()
+                    .map(|item| EntityTag::from_data(item).tag().to_owned())
+                    .collect::<Vec<_>>()
+                    .concat();
+                EntityTag::from_data(tags.as_bytes())
+            }
+            ResBody::None => {
+                tracing::debug!("etag not supported for empty body");
+                return None;
+            }
+            ResBody::Error(_) => return None,
+            _ => {
+                if self.stream_limit == 0 {
+                    tracing::debug!("etag not supported for streaming body");
+                    return None;
+                }
+                self.compute_stream(res).await?
+            }
+        };
+        if self.weak_tags {
+            Some(EntityTag::weak(tag.tag()))
+        } else {
+            Some(tag)
+        }
+    }
+
+    /// Hash the streamed body chunk by chunk, the same way as [`ResBody::Chunks`].
+    async fn compute_stream(&self, res: &mut Response) -> Option<EntityTag> {
+        let mut body = res.take_body();
+        let mut frames = VecDeque::new();
+        let mut tags = String::new();
+        let mut size = 0;
+        let mut has_trailers = false;
+        loop {
+            match body.next().await {
+                Some(Ok(frame)) => {
+                    if let Some(data) = frame.data_ref() {
+                        size += data.len();
+                        tags.push_str(EntityTag::from_data(data).tag());
+                    } else {
+                        has_trailers = true;
+                    }
+                    frames.push_back(frame);
+                    if size > self.stream_limit {
+                        tracing::debug!("streaming body is too large for etag");
+                        res.body(resume(frames, Some(body)));
+                        return None;
+                    }
+                }
+                Some(Err(e)) => {
+                    let failed = stream::iter(frames.into_iter().map(|frame| Ok(BytesFrame(frame))))
+                        .chain(stream::once(async move { Err::<BytesFrame, _>(e) }));
+                    res.body(ResBody::stream(failed));
+                    return None;
+                }
+                None => break,
+            }
+        }
+        if has_trailers {
+            res.body(resume(frames, None));
+        } else {
+            let chunks = frames
+                .into_iter()
+                .filter_map(|frame| frame.into_data().ok())
+                .collect::<VecDeque<Bytes>>();
+            res.body(ResBody::Chunks(chunks));
+        }
+        Some(EntityTag::from_data(tags.as_bytes()))
+    }
+}
+
+/// Stream the buffered frames, followed by the rest of the body.
+fn resume(frames: VecDeque<Frame<Bytes>>, rest: Option<ResBody>) -> ResBody {
+    let buffered = stream::iter(frames.into_iter().map(|frame| Ok::<_, IoError>(BytesFrame(frame))));
+    match rest {
+        Some(rest) => ResBody::stream(buffered.chain(rest.map_ok(BytesFrame))),
+        None => ResBody::stream(buffered),
+    }
+}
+
+fn insert_etag(res: &mut Response, etag: &EntityTag) {
+    match etag.to_string().parse::<headers::ETag>() {
+        Ok(etag) => res.headers_mut().typed_insert(etag),
+        Err(e) => {
+            tracing::error!(error = ?e, "failed to parse etag");
+        }
+    }
+}
+
+#[async_trait]
+impl Handler for ETag {
+    async fn handle(&self, req: &mut Request, depot: &mut Depot, res: &mut Response, ctrl: &mut FlowCtrl) {
+        if self.precheck(req, depot, res) {
+            ctrl.skip_rest();
+            return;
+        }
+        ctrl.call_next(req, depot, res).await;
+        if ctrl.is_ceased() {
+            return;
+        }
+
+        let etag = res
+            .headers()
+            .get(ETAG)
+            .and_then(|etag| etag.to_str().ok())
+            .and_then(|etag| etag.parse().ok());
+        let etag = match etag {
+            Some(etag) => Some(etag),
+            None => {
+                let etag = match depot.etag() {
+                    Some(etag) => Some(etag.clone()),
+                    None => self.compute(res).await,
+                };
+                if let Some(etag) = &etag {
+                    insert_etag(res, etag);
+                }
+                etag
+            }
+        };
+
+        if let Some(etag) = etag {
+            if is_cacheable(req) && self.is_matched(req, &etag) {
+                not_modified(res);
+            }
+        }
+    }
+}
+
+/// # A handler for the `Last-Modified` and `If-Modified-Since` header interaction.
+///
+/// This handler does not set a `Last-Modified` header on its own, but
+/// relies on other handlers doing so, or supplying the time with
+/// [`CachingHeadersDepotExt::set_last_modified`].
+#[derive(Clone, Debug, Copy, Default)]
+pub struct Modified {
+    _private: (),
+}
+
+impl Modified {
+    /// Constructs a new Modified handler
+    pub fn new() -> Self {
+        Self { _private: () }
+    }
+
+    fn is_modified(req: &Request, last_modified: SystemTime) -> bool {
+        // `If-Modified-Since` is ignored when `If-None-Match` is present.
+        if req.headers().contains_key(IF_NONE_MATCH) {
+            return true;
+        }
+        match req.headers().typed_get::<headers::IfModifiedSince>() {
+            Some(if_modified_since) => if_modified_since.is_modified(last_modified),
+            None => true,
+        }
+    }
+
+    /// Answer `304 Not Modified` with the time supplied in the depot, before running other handlers.
+    fn precheck(&self, req: &Request, depot: &Depot, res: &mut Response) -> bool {
+        let Some(last_modified) = depot.last_modified() else {
+            return false;
+        };
+        if !is_cacheable(req) || Self::is_modified(req, last_modified) {
+            return false;
+        }
+        res.headers_mut()
+            .typed_insert(headers::LastModified::from(last_modified));
+        not_modified(res);
+        true
+    }
+}
+
+#[async_trait]
+impl Handler for Modified {
+    async fn handle(&self, req: &mut Request, depot: &mut Depot, res: &mut Response, ctrl: &mut FlowCtrl) {
+        if self.precheck(req, depot, res) {
+            ctrl.skip_rest();
+            return;
+        }
+        ctrl.call_next(req, depot, res).await;
+        if ctrl.is_ceased() {
+            return;
+        }
+
+        let last_modified = match res.headers().typed_get::<headers::LastModified>() {
+            Some(last_modified) => Some(last_modified.into()),
+            None => {
+                let last_modified = depot.last_modified();
+                if let Some(last_modified) = last_modified {
+                    res.headers_mut()
+                        .typed_insert(headers::LastModified::from(last_modified));
+                }
+                last_modified
+            }
+        };
+        if let Some(last_modified) = last_modified {
+            if is_cacheable(req) && !Self::is_modified(req, last_modified) {
+                not_modified(res);
+            }
+        }
+    }
+}
+
+/// A combined handler that provides both [`ETag`] and [`Modified`] behavior.
+#[derive(Clone, Debug, Copy, Default)]
+pub struct CachingHeaders(Modified, ETag);
+
+impl CachingHeaders {
+    /// Constructs a new combination modified and etag handler
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Use strong etag comparison only, see [`ETag::strong`].
+    pub fn strong(mut self) -> Self {
+        self.1 = self.1.strong();
+        self
+    }
+
+    /// Compute weak etags from the body, see [`ETag::weak_tags`].
+    pub fn weak_tags(mut self) -> Self {
+        self.1 = self.1.weak_tags();
+        self
+    }
+
+    /// Compute etags of streamed bodies up to `limit` bytes, see [`ETag::stream_limit`].
+    pub fn stream_limit(mut self, limit: usize) -> Self {
+        self.1 = self.1.stream_limit(limit);
+        self
+    }
+}
+
+#[async_trait]
+impl Handler for CachingHeaders {
+    async fn handle(&self, req: &mut Request, depot: &mut Depot, res: &mut Response, ctrl: &mut FlowCtrl) {
+        if self.1.precheck(req, depot, res) {
+            if let Some(last_modified) = depot.last_modified() {
+                res.headers_mut()
+                    .typed_insert(headers::LastModified::from(last_modified));
+            }
+            ctrl.skip_rest();
+            return;
+        }
+        self.0.handle(req, depot, res, ctrl).await;
+        if res.status_code != Some(StatusCode::NOT_MODIFIED) {
+            self.1.handle(req, depot, res, ctrl).await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use salvo_core::http::header::*;
+    use salvo_core::prelude::*;
+    use salvo_core::test::{ResponseExt, TestClient};
+
+    use super::*;
+
+    #[handler]
+    async fn hello() -> &'static str {
+        "Hello World"
+    }
+
+    #[tokio::test]
+    async fn test_affix() {
+        let router = Router::with_hoop(CachingHeaders::new()).get(hello);
+        let service = Service::new(router);
+
+        let respone = TestClient::get("http://127.0.0.1:5800/").send(&service).await;
+        assert_eq!(respone.status_code, Some(StatusCode::OK));
+
+        let etag = respone.headers().get(ETAG).unwrap();
+        let respone = TestClient::get("http://127.0.0.1:5800/")
+            .add_header(IF_NONE_MATCH, etag, true)
+            .send(&service)
+            .await;
+        assert_eq!(respone.status_code, Some(StatusCode::NOT_MODIFIED));
+        assert!(respone.body.is_none());
+    }
+
+    #[handler]
+    async fn streamed(res: &mut Response) {
+        let chunks: Vec<Result<&'static str, IoError>> = vec![Ok("Hello "), Ok("World")];
+        res.stream(stream::iter(chunks));
+    }
+
+    #[tokio::test]
+    async fn test_stream_etag() {
+        let router = Router::new()
+            .push(Router::with_path("small").hoop(CachingHeaders::new().stream_limit(1024)).get(streamed))
+            .push(Router::with_path("large").hoop(CachingHeaders::new().stream_limit(8)).get(streamed));
+        let service = Service::new(router);
+
+        let mut respone = TestClient::get("http://127.0.0.1:5800/small").send(&service).await;
+        assert_eq!(respone.take_string().await.unwrap(), "Hello World");
+        let etag = respone.headers().get(ETAG).unwrap().clone();
+
+        let respone = TestClient::get("http://127.0.0.1:5800/small")
+            .add_header(IF_NONE_MATCH, &etag, true)
+            .send(&service)
+            .await;
+        assert_eq!(respone.status_code, Some(StatusCode::NOT_MODIFIED));
+
+        let mut respone = TestClient::get("http://127.0.0.1:5800/large").send(&service).await;
+        assert!(respone.headers().get(ETAG).is_none());
+        assert_eq!(respone.take_string().await.unwrap(), "Hello World");
+    }
+
+    #[tokio::test]
+    async fn test_depot_validators() {
+        static CALLED: AtomicUsize = AtomicUsize::new(0);
+
+        #[handler]
+        async fn version(depot: &mut Depot) {
+            depot.set_etag(EntityTag::weak("v1"));
+            depot.set_last_modified(SystemTime::UNIX_EPOCH);
+        }
+        #[handler]
+        async fn expensive() -> &'static str {
+            CALLED.fetch_add(1, Ordering::SeqCst);
+            "Expensive"
+        }
+        let router = Router::new()
+            .hoop(version)
+            .hoop(CachingHeaders::new())
+            .get(expensive)
+            .put(expensive);
+        let service = Service::new(router);
+
+        let respone = TestClient::get("http://127.0.0.1:5800/").send(&service).await;
+        assert_eq!(respone.status_code, Some(StatusCode::OK));
+        assert_eq!(respone.headers()[ETAG], "W/\"v1\"");
+        assert!(respone.headers().contains_key(LAST_MODIFIED));
+        assert_eq!(CALLED.load(Ordering::SeqCst), 1);
+
+        let respone = TestClient::get("http://127.0.0.1:5800/")
+            .add_header(IF_NONE_MATCH, "\"v0\", W/\"v1\"", true)
+            .send(&service)
+            .await;
+        assert_eq!(respone.status_code, Some(StatusCode::NOT_MODIFIED));
+        assert_eq!(CALLED.load(Ordering::SeqCst), 1);
+
+        let respone = TestClient::get("http://127.0.0.1:5800/")
+            .add_header(IF_MODIFIED_SINCE, "Thu, 01 Jan 1970 00:00:00 GMT", true)
+            .send(&service)
+            .await;
+        assert_eq!(respone.status_code, Some(StatusCode::NOT_MODIFIED));
+        assert_eq!(CALLED.load(Ordering::SeqCst), 1);
+
+        // Other methods are never answered with `304 Not Modified`.
+        let respone = TestClient::put("http://127.0.0.1:5800/")
+            .add_header(IF_NONE_MATCH, "W/\"v1\"", true)
+            .send(&service)
+            .await;
+        assert_eq!(respone.status_code, Some(StatusCode::OK));
+        let respone = TestClient::put("http://127.0.0.1:5800/")
+            .add_header(IF_MODIFIED_SINCE, "Thu, 01 Jan 1970 00:00:00 GMT", true)
+            .send(&service)
+            .await;
+        assert_eq!(respone.status_code, Some(StatusCode::OK));
+        assert_eq!(CALLED.load(Ordering::SeqCst), 3);
+    }
+}
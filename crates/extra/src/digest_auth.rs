@@ -0,0 +1,532 @@
+//! Middleware for HTTP Digest authentication ([RFC 7616](https://www.rfc-editor.org/rfc/rfc7616)).
+//!
+//! Only `qop=auth` is supported. Nonces are generated statelessly from a server secret and expire after
+//! [`DigestAuth::nonce_ttl`]. The nonce count (`nc`) of every nonce and client nonce (`cnonce`) pair is tracked,
+//! so a replayed `Authorization` header is rejected.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use salvo_core::prelude::*;
+//! use salvo_extra::digest_auth::{DigestAuth, DigestAuthValidator};
+//!
+//! struct Validator;
+//! impl DigestAuthValidator for Validator {
+//!     async fn password(&self, username: &str, _realm: &str, _depot: &mut Depot) -> Option<String> {
+//!         (username == "root").then(|| "pwd".to_owned())
+//!     }
+//! }
+//!
+//! #[handler]
+//! async fn hello() -> &'static str {
+//!     "Hello"
+//! }
+//!
+//! #[tokio::main]
+//! async fn main() {
+//!     let auth_handler = DigestAuth::new("api@example.org", Validator);
+//!     let router = Router::with_hoop(auth_handler).goal(hello);
+//!
+//!     let acceptor = TcpListener::new("0.0.0.0:5800").bind().await;
+//!     Server::new(acceptor).serve(router).await;
+//! }
+//! ```
+use std::collections::HashMap;
+use std::fmt::{self, Display, Formatter};
+use std::future::Future;
+use std::str::FromStr;
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use md5::Md5;
+use salvo_core::http::header::{HeaderValue, AUTHORIZATION, WWW_AUTHENTICATE};
+use salvo_core::http::{Request, Response, StatusCode};
+use salvo_core::{async_trait, Depot, Error, FlowCtrl, Handler};
+use sha2::{Digest, Sha256};
+
+/// key used when insert into depot.
+pub const USERNAME_KEY: &str = "::salvo::digest_auth::username";
+
+/// DigestAuthValidator
+pub trait DigestAuthValidator: Send + Sync {
+    /// Get the password of user in the realm, returns `None` if the user does not exist.
+    fn password(&self, username: &str, realm: &str, depot: &mut Depot) -> impl Future<Output = Option<String>> + Send;
+}
+
+/// DigestAuthDepotExt
+pub trait DigestAuthDepotExt {
+    /// Get digest auth username reference.
+    fn digest_auth_username(&self) -> Option<&str>;
+}
+
+impl DigestAuthDepotExt for Depot {
+    fn digest_auth_username(&self) -> Option<&str> {
+        self.get::<String>(USERNAME_KEY).map(|v| &**v).ok()
+    }
+}
+
+/// Hash algorithm used by digest authentication.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum DigestAlgorithm {
+    /// `MD5`, only for compatibility with old clients.
+    Md5,
+    /// `SHA-256`.
+    Sha256,
+}
+impl DigestAlgorithm {
+    /// Hash the data and returns lowercase hex string.
+    pub fn hash(&self, data: &str) -> String {
+        match self {
+            Self::Md5 => hex::encode(Md5::digest(data.as_bytes())),
+            Self::Sha256 => hex::encode(Sha256::digest(data.as_bytes())),
+        }
+    }
+}
+impl Display for DigestAlgorithm {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            Self::Md5 => f.write_str("MD5"),
+            Self::Sha256 => f.write_str("SHA-256"),
+        }
+    }
+}
+impl FromStr for DigestAlgorithm {
+    type Err = Error;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.eq_ignore_ascii_case("MD5") {
+            Ok(Self::Md5)
+        } else if s.eq_ignore_ascii_case("SHA-256") {
+            Ok(Self::Sha256)
+        } else {
+            Err(Error::other(format!("unsupported digest algorithm `{s}`")))
+        }
+    }
+}
+
+/// Credentials parsed from a `Digest` authorization header.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct DigestCredentials {
+    /// Username.
+    pub username: String,
+    /// Realm.
+    pub realm: String,
+    /// Server nonce.
+    pub nonce: String,
+    /// Request uri.
+    pub uri: String,
+    /// Client response.
+    pub response: String,
+    /// Algorithm, `MD5` if absent.
+    pub algorithm: Option<String>,
+    /// Opaque value returned by client.
+    pub opaque: Option<String>,
+    /// Quality of protection.
+    pub qop: Option<String>,
+    /// Nonce count.
+    pub nc: Option<String>,
+    /// Client nonce.
+    pub cnonce: Option<String>,
+}
+
+/// Parse the value of a `Digest` authorization header.
+pub fn parse_credentials(value: &str) -> Result<DigestCredentials, Error> {
+    let value = value.trim();
+    let params = match value.split_once(' ') {
+        Some((scheme, params)) if scheme.eq_ignore_ascii_case("Digest") => params,
+        _ => return Err(Error::other("`authorization` is not digest scheme")),
+    };
+    let mut credentials = DigestCredentials::default();
+    for (key, value) in parse_params(params)? {
+        match &*key.to_ascii_lowercase() {
+            "username" => credentials.username = value,
+            "realm" => credentials.realm = value,
+            "nonce" => credentials.nonce = value,
+            "uri" => credentials.uri = value,
+            "response" => credentials.response = value,
+            "algorithm" => credentials.algorithm = Some(value),
+            "opaque" => credentials.opaque = Some(value),
+            "qop" => credentials.qop = Some(value),
+            "nc" => credentials.nc = Some(value),
+            "cnonce" => credentials.cnonce = Some(value),
+            _ => {}
+        }
+    }
+    if credentials.username.is_empty() || credentials.nonce.is_empty() || credentials.response.is_empty() {
+        return Err(Error::other("`authorization` has bad format"));
+    }
+    Ok(credentials)
+}
+
+/// Parse comma separated `key=value` pairs, values may be quoted strings.
+fn parse_params(input: &str) -> Result<Vec<(String, String)>, Error> {
+    let mut params = Vec::new();
+    let mut chars = input.chars().peekable();
+    loop {
+        while chars.peek().is_some_and(|c| c.is_whitespace() || *c == ',') {
+            chars.next();
+        }
+        if chars.peek().is_none() {
+            break;
+        }
+        let mut key = String::new();
+        while let Some(c) = chars.next_if(|c| *c != '=') {
+            key.push(c);
+        }
+        if chars.next() != Some('=') {
+            return Err(Error::other("`authorization` has bad format"));
+        }
+        let mut value = String::new();
+        if chars.next_if_eq(&'"').is_some() {
+            loop {
+                match chars.next() {
+                    Some('\\') => value.extend(chars.next()),
+                    Some('"') => break,
+                    Some(c) => value.push(c),
+                    None => return Err(Error::other("unterminated quoted string")),
+                }
+            }
+        } else {
+            while let Some(c) = chars.next_if(|c| *c != ',') {
+                value.push(c);
+            }
+        }
+        params.push((key.trim().to_owned(), value.trim().to_owned()));
+    }
+    Ok(params)
+}
+
+/// DigestAuth
+pub struct DigestAuth<V: DigestAuthValidator> {
+    realm: String,
+    algorithms: Vec<DigestAlgorithm>,
+    secret: [u8; 32],
+    nonce_ttl: Duration,
+    nonce_counts: Mutex<HashMap<(String, String), u64>>,
+    validator: V,
+}
+
+impl<V> DigestAuth<V>
+where
+    V: DigestAuthValidator,
+{
+    /// Create new `DigestAuth`.
+    #[inline]
+    pub fn new(realm: impl Into<String>, validator: V) -> Self {
+        DigestAuth {
+            realm: realm.into(),
+            algorithms: vec![DigestAlgorithm::Sha256, DigestAlgorithm::Md5],
+            secret: rand::random(),
+            nonce_ttl: Duration::from_secs(300),
+            nonce_counts: Mutex::new(HashMap::new()),
+            validator,
+        }
+    }
+
+    /// Set the supported algorithms, one challenge is sent for each of them in order of preference.
+    #[inline]
+    pub fn algorithms(mut self, algorithms: impl Into<Vec<DigestAlgorithm>>) -> Self {
+        self.algorithms = algorithms.into();
+        self
+    }
+
+    /// Set the secret used to generate nonces and the opaque value. It should be shared when running multiple
+    /// instances.
+    #[inline]
+    pub fn secret(mut self, secret: [u8; 32]) -> Self {
+        self.secret = secret;
+        self
+    }
+
+    /// Set how long a nonce is valid, the default is 5 minutes.
+    #[inline]
+    pub fn nonce_ttl(mut self, nonce_ttl: Duration) -> Self {
+        self.nonce_ttl = nonce_ttl;
+        self
+    }
+
+    fn now_secs() -> u64 {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or_default()
+    }
+
+    fn nonce_signature(&self, timestamp: &str, random: &str) -> String {
+        let mut hasher = Sha256::new();
+        hasher.update(timestamp.as_bytes());
+        hasher.update(b":");
+        hasher.update(random.as_bytes());
+        hasher.update(b":");
+        hasher.update(self.secret);
+        hex::encode(hasher.finalize())
+    }
+
+    /// Opaque value derived from the secret, so that it is the same for all instances sharing it.
+    fn opaque(&self) -> String {
+        let mut hasher = Sha256::new();
+        hasher.update(b"opaque:");
+        hasher.update(self.secret);
+        hex::encode(&hasher.finalize()[..16])
+    }
+
+    #[doc(hidden)]
+    pub fn generate_nonce(&self) -> String {
+        let timestamp = format!("{:x}", Self::now_secs());
+        let random = hex::encode(rand::random::<[u8; 16]>());
+        let signature = self.nonce_signature(&timestamp, &random);
+        format!("{timestamp}.{random}.{signature}")
+    }
+
+    /// Check nonce is generated by this server, returns `Some(true)` if it is valid and `Some(false)` if it is
+    /// stale.
+    fn check_nonce(&self, nonce: &str) -> Option<bool> {
+        let (timestamp, rest) = nonce.split_once('.')?;
+        let (random, signature) = rest.split_once('.')?;
+        if !constant_time_eq(self.nonce_signature(timestamp, random).as_bytes(), signature.as_bytes()) {
+            return None;
+        }
+        let issued = u64::from_str_radix(timestamp, 16).ok()?;
+        Some(Self::now_secs().saturating_sub(issued) <= self.nonce_ttl.as_secs())
+    }
+
+    /// Record the nonce count of the nonce and client nonce pair, returns `false` if it is not greater than the
+    /// last one, which means a replay.
+    fn record_nonce_count(&self, nonce: &str, cnonce: &str, nc: u64) -> bool {
+        let mut counts = self.nonce_counts.lock().unwrap_or_else(|e| e.into_inner());
+        let now = Self::now_secs();
+        let ttl = self.nonce_ttl.as_secs();
+        counts.retain(|(nonce, _), _| {
+            nonce
+                .split_once('.')
+                .and_then(|(timestamp, _)| u64::from_str_radix(timestamp, 16).ok())
+                .is_some_and(|issued| now.saturating_sub(issued) <= ttl)
+        });
+        let last = counts.entry((nonce.to_owned(), cnonce.to_owned())).or_default();
+        if nc <= *last {
+            false
+        } else {
+            *last = nc;
+            true
+        }
+    }
+
+    #[doc(hidden)]
+    pub fn ask_credentials(&self, res: &mut Response, stale: bool) {
+        let nonce = self.generate_nonce();
+        let opaque = self.opaque();
+        for algorithm in &self.algorithms {
+            let mut challenge = format!(
+                r#"Digest realm="{}", qop="auth", algorithm={}, nonce="{}", opaque="{}""#,
+                self.realm, algorithm, nonce, opaque
+            );
+            if stale {
+                challenge.push_str(", stale=true");
+            }
+            if let Ok(value) = HeaderValue::from_str(&challenge) {
+                res.headers_mut().append(WWW_AUTHENTICATE, value);
+            }
+        }
+        res.status_code(StatusCode::UNAUTHORIZED);
+    }
+
+    /// Verify credentials, returns `Err(true)` if the nonce is stale.
+    async fn verify(&self, req: &Request, credentials: &DigestCredentials, depot: &mut Depot) -> Result<(), bool> {
+        let algorithm = match &credentials.algorithm {
+            Some(algorithm) => algorithm.parse::<DigestAlgorithm>().map_err(|_| false)?,
+            None => DigestAlgorithm::Md5,
+        };
+        let opaque = self.opaque();
+        if !self.algorithms.contains(&algorithm)
+            || credentials.realm != self.realm
+            || credentials.opaque.as_deref().is_some_and(|value| value != opaque)
+            || credentials.qop.as_deref() != Some("auth")
+        {
+            return Err(false);
+        }
+        let request_uri = req.uri().path_and_query().map(|p| p.as_str()).unwrap_or("/");
+        if credentials.uri != request_uri {
+            return Err(false);
+        }
+        if !self.check_nonce(&credentials.nonce).ok_or(false)? {
+            return Err(true);
+        }
+        let (Some(nc), Some(cnonce)) = (&credentials.nc, &credentials.cnonce) else {
+            return Err(false);
+        };
+        let nc_value = u64::from_str_radix(nc, 16).map_err(|_| false)?;
+
+        let password = self
+            .validator
+            .password(&credentials.username, &self.realm, depot)
+            .await
+            .ok_or(false)?;
+        let ha1 = algorithm.hash(&format!("{}:{}:{}", credentials.username, self.realm, password));
+        let ha2 = algorithm.hash(&format!("{}:{}", req.method(), credentials.uri));
+        let expected = algorithm.hash(&format!("{ha1}:{}:{nc}:{cnonce}:auth:{ha2}", credentials.nonce));
+        if !constant_time_eq(expected.as_bytes(), credentials.response.to_ascii_lowercase().as_bytes()) {
+            return Err(false);
+        }
+        if !self.record_nonce_count(&credentials.nonce, cnonce, nc_value) {
+            tracing::warn!(username = %credentials.username, "digest auth nonce replayed");
+            return Err(false);
+        }
+        Ok(())
+    }
+}
+
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+#[async_trait]
+impl<V> Handler for DigestAuth<V>
+where
+    V: DigestAuthValidator + 'static,
+{
+    async fn handle(&self, req: &mut Request, depot: &mut Depot, res: &mut Response, ctrl: &mut FlowCtrl) {
+        let mut stale = false;
+        let credentials = req
+            .headers()
+            .get(AUTHORIZATION)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| parse_credentials(v).ok());
+        if let Some(credentials) = credentials {
+            match self.verify(req, &credentials, depot).await {
+                Ok(()) => {
+                    depot.insert(USERNAME_KEY, credentials.username);
+                    ctrl.call_next(req, depot, res).await;
+                    return;
+                }
+                Err(is_stale) => stale = is_stale,
+            }
+        }
+        self.ask_credentials(res, stale);
+        ctrl.skip_rest();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use salvo_core::prelude::*;
+    use salvo_core::test::{ResponseExt, TestClient};
+
+    use super::*;
+
+    #[handler]
+    async fn hello(depot: &mut Depot) -> String {
+        format!("Hello {}", depot.digest_auth_username().unwrap_or_default())
+    }
+
+    struct Validator;
+    impl DigestAuthValidator for Validator {
+        async fn password(&self, username: &str, _realm: &str, _depot: &mut Depot) -> Option<String> {
+            (username == "root").then(|| "pwd".to_owned())
+        }
+    }
+
+    fn authorization(nonce: &str, opaque: &str, nc: &str, password: &str) -> String {
+        authorization_with_cnonce(nonce, opaque, nc, "abc", password)
+    }
+
+    fn authorization_with_cnonce(nonce: &str, opaque: &str, nc: &str, cnonce: &str, password: &str) -> String {
+        let algorithm = DigestAlgorithm::Sha256;
+        let ha1 = algorithm.hash(&format!("root:test:{password}"));
+        let ha2 = algorithm.hash("GET:/hello");
+        let response = algorithm.hash(&format!("{ha1}:{nonce}:{nc}:{cnonce}:auth:{ha2}"));
+        format!(
+            r#"Digest username="root", realm="test", nonce="{nonce}", uri="/hello", qop=auth, nc={nc}, cnonce="{cnonce}", response="{response}", opaque="{opaque}", algorithm=SHA-256"#
+        )
+    }
+
+    #[test]
+    fn test_parse_credentials() {
+        let credentials = parse_credentials(
+            r#"Digest username="Mufasa", realm="http-auth@example.org", uri="/dir/index.html", algorithm=SHA-256, nonce="7ypf/xlj9XXwfDPEoM4URrv/xwf94BcCAzFZH4GiTo0v", nc=00000001, cnonce="f2/wE4q74E6zIJEtWaHKaf5wv/H5QzzpXusqGemxURZJ", qop=auth, response="753927fa0e85d155564e2e272a28d1802ca10daf4496794697cf8db5856cb6c1", opaque="FQhe/qaU925kfnzjCev0ciny7QMkPqMAFRtzCUYo5tdS""#,
+        )
+        .unwrap();
+        assert_eq!(credentials.username, "Mufasa");
+        assert_eq!(credentials.realm, "http-auth@example.org");
+        assert_eq!(credentials.algorithm.as_deref(), Some("SHA-256"));
+        assert_eq!(credentials.nc.as_deref(), Some("00000001"));
+        assert_eq!(credentials.qop.as_deref(), Some("auth"));
+    }
+
+    #[test]
+    fn test_rfc7616_example() {
+        let algorithm = DigestAlgorithm::Sha256;
+        let ha1 = algorithm.hash("Mufasa:http-auth@example.org:Circle of Life");
+        let ha2 = algorithm.hash("GET:/dir/index.html");
+        let response = algorithm.hash(&format!(
+            "{ha1}:7ypf/xlj9XXwfDPEoM4URrv/xwf94BcCAzFZH4GiTo0v:00000001:f2/wE4q74E6zIJEtWaHKaf5wv/H5QzzpXusqGemxURZJ:auth:{ha2}"
+        ));
+        assert_eq!(response, "753927fa0e85d155564e2e272a28d1802ca10daf4496794697cf8db5856cb6c1");
+    }
+
+    #[tokio::test]
+    async fn test_digest_auth() {
+        let auth_handler = DigestAuth::new("test", Validator);
+        let nonce = auth_handler.generate_nonce();
+        let opaque = auth_handler.opaque();
+        let router = Router::with_hoop(auth_handler).push(Router::with_path("hello").get(hello));
+        let service = Service::new(router);
+
+        let res = TestClient::get("http://127.0.0.1:5800/hello").send(&service).await;
+        assert_eq!(res.status_code, Some(StatusCode::UNAUTHORIZED));
+        assert_eq!(res.headers().get_all(WWW_AUTHENTICATE).iter().count(), 2);
+
+        let content = TestClient::get("http://127.0.0.1:5800/hello")
+            .add_header(AUTHORIZATION, authorization(&nonce, &opaque, "00000001", "pwd"), true)
+            .send(&service)
+            .await
+            .take_string()
+            .await
+            .unwrap();
+        assert_eq!(content, "Hello root");
+
+        // Replay the same nonce count.
+        let res = TestClient::get("http://127.0.0.1:5800/hello")
+            .add_header(AUTHORIZATION, authorization(&nonce, &opaque, "00000001", "pwd"), true)
+            .send(&service)
+            .await;
+        assert_eq!(res.status_code, Some(StatusCode::UNAUTHORIZED));
+
+        let res = TestClient::get("http://127.0.0.1:5800/hello")
+            .add_header(AUTHORIZATION, authorization(&nonce, &opaque, "00000002", "pwd"), true)
+            .send(&service)
+            .await;
+        assert_eq!(res.status_code, Some(StatusCode::OK));
+
+        let res = TestClient::get("http://127.0.0.1:5800/hello")
+            .add_header(AUTHORIZATION, authorization(&nonce, &opaque, "00000003", "wrong"), true)
+            .send(&service)
+            .await;
+        assert_eq!(res.status_code, Some(StatusCode::UNAUTHORIZED));
+
+        // Another client using the same nonce has its own nonce counts.
+        let res = TestClient::get("http://127.0.0.1:5800/hello")
+            .add_header(
+                AUTHORIZATION,
+                authorization_with_cnonce(&nonce, &opaque, "00000001", "def", "pwd"),
+                true,
+            )
+            .send(&service)
+            .await;
+        assert_eq!(res.status_code, Some(StatusCode::OK));
+    }
+
+    #[test]
+    fn test_nonce_and_opaque() {
+        let auth_handler = DigestAuth::new("test", Validator).secret([1; 32]);
+        let nonce = auth_handler.generate_nonce();
+        assert_ne!(nonce, auth_handler.generate_nonce());
+        assert_eq!(auth_handler.check_nonce(&nonce), Some(true));
+        assert_eq!(auth_handler.check_nonce(&nonce.replacen('.', ".0", 1)), None);
+
+        // Instances sharing the secret accept the nonces and opaque values of each other.
+        let other = DigestAuth::new("test", Validator).secret([1; 32]);
+        assert_eq!(other.check_nonce(&nonce), Some(true));
+        assert_eq!(other.opaque(), auth_handler.opaque());
+        assert_ne!(DigestAuth::new("test", Validator).opaque(), auth_handler.opaque());
+    }
+}
@@ -0,0 +1,654 @@
+//! Middleware for verifying HTTP message signatures ([RFC 9421](https://www.rfc-editor.org/rfc/rfc9421)).
+//!
+//! Requests are signed by the client with a shared secret using `hmac-sha256`. The `Signature-Input` and
+//! `Signature` headers are verified against a key looked up through [`HttpSignatureKeyResolver`].
+//!
+//! Replay protection is based on the `created` parameter, which must be within [`HttpSignature::max_age`], and on
+//! the optional `nonce` parameter, which can only be used once while it is within that window. If the
+//! `content-digest` header is covered by the signature, the request body is verified against it as described in
+//! [RFC 9530](https://www.rfc-editor.org/rfc/rfc9530).
+//!
+//! # Example
+//!
+//! ```no_run
+//! use salvo_core::prelude::*;
+//! use salvo_extra::http_signature::{HttpSignature, HttpSignatureKeyResolver};
+//!
+//! struct KeyResolver;
+//! impl HttpSignatureKeyResolver for KeyResolver {
+//!     async fn resolve(&self, key_id: &str, _depot: &mut Depot) -> Option<Vec<u8>> {
+//!         (key_id == "partner-a").then(|| b"shared-secret".to_vec())
+//!     }
+//! }
+//!
+//! #[handler]
+//! async fn hello() -> &'static str {
+//!     "Hello"
+//! }
+//!
+//! #[tokio::main]
+//! async fn main() {
+//!     let auth_handler = HttpSignature::new(KeyResolver).require_content_digest(true);
+//!     let router = Router::with_hoop(auth_handler).goal(hello);
+//!
+//!     let acceptor = TcpListener::new("0.0.0.0:5800").bind().await;
+//!     Server::new(acceptor).serve(router).await;
+//! }
+//! ```
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use base64::engine::{general_purpose, Engine};
+use hmac::{Hmac, Mac};
+use salvo_core::http::header::HOST;
+use salvo_core::http::{Request, Response, StatusCode};
+use salvo_core::{async_trait, Depot, Error, FlowCtrl, Handler};
+use sha2::{Digest, Sha256, Sha512};
+
+/// key used when insert into depot.
+pub const KEY_ID_KEY: &str = "::salvo::http_signature::key_id";
+
+/// The only supported signature algorithm.
+pub const HMAC_SHA256: &str = "hmac-sha256";
+
+/// HttpSignatureKeyResolver
+pub trait HttpSignatureKeyResolver: Send + Sync {
+    /// Get the shared secret of the key id, returns `None` if the key does not exist.
+    fn resolve(&self, key_id: &str, depot: &mut Depot) -> impl Future<Output = Option<Vec<u8>>> + Send;
+}
+
+/// HttpSignatureDepotExt
+pub trait HttpSignatureDepotExt {
+    /// Get the key id of the verified signature.
+    fn signature_key_id(&self) -> Option<&str>;
+}
+
+impl HttpSignatureDepotExt for Depot {
+    fn signature_key_id(&self) -> Option<&str> {
+        self.get::<String>(KEY_ID_KEY).map(|v| &**v).ok()
+    }
+}
+
+/// A parameter value of signature input.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ParamValue {
+    /// Integer value.
+    Integer(i64),
+    /// String value.
+    String(String),
+    /// Token or boolean value.
+    Token(String),
+}
+impl ParamValue {
+    /// Get the value as integer.
+    pub fn as_integer(&self) -> Option<i64> {
+        match self {
+            Self::Integer(value) => Some(*value),
+            _ => None,
+        }
+    }
+    /// Get the value as string.
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Self::String(value) | Self::Token(value) => Some(value),
+            Self::Integer(_) => None,
+        }
+    }
+}
+
+/// A parsed member of the `Signature-Input` header.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SignatureInput {
+    /// Signature label.
+    pub label: String,
+    /// Covered component identifiers, such as `@method` or `content-digest`.
+    pub components: Vec<String>,
+    /// Signature parameters, such as `created` and `keyid`.
+    pub params: Vec<(String, ParamValue)>,
+    /// The serialized inner list with parameters, used as `@signature-params` in the signature base.
+    pub raw: String,
+}
+impl SignatureInput {
+    /// Get a parameter by name.
+    pub fn param(&self, name: &str) -> Option<&ParamValue> {
+        self.params.iter().find(|(key, _)| key == name).map(|(_, value)| value)
+    }
+}
+
+struct Cursor<'a> {
+    input: &'a str,
+    pos: usize,
+}
+impl<'a> Cursor<'a> {
+    fn peek(&self) -> Option<char> {
+        self.input[self.pos..].chars().next()
+    }
+    fn bump(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.pos += c.len_utf8();
+        Some(c)
+    }
+    fn skip_whitespace(&mut self) {
+        while self.peek().is_some_and(|c| c == ' ' || c == '\t') {
+            self.pos += 1;
+        }
+    }
+    fn take_while(&mut self, f: impl Fn(char) -> bool) -> &'a str {
+        let start = self.pos;
+        while self.peek().is_some_and(&f) {
+            self.bump();
+        }
+        &self.input[start..self.pos]
+    }
+    fn expect(&mut self, c: char) -> Result<(), Error> {
+        if self.bump() == Some(c) {
+            Ok(())
+        } else {
+            Err(Error::other(format!("expected `{c}` in signature header")))
+        }
+    }
+    fn string(&mut self) -> Result<String, Error> {
+        self.expect('"')?;
+        let mut value = String::new();
+        loop {
+            match self.bump() {
+                Some('\\') => value.extend(self.bump()),
+                Some('"') => return Ok(value),
+                Some(c) => value.push(c),
+                None => return Err(Error::other("unterminated string in signature header")),
+            }
+        }
+    }
+    fn key(&mut self) -> &'a str {
+        self.take_while(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '.' | '*'))
+    }
+    fn param_value(&mut self) -> Result<ParamValue, Error> {
+        match self.peek() {
+            Some('"') => self.string().map(ParamValue::String),
+            Some(c) if c.is_ascii_digit() || c == '-' => {
+                let value = self.take_while(|c| c.is_ascii_digit() || c == '-');
+                value
+                    .parse()
+                    .map(ParamValue::Integer)
+                    .map_err(|_| Error::other("invalid integer in signature header"))
+            }
+            _ => Ok(ParamValue::Token(
+                self.take_while(|c| !matches!(c, ';' | ',' | ' ')).to_owned(),
+            )),
+        }
+    }
+    fn params(&mut self) -> Result<Vec<(String, ParamValue)>, Error> {
+        let mut params = Vec::new();
+        while self.peek() == Some(';') {
+            self.bump();
+            self.skip_whitespace();
+            let key = self.key().to_owned();
+            let value = if self.peek() == Some('=') {
+                self.bump();
+                self.param_value()?
+            } else {
+                ParamValue::Token("?1".into())
+            };
+            params.push((key, value));
+        }
+        Ok(params)
+    }
+    fn next_member(&mut self) -> Result<Option<&'a str>, Error> {
+        self.skip_whitespace();
+        if self.peek().is_none() {
+            return Ok(None);
+        }
+        let label = self.key();
+        if label.is_empty() {
+            return Err(Error::other("missing label in signature header"));
+        }
+        self.expect('=')?;
+        Ok(Some(label))
+    }
+    fn end_member(&mut self) -> Result<(), Error> {
+        self.skip_whitespace();
+        match self.bump() {
+            None | Some(',') => Ok(()),
+            Some(c) => Err(Error::other(format!("unexpected `{c}` in signature header"))),
+        }
+    }
+}
+
+/// Parse the value of `Signature-Input` header.
+pub fn parse_signature_input(value: &str) -> Result<Vec<SignatureInput>, Error> {
+    let mut cursor = Cursor { input: value, pos: 0 };
+    let mut inputs = Vec::new();
+    while let Some(label) = cursor.next_member()? {
+        let start = cursor.pos;
+        cursor.expect('(')?;
+        let mut components = Vec::new();
+        loop {
+            cursor.skip_whitespace();
+            if cursor.peek() == Some(')') {
+                cursor.bump();
+                break;
+            }
+            components.push(cursor.string()?);
+            // Component parameters such as `;sf` are not supported.
+            if cursor.peek() == Some(';') {
+                return Err(Error::other("component parameters are not supported"));
+            }
+        }
+        let params = cursor.params()?;
+        let raw = cursor.input[start..cursor.pos].to_owned();
+        inputs.push(SignatureInput {
+            label: label.to_owned(),
+            components,
+            params,
+            raw,
+        });
+        cursor.end_member()?;
+    }
+    Ok(inputs)
+}
+
+/// Parse a dictionary header with byte sequence values, such as `Signature` and `Content-Digest`.
+pub fn parse_byte_sequences(value: &str) -> Result<Vec<(String, Vec<u8>)>, Error> {
+    let mut cursor = Cursor { input: value, pos: 0 };
+    let mut items = Vec::new();
+    while let Some(label) = cursor.next_member()? {
+        cursor.expect(':')?;
+        let encoded = cursor.take_while(|c| c != ':');
+        cursor.expect(':')?;
+        let bytes = general_purpose::STANDARD.decode(encoded).map_err(Error::other)?;
+        cursor.params()?;
+        items.push((label.to_owned(), bytes));
+        cursor.end_member()?;
+    }
+    Ok(items)
+}
+
+/// Get the value of a covered component from request.
+pub fn component_value(req: &Request, component: &str) -> Result<String, Error> {
+    let authority = || {
+        req.headers()
+            .get(HOST)
+            .and_then(|v| v.to_str().ok())
+            .or_else(|| req.uri().authority().map(|a| a.as_str()))
+            .map(|v| v.to_ascii_lowercase())
+            .ok_or_else(|| Error::other("request has no authority"))
+    };
+    let value = match component {
+        "@method" => req.method().as_str().to_owned(),
+        "@scheme" => req.scheme().as_str().to_ascii_lowercase(),
+        "@authority" => authority()?,
+        "@path" => req.uri().path().to_owned(),
+        "@query" => format!("?{}", req.uri().query().unwrap_or_default()),
+        "@request-target" => req
+            .uri()
+            .path_and_query()
+            .map(|p| p.as_str().to_owned())
+            .unwrap_or_else(|| "/".into()),
+        "@target-uri" => format!(
+            "{}://{}{}",
+            req.scheme().as_str().to_ascii_lowercase(),
+            authority()?,
+            req.uri().path_and_query().map(|p| p.as_str()).unwrap_or("/")
+        ),
+        name if name.starts_with('@') => return Err(Error::other(format!("unsupported component `{name}`"))),
+        name => {
+            let values = req
+                .headers()
+                .get_all(name)
+                .iter()
+                .map(|v| v.to_str().map(str::trim))
+                .collect::<Result<Vec<_>, _>>()
+                .map_err(Error::other)?;
+            if values.is_empty() {
+                return Err(Error::other(format!("header `{name}` is missing")));
+            }
+            values.join(", ")
+        }
+    };
+    Ok(value)
+}
+
+/// Build the signature base of request for the signature input.
+pub fn signature_base(req: &Request, input: &SignatureInput) -> Result<String, Error> {
+    let mut base = String::new();
+    for component in &input.components {
+        base.push_str(&format!("\"{}\": {}\n", component, component_value(req, component)?));
+    }
+    base.push_str(&format!("\"@signature-params\": {}", input.raw));
+    Ok(base)
+}
+
+/// Sign the signature base with `hmac-sha256`.
+pub fn sign_hmac_sha256(base: &str, key: &[u8]) -> Vec<u8> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC can take key of any size");
+    mac.update(base.as_bytes());
+    mac.finalize().into_bytes().to_vec()
+}
+
+/// Verify the body against the value of `Content-Digest` header.
+///
+/// Returns `false` if no supported algorithm (`sha-256` or `sha-512`) is present, or any of them mismatches.
+pub fn verify_content_digest(header: &str, body: &[u8]) -> bool {
+    let Ok(digests) = parse_byte_sequences(header) else {
+        return false;
+    };
+    let mut verified = false;
+    for (algorithm, digest) in digests {
+        let expected = match &*algorithm {
+            "sha-256" => Sha256::digest(body).to_vec(),
+            "sha-512" => Sha512::digest(body).to_vec(),
+            _ => continue,
+        };
+        if expected != digest {
+            return false;
+        }
+        verified = true;
+    }
+    verified
+}
+
+/// HttpSignature
+pub struct HttpSignature<R: HttpSignatureKeyResolver> {
+    label: Option<String>,
+    required_components: Vec<String>,
+    max_age: Duration,
+    require_nonce: bool,
+    require_content_digest: bool,
+    seen_nonces: Mutex<HashMap<String, u64>>,
+    resolver: R,
+}
+
+impl<R> HttpSignature<R>
+where
+    R: HttpSignatureKeyResolver,
+{
+    /// Create new `HttpSignature`. By default `@method` and `@target-uri` must be covered by the signature.
+    #[inline]
+    pub fn new(resolver: R) -> Self {
+        HttpSignature {
+            label: None,
+            required_components: vec!["@method".into(), "@target-uri".into()],
+            max_age: Duration::from_secs(300),
+            require_nonce: false,
+            require_content_digest: false,
+            seen_nonces: Mutex::new(HashMap::new()),
+            resolver,
+        }
+    }
+
+    /// Only verify the signature with this label. By default the first signature is verified.
+    #[inline]
+    pub fn label(mut self, label: impl Into<String>) -> Self {
+        self.label = Some(label.into());
+        self
+    }
+
+    /// Set components which must be covered by the signature.
+    #[inline]
+    pub fn required_components<I, S>(mut self, components: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.required_components = components.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Set the max age of signatures based on the `created` parameter, the default is 5 minutes.
+    #[inline]
+    pub fn max_age(mut self, max_age: Duration) -> Self {
+        self.max_age = max_age;
+        self
+    }
+
+    /// Require the `nonce` parameter, each nonce can only be used once.
+    #[inline]
+    pub fn require_nonce(mut self, require_nonce: bool) -> Self {
+        self.require_nonce = require_nonce;
+        self
+    }
+
+    /// Require requests with body to cover a `content-digest` header in the signature.
+    #[inline]
+    pub fn require_content_digest(mut self, require_content_digest: bool) -> Self {
+        self.require_content_digest = require_content_digest;
+        self
+    }
+
+    fn check_nonce(&self, nonce: &str, now: u64, created: i64) -> bool {
+        let mut seen = self.seen_nonces.lock().unwrap_or_else(|e| e.into_inner());
+        seen.retain(|_, expires| *expires > now);
+        if seen.contains_key(nonce) {
+            false
+        } else {
+            // The signature is accepted until `created + max_age`, which is later than `now + max_age` when
+            // `created` is in the future.
+            let expires = created.saturating_add_unsigned(self.max_age.as_secs());
+            seen.insert(nonce.to_owned(), u64::try_from(expires).unwrap_or_default());
+            true
+        }
+    }
+
+    async fn verify(&self, req: &mut Request, depot: &mut Depot) -> Result<String, Error> {
+        let headers = req.headers();
+        let inputs = headers
+            .get("signature-input")
+            .and_then(|v| v.to_str().ok())
+            .ok_or_else(|| Error::other("`signature-input` header is missing"))
+            .and_then(parse_signature_input)?;
+        let signatures = headers
+            .get("signature")
+            .and_then(|v| v.to_str().ok())
+            .ok_or_else(|| Error::other("`signature` header is missing"))
+            .and_then(parse_byte_sequences)?;
+        let (input, signature) = inputs
+            .into_iter()
+            .filter(|input| self.label.as_ref().map_or(true, |label| &input.label == label))
+            .find_map(|input| {
+                signatures
+                    .iter()
+                    .find(|(label, _)| label == &input.label)
+                    .map(|(_, signature)| (input, signature.clone()))
+            })
+            .ok_or_else(|| Error::other("no matched signature"))?;
+
+        if let Some(missing) = self
+            .required_components
+            .iter()
+            .find(|component| !input.components.contains(component))
+        {
+            return Err(Error::other(format!("component `{missing}` is not covered")));
+        }
+        if let Some(alg) = input.param("alg") {
+            if alg.as_str() != Some(HMAC_SHA256) {
+                return Err(Error::other("unsupported signature algorithm"));
+            }
+        }
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or_default();
+        let created = input
+            .param("created")
+            .and_then(ParamValue::as_integer)
+            .ok_or_else(|| Error::other("`created` parameter is missing"))?;
+        if (now as i64).abs_diff(created) > self.max_age.as_secs() {
+            return Err(Error::other("signature is expired"));
+        }
+        if let Some(expires) = input.param("expires").and_then(ParamValue::as_integer) {
+            if expires < now as i64 {
+                return Err(Error::other("signature is expired"));
+            }
+        }
+        let covers_digest = input.components.iter().any(|c| c == "content-digest");
+        if self.require_content_digest && !covers_digest && !req.body().is_none() {
+            return Err(Error::other("`content-digest` is not covered"));
+        }
+
+        let key_id = input
+            .param("keyid")
+            .and_then(ParamValue::as_str)
+            .ok_or_else(|| Error::other("`keyid` parameter is missing"))?
+            .to_owned();
+        let key = self
+            .resolver
+            .resolve(&key_id, depot)
+            .await
+            .ok_or_else(|| Error::other("unknown key id"))?;
+        let base = signature_base(req, &input)?;
+        let mut mac = Hmac::<Sha256>::new_from_slice(&key).map_err(Error::other)?;
+        mac.update(base.as_bytes());
+        mac.verify_slice(&signature)
+            .map_err(|_| Error::other("signature mismatch"))?;
+
+        if covers_digest {
+            let header = component_value(req, "content-digest")?;
+            let body = req.payload().await.map_err(Error::other)?;
+            if !verify_content_digest(&header, body) {
+                return Err(Error::other("content digest mismatch"));
+            }
+        }
+        match input.param("nonce").and_then(ParamValue::as_str) {
+            Some(nonce) if !self.check_nonce(nonce, now, created) => return Err(Error::other("nonce replayed")),
+            None if self.require_nonce => return Err(Error::other("`nonce` parameter is missing")),
+            _ => {}
+        }
+        Ok(key_id)
+    }
+}
+
+#[async_trait]
+impl<R> Handler for HttpSignature<R>
+where
+    R: HttpSignatureKeyResolver + 'static,
+{
+    async fn handle(&self, req: &mut Request, depot: &mut Depot, res: &mut Response, ctrl: &mut FlowCtrl) {
+        match self.verify(req, depot).await {
+            Ok(key_id) => {
+                depot.insert(KEY_ID_KEY, key_id);
+                ctrl.call_next(req, depot, res).await;
+            }
+            Err(e) => {
+                tracing::debug!(error = ?e, "http signature verification failed");
+                res.status_code(StatusCode::UNAUTHORIZED);
+                ctrl.skip_rest();
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use salvo_core::prelude::*;
+    use salvo_core::test::{ResponseExt, TestClient};
+
+    use super::*;
+
+    #[handler]
+    async fn hello(depot: &mut Depot) -> String {
+        format!("Hello {}", depot.signature_key_id().unwrap_or_default())
+    }
+
+    struct KeyResolver;
+    impl HttpSignatureKeyResolver for KeyResolver {
+        async fn resolve(&self, key_id: &str, _depot: &mut Depot) -> Option<Vec<u8>> {
+            (key_id == "test-key").then(|| b"secret".to_vec())
+        }
+    }
+
+    fn now() -> i64 {
+        SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs() as i64
+    }
+
+    #[test]
+    fn test_parse_signature_input() {
+        let inputs = parse_signature_input(
+            r#"sig1=("@method" "@authority" "content-digest");created=1618884473;keyid="test-key", sig2=();alg="hmac-sha256""#,
+        )
+        .unwrap();
+        assert_eq!(inputs.len(), 2);
+        assert_eq!(inputs[0].label, "sig1");
+        assert_eq!(inputs[0].components, vec!["@method", "@authority", "content-digest"]);
+        assert_eq!(inputs[0].param("created"), Some(&ParamValue::Integer(1618884473)));
+        assert_eq!(inputs[0].param("keyid").and_then(ParamValue::as_str), Some("test-key"));
+        assert_eq!(
+            inputs[0].raw,
+            r#"("@method" "@authority" "content-digest");created=1618884473;keyid="test-key""#
+        );
+        assert!(inputs[1].components.is_empty());
+    }
+
+    #[test]
+    fn test_verify_content_digest() {
+        let header = "sha-256=:X48E9qOokqqrvdts8nOJRJN3OWDUoyWxBf7kbu9DBPE=:";
+        assert!(verify_content_digest(header, br#"{"hello": "world"}"#));
+        assert!(!verify_content_digest(header, br#"{"hello": "salvo"}"#));
+        assert!(!verify_content_digest("md5=:AAAA:", b"hello"));
+    }
+
+    async fn signed_request(path: &str, body: &'static str, created: i64, nonce: &str, key: &[u8]) -> salvo_core::Request {
+        let digest = general_purpose::STANDARD.encode(Sha256::digest(body.as_bytes()));
+        let mut req = TestClient::post(format!("http://127.0.0.1:5800{path}"))
+            .add_header("content-digest", format!("sha-256=:{digest}:"), true)
+            .text(body)
+            .build();
+        let raw = format!(
+            r#"("@method" "@target-uri" "content-digest");created={created};keyid="test-key";nonce="{nonce}""#
+        );
+        let input = parse_signature_input(&format!("sig1={raw}")).unwrap().remove(0);
+        let base = signature_base(&req, &input).unwrap();
+        let signature = general_purpose::STANDARD.encode(sign_hmac_sha256(&base, key));
+        req.add_header("signature-input", format!("sig1={raw}"), true).unwrap();
+        req.add_header("signature", format!("sig1=:{signature}:"), true).unwrap();
+        req
+    }
+
+    #[tokio::test]
+    async fn test_http_signature() {
+        let router = Router::with_hoop(HttpSignature::new(KeyResolver).require_content_digest(true)).post(hello);
+        let service = Service::new(router);
+
+        let req = signed_request("/", "hello", now(), "n1", b"secret").await;
+        let content = service.handle(req).await.take_string().await.unwrap();
+        assert_eq!(content, "Hello test-key");
+
+        // Replayed nonce.
+        let req = signed_request("/", "hello", now(), "n1", b"secret").await;
+        let res = service.handle(req).await;
+        assert_eq!(res.status_code, Some(StatusCode::UNAUTHORIZED));
+
+        // Wrong key.
+        let req = signed_request("/", "hello", now(), "n2", b"wrong").await;
+        let res = service.handle(req).await;
+        assert_eq!(res.status_code, Some(StatusCode::UNAUTHORIZED));
+
+        // Expired.
+        let req = signed_request("/", "hello", now() - 3600, "n3", b"secret").await;
+        let res = service.handle(req).await;
+        assert_eq!(res.status_code, Some(StatusCode::UNAUTHORIZED));
+
+        // Tampered body.
+        let mut req = signed_request("/", "hello", now(), "n4", b"secret").await;
+        req.replace_body("tampered".into());
+        let res = service.handle(req).await;
+        assert_eq!(res.status_code, Some(StatusCode::UNAUTHORIZED));
+
+        // Out of range.
+        let req = signed_request("/", "hello", i64::MIN, "n5", b"secret").await;
+        let res = service.handle(req).await;
+        assert_eq!(res.status_code, Some(StatusCode::UNAUTHORIZED));
+    }
+
+    #[test]
+    fn test_nonce_expiration() {
+        let signature = HttpSignature::new(KeyResolver).max_age(Duration::from_secs(300));
+        let created = now() + 300;
+        let now = now() as u64;
+        // Created in the future, the nonce is kept until the signature expires.
+        assert!(signature.check_nonce("n1", now, created));
+        assert!(!signature.check_nonce("n1", now + 400, created));
+        assert!(signature.check_nonce("n1", now + 700, created));
+    }
+}
@@ -0,0 +1,623 @@
+//! Middleware and filter for restricting access by client IP address.
+//!
+//! [`IpFilter`] checks the client address against allow and deny lists of IPv4/IPv6 CIDR ranges, and optionally
+//! against country rules resolved by a [`CountryLookup`], such as a local MaxMind database when the `maxminddb`
+//! feature is enabled.
+//!
+//! The client address is read from the [`Depot`] if another middleware already stored it under
+//! [`CLIENT_IP_KEY`]. Otherwise, when the remote address is one of the trusted proxies, it is resolved from the
+//! `X-Forwarded-For` or `Forwarded` headers.
+//!
+//! Rules are kept behind an [`IpRulesHandle`], so they can be replaced at runtime, for example by
+//! [`IpRulesHandle::watch_file`].
+//!
+//! # Example
+//!
+//! ```no_run
+//! use salvo_core::prelude::*;
+//! use salvo_extra::ip_filter::{IpFilter, IpRules};
+//!
+//! #[handler]
+//! async fn admin() -> &'static str {
+//!     "admin"
+//! }
+//!
+//! #[tokio::main]
+//! async fn main() {
+//!     let rules = IpRules::new().allow("10.0.0.0/8").unwrap().deny("10.0.13.0/24").unwrap();
+//!     let router = Router::with_path("admin")
+//!         .hoop(IpFilter::new(rules).trusted_proxies(["127.0.0.1"]).unwrap())
+//!         .get(admin);
+//!
+//!     let acceptor = TcpListener::new("0.0.0.0:5800").bind().await;
+//!     Server::new(acceptor).serve(router).await;
+//! }
+//! ```
+use std::fmt::{self, Debug, Formatter};
+use std::net::IpAddr;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+
+use ipnet::IpNet;
+use salvo_core::http::header::{HeaderName, FORWARDED};
+use salvo_core::http::{Request, Response, StatusCode};
+use salvo_core::routing::{Filter, PathState};
+use salvo_core::{async_trait, Depot, Error, FlowCtrl, Handler};
+
+/// Key for client ip address in depot.
+pub const CLIENT_IP_KEY: &str = "::salvo::ip_filter::client_ip";
+
+/// Extension for Depot.
+pub trait IpFilterDepotExt {
+    /// Get the resolved client ip address.
+    fn client_ip(&self) -> Option<IpAddr>;
+}
+
+impl IpFilterDepotExt for Depot {
+    #[inline]
+    fn client_ip(&self) -> Option<IpAddr> {
+        self.get::<IpAddr>(CLIENT_IP_KEY).ok().copied()
+    }
+}
+
+/// A set of IPv4 and IPv6 CIDR ranges.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct IpSet {
+    nets: Vec<IpNet>,
+}
+impl IpSet {
+    /// Create an empty set.
+    #[inline]
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// Add a CIDR range or a single address, such as `192.168.0.0/16` or `::1`.
+    pub fn insert(&mut self, net: &str) -> Result<(), Error> {
+        self.nets.push(parse_net(net)?);
+        Ok(())
+    }
+    /// Check if the address is in this set.
+    pub fn contains(&self, ip: &IpAddr) -> bool {
+        let ip = canonical(*ip);
+        self.nets.iter().any(|net| net.contains(&ip))
+    }
+    /// Check if this set is empty.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.nets.is_empty()
+    }
+    /// Get the ranges in this set.
+    #[inline]
+    pub fn nets(&self) -> &[IpNet] {
+        &self.nets
+    }
+}
+impl FromIterator<IpNet> for IpSet {
+    fn from_iter<T: IntoIterator<Item = IpNet>>(iter: T) -> Self {
+        Self {
+            nets: iter.into_iter().collect(),
+        }
+    }
+}
+
+fn parse_net(net: &str) -> Result<IpNet, Error> {
+    let net = net.trim();
+    if net.contains('/') {
+        net.parse::<IpNet>().map(|net| net.trunc()).map_err(Error::other)
+    } else {
+        net.parse::<IpAddr>().map(IpNet::from).map_err(Error::other)
+    }
+}
+
+/// Map IPv4-mapped IPv6 addresses, like `::ffff:10.0.0.1`, to IPv4.
+fn canonical(ip: IpAddr) -> IpAddr {
+    match ip {
+        IpAddr::V6(v6) => v6.to_ipv4_mapped().map(IpAddr::V4).unwrap_or(ip),
+        ip => ip,
+    }
+}
+
+/// Which list wins when an address matches both.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Precedence {
+    /// The deny list is checked first, so an address in both lists is denied.
+    #[default]
+    DenyFirst,
+    /// The allow list is checked first, so an address in both lists is allowed.
+    AllowFirst,
+}
+
+/// Allow and deny rules of [`IpFilter`].
+///
+/// If the allow list and allowed countries are both empty, all addresses which are not denied are allowed.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct IpRules {
+    allow: IpSet,
+    deny: IpSet,
+    allow_countries: Vec<String>,
+    deny_countries: Vec<String>,
+    precedence: Precedence,
+}
+impl IpRules {
+    /// Create empty rules which allow everything.
+    #[inline]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Parse rules from text.
+    ///
+    /// Each line is one rule: `allow <cidr>`, `deny <cidr>`, `allow-country <iso code>`, `deny-country <iso code>`
+    /// or `precedence allow-first|deny-first`. Empty lines and lines starting with `#` are ignored.
+    pub fn parse(text: &str) -> Result<Self, Error> {
+        let mut rules = Self::new();
+        for line in text.lines() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (kind, value) = line
+                .split_once(char::is_whitespace)
+                .ok_or_else(|| Error::other(format!("invalid ip rule `{line}`")))?;
+            let value = value.trim();
+            rules = match kind {
+                "allow" => rules.allow(value)?,
+                "deny" => rules.deny(value)?,
+                "allow-country" => rules.allow_country(value),
+                "deny-country" => rules.deny_country(value),
+                "precedence" if value == "allow-first" => rules.precedence(Precedence::AllowFirst),
+                "precedence" if value == "deny-first" => rules.precedence(Precedence::DenyFirst),
+                _ => return Err(Error::other(format!("invalid ip rule `{line}`"))),
+            };
+        }
+        Ok(rules)
+    }
+
+    /// Allow a CIDR range or a single address.
+    pub fn allow(mut self, net: &str) -> Result<Self, Error> {
+        self.allow.insert(net)?;
+        Ok(self)
+    }
+    /// Deny a CIDR range or a single address.
+    pub fn deny(mut self, net: &str) -> Result<Self, Error> {
+        self.deny.insert(net)?;
+        Ok(self)
+    }
+    /// Allow a country by its ISO 3166-1 alpha-2 code, this requires a [`CountryLookup`].
+    pub fn allow_country(mut self, code: impl Into<String>) -> Self {
+        self.allow_countries.push(code.into().to_ascii_uppercase());
+        self
+    }
+    /// Deny a country by its ISO 3166-1 alpha-2 code, this requires a [`CountryLookup`].
+    pub fn deny_country(mut self, code: impl Into<String>) -> Self {
+        self.deny_countries.push(code.into().to_ascii_uppercase());
+        self
+    }
+    /// Set which list wins when an address matches both, the default is [`Precedence::DenyFirst`].
+    pub fn precedence(mut self, precedence: Precedence) -> Self {
+        self.precedence = precedence;
+        self
+    }
+
+    /// Check if rules contain country rules.
+    pub fn has_country_rules(&self) -> bool {
+        !self.allow_countries.is_empty() || !self.deny_countries.is_empty()
+    }
+
+    /// Check if the address is allowed, `country` is the ISO code of the address if known.
+    pub fn is_allowed(&self, ip: Option<IpAddr>, country: Option<&str>) -> bool {
+        let in_country = |list: &[String]| country.is_some_and(|c| list.iter().any(|item| item.eq_ignore_ascii_case(c)));
+        let allowed = ip.is_some_and(|ip| self.allow.contains(&ip)) || in_country(&self.allow_countries);
+        let denied = ip.is_some_and(|ip| self.deny.contains(&ip)) || in_country(&self.deny_countries);
+        let open = self.allow.is_empty() && self.allow_countries.is_empty();
+        match self.precedence {
+            Precedence::DenyFirst => !denied && (open || allowed),
+            Precedence::AllowFirst => allowed || (!denied && open),
+        }
+    }
+}
+
+/// A shared handle of [`IpRules`], used to replace rules at runtime.
+#[derive(Clone, Default)]
+pub struct IpRulesHandle {
+    inner: Arc<RwLock<Arc<IpRules>>>,
+}
+impl Debug for IpRulesHandle {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        f.debug_tuple("IpRulesHandle").field(&self.load()).finish()
+    }
+}
+impl IpRulesHandle {
+    /// Create a new handle.
+    pub fn new(rules: IpRules) -> Self {
+        Self {
+            inner: Arc::new(RwLock::new(Arc::new(rules))),
+        }
+    }
+    /// Get current rules.
+    pub fn load(&self) -> Arc<IpRules> {
+        self.inner.read().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+    /// Replace current rules.
+    pub fn store(&self, rules: IpRules) {
+        *self.inner.write().unwrap_or_else(|e| e.into_inner()) = Arc::new(rules);
+    }
+    /// Load rules from a file in the format of [`IpRules::parse`].
+    pub async fn reload_file(&self, path: impl AsRef<Path>) -> Result<(), Error> {
+        let text = tokio::fs::read_to_string(path).await?;
+        self.store(IpRules::parse(&text)?);
+        Ok(())
+    }
+    /// Spawn a task which reloads rules from the file when it is modified.
+    ///
+    /// Invalid files are logged and ignored, the previous rules are kept.
+    pub fn watch_file(&self, path: impl Into<PathBuf>, interval: Duration) -> tokio::task::JoinHandle<()> {
+        let handle = self.clone();
+        let path = path.into();
+        tokio::spawn(async move {
+            let mut last_modified = None;
+            let mut ticker = tokio::time::interval(interval);
+            loop {
+                ticker.tick().await;
+                let modified = match tokio::fs::metadata(&path).await.and_then(|m| m.modified()) {
+                    Ok(modified) => modified,
+                    Err(e) => {
+                        tracing::warn!(error = ?e, path = ?path, "read ip rules file metadata failed");
+                        continue;
+                    }
+                };
+                if last_modified == Some(modified) {
+                    continue;
+                }
+                match handle.reload_file(&path).await {
+                    Ok(()) => {
+                        tracing::info!(path = ?path, "ip rules reloaded");
+                        last_modified = Some(modified);
+                    }
+                    Err(e) => tracing::warn!(error = ?e, path = ?path, "reload ip rules failed"),
+                }
+            }
+        })
+    }
+}
+impl From<IpRules> for IpRulesHandle {
+    fn from(rules: IpRules) -> Self {
+        Self::new(rules)
+    }
+}
+
+/// Lookup the country of an ip address.
+pub trait CountryLookup: Send + Sync + 'static {
+    /// Get the ISO 3166-1 alpha-2 country code of the address.
+    fn country(&self, ip: IpAddr) -> Option<String>;
+}
+
+impl<F> CountryLookup for F
+where
+    F: Fn(IpAddr) -> Option<String> + Send + Sync + 'static,
+{
+    fn country(&self, ip: IpAddr) -> Option<String> {
+        self(ip)
+    }
+}
+
+cfg_feature! {
+    #![feature = "maxminddb"]
+    /// A [`CountryLookup`] backed by a local MaxMind format database, such as `GeoLite2-Country.mmdb`.
+    pub struct MaxMindCountryLookup {
+        path: PathBuf,
+        reader: RwLock<Arc<maxminddb::Reader<Vec<u8>>>>,
+    }
+    impl Debug for MaxMindCountryLookup {
+        fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+            f.debug_struct("MaxMindCountryLookup").field("path", &self.path).finish()
+        }
+    }
+    impl MaxMindCountryLookup {
+        /// Open the database file.
+        pub fn open(path: impl Into<PathBuf>) -> Result<Self, Error> {
+            let path = path.into();
+            let reader = maxminddb::Reader::open_readfile(&path).map_err(Error::other)?;
+            Ok(Self {
+                path,
+                reader: RwLock::new(Arc::new(reader)),
+            })
+        }
+        /// Reload the database file, used after the file is updated.
+        pub fn reload(&self) -> Result<(), Error> {
+            let reader = maxminddb::Reader::open_readfile(&self.path).map_err(Error::other)?;
+            *self.reader.write().unwrap_or_else(|e| e.into_inner()) = Arc::new(reader);
+            Ok(())
+        }
+    }
+    impl CountryLookup for MaxMindCountryLookup {
+        fn country(&self, ip: IpAddr) -> Option<String> {
+            let reader = self.reader.read().unwrap_or_else(|e| e.into_inner()).clone();
+            let country = reader.lookup::<maxminddb::geoip2::Country>(ip).ok()?;
+            country.country?.iso_code.map(ToOwned::to_owned)
+        }
+    }
+}
+
+/// Resolve the client address of request.
+///
+/// If the remote address is in `trusted_proxies`, addresses in `X-Forwarded-For` (or `Forwarded` if it is absent)
+/// are walked from right to left, and the first one which is not a trusted proxy is returned. The walk stops at
+/// the first hop which can not be parsed, as the hops on its left can not be trusted, and the address of the hop
+/// which forwarded it is returned.
+pub fn resolve_client_ip(req: &Request, trusted_proxies: &IpSet) -> Option<IpAddr> {
+    let remote = req.remote_addr().clone().into_std().map(|addr| canonical(addr.ip()))?;
+    if !trusted_proxies.contains(&remote) {
+        return Some(remote);
+    }
+    let mut chain = forwarded_for(req, &HeaderName::from_static("x-forwarded-for"));
+    if chain.is_empty() {
+        chain = forwarded_for(req, &FORWARDED);
+    }
+    let mut client = remote;
+    for ip in chain.into_iter().rev() {
+        let Some(ip) = ip else {
+            break;
+        };
+        client = ip;
+        if !trusted_proxies.contains(&ip) {
+            break;
+        }
+    }
+    Some(client)
+}
+
+fn forwarded_for(req: &Request, name: &HeaderName) -> Vec<Option<IpAddr>> {
+    let is_forwarded = name == FORWARDED;
+    req.headers()
+        .get_all(name)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .map(|item| {
+            let item = if is_forwarded {
+                item.split(';')
+                    .filter_map(|pair| pair.trim().split_once('='))
+                    .find(|(key, _)| key.eq_ignore_ascii_case("for"))
+                    .map(|(_, value)| value.trim_matches('"'))?
+            } else {
+                item
+            };
+            parse_node(item.trim())
+        })
+        .collect()
+}
+
+/// Parse a node like `192.0.2.43`, `192.0.2.43:8080`, `[2001:db8::1]` or `[2001:db8::1]:8080`.
+fn parse_node(node: &str) -> Option<IpAddr> {
+    if let Ok(ip) = IpAddr::from_str(node) {
+        return Some(canonical(ip));
+    }
+    if let Some(rest) = node.strip_prefix('[') {
+        return rest.split_once(']').and_then(|(ip, _)| ip.parse().ok()).map(canonical);
+    }
+    node.rsplit_once(':')
+        .and_then(|(ip, _)| ip.parse().ok())
+        .map(canonical)
+}
+
+/// Middleware and filter for restricting access by client IP address.
+///
+/// View [module level documentation](index.html) for more details.
+#[derive(Clone)]
+pub struct IpFilter {
+    rules: IpRulesHandle,
+    trusted_proxies: IpSet,
+    country_lookup: Option<Arc<dyn CountryLookup>>,
+    status: StatusCode,
+}
+impl Debug for IpFilter {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        f.debug_struct("IpFilter")
+            .field("rules", &self.rules)
+            .field("trusted_proxies", &self.trusted_proxies)
+            .field("status", &self.status)
+            .finish()
+    }
+}
+impl IpFilter {
+    /// Create a new `IpFilter` with rules or a shared [`IpRulesHandle`].
+    pub fn new(rules: impl Into<IpRulesHandle>) -> Self {
+        Self {
+            rules: rules.into(),
+            trusted_proxies: IpSet::new(),
+            country_lookup: None,
+            status: StatusCode::FORBIDDEN,
+        }
+    }
+
+    /// Set trusted proxies, the client address is resolved from forwarding headers for requests from them.
+    pub fn trusted_proxies<I, S>(mut self, proxies: I) -> Result<Self, Error>
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        let mut set = IpSet::new();
+        for proxy in proxies {
+            set.insert(proxy.as_ref())?;
+        }
+        self.trusted_proxies = set;
+        Ok(self)
+    }
+
+    /// Set the country lookup used by country rules.
+    pub fn country_lookup(mut self, lookup: impl CountryLookup) -> Self {
+        self.country_lookup = Some(Arc::new(lookup));
+        self
+    }
+
+    /// Set the status code of rejected requests, the default is `403 Forbidden`.
+    pub fn status(mut self, status: StatusCode) -> Self {
+        self.status = status;
+        self
+    }
+
+    /// Get the rules handle, used to replace rules at runtime.
+    #[inline]
+    pub fn rules(&self) -> &IpRulesHandle {
+        &self.rules
+    }
+
+    /// Check if the address is allowed by current rules.
+    pub fn is_allowed(&self, ip: Option<IpAddr>) -> bool {
+        let rules = self.rules.load();
+        let country = match (&self.country_lookup, ip) {
+            (Some(lookup), Some(ip)) if rules.has_country_rules() => lookup.country(ip),
+            _ => None,
+        };
+        rules.is_allowed(ip, country.as_deref())
+    }
+}
+
+#[async_trait]
+impl Filter for IpFilter {
+    async fn filter(&self, req: &mut Request, _path: &mut PathState) -> bool {
+        self.is_allowed(resolve_client_ip(req, &self.trusted_proxies))
+    }
+}
+
+#[async_trait]
+impl Handler for IpFilter {
+    async fn handle(&self, req: &mut Request, depot: &mut Depot, res: &mut Response, ctrl: &mut FlowCtrl) {
+        let ip = match depot.client_ip() {
+            Some(ip) => Some(ip),
+            None => {
+                let ip = resolve_client_ip(req, &self.trusted_proxies);
+                if let Some(ip) = ip {
+                    depot.insert(CLIENT_IP_KEY, ip);
+                }
+                ip
+            }
+        };
+        if !self.is_allowed(ip) {
+            tracing::debug!(ip = ?ip, "request rejected by ip filter");
+            res.status_code(self.status);
+            ctrl.skip_rest();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use salvo_core::prelude::*;
+    use salvo_core::test::TestClient;
+
+    use super::*;
+
+    #[handler]
+    async fn hello() -> &'static str {
+        "hello"
+    }
+
+    #[handler]
+    async fn local(req: &mut Request) {
+        *req.remote_addr_mut() = std::net::SocketAddr::from(([127, 0, 0, 1], 5800)).into();
+    }
+
+    fn ip(s: &str) -> Option<IpAddr> {
+        s.parse().ok()
+    }
+
+    #[test]
+    fn test_ip_rules() {
+        let rules = IpRules::parse(
+            "# office\nallow 10.0.0.0/8\nallow 2001:db8::/32\ndeny 10.0.13.0/24\ndeny-country XX\n",
+        )
+        .unwrap();
+        assert!(rules.is_allowed(ip("10.1.2.3"), None));
+        assert!(rules.is_allowed(ip("::ffff:10.1.2.3"), None));
+        assert!(rules.is_allowed(ip("2001:db8::1"), None));
+        assert!(!rules.is_allowed(ip("10.0.13.7"), None));
+        assert!(!rules.is_allowed(ip("192.168.1.1"), None));
+        assert!(!rules.is_allowed(ip("10.1.2.3"), Some("xx")));
+        assert!(!rules.is_allowed(None, None));
+
+        let rules = rules.precedence(Precedence::AllowFirst);
+        assert!(rules.is_allowed(ip("10.0.13.7"), None));
+
+        let rules = IpRules::new().deny("192.168.0.0/16").unwrap();
+        assert!(rules.is_allowed(ip("10.1.2.3"), None));
+        assert!(!rules.is_allowed(ip("192.168.3.4"), None));
+
+        let rules = IpRules::new().allow_country("de");
+        assert!(rules.is_allowed(ip("1.2.3.4"), Some("DE")));
+        assert!(!rules.is_allowed(ip("1.2.3.4"), Some("FR")));
+        assert!(IpRules::parse("allow nonsense").is_err());
+    }
+
+    #[test]
+    fn test_parse_node() {
+        assert_eq!(parse_node("192.0.2.43"), ip("192.0.2.43"));
+        assert_eq!(parse_node("192.0.2.43:8080"), ip("192.0.2.43"));
+        assert_eq!(parse_node("[2001:db8::1]:8080"), ip("2001:db8::1"));
+        assert_eq!(parse_node("2001:db8::1"), ip("2001:db8::1"));
+        assert_eq!(parse_node("unknown"), None);
+    }
+
+    #[tokio::test]
+    async fn test_ip_filter() {
+        let filter = IpFilter::new(IpRules::new().allow("10.0.0.0/8").unwrap())
+            .trusted_proxies(["127.0.0.1"])
+            .unwrap();
+        let handle = filter.rules().clone();
+        let router = Router::new().hoop(local).hoop(filter).get(hello);
+        let service = Service::new(router);
+
+        let res = TestClient::get("http://127.0.0.1:5800/").send(&service).await;
+        assert_eq!(res.status_code, Some(StatusCode::FORBIDDEN));
+
+        let res = TestClient::get("http://127.0.0.1:5800/")
+            .add_header("x-forwarded-for", "10.1.1.1, 127.0.0.1", true)
+            .send(&service)
+            .await;
+        assert_eq!(res.status_code, Some(StatusCode::OK));
+
+        let res = TestClient::get("http://127.0.0.1:5800/")
+            .add_header("forwarded", "for=10.1.1.1;proto=https", true)
+            .send(&service)
+            .await;
+        assert_eq!(res.status_code, Some(StatusCode::OK));
+
+        // Spoofed left-most address is ignored because `192.168.1.1` is not trusted.
+        let res = TestClient::get("http://127.0.0.1:5800/")
+            .add_header("x-forwarded-for", "10.1.1.1, 192.168.1.1", true)
+            .send(&service)
+            .await;
+        assert_eq!(res.status_code, Some(StatusCode::FORBIDDEN));
+
+        // Invalid hops stop the walk instead of being skipped.
+        let res = TestClient::get("http://127.0.0.1:5800/")
+            .add_header("x-forwarded-for", "10.1.1.1, garbage, 127.0.0.1", true)
+            .send(&service)
+            .await;
+        assert_eq!(res.status_code, Some(StatusCode::FORBIDDEN));
+
+        handle.store(IpRules::new().deny("10.0.0.0/8").unwrap());
+        let res = TestClient::get("http://127.0.0.1:5800/")
+            .add_header("x-forwarded-for", "10.1.1.1", true)
+            .send(&service)
+            .await;
+        assert_eq!(res.status_code, Some(StatusCode::FORBIDDEN));
+        let res = TestClient::get("http://127.0.0.1:5800/").send(&service).await;
+        assert_eq!(res.status_code, Some(StatusCode::OK));
+    }
+
+    #[tokio::test]
+    async fn test_ip_filter_country() {
+        let filter = IpFilter::new(IpRules::new().deny_country("XX"))
+            .country_lookup(|ip: IpAddr| (ip == IpAddr::from([127, 0, 0, 1])).then(|| "XX".to_owned()));
+        assert!(filter.is_allowed(ip("10.1.1.1")));
+        assert!(!filter.is_allowed(ip("127.0.0.1")));
+
+        let router = Router::new().hoop(local).hoop(filter).get(hello);
+        let res = TestClient::get("http://127.0.0.1:5800/").send(router).await;
+        assert_eq!(res.status_code, Some(StatusCode::FORBIDDEN));
+    }
+}
@@ -10,13 +10,20 @@
 //! | Feature | Description |
 //! | --- | --- |
 //! | [`affix-state`](affix_state) | Middleware for adding prefix and suffix to the request path |
+//! | [`api-key`](api_key) | Middleware for API key authentication |
 //! | [`basic-auth`](basic_auth) | Middleware for basic authentication |
+//! | [`body-capture`](body_capture) | Middleware for capturing request and response bodies |
 //! | [`caching-headers`](caching_headers) | Middleware for setting caching headers |
 //! | [`catch-panic`](catch_panic) | Middleware for catching panics |
 //! | [`concurrency-limiter`](concurrency_limiter) | Middleware for limiting concurrency |
+//! | [`digest-auth`](digest_auth) | Middleware for HTTP Digest authentication |
 //! | [`force-https`](force_https) | Middleware for forcing HTTPS |
+//! | [`http-signature`](http_signature) | Middleware for verifying HTTP message signatures |
+//! | [`ip-filter`](ip_filter) | Middleware for restricting access by client IP address |
 //! | [`logging`] | Middleware for logging requests and responses |
+//! | [`precondition`] | Middleware for conditional requests of unsafe methods |
 //! | [`request-id`](request_id) | Middleware for setting a request ID |
+//! | [`security-headers`](security_headers) | Middleware for setting security related headers |
 //! | [`size-limiter`](size_limiter) | Middleware for limiting request size |
 //! | [`sse`] | Server-Sent Events (SSE) middleware |
 //! | [`timeout`] | Middleware for setting a timeout |
@@ -35,6 +42,31 @@ cfg_feature! {
     pub mod basic_auth;
 }
 
+cfg_feature! {
+    #![feature = "body-capture"]
+    pub mod body_capture;
+}
+
+cfg_feature! {
+    #![feature = "api-key"]
+    pub mod api_key;
+}
+
+cfg_feature! {
+    #![feature = "digest-auth"]
+    pub mod digest_auth;
+}
+
+cfg_feature! {
+    #![feature = "http-signature"]
+    pub mod http_signature;
+}
+
+cfg_feature! {
+    #![feature = "ip-filter"]
+    pub mod ip_filter;
+}
+
 cfg_feature! {
     #![feature = "affix-state"]
     pub mod affix_state;
@@ -81,10 +113,18 @@ cfg_feature! {
     #![feature = "caching-headers"]
     pub mod caching_headers;
 }
+cfg_feature! {
+    #![feature = "precondition"]
+    pub mod precondition;
+}
 cfg_feature! {
     #![feature = "request-id"]
     pub mod request_id;
 }
+cfg_feature! {
+    #![feature = "security-headers"]
+    pub mod security_headers;
+}
 cfg_feature! {
     #![feature ="tower-compat"]
     pub mod tower_compat;
@@ -0,0 +1,423 @@
+//! Middleware for conditional requests of unsafe methods.
+//!
+//! [`Preconditions`] evaluates `If-Match`, `If-None-Match` and `If-Unmodified-Since` headers of `PUT`,
+//! `PATCH`, `DELETE` and other unsafe requests against the current version of the target resource, which is
+//! provided by a [`ValidatorResolver`] before the handler runs. A request whose preconditions fail is
+//! answered with `412 Precondition Failed`, so that lost updates are prevented with optimistic concurrency.
+//! Optionally, requests to existing resources without preconditions are answered with
+//! `428 Precondition Required`.
+//!
+//! # Example
+//!
+//! ```
+//! use salvo_core::prelude::*;
+//! use salvo_extra::precondition::{Preconditions, Validators};
+//!
+//! #[handler]
+//! async fn update() -> &'static str {
+//!     "updated"
+//! }
+//!
+//! let preconditions = Preconditions::new(|req: &mut Request, _: &mut Depot| {
+//!     let id = req.param::<u64>("id")?;
+//!     // Load the version of the article from database.
+//!     Some(Validators::new().etag(format!("\"{id}-1\"").parse().ok()?))
+//! })
+//! .required(true);
+//! let router = Router::with_path("articles/{id}").hoop(preconditions).put(update);
+//! ```
+
+use std::future::Future;
+use std::time::SystemTime;
+
+use etag::EntityTag;
+use salvo_core::http::header::{IF_MATCH, IF_NONE_MATCH};
+use salvo_core::http::headers::{HeaderMapExt, IfUnmodifiedSince};
+use salvo_core::http::{HeaderName, StatusError};
+use salvo_core::{async_trait, Depot, FlowCtrl, Handler, Request, Response};
+
+/// Validators of the current version of a resource.
+#[derive(Default, Clone, Debug)]
+#[non_exhaustive]
+pub struct Validators {
+    /// Entity tag of the resource.
+    pub etag: Option<EntityTag>,
+    /// Last modified time of the resource.
+    pub last_modified: Option<SystemTime>,
+}
+
+impl Validators {
+    /// Create new empty `Validators`.
+    #[inline]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the entity tag and returns new `Validators`.
+    #[inline]
+    pub fn etag(mut self, etag: EntityTag) -> Self {
+        self.etag = Some(etag);
+        self
+    }
+
+    /// Sets the last modified time and returns new `Validators`.
+    #[inline]
+    pub fn last_modified(mut self, last_modified: SystemTime) -> Self {
+        self.last_modified = Some(last_modified);
+        self
+    }
+}
+
+/// Resolves the validators of the resource targeted by the request.
+pub trait ValidatorResolver: Send + Sync + 'static {
+    /// Returns the validators of the current version of the resource, or `None` if the resource does not
+    /// exist.
+    ///
+    /// The loaded resource can be saved in the depot, so that the handler does not load it again.
+    fn resolve(
+        &self,
+        req: &mut Request,
+        depot: &mut Depot,
+    ) -> impl Future<Output = Option<Validators>> + Send;
+}
+impl<F> ValidatorResolver for F
+where
+    F: Fn(&mut Request, &mut Depot) -> Option<Validators> + Send + Sync + 'static,
+{
+    async fn resolve(&self, req: &mut Request, depot: &mut Depot) -> Option<Validators> {
+        (self)(req, depot)
+    }
+}
+
+/// Precondition handler for unsafe methods.
+///
+/// Safe methods (`GET`, `HEAD`, `OPTIONS` and `TRACE`) are passed through, they are handled by
+/// [`CachingHeaders`](crate::caching_headers::CachingHeaders) instead.
+///
+/// The preconditions are evaluated in the order given by
+/// [RFC 9110](https://www.rfc-editor.org/rfc/rfc9110#section-13.2.2):
+///
+/// 1. `If-Match` is compared with the strong comparison, it fails if the resource does not exist.
+/// 2. `If-Unmodified-Since` is evaluated only without `If-Match`, and ignored if the resource has no last
+///    modified time.
+/// 3. `If-None-Match` fails if it matches the resource, use `If-None-Match: *` to only create resources.
+pub struct Preconditions<R> {
+    resolver: R,
+    required: bool,
+}
+
+impl<R: ValidatorResolver> Preconditions<R> {
+    /// Create new `Preconditions`.
+    #[inline]
+    pub fn new(resolver: R) -> Self {
+        Self {
+            resolver,
+            required: false,
+        }
+    }
+
+    /// Sets `required` and returns new `Preconditions`.
+    /// If `required` is true, requests to existing resources without `If-Match` or `If-Unmodified-Since`
+    /// headers are answered with `428 Precondition Required`.
+    #[inline]
+    pub fn required(mut self, required: bool) -> Self {
+        self.required = required;
+        self
+    }
+
+    /// Documents the precondition headers and the `412` and `428` responses on the endpoints of unsafe
+    /// methods in the router and it's descents.
+    ///
+    /// Call it on the router the middleware is added to.
+    #[cfg(feature = "oapi")]
+    pub fn oapi_document(&self, router: salvo_core::Router) -> salvo_core::Router {
+        use salvo_core::routing::filters::MethodFilter;
+        use salvo_oapi::{Parameter, ParameterIn, Required, RouterExt};
+
+        fn document(mut router: salvo_core::Router, required: bool) -> salvo_core::Router {
+            for child in router.routers_mut() {
+                *child = document(std::mem::take(child), required);
+            }
+            let unsafe_method = router.filters().iter().any(|filter| {
+                filter
+                    .as_method_filter()
+                    .map(MethodFilter::method)
+                    .is_some_and(|method| !method.is_safe())
+            });
+            if !unsafe_method {
+                return router;
+            }
+            let header = |name: &str, description: &str| {
+                Parameter::new(name)
+                    .parameter_in(ParameterIn::Header)
+                    .required(Required::False)
+                    .description(description)
+            };
+            let router = router
+                .oapi_parameter(header(
+                    "If-Match",
+                    "Entity tags of which one must match the current version of the resource.",
+                ))
+                .oapi_parameter(header(
+                    "If-None-Match",
+                    "Entity tags which must not match the current version of the resource, `*` to only create it.",
+                ))
+                .oapi_parameter(header(
+                    "If-Unmodified-Since",
+                    "The resource must not be modified after this date.",
+                ))
+                .oapi_response(
+                    "412",
+                    salvo_oapi::Response::new("The resource does not meet the preconditions."),
+                );
+            if required {
+                router.oapi_response(
+                    "428",
+                    salvo_oapi::Response::new(
+                        "The request to an existing resource must be conditional.",
+                    ),
+                )
+            } else {
+                router
+            }
+        }
+        document(router, self.required)
+    }
+}
+
+fn header_values<'a>(req: &'a Request, name: &HeaderName) -> Option<Vec<&'a str>> {
+    let mut values = req.headers().get_all(name).iter().peekable();
+    values.peek()?;
+    Some(
+        values
+            .filter_map(|value| value.to_str().ok())
+            .flat_map(|value| value.split(','))
+            .map(str::trim)
+            .filter(|value| !value.is_empty())
+            .collect(),
+    )
+}
+
+fn is_matched(values: &[&str], etag: Option<&EntityTag>, strong: bool) -> bool {
+    let Some(etag) = etag else {
+        return false;
+    };
+    values.iter().any(|value| {
+        *value == "*"
+            || value.parse::<EntityTag>().is_ok_and(|other| {
+                if strong {
+                    etag.strong_eq(&other)
+                } else {
+                    etag.weak_eq(&other)
+                }
+            })
+    })
+}
+
+#[async_trait]
+impl<R: ValidatorResolver> Handler for Preconditions<R> {
+    async fn handle(
+        &self,
+        req: &mut Request,
+        depot: &mut Depot,
+        res: &mut Response,
+        ctrl: &mut FlowCtrl,
+    ) {
+        if req.method().is_safe() {
+            return;
+        }
+        let validators = self.resolver.resolve(req, depot).await;
+        let exists = validators.is_some();
+        let validators = validators.unwrap_or_default();
+
+        let if_match = header_values(req, &IF_MATCH);
+        let if_unmodified_since = req.headers().typed_get::<IfUnmodifiedSince>();
+        let passed = if let Some(values) = &if_match {
+            // `*` matches any current version, but never a missing resource.
+            exists && (values.contains(&"*") || is_matched(values, validators.etag.as_ref(), true))
+        } else if let (Some(since), Some(last_modified)) =
+            (&if_unmodified_since, validators.last_modified)
+        {
+            exists && since.precondition_passes(last_modified)
+        } else {
+            true
+        };
+        let passed = passed
+            && match header_values(req, &IF_NONE_MATCH) {
+                Some(values) => {
+                    !(exists
+                        && (values.contains(&"*")
+                            || is_matched(&values, validators.etag.as_ref(), false)))
+                }
+                None => true,
+            };
+        if !passed {
+            tracing::debug!(path = req.uri().path(), "precondition failed");
+            res.render(StatusError::precondition_failed());
+            ctrl.skip_rest();
+        } else if self.required && exists && if_match.is_none() && if_unmodified_since.is_none() {
+            res.render(
+                StatusError::precondition_required()
+                    .brief("The request must be conditional, use `If-Match` header."),
+            );
+            ctrl.skip_rest();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, SystemTime};
+
+    use salvo_core::http::header::{IF_MATCH, IF_NONE_MATCH, IF_UNMODIFIED_SINCE};
+    use salvo_core::prelude::*;
+    use salvo_core::test::{ResponseExt, TestClient};
+
+    use super::*;
+
+    #[handler]
+    async fn update() -> &'static str {
+        "updated"
+    }
+
+    fn service(required: bool, modified: SystemTime) -> Service {
+        let preconditions = Preconditions::new(move |req: &mut Request, _: &mut Depot| {
+            if req.param::<String>("id")? != "1" {
+                return None;
+            }
+            Some(
+                Validators::new()
+                    .etag("\"v2\"".parse().unwrap())
+                    .last_modified(modified),
+            )
+        })
+        .required(required);
+        let router = Router::with_path("articles/{id}")
+            .hoop(preconditions)
+            .get(update)
+            .put(update);
+        Service::new(router)
+    }
+
+    #[tokio::test]
+    async fn test_if_match() {
+        let service = service(false, SystemTime::now());
+
+        let res = TestClient::put("http://127.0.0.1:5801/articles/1")
+            .add_header(IF_MATCH, "\"v1\"", true)
+            .send(&service)
+            .await;
+        assert_eq!(res.status_code, Some(StatusCode::PRECONDITION_FAILED));
+
+        let mut res = TestClient::put("http://127.0.0.1:5801/articles/1")
+            .add_header(IF_MATCH, "\"v1\", \"v2\"", true)
+            .send(&service)
+            .await;
+        assert_eq!(res.status_code, Some(StatusCode::OK));
+        assert_eq!(res.take_string().await.unwrap(), "updated");
+
+        // Weak etags never match `If-Match`.
+        let res = TestClient::put("http://127.0.0.1:5801/articles/1")
+            .add_header(IF_MATCH, "W/\"v2\"", true)
+            .send(&service)
+            .await;
+        assert_eq!(res.status_code, Some(StatusCode::PRECONDITION_FAILED));
+
+        let res = TestClient::put("http://127.0.0.1:5801/articles/2")
+            .add_header(IF_MATCH, "*", true)
+            .send(&service)
+            .await;
+        assert_eq!(res.status_code, Some(StatusCode::PRECONDITION_FAILED));
+
+        // Safe methods are not checked.
+        let res = TestClient::get("http://127.0.0.1:5801/articles/1")
+            .add_header(IF_MATCH, "\"v1\"", true)
+            .send(&service)
+            .await;
+        assert_eq!(res.status_code, Some(StatusCode::OK));
+    }
+
+    #[tokio::test]
+    async fn test_if_unmodified_since_and_if_none_match() {
+        // Sun, 09 Sep 2001 01:46:40 GMT
+        let service = service(
+            false,
+            SystemTime::UNIX_EPOCH + Duration::from_secs(1_000_000_000),
+        );
+
+        let res = TestClient::put("http://127.0.0.1:5801/articles/1")
+            .add_header(IF_UNMODIFIED_SINCE, "Sat, 08 Sep 2001 00:00:00 GMT", true)
+            .send(&service)
+            .await;
+        assert_eq!(res.status_code, Some(StatusCode::PRECONDITION_FAILED));
+
+        let res = TestClient::put("http://127.0.0.1:5801/articles/1")
+            .add_header(IF_UNMODIFIED_SINCE, "Sun, 09 Sep 2001 01:46:40 GMT", true)
+            .send(&service)
+            .await;
+        assert_eq!(res.status_code, Some(StatusCode::OK));
+
+        let res = TestClient::put("http://127.0.0.1:5801/articles/1")
+            .add_header(IF_NONE_MATCH, "*", true)
+            .send(&service)
+            .await;
+        assert_eq!(res.status_code, Some(StatusCode::PRECONDITION_FAILED));
+
+        let res = TestClient::put("http://127.0.0.1:5801/articles/2")
+            .add_header(IF_NONE_MATCH, "*", true)
+            .send(&service)
+            .await;
+        assert_eq!(res.status_code, Some(StatusCode::OK));
+    }
+
+    #[cfg(feature = "oapi")]
+    #[test]
+    fn test_oapi_document() {
+        use salvo_oapi::{OpenApi, PathItemType};
+
+        #[salvo_oapi::endpoint]
+        async fn article() -> &'static str {
+            "article"
+        }
+
+        let preconditions =
+            Preconditions::new(|_: &mut Request, _: &mut Depot| None).required(true);
+        let router = preconditions
+            .oapi_document(Router::with_path("articles/{id}").get(article).put(article));
+        let doc = OpenApi::new("test", "0.0.1").merge_router(&router);
+        let operations = &doc.paths["/articles/{id}"].operations;
+        let has_precondition = |method| {
+            let operation = &operations[&method];
+            operation
+                .parameters
+                .0
+                .iter()
+                .any(|parameter| parameter.name == "If-Match")
+                && operation.responses.contains_key("412")
+        };
+        assert!(has_precondition(PathItemType::Put));
+        assert!(!has_precondition(PathItemType::Get));
+    }
+
+    #[tokio::test]
+    async fn test_required() {
+        let service = service(true, SystemTime::now());
+
+        let res = TestClient::put("http://127.0.0.1:5801/articles/1")
+            .send(&service)
+            .await;
+        assert_eq!(res.status_code, Some(StatusCode::PRECONDITION_REQUIRED));
+
+        let res = TestClient::put("http://127.0.0.1:5801/articles/1")
+            .add_header(IF_MATCH, "\"v2\"", true)
+            .send(&service)
+            .await;
+        assert_eq!(res.status_code, Some(StatusCode::OK));
+
+        // Creating a resource does not need a precondition.
+        let res = TestClient::put("http://127.0.0.1:5801/articles/2")
+            .send(&service)
+            .await;
+        assert_eq!(res.status_code, Some(StatusCode::OK));
+    }
+}
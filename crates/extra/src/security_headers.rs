@@ -0,0 +1,831 @@
+//! Middleware for setting security related response headers.
+//!
+//! [`SecurityHeaders`] sets `Content-Security-Policy`, `Strict-Transport-Security`, `X-Content-Type-Options`,
+//! `X-Frame-Options`, `Referrer-Policy`, `Permissions-Policy` and the `Cross-Origin-*` headers. Each header has a
+//! typed builder, and headers already set by the handlers are not overwritten.
+//!
+//! If the content security policy contains [`ContentSecurityPolicy::NONCE`], a random nonce is generated for
+//! every request and stored in the [`Depot`]. Templates can read it with [`SecurityHeadersDepotExt::csp_nonce`]
+//! and use it in `<script nonce="...">` tags.
+//!
+//! Violation reports can be collected with [`CspReportCollector`], which accepts both the legacy
+//! `application/csp-report` format and the Reporting API format.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use salvo_core::prelude::*;
+//! use salvo_extra::security_headers::{
+//!     ContentSecurityPolicy, CspReportCollector, SecurityHeaders, SecurityHeadersDepotExt,
+//! };
+//!
+//! #[handler]
+//! async fn hello(depot: &mut Depot, res: &mut Response) {
+//!     let nonce = depot.csp_nonce().unwrap_or_default();
+//!     res.render(Text::Html(format!(r#"<script nonce="{nonce}">console.log("hello")</script>"#)));
+//! }
+//!
+//! #[tokio::main]
+//! async fn main() {
+//!     let csp = ContentSecurityPolicy::new()
+//!         .default_src(["'self'"])
+//!         .script_src(["'self'", ContentSecurityPolicy::NONCE])
+//!         .report_uri("/csp-report")
+//!         .report_only(true);
+//!     let router = Router::new()
+//!         .hoop(SecurityHeaders::new().content_security_policy(csp))
+//!         .get(hello)
+//!         .push(Router::with_path("csp-report").post(CspReportCollector::new()));
+//!
+//!     let acceptor = TcpListener::new("0.0.0.0:5800").bind().await;
+//!     Server::new(acceptor).serve(router).await;
+//! }
+//! ```
+use std::fmt::{self, Display, Formatter};
+use std::future::Future;
+use std::time::Duration;
+
+use base64::engine::{general_purpose, Engine};
+use salvo_core::http::header::{
+    HeaderName, HeaderValue, CONTENT_SECURITY_POLICY, CONTENT_SECURITY_POLICY_REPORT_ONLY, REFERRER_POLICY,
+    STRICT_TRANSPORT_SECURITY, X_CONTENT_TYPE_OPTIONS, X_FRAME_OPTIONS,
+};
+use salvo_core::http::{Request, Response, StatusCode};
+use salvo_core::{async_trait, Depot, FlowCtrl, Handler};
+use serde::Deserialize;
+use serde_json::Value;
+
+/// Key for CSP nonce in depot.
+pub const CSP_NONCE_KEY: &str = "::salvo::security_headers::csp_nonce";
+
+/// Extension for Depot.
+pub trait SecurityHeadersDepotExt {
+    /// Get the CSP nonce of current request.
+    fn csp_nonce(&self) -> Option<&str>;
+}
+
+impl SecurityHeadersDepotExt for Depot {
+    #[inline]
+    fn csp_nonce(&self) -> Option<&str> {
+        self.get::<String>(CSP_NONCE_KEY).map(|v| &**v).ok()
+    }
+}
+
+/// `Content-Security-Policy` header builder.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ContentSecurityPolicy {
+    directives: Vec<(String, Vec<String>)>,
+    report_only: bool,
+}
+impl ContentSecurityPolicy {
+    /// Source which is replaced with `'nonce-<value>'` for every request.
+    pub const NONCE: &'static str = "'nonce'";
+
+    /// Create an empty policy.
+    #[inline]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set a directive, replacing the sources if it already exists.
+    pub fn directive<I, S>(mut self, name: impl Into<String>, sources: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        let name = name.into();
+        let sources = sources.into_iter().map(Into::into).collect();
+        match self.directives.iter_mut().find(|(n, _)| *n == name) {
+            Some((_, values)) => *values = sources,
+            None => self.directives.push((name, sources)),
+        }
+        self
+    }
+
+    /// Set the `default-src` directive.
+    pub fn default_src<I: IntoIterator<Item = S>, S: Into<String>>(self, sources: I) -> Self {
+        self.directive("default-src", sources)
+    }
+    /// Set the `script-src` directive.
+    pub fn script_src<I: IntoIterator<Item = S>, S: Into<String>>(self, sources: I) -> Self {
+        self.directive("script-src", sources)
+    }
+    /// Set the `style-src` directive.
+    pub fn style_src<I: IntoIterator<Item = S>, S: Into<String>>(self, sources: I) -> Self {
+        self.directive("style-src", sources)
+    }
+    /// Set the `img-src` directive.
+    pub fn img_src<I: IntoIterator<Item = S>, S: Into<String>>(self, sources: I) -> Self {
+        self.directive("img-src", sources)
+    }
+    /// Set the `font-src` directive.
+    pub fn font_src<I: IntoIterator<Item = S>, S: Into<String>>(self, sources: I) -> Self {
+        self.directive("font-src", sources)
+    }
+    /// Set the `connect-src` directive.
+    pub fn connect_src<I: IntoIterator<Item = S>, S: Into<String>>(self, sources: I) -> Self {
+        self.directive("connect-src", sources)
+    }
+    /// Set the `object-src` directive.
+    pub fn object_src<I: IntoIterator<Item = S>, S: Into<String>>(self, sources: I) -> Self {
+        self.directive("object-src", sources)
+    }
+    /// Set the `frame-src` directive.
+    pub fn frame_src<I: IntoIterator<Item = S>, S: Into<String>>(self, sources: I) -> Self {
+        self.directive("frame-src", sources)
+    }
+    /// Set the `frame-ancestors` directive.
+    pub fn frame_ancestors<I: IntoIterator<Item = S>, S: Into<String>>(self, sources: I) -> Self {
+        self.directive("frame-ancestors", sources)
+    }
+    /// Set the `base-uri` directive.
+    pub fn base_uri<I: IntoIterator<Item = S>, S: Into<String>>(self, sources: I) -> Self {
+        self.directive("base-uri", sources)
+    }
+    /// Set the `form-action` directive.
+    pub fn form_action<I: IntoIterator<Item = S>, S: Into<String>>(self, sources: I) -> Self {
+        self.directive("form-action", sources)
+    }
+    /// Add the `upgrade-insecure-requests` directive.
+    pub fn upgrade_insecure_requests(self) -> Self {
+        self.directive("upgrade-insecure-requests", Vec::<String>::new())
+    }
+    /// Set the `report-uri` directive.
+    pub fn report_uri(self, uri: impl Into<String>) -> Self {
+        self.directive("report-uri", [uri])
+    }
+    /// Set the `report-to` directive, the group should be defined with a `Reporting-Endpoints` header.
+    pub fn report_to(self, group: impl Into<String>) -> Self {
+        self.directive("report-to", [group])
+    }
+
+    /// Send the policy with `Content-Security-Policy-Report-Only` header, so violations are only reported.
+    pub fn report_only(mut self, report_only: bool) -> Self {
+        self.report_only = report_only;
+        self
+    }
+
+    /// Check if the policy uses per-request nonce.
+    pub fn uses_nonce(&self) -> bool {
+        self.directives
+            .iter()
+            .any(|(_, sources)| sources.iter().any(|s| s == Self::NONCE))
+    }
+
+    /// Get the header name of this policy.
+    pub fn header_name(&self) -> HeaderName {
+        if self.report_only {
+            CONTENT_SECURITY_POLICY_REPORT_ONLY
+        } else {
+            CONTENT_SECURITY_POLICY
+        }
+    }
+
+    /// Build the header value, [`Self::NONCE`] sources are replaced with the given nonce.
+    pub fn header_value(&self, nonce: Option<&str>) -> String {
+        self.directives
+            .iter()
+            .map(|(name, sources)| {
+                let mut directive = name.clone();
+                for source in sources {
+                    directive.push(' ');
+                    match nonce {
+                        Some(nonce) if source == Self::NONCE => directive.push_str(&format!("'nonce-{nonce}'")),
+                        _ => directive.push_str(source),
+                    }
+                }
+                directive
+            })
+            .collect::<Vec<_>>()
+            .join("; ")
+    }
+}
+
+/// `Strict-Transport-Security` header builder.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Hsts {
+    max_age: Duration,
+    include_subdomains: bool,
+    preload: bool,
+}
+impl Default for Hsts {
+    fn default() -> Self {
+        Self {
+            max_age: Duration::from_secs(365 * 24 * 60 * 60),
+            include_subdomains: true,
+            preload: false,
+        }
+    }
+}
+impl Hsts {
+    /// Create a new `Hsts` with one year `max-age` and `includeSubDomains`.
+    #[inline]
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// Set `max-age`.
+    pub fn max_age(mut self, max_age: Duration) -> Self {
+        self.max_age = max_age;
+        self
+    }
+    /// Set whether to add `includeSubDomains`.
+    pub fn include_subdomains(mut self, include_subdomains: bool) -> Self {
+        self.include_subdomains = include_subdomains;
+        self
+    }
+    /// Set whether to add `preload`.
+    pub fn preload(mut self, preload: bool) -> Self {
+        self.preload = preload;
+        self
+    }
+}
+impl Display for Hsts {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "max-age={}", self.max_age.as_secs())?;
+        if self.include_subdomains {
+            f.write_str("; includeSubDomains")?;
+        }
+        if self.preload {
+            f.write_str("; preload")?;
+        }
+        Ok(())
+    }
+}
+
+/// `X-Frame-Options` header value.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FrameOptions {
+    /// `DENY`
+    Deny,
+    /// `SAMEORIGIN`
+    SameOrigin,
+}
+impl Display for FrameOptions {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            Self::Deny => f.write_str("DENY"),
+            Self::SameOrigin => f.write_str("SAMEORIGIN"),
+        }
+    }
+}
+
+/// `Referrer-Policy` header value.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ReferrerPolicy {
+    /// `no-referrer`
+    NoReferrer,
+    /// `no-referrer-when-downgrade`
+    NoReferrerWhenDowngrade,
+    /// `origin`
+    Origin,
+    /// `origin-when-cross-origin`
+    OriginWhenCrossOrigin,
+    /// `same-origin`
+    SameOrigin,
+    /// `strict-origin`
+    StrictOrigin,
+    /// `strict-origin-when-cross-origin`
+    StrictOriginWhenCrossOrigin,
+    /// `unsafe-url`
+    UnsafeUrl,
+}
+impl Display for ReferrerPolicy {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Self::NoReferrer => "no-referrer",
+            Self::NoReferrerWhenDowngrade => "no-referrer-when-downgrade",
+            Self::Origin => "origin",
+            Self::OriginWhenCrossOrigin => "origin-when-cross-origin",
+            Self::SameOrigin => "same-origin",
+            Self::StrictOrigin => "strict-origin",
+            Self::StrictOriginWhenCrossOrigin => "strict-origin-when-cross-origin",
+            Self::UnsafeUrl => "unsafe-url",
+        })
+    }
+}
+
+/// `Permissions-Policy` header builder.
+///
+/// Each feature has an allowlist, an empty allowlist disables the feature, for example `camera=()`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct PermissionsPolicy {
+    features: Vec<(String, Vec<String>)>,
+}
+impl PermissionsPolicy {
+    /// Create an empty policy.
+    #[inline]
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// Set the allowlist of a feature, such as `self` or `"https://example.com"`.
+    pub fn feature<I, S>(mut self, name: impl Into<String>, allowlist: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        let name = name.into();
+        let allowlist = allowlist.into_iter().map(Into::into).collect();
+        match self.features.iter_mut().find(|(n, _)| *n == name) {
+            Some((_, values)) => *values = allowlist,
+            None => self.features.push((name, allowlist)),
+        }
+        self
+    }
+    /// Disable a feature for all origins.
+    pub fn disable(self, name: impl Into<String>) -> Self {
+        self.feature(name, Vec::<String>::new())
+    }
+}
+impl Display for PermissionsPolicy {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        let value = self
+            .features
+            .iter()
+            .map(|(name, allowlist)| format!("{}=({})", name, allowlist.join(" ")))
+            .collect::<Vec<_>>()
+            .join(", ");
+        f.write_str(&value)
+    }
+}
+
+/// `Cross-Origin-Opener-Policy` header value.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CrossOriginOpenerPolicy {
+    /// `unsafe-none`
+    UnsafeNone,
+    /// `same-origin-allow-popups`
+    SameOriginAllowPopups,
+    /// `same-origin`
+    SameOrigin,
+}
+impl Display for CrossOriginOpenerPolicy {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Self::UnsafeNone => "unsafe-none",
+            Self::SameOriginAllowPopups => "same-origin-allow-popups",
+            Self::SameOrigin => "same-origin",
+        })
+    }
+}
+
+/// `Cross-Origin-Embedder-Policy` header value.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CrossOriginEmbedderPolicy {
+    /// `unsafe-none`
+    UnsafeNone,
+    /// `require-corp`
+    RequireCorp,
+    /// `credentialless`
+    Credentialless,
+}
+impl Display for CrossOriginEmbedderPolicy {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Self::UnsafeNone => "unsafe-none",
+            Self::RequireCorp => "require-corp",
+            Self::Credentialless => "credentialless",
+        })
+    }
+}
+
+/// `Cross-Origin-Resource-Policy` header value.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CrossOriginResourcePolicy {
+    /// `same-site`
+    SameSite,
+    /// `same-origin`
+    SameOrigin,
+    /// `cross-origin`
+    CrossOrigin,
+}
+impl Display for CrossOriginResourcePolicy {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Self::SameSite => "same-site",
+            Self::SameOrigin => "same-origin",
+            Self::CrossOrigin => "cross-origin",
+        })
+    }
+}
+
+/// Middleware for setting security related response headers.
+///
+/// View [module level documentation](index.html) for more details.
+#[derive(Clone, Debug)]
+pub struct SecurityHeaders {
+    content_security_policy: Option<ContentSecurityPolicy>,
+    hsts: Option<Hsts>,
+    content_type_options: bool,
+    frame_options: Option<FrameOptions>,
+    referrer_policy: Option<ReferrerPolicy>,
+    permissions_policy: Option<PermissionsPolicy>,
+    cross_origin_opener_policy: Option<CrossOriginOpenerPolicy>,
+    cross_origin_embedder_policy: Option<CrossOriginEmbedderPolicy>,
+    cross_origin_resource_policy: Option<CrossOriginResourcePolicy>,
+}
+impl Default for SecurityHeaders {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+impl SecurityHeaders {
+    /// Create a new `SecurityHeaders` with secure defaults.
+    ///
+    /// The default content security policy is `default-src 'self'; base-uri 'self'; form-action 'self';
+    /// frame-ancestors 'self'; object-src 'none'`.
+    pub fn new() -> Self {
+        Self {
+            content_security_policy: Some(
+                ContentSecurityPolicy::new()
+                    .default_src(["'self'"])
+                    .base_uri(["'self'"])
+                    .form_action(["'self'"])
+                    .frame_ancestors(["'self'"])
+                    .object_src(["'none'"]),
+            ),
+            hsts: Some(Hsts::new()),
+            content_type_options: true,
+            frame_options: Some(FrameOptions::SameOrigin),
+            referrer_policy: Some(ReferrerPolicy::NoReferrer),
+            permissions_policy: None,
+            cross_origin_opener_policy: Some(CrossOriginOpenerPolicy::SameOrigin),
+            cross_origin_embedder_policy: None,
+            cross_origin_resource_policy: Some(CrossOriginResourcePolicy::SameOrigin),
+        }
+    }
+
+    /// Create a new `SecurityHeaders` which sets no header.
+    pub fn empty() -> Self {
+        Self {
+            content_security_policy: None,
+            hsts: None,
+            content_type_options: false,
+            frame_options: None,
+            referrer_policy: None,
+            permissions_policy: None,
+            cross_origin_opener_policy: None,
+            cross_origin_embedder_policy: None,
+            cross_origin_resource_policy: None,
+        }
+    }
+
+    /// Set `Content-Security-Policy`, `None` to disable it.
+    pub fn content_security_policy(mut self, policy: impl Into<Option<ContentSecurityPolicy>>) -> Self {
+        self.content_security_policy = policy.into();
+        self
+    }
+    /// Set `Strict-Transport-Security`, `None` to disable it.
+    pub fn hsts(mut self, hsts: impl Into<Option<Hsts>>) -> Self {
+        self.hsts = hsts.into();
+        self
+    }
+    /// Set whether to send `X-Content-Type-Options: nosniff`.
+    pub fn content_type_options(mut self, nosniff: bool) -> Self {
+        self.content_type_options = nosniff;
+        self
+    }
+    /// Set `X-Frame-Options`, `None` to disable it.
+    pub fn frame_options(mut self, frame_options: impl Into<Option<FrameOptions>>) -> Self {
+        self.frame_options = frame_options.into();
+        self
+    }
+    /// Set `Referrer-Policy`, `None` to disable it.
+    pub fn referrer_policy(mut self, referrer_policy: impl Into<Option<ReferrerPolicy>>) -> Self {
+        self.referrer_policy = referrer_policy.into();
+        self
+    }
+    /// Set `Permissions-Policy`, `None` to disable it.
+    pub fn permissions_policy(mut self, permissions_policy: impl Into<Option<PermissionsPolicy>>) -> Self {
+        self.permissions_policy = permissions_policy.into();
+        self
+    }
+    /// Set `Cross-Origin-Opener-Policy`, `None` to disable it.
+    pub fn cross_origin_opener_policy(mut self, policy: impl Into<Option<CrossOriginOpenerPolicy>>) -> Self {
+        self.cross_origin_opener_policy = policy.into();
+        self
+    }
+    /// Set `Cross-Origin-Embedder-Policy`, `None` to disable it.
+    pub fn cross_origin_embedder_policy(mut self, policy: impl Into<Option<CrossOriginEmbedderPolicy>>) -> Self {
+        self.cross_origin_embedder_policy = policy.into();
+        self
+    }
+    /// Set `Cross-Origin-Resource-Policy`, `None` to disable it.
+    pub fn cross_origin_resource_policy(mut self, policy: impl Into<Option<CrossOriginResourcePolicy>>) -> Self {
+        self.cross_origin_resource_policy = policy.into();
+        self
+    }
+}
+
+fn generate_nonce() -> String {
+    general_purpose::STANDARD.encode(rand::random::<[u8; 16]>())
+}
+
+fn set_header(res: &mut Response, name: HeaderName, value: impl ToString) {
+    if let Ok(value) = HeaderValue::from_str(&value.to_string()) {
+        res.headers_mut().entry(name).or_insert(value);
+    }
+}
+
+#[async_trait]
+impl Handler for SecurityHeaders {
+    async fn handle(&self, req: &mut Request, depot: &mut Depot, res: &mut Response, ctrl: &mut FlowCtrl) {
+        let nonce = match &self.content_security_policy {
+            Some(policy) if policy.uses_nonce() => {
+                let nonce = generate_nonce();
+                depot.insert(CSP_NONCE_KEY, nonce.clone());
+                Some(nonce)
+            }
+            _ => None,
+        };
+        ctrl.call_next(req, depot, res).await;
+
+        if let Some(policy) = &self.content_security_policy {
+            set_header(res, policy.header_name(), policy.header_value(nonce.as_deref()));
+        }
+        if let Some(hsts) = &self.hsts {
+            set_header(res, STRICT_TRANSPORT_SECURITY, hsts);
+        }
+        if self.content_type_options {
+            set_header(res, X_CONTENT_TYPE_OPTIONS, "nosniff");
+        }
+        if let Some(frame_options) = &self.frame_options {
+            set_header(res, X_FRAME_OPTIONS, frame_options);
+        }
+        if let Some(referrer_policy) = &self.referrer_policy {
+            set_header(res, REFERRER_POLICY, referrer_policy);
+        }
+        if let Some(permissions_policy) = &self.permissions_policy {
+            set_header(res, HeaderName::from_static("permissions-policy"), permissions_policy);
+        }
+        if let Some(policy) = &self.cross_origin_opener_policy {
+            set_header(res, HeaderName::from_static("cross-origin-opener-policy"), policy);
+        }
+        if let Some(policy) = &self.cross_origin_embedder_policy {
+            set_header(res, HeaderName::from_static("cross-origin-embedder-policy"), policy);
+        }
+        if let Some(policy) = &self.cross_origin_resource_policy {
+            set_header(res, HeaderName::from_static("cross-origin-resource-policy"), policy);
+        }
+    }
+}
+
+/// A content security policy violation report.
+#[derive(Clone, Debug, Default, PartialEq)]
+#[non_exhaustive]
+pub struct CspReport {
+    /// The url of the document in which the violation occurred.
+    pub document_uri: Option<String>,
+    /// The url of the resource that was blocked.
+    pub blocked_uri: Option<String>,
+    /// The directive whose enforcement caused the violation.
+    pub effective_directive: Option<String>,
+    /// The original policy.
+    pub original_policy: Option<String>,
+    /// `enforce` or `report`.
+    pub disposition: Option<String>,
+    /// The raw report body.
+    pub raw: Value,
+}
+impl CspReport {
+    /// Parse reports from request body, both `application/csp-report` and `application/reports+json` formats
+    /// are supported.
+    pub fn parse(body: &[u8]) -> Result<Vec<Self>, serde_json::Error> {
+        #[derive(Deserialize)]
+        struct Reporting {
+            #[serde(rename = "type")]
+            kind: String,
+            body: Value,
+        }
+        fn field(value: &Value, names: &[&str]) -> Option<String> {
+            names
+                .iter()
+                .find_map(|name| value.get(name).and_then(Value::as_str).map(ToOwned::to_owned))
+        }
+
+        let mut value = serde_json::from_slice::<Value>(body)?;
+        if let Some(raw) = value.get_mut("csp-report").map(Value::take) {
+            return Ok(vec![Self {
+                document_uri: field(&raw, &["document-uri"]),
+                blocked_uri: field(&raw, &["blocked-uri"]),
+                effective_directive: field(&raw, &["effective-directive", "violated-directive"]),
+                original_policy: field(&raw, &["original-policy"]),
+                disposition: field(&raw, &["disposition"]),
+                raw,
+            }]);
+        }
+        let reports = serde_json::from_value::<Vec<Reporting>>(value)?;
+        Ok(reports
+            .into_iter()
+            .filter(|report| report.kind == "csp-violation")
+            .map(|report| {
+                let raw = report.body;
+                Self {
+                    document_uri: field(&raw, &["documentURL"]),
+                    blocked_uri: field(&raw, &["blockedURL"]),
+                    effective_directive: field(&raw, &["effectiveDirective"]),
+                    original_policy: field(&raw, &["originalPolicy"]),
+                    disposition: field(&raw, &["disposition"]),
+                    raw,
+                }
+            })
+            .collect())
+    }
+}
+
+/// Receiver of CSP violation reports.
+pub trait CspReportSink: Send + Sync {
+    /// Handle a report.
+    fn report(&self, report: CspReport) -> impl Future<Output = ()> + Send;
+}
+
+/// A [`CspReportSink`] which logs reports with `tracing`.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct TracingReportSink;
+impl CspReportSink for TracingReportSink {
+    async fn report(&self, report: CspReport) {
+        tracing::warn!(
+            document_uri = ?report.document_uri,
+            blocked_uri = ?report.blocked_uri,
+            effective_directive = ?report.effective_directive,
+            disposition = ?report.disposition,
+            "content security policy violation"
+        );
+    }
+}
+
+/// Handler for collecting CSP violation reports.
+///
+/// Mount it on the path used in `report-uri` or `Reporting-Endpoints`, it responds `204 No Content`.
+#[derive(Clone, Debug)]
+pub struct CspReportCollector<S = TracingReportSink> {
+    sink: S,
+    max_size: usize,
+}
+impl CspReportCollector {
+    /// Create a new `CspReportCollector` which logs reports with `tracing`.
+    pub fn new() -> Self {
+        Self::with_sink(TracingReportSink)
+    }
+}
+impl Default for CspReportCollector {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+impl<S: CspReportSink> CspReportCollector<S> {
+    /// Create a new `CspReportCollector` with custom sink.
+    pub fn with_sink(sink: S) -> Self {
+        Self { sink, max_size: 64 * 1024 }
+    }
+    /// Set the max size of report body, the default is 64KB.
+    pub fn max_size(mut self, max_size: usize) -> Self {
+        self.max_size = max_size;
+        self
+    }
+}
+
+#[async_trait]
+impl<S> Handler for CspReportCollector<S>
+where
+    S: CspReportSink + 'static,
+{
+    async fn handle(&self, req: &mut Request, _depot: &mut Depot, res: &mut Response, _ctrl: &mut FlowCtrl) {
+        let reports = match req.payload_with_max_size(self.max_size).await {
+            Ok(body) => CspReport::parse(body),
+            Err(e) => {
+                tracing::debug!(error = ?e, "read csp report failed");
+                res.status_code(StatusCode::BAD_REQUEST);
+                return;
+            }
+        };
+        match reports {
+            Ok(reports) => {
+                for report in reports {
+                    self.sink.report(report).await;
+                }
+                res.status_code(StatusCode::NO_CONTENT);
+            }
+            Err(e) => {
+                tracing::debug!(error = ?e, "parse csp report failed");
+                res.status_code(StatusCode::BAD_REQUEST);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use salvo_core::prelude::*;
+    use salvo_core::test::{ResponseExt, TestClient};
+
+    use super::*;
+
+    #[handler]
+    async fn hello(depot: &mut Depot) -> String {
+        depot.csp_nonce().unwrap_or_default().to_owned()
+    }
+
+    #[tokio::test]
+    async fn test_security_headers() {
+        let csp = ContentSecurityPolicy::new()
+            .default_src(["'self'"])
+            .script_src(["'self'", ContentSecurityPolicy::NONCE])
+            .upgrade_insecure_requests();
+        let router = Router::new()
+            .hoop(
+                SecurityHeaders::new()
+                    .content_security_policy(csp)
+                    .hsts(Hsts::new().preload(true))
+                    .frame_options(FrameOptions::Deny)
+                    .permissions_policy(PermissionsPolicy::new().disable("camera").feature("geolocation", ["self"])),
+            )
+            .get(hello);
+        let service = Service::new(router);
+
+        let mut res = TestClient::get("http://127.0.0.1:5800/").send(&service).await;
+        let headers = res.headers().clone();
+        let nonce = res.take_string().await.unwrap();
+        assert!(!nonce.is_empty());
+        assert_eq!(
+            headers.get(CONTENT_SECURITY_POLICY).unwrap(),
+            &format!("default-src 'self'; script-src 'self' 'nonce-{nonce}'; upgrade-insecure-requests")
+        );
+        assert_eq!(
+            headers.get(STRICT_TRANSPORT_SECURITY).unwrap(),
+            "max-age=31536000; includeSubDomains; preload"
+        );
+        assert_eq!(headers.get(X_CONTENT_TYPE_OPTIONS).unwrap(), "nosniff");
+        assert_eq!(headers.get(X_FRAME_OPTIONS).unwrap(), "DENY");
+        assert_eq!(headers.get(REFERRER_POLICY).unwrap(), "no-referrer");
+        assert_eq!(
+            headers.get("permissions-policy").unwrap(),
+            "camera=(), geolocation=(self)"
+        );
+        assert_eq!(headers.get("cross-origin-opener-policy").unwrap(), "same-origin");
+        assert!(headers.get("cross-origin-embedder-policy").is_none());
+
+        let res = TestClient::get("http://127.0.0.1:5800/").send(&service).await;
+        let other = res.headers().get(CONTENT_SECURITY_POLICY).unwrap();
+        assert_ne!(headers.get(CONTENT_SECURITY_POLICY).unwrap(), other);
+    }
+
+    #[tokio::test]
+    async fn test_report_only() {
+        let csp = ContentSecurityPolicy::new()
+            .default_src(["'self'"])
+            .report_uri("/csp-report")
+            .report_only(true);
+        let router = Router::new()
+            .hoop(SecurityHeaders::empty().content_security_policy(csp))
+            .get(hello);
+        let res = TestClient::get("http://127.0.0.1:5800/").send(router).await;
+        assert!(res.headers().get(CONTENT_SECURITY_POLICY).is_none());
+        assert_eq!(
+            res.headers().get(CONTENT_SECURITY_POLICY_REPORT_ONLY).unwrap(),
+            "default-src 'self'; report-uri /csp-report"
+        );
+        assert!(res.headers().get(X_FRAME_OPTIONS).is_none());
+    }
+
+    #[derive(Clone, Default)]
+    struct MemorySink(Arc<Mutex<Vec<CspReport>>>);
+    impl CspReportSink for MemorySink {
+        async fn report(&self, report: CspReport) {
+            self.0.lock().unwrap().push(report);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_report_collector() {
+        let sink = MemorySink::default();
+        let router = Router::new().post(CspReportCollector::with_sink(sink.clone()));
+        let service = Service::new(router);
+
+        let res = TestClient::post("http://127.0.0.1:5800/")
+            .raw_json(r#"{"csp-report": {"document-uri": "https://example.com/", "blocked-uri": "https://evil.com/x.js", "violated-directive": "script-src"}}"#)
+            .send(&service)
+            .await;
+        assert_eq!(res.status_code, Some(StatusCode::NO_CONTENT));
+
+        let res = TestClient::post("http://127.0.0.1:5800/")
+            .raw_json(r#"[{"type": "csp-violation", "body": {"documentURL": "https://example.com/", "blockedURL": "inline", "effectiveDirective": "script-src-elem", "disposition": "report"}}]"#)
+            .send(&service)
+            .await;
+        assert_eq!(res.status_code, Some(StatusCode::NO_CONTENT));
+
+        let res = TestClient::post("http://127.0.0.1:5800/")
+            .raw_json("not json")
+            .send(&service)
+            .await;
+        assert_eq!(res.status_code, Some(StatusCode::BAD_REQUEST));
+
+        let reports = sink.0.lock().unwrap();
+        assert_eq!(reports.len(), 2);
+        assert_eq!(reports[0].blocked_uri.as_deref(), Some("https://evil.com/x.js"));
+        assert_eq!(reports[0].effective_directive.as_deref(), Some("script-src"));
+        assert_eq!(reports[1].effective_directive.as_deref(), Some("script-src-elem"));
+        assert_eq!(reports[1].disposition.as_deref(), Some("report"));
+    }
+}
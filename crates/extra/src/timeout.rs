@@ -5,6 +5,13 @@
 //!
 //! This middleware can be used to deal with slow network attacks.
 //!
+//! The computed deadline is stored in the [`Depot`] as a [`Deadline`], so downstream handlers can read the
+//! remaining budget through [`TimeoutDepotExt::deadline`] and propagate it to other services, for example as a
+//! `grpc-timeout` header. When a `Timeout` is nested inside another one, the earlier deadline always wins.
+//!
+//! Handlers can also observe [`Deadline::cancelled`] to stop work cooperatively. Use [`Timeout::grace`] to give
+//! the handler some time to finish after the deadline is reached instead of dropping it immediately.
+//!
 //! # Example
 //!
 //! ```no_run
@@ -35,18 +42,164 @@
 //! }
 //! ```
 
-use std::time::Duration;
+use std::fmt::{self, Debug, Formatter};
+use std::future::Future;
+use std::io::{Error as IoError, ErrorKind};
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
 
+use salvo_core::http::body::{Body, Frame, SizeHint};
+use salvo_core::http::header::HeaderName;
 use salvo_core::http::headers::{Connection, HeaderMapExt};
-use salvo_core::http::{Request, Response, StatusError};
-use salvo_core::{async_trait, Depot, FlowCtrl, Handler};
+use salvo_core::http::{ReqBody, Request, Response, StatusError};
+use salvo_core::{async_trait, BoxedError, Depot, FlowCtrl, Handler};
+use tokio::time::Sleep;
+use tokio_util::sync::{CancellationToken, WaitForCancellationFuture};
+
+/// Key for deadline in depot.
+pub const DEADLINE_KEY: &str = "::salvo::timeout::deadline";
+
+/// The deadline of current request, computed by [`Timeout`].
+#[derive(Clone, Debug)]
+pub struct Deadline {
+    instant: Instant,
+    token: CancellationToken,
+}
+impl Deadline {
+    /// Create a new `Deadline` which expires at `instant`.
+    #[inline]
+    pub fn new(instant: Instant) -> Self {
+        Self {
+            instant,
+            token: CancellationToken::new(),
+        }
+    }
+    /// Get the instant when this deadline expires.
+    #[inline]
+    pub fn instant(&self) -> Instant {
+        self.instant
+    }
+    /// Get the remaining time budget, returns `Duration::ZERO` if the deadline is already reached.
+    #[inline]
+    pub fn remaining(&self) -> Duration {
+        self.instant.saturating_duration_since(Instant::now())
+    }
+    /// Check if the deadline is already reached.
+    #[inline]
+    pub fn is_expired(&self) -> bool {
+        Instant::now() >= self.instant
+    }
+    /// Check if the request is cancelled because of timeout.
+    #[inline]
+    pub fn is_cancelled(&self) -> bool {
+        self.token.is_cancelled()
+    }
+    /// Returns a future that completes when the request is cancelled because of timeout.
+    #[inline]
+    pub fn cancelled(&self) -> WaitForCancellationFuture<'_> {
+        self.token.cancelled()
+    }
+    /// Get the cancellation token of this deadline.
+    #[inline]
+    pub fn cancellation_token(&self) -> CancellationToken {
+        self.token.clone()
+    }
+    /// Format the remaining time budget as a `grpc-timeout` header value, like `1500m`.
+    pub fn grpc_timeout(&self) -> String {
+        format_grpc_timeout(self.remaining())
+    }
+    /// Get the remaining time budget in milliseconds.
+    #[inline]
+    pub fn remaining_millis(&self) -> u128 {
+        self.remaining().as_millis()
+    }
+}
+
+/// Extension for Depot.
+pub trait TimeoutDepotExt {
+    /// Get deadline reference from depot.
+    fn deadline(&self) -> Option<&Deadline>;
+}
+
+impl TimeoutDepotExt for Depot {
+    #[inline]
+    fn deadline(&self) -> Option<&Deadline> {
+        self.get::<Deadline>(DEADLINE_KEY).ok()
+    }
+}
+
+/// Parse a timeout header value.
+///
+/// Both the `grpc-timeout` format (an integer followed by one of the units `H`, `M`, `S`, `m`, `u`, `n`)
+/// and a bare integer of milliseconds are accepted.
+pub fn parse_timeout_header(value: &str) -> Option<Duration> {
+    let value = value.trim();
+    if value.is_empty() {
+        return None;
+    }
+    if let Ok(millis) = value.parse::<u64>() {
+        return Some(Duration::from_millis(millis));
+    }
+    let (index, unit) = value.char_indices().last()?;
+    let digits = &value[..index];
+    // gRPC limits the value to at most 8 digits.
+    if digits.is_empty() || digits.len() > 8 {
+        return None;
+    }
+    let amount = digits.parse::<u64>().ok()?;
+    match unit {
+        'H' => Some(Duration::from_secs(amount * 3600)),
+        'M' => Some(Duration::from_secs(amount * 60)),
+        'S' => Some(Duration::from_secs(amount)),
+        'm' => Some(Duration::from_millis(amount)),
+        'u' => Some(Duration::from_micros(amount)),
+        'n' => Some(Duration::from_nanos(amount)),
+        _ => None,
+    }
+}
+
+/// Format a duration as a `grpc-timeout` header value.
+pub fn format_grpc_timeout(duration: Duration) -> String {
+    const MAX: u128 = 99_999_999;
+    let nanos = duration.as_nanos();
+    if nanos <= MAX {
+        return format!("{nanos}n");
+    }
+    let micros = duration.as_micros();
+    if micros <= MAX {
+        return format!("{micros}u");
+    }
+    let millis = duration.as_millis();
+    if millis <= MAX {
+        return format!("{millis}m");
+    }
+    let secs = duration.as_secs() as u128;
+    if secs <= MAX {
+        return format!("{secs}S");
+    }
+    format!("{}H", (secs / 3600).min(MAX))
+}
 
 /// Middleware for controlling request timeout.
-/// 
+///
 /// View [module level documentation](index.html) for more details.
 pub struct Timeout {
     value: Duration,
     error: Box<dyn Fn() -> StatusError + Send + Sync + 'static>,
+    client_header: Option<HeaderName>,
+    grace: Option<Duration>,
+    read_timeout: Option<Duration>,
+}
+impl Debug for Timeout {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        f.debug_struct("Timeout")
+            .field("value", &self.value)
+            .field("client_header", &self.client_header)
+            .field("grace", &self.grace)
+            .field("read_timeout", &self.read_timeout)
+            .finish()
+    }
 }
 impl Timeout {
     /// Create a new `Timeout`.
@@ -58,6 +211,9 @@ impl Timeout {
         Timeout {
             value,
             error: Box::new(|| StatusError::service_unavailable().brief("Server process the request timeout.")),
+            client_header: None,
+            grace: None,
+            read_timeout: None,
         }
     }
 
@@ -70,22 +226,148 @@ impl Timeout {
         self.error = Box::new(error);
         self
     }
+
+    /// Honor a client supplied timeout header, such as `grpc-timeout` or `x-request-timeout`.
+    ///
+    /// The header value is parsed by [`parse_timeout_header`]. It can only shorten the deadline, the timeout
+    /// value of this middleware is always used as the cap.
+    pub fn client_header(mut self, name: HeaderName) -> Self {
+        self.client_header = Some(name);
+        self
+    }
+
+    /// Set the grace period given to the handler after the deadline is reached.
+    ///
+    /// When the deadline is reached, [`Deadline::cancelled`] is notified first. If the handler finishes within the
+    /// grace period, its response is kept, otherwise it is dropped and the timeout error is rendered.
+    pub fn grace(mut self, grace: Duration) -> Self {
+        self.grace = Some(grace);
+        self
+    }
+
+    /// Set the maximum time waiting for the next frame when reading the request body.
+    ///
+    /// The timer starts when the body is polled and restarts after each frame, so handlers can do other work
+    /// before reading the body. This is checked separately from the overall deadline, so it can be used to reject
+    /// slow uploads early. When the timeout is reached, reading the body returns an error of kind
+    /// [`ErrorKind::TimedOut`].
+    pub fn read_timeout(mut self, read_timeout: Duration) -> Self {
+        self.read_timeout = Some(read_timeout);
+        self
+    }
 }
 #[async_trait]
 impl Handler for Timeout {
     #[inline]
     async fn handle(&self, req: &mut Request, depot: &mut Depot, res: &mut Response, ctrl: &mut FlowCtrl) {
-        tokio::select! {
-            _ = ctrl.call_next(req, depot, res) => {},
-            _ = tokio::time::sleep(self.value) => {
-                res.headers_mut().typed_insert(Connection::close());
-                res.render((self.error)());
-                ctrl.skip_rest();
+        let mut budget = self.value;
+        if let Some(client_budget) = self
+            .client_header
+            .as_ref()
+            .and_then(|name| req.headers().get(name))
+            .and_then(|value| value.to_str().ok())
+            .and_then(parse_timeout_header)
+        {
+            budget = budget.min(client_budget);
+        }
+        let instant = Instant::now() + budget;
+        let parent = depot.remove::<Deadline>(DEADLINE_KEY).ok();
+        let deadline = match &parent {
+            Some(parent) => Deadline {
+                instant: parent.instant.min(instant),
+                token: parent.token.child_token(),
+            },
+            None => Deadline::new(instant),
+        };
+        depot.insert(DEADLINE_KEY, deadline.clone());
+
+        if let Some(read_timeout) = self.read_timeout {
+            let body = req.take_body();
+            req.replace_body(ReqBody::Boxed {
+                inner: Box::pin(ReadTimeoutBody::new(body, read_timeout)),
+                fusewire: None,
+            });
+        }
+
+        let completed = {
+            let fut = ctrl.call_next(req, depot, res);
+            tokio::pin!(fut);
+            tokio::select! {
+                _ = &mut fut => true,
+                _ = tokio::time::sleep_until(deadline.instant.into()) => {
+                    deadline.token.cancel();
+                    match self.grace {
+                        Some(grace) => tokio::time::timeout(grace, fut).await.is_ok(),
+                        None => false,
+                    }
+                }
             }
+        };
+        if !completed {
+            res.headers_mut().typed_insert(Connection::close());
+            res.render((self.error)());
+            ctrl.skip_rest();
+        }
+
+        depot.delete(DEADLINE_KEY);
+        if let Some(parent) = parent {
+            depot.insert(DEADLINE_KEY, parent);
         }
     }
 }
 
+/// Request body wrapper which fails when no frame is received within the read timeout.
+struct ReadTimeoutBody {
+    inner: ReqBody,
+    timeout: Duration,
+    /// Started by the first poll waiting for a frame, cleared when a frame is received.
+    sleep: Option<Pin<Box<Sleep>>>,
+}
+impl ReadTimeoutBody {
+    fn new(inner: ReqBody, timeout: Duration) -> Self {
+        Self {
+            inner,
+            timeout,
+            sleep: None,
+        }
+    }
+}
+impl Body for ReadTimeoutBody {
+    type Data = salvo_core::hyper::body::Bytes;
+    type Error = BoxedError;
+
+    fn poll_frame(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Result<Frame<Self::Data>, Self::Error>>> {
+        match Pin::new(&mut self.inner).poll_frame(cx) {
+            Poll::Ready(Some(Ok(frame))) => {
+                self.sleep = None;
+                Poll::Ready(Some(Ok(frame)))
+            }
+            Poll::Ready(Some(Err(e))) => Poll::Ready(Some(Err(e.into()))),
+            Poll::Ready(None) => Poll::Ready(None),
+            Poll::Pending => {
+                let timeout = self.timeout;
+                let sleep = self.sleep.get_or_insert_with(|| Box::pin(tokio::time::sleep(timeout)));
+                match sleep.as_mut().poll(cx) {
+                    Poll::Ready(()) => Poll::Ready(Some(Err(IoError::new(
+                        ErrorKind::TimedOut,
+                        "read request body timeout",
+                    )
+                    .into()))),
+                    Poll::Pending => Poll::Pending,
+                }
+            }
+        }
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.inner.is_end_stream()
+    }
+
+    fn size_hint(&self) -> SizeHint {
+        self.inner.size_hint()
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use salvo_core::prelude::*;
@@ -127,4 +409,211 @@ mod tests {
             .unwrap();
         assert!(content.contains("hello"));
     }
+
+    #[tokio::test]
+    async fn test_timeout_deadline_in_depot() {
+        #[handler]
+        async fn budget(depot: &mut Depot) -> String {
+            depot.deadline().unwrap().remaining_millis().to_string()
+        }
+
+        let router = Router::new()
+            .hoop(Timeout::new(Duration::from_secs(5)))
+            .push(
+                Router::with_path("inner")
+                    .hoop(Timeout::new(Duration::from_secs(60)))
+                    .get(budget),
+            )
+            .push(
+                Router::with_path("short")
+                    .hoop(Timeout::new(Duration::from_secs(1)))
+                    .get(budget),
+            );
+        let service = Service::new(router);
+
+        let remaining = TestClient::get("http://127.0.0.1:5801/inner")
+            .send(&service)
+            .await
+            .take_string()
+            .await
+            .unwrap()
+            .parse::<u64>()
+            .unwrap();
+        assert!(remaining > 1000 && remaining <= 5000);
+
+        let remaining = TestClient::get("http://127.0.0.1:5801/short")
+            .send(&service)
+            .await
+            .take_string()
+            .await
+            .unwrap()
+            .parse::<u64>()
+            .unwrap();
+        assert!(remaining <= 1000);
+    }
+
+    #[tokio::test]
+    async fn test_timeout_client_header() {
+        #[handler]
+        async fn slow() -> &'static str {
+            tokio::time::sleep(Duration::from_secs(1)).await;
+            "hello"
+        }
+
+        let router = Router::new()
+            .hoop(Timeout::new(Duration::from_secs(5)).client_header(HeaderName::from_static("grpc-timeout")))
+            .get(slow);
+        let service = Service::new(router);
+
+        let content = TestClient::get("http://127.0.0.1:5801/")
+            .add_header("grpc-timeout", "100m", true)
+            .send(&service)
+            .await
+            .take_string()
+            .await
+            .unwrap();
+        assert!(content.contains("timeout"));
+
+        let content = TestClient::get("http://127.0.0.1:5801/")
+            .add_header("grpc-timeout", "10H", true)
+            .send(&service)
+            .await
+            .take_string()
+            .await
+            .unwrap();
+        assert!(content.contains("hello"));
+    }
+
+    #[tokio::test]
+    async fn test_timeout_grace() {
+        #[handler]
+        async fn cooperative(depot: &mut Depot, res: &mut Response) {
+            let deadline = depot.deadline().unwrap().clone();
+            tokio::select! {
+                _ = tokio::time::sleep(Duration::from_secs(6)) => res.render("hello"),
+                _ = deadline.cancelled() => res.render("cancelled"),
+            }
+        }
+
+        let router = Router::new()
+            .hoop(Timeout::new(Duration::from_millis(100)).grace(Duration::from_secs(1)))
+            .get(cooperative);
+        let content = TestClient::get("http://127.0.0.1:5801/")
+            .send(router)
+            .await
+            .take_string()
+            .await
+            .unwrap();
+        assert_eq!(content, "cancelled");
+    }
+
+    #[tokio::test]
+    async fn test_timeout_read_body() {
+        #[handler]
+        async fn echo(req: &mut Request) -> String {
+            match req.payload().await {
+                Ok(payload) => String::from_utf8_lossy(payload).into_owned(),
+                Err(_) => "read body failed".into(),
+            }
+        }
+
+        let router = Router::new()
+            .hoop(Timeout::new(Duration::from_secs(5)).read_timeout(Duration::from_millis(100)))
+            .post(echo);
+        let service = Service::new(router);
+        let content = TestClient::post("http://127.0.0.1:5801/")
+            .text("hello")
+            .send(&service)
+            .await
+            .take_string()
+            .await
+            .unwrap();
+        assert_eq!(content, "hello");
+
+        // The body never sends its next frame.
+        let stalled = http_body_util::StreamBody::new(tokio_stream::pending::<
+            Result<salvo_core::hyper::body::Frame<salvo_core::hyper::body::Bytes>, BoxedError>,
+        >());
+        let content = TestClient::post("http://127.0.0.1:5801/")
+            .body(ReqBody::Boxed {
+                inner: Box::pin(stalled),
+                fusewire: None,
+            })
+            .send(&service)
+            .await
+            .take_string()
+            .await
+            .unwrap();
+        assert_eq!(content, "read body failed");
+    }
+
+    #[tokio::test]
+    async fn test_timeout_read_body_after_work() {
+        #[handler]
+        async fn echo(req: &mut Request) -> String {
+            // Work done before reading the body does not count against the read timeout.
+            tokio::time::sleep(Duration::from_millis(300)).await;
+            match req.payload().await {
+                Ok(payload) => String::from_utf8_lossy(payload).into_owned(),
+                Err(_) => "read body failed".into(),
+            }
+        }
+        /// Body sending its only frame 50 milliseconds after it is first polled.
+        struct Delayed {
+            sleep: Option<Pin<Box<Sleep>>>,
+            sent: bool,
+        }
+        impl Body for Delayed {
+            type Data = salvo_core::hyper::body::Bytes;
+            type Error = BoxedError;
+
+            fn poll_frame(
+                mut self: Pin<&mut Self>,
+                cx: &mut Context<'_>,
+            ) -> Poll<Option<Result<Frame<Self::Data>, Self::Error>>> {
+                if self.sent {
+                    return Poll::Ready(None);
+                }
+                let sleep = self
+                    .sleep
+                    .get_or_insert_with(|| Box::pin(tokio::time::sleep(Duration::from_millis(50))));
+                std::task::ready!(sleep.as_mut().poll(cx));
+                self.sent = true;
+                Poll::Ready(Some(Ok(Frame::data("hello".into()))))
+            }
+        }
+
+        let router = Router::new()
+            .hoop(Timeout::new(Duration::from_secs(5)).read_timeout(Duration::from_millis(100)))
+            .post(echo);
+        let content = TestClient::post("http://127.0.0.1:5801/")
+            .body(ReqBody::Boxed {
+                inner: Box::pin(Delayed {
+                    sleep: None,
+                    sent: false,
+                }),
+                fusewire: None,
+            })
+            .send(&Service::new(router))
+            .await
+            .take_string()
+            .await
+            .unwrap();
+        assert_eq!(content, "hello");
+    }
+
+    #[test]
+    fn test_parse_timeout_header() {
+        assert_eq!(parse_timeout_header("100m"), Some(Duration::from_millis(100)));
+        assert_eq!(parse_timeout_header("2S"), Some(Duration::from_secs(2)));
+        assert_eq!(parse_timeout_header("1H"), Some(Duration::from_secs(3600)));
+        assert_eq!(parse_timeout_header("1500"), Some(Duration::from_millis(1500)));
+        assert_eq!(parse_timeout_header("123456789S"), None);
+        assert_eq!(parse_timeout_header("10x"), None);
+        assert_eq!(parse_timeout_header("10é"), None);
+        assert_eq!(parse_timeout_header("é"), None);
+        assert_eq!(parse_timeout_header(""), None);
+        assert_eq!(format_grpc_timeout(Duration::from_millis(1500)), "1500000u");
+        assert_eq!(format_grpc_timeout(Duration::from_secs(200_000)), "200000S");
+    }
 }
@@ -465,6 +465,14 @@ impl OpenApi {
                 operation
                     .securities
                     .extend(node.metadata.securities.iter().cloned());
+                for parameter in &node.metadata.parameters {
+                    operation.parameters.insert(parameter.clone());
+                }
+                for (status, response) in node.metadata.responses.iter() {
+                    if !operation.responses.contains_key(status) {
+                        operation.responses.insert(status.clone(), response.clone());
+                    }
+                }
                 let methods = if let Some(method) = &node.method {
                     vec![*method]
                 } else {
@@ -743,7 +751,7 @@ mod tests {
         extract::*,
         security::{ApiKey, ApiKeyValue, Http, HttpAuthScheme},
         server::Server,
-        ToSchema,
+        RouterExt, ToSchema,
     };
 
     use salvo_core::{http::ResBody, prelude::*};
@@ -1279,6 +1287,38 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_router_parameters_and_responses() {
+        #[salvo_oapi::endpoint(responses(
+            (status_code = 200, description = "City updated"),
+            (status_code = 412, description = "City was changed by others")
+        ))]
+        async fn update_city() -> &'static str {
+            "updated"
+        }
+
+        let router = Router::with_path("cities/{id}").push(
+            Router::new()
+                .oapi_parameter(Parameter::new("If-Match").parameter_in(ParameterIn::Header))
+                .oapi_response("412", Response::new("Precondition failed"))
+                .oapi_response("428", Response::new("Precondition required"))
+                .put(update_city),
+        );
+        let doc = OpenApi::new("my application", "0.1.0").merge_router(&router);
+        let operation = &doc.paths["/cities/{id}"].operations[&PathItemType::Put];
+
+        assert!(operation
+            .parameters
+            .contains("If-Match", ParameterIn::Header));
+        assert!(operation.responses.contains_key("200"));
+        assert!(operation.responses.contains_key("428"));
+        // Responses documented by the endpoint are kept.
+        assert_eq!(
+            operation.responses["412"],
+            RefOr::Type(Response::new("City was changed by others"))
+        );
+    }
+
     #[test]
     fn test_openapi_schema_work_with_generics() {
         #[derive(Serialize, Deserialize, Clone, Debug, ToSchema)]
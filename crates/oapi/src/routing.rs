@@ -5,7 +5,7 @@ use std::sync::{LazyLock, RwLock};
 use regex::Regex;
 use salvo_core::Router;
 
-use crate::{path::PathItemType, SecurityRequirement};
+use crate::{path::PathItemType, Parameter, RefOr, Response, Responses, SecurityRequirement};
 
 #[derive(Debug, Default)]
 pub(crate) struct NormNode {
@@ -33,6 +33,14 @@ impl NormNode {
             node.metadata
                 .securities
                 .extend(metadata.securities.iter().cloned());
+            node.metadata
+                .parameters
+                .extend(metadata.parameters.iter().cloned());
+            for (status, response) in metadata.responses.iter() {
+                node.metadata
+                    .responses
+                    .insert(status.clone(), response.clone());
+            }
         }
 
         let regex = Regex::new(r#"<([^/:>]+)(:[^>]*)?>"#).expect("invalid regex");
@@ -106,6 +114,21 @@ pub trait RouterExt {
     where
         I: IntoIterator<Item = V>,
         V: Into<String>;
+
+    /// Add parameter to the router.
+    ///
+    /// All endpoints in the router and it's descents will inherit this parameter, useful for parameters
+    /// handled by middlewares, such as headers.
+    fn oapi_parameter(self, parameter: Parameter) -> Self;
+
+    /// Add response to the router.
+    ///
+    /// All endpoints in the router and it's descents will inherit this response if they do not document
+    /// the same status code themselves.
+    fn oapi_response<S, R>(self, status: S, response: R) -> Self
+    where
+        S: Into<String>,
+        R: Into<RefOr<Response>>;
 }
 
 impl RouterExt for Router {
@@ -148,6 +171,26 @@ impl RouterExt for Router {
         metadata.tags.extend(iter.into_iter().map(Into::into));
         self
     }
+    fn oapi_parameter(self, parameter: Parameter) -> Self {
+        let mut guard = METADATA_REGISTRY
+            .write()
+            .expect("failed to lock METADATA_REGISTRY for write");
+        let metadata = guard.entry(self.id).or_default();
+        metadata.parameters.push(parameter);
+        self
+    }
+    fn oapi_response<S, R>(self, status: S, response: R) -> Self
+    where
+        S: Into<String>,
+        R: Into<RefOr<Response>>,
+    {
+        let mut guard = METADATA_REGISTRY
+            .write()
+            .expect("failed to lock METADATA_REGISTRY for write");
+        let metadata = guard.entry(self.id).or_default();
+        metadata.responses.insert(status, response);
+        self
+    }
 }
 
 #[non_exhaustive]
@@ -155,4 +198,6 @@ impl RouterExt for Router {
 pub(crate) struct Metadata {
     pub(crate) tags: BTreeSet<String>,
     pub(crate) securities: Vec<SecurityRequirement>,
+    pub(crate) parameters: Vec<Parameter>,
+    pub(crate) responses: Responses,
 }
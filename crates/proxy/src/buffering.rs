@@ -0,0 +1,125 @@
+//! Buffering of upstream response bodies.
+
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+
+use futures_util::future::poll_fn;
+use salvo_core::http::ResBody;
+use salvo_core::hyper::body::{Body, Bytes, Frame, SizeHint};
+use salvo_core::BoxedError;
+use tokio::sync::{mpsc, Semaphore};
+
+/// Reads `body` ahead into a buffer of at most `max_size` bytes, independently of how fast the client
+/// consumes it.
+///
+/// Bodies no larger than the buffer are read completely, so the upstream connection is released
+/// immediately.
+pub(crate) fn buffer(mut body: ResBody, max_size: usize) -> ResBody {
+    if body.is_end_stream() {
+        return body;
+    }
+    let size_hint = body.size_hint();
+    let permits = Arc::new(Semaphore::new(max_size.clamp(1, Semaphore::MAX_PERMITS)));
+    let (tx, rx) = mpsc::unbounded_channel();
+    let pump_permits = permits.clone();
+    tokio::spawn(async move {
+        while let Some(frame) = poll_fn(|cx| Pin::new(&mut body).poll_frame(cx)).await {
+            let frame = match frame {
+                Ok(frame) => frame,
+                Err(e) => {
+                    let _ = tx.send(Err(e.into()));
+                    return;
+                }
+            };
+            let size = frame.data_ref().map(Bytes::len).unwrap_or_default();
+            // Frames larger than the buffer are passed through one at a time.
+            let size = size.min(max_size).clamp(1, Semaphore::MAX_PERMITS) as u32;
+            // The semaphore is closed when the client drops the body.
+            match pump_permits.acquire_many(size).await {
+                Ok(permit) => permit.forget(),
+                Err(_) => return,
+            }
+            if tx.send(Ok((frame, size))).is_err() {
+                return;
+            }
+        }
+    });
+    ResBody::Boxed(Box::pin(BufferedBody {
+        rx,
+        permits,
+        size_hint,
+    }))
+}
+
+type BufferedFrame = Result<(Frame<Bytes>, u32), BoxedError>;
+
+struct BufferedBody {
+    rx: mpsc::UnboundedReceiver<BufferedFrame>,
+    permits: Arc<Semaphore>,
+    size_hint: SizeHint,
+}
+
+impl Body for BufferedBody {
+    type Data = Bytes;
+    type Error = BoxedError;
+
+    fn poll_frame(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Frame<Self::Data>, Self::Error>>> {
+        match self.rx.poll_recv(cx) {
+            Poll::Ready(Some(Ok((frame, size)))) => {
+                self.permits.add_permits(size as usize);
+                Poll::Ready(Some(Ok(frame)))
+            }
+            Poll::Ready(Some(Err(e))) => Poll::Ready(Some(Err(e))),
+            Poll::Ready(None) => Poll::Ready(None),
+            Poll::Pending => Poll::Pending,
+        }
+    }
+
+    fn size_hint(&self) -> SizeHint {
+        self.size_hint
+    }
+}
+
+impl Drop for BufferedBody {
+    fn drop(&mut self) {
+        self.permits.close();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::Duration;
+
+    use futures_util::{stream, StreamExt, TryStreamExt};
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_buffer() {
+        let read = Arc::new(AtomicUsize::new(0));
+        let counter = read.clone();
+        let chunks = ["ab", "cd", "ef", "gh", "ij"].map(Ok::<_, BoxedError>);
+        let body = ResBody::stream(stream::iter(chunks).inspect(move |_| {
+            counter.fetch_add(1, Ordering::SeqCst);
+        }));
+        let body = buffer(body, 4);
+        // Two chunks fill the buffer, and the third one waits for the client to consume them.
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert_eq!(read.load(Ordering::SeqCst), 3);
+
+        let data = body
+            .try_fold(Vec::new(), |mut data, frame| async move {
+                data.extend_from_slice(&frame.into_data().unwrap());
+                Ok(data)
+            })
+            .await
+            .unwrap();
+        assert_eq!(data, b"abcdefghij");
+        assert_eq!(read.load(Ordering::SeqCst), 5);
+    }
+}
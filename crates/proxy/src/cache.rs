@@ -0,0 +1,62 @@
+//! Caching of proxied responses with [`salvo_cache`].
+
+use std::sync::Arc;
+
+use salvo_cache::{Cache, CacheIssuer, CacheStore};
+use salvo_core::{async_trait, BoxedError, Depot, FlowCtrl, Handler, Request, Response};
+
+use crate::{Client, Proxy, Upstreams};
+
+/// [`Proxy`] serving responses from a [`Cache`], created by [`Proxy::cache`].
+///
+/// Upstream responses are stored following the caching rules of [`Cache`], which honour their
+/// `Cache-Control`, `Expires` and `Vary` headers.
+pub struct CachedProxy {
+    handlers: Vec<Arc<dyn Handler>>,
+}
+
+impl<U, C> Proxy<U, C>
+where
+    U: Upstreams,
+    U::Error: Into<BoxedError>,
+    C: Client,
+{
+    /// Serve responses from `cache`, only requests which are not skipped by the cache, by default `GET`
+    /// requests, are looked up.
+    ///
+    /// This consumes the `Proxy`, so it is usually called last.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use salvo_cache::{Cache, MokaStore, RequestIssuer};
+    /// use salvo_proxy::Proxy;
+    ///
+    /// let proxy = Proxy::use_hyper_client("http://127.0.0.1:8080")
+    ///     .cache(Cache::new(MokaStore::new(1000), RequestIssuer::default()));
+    /// ```
+    pub fn cache<S, I>(self, cache: Cache<S, I>) -> CachedProxy
+    where
+        S: CacheStore<Key = I::Key> + Clone,
+        I: CacheIssuer,
+    {
+        CachedProxy {
+            handlers: vec![Arc::new(cache), Arc::new(self)],
+        }
+    }
+}
+
+#[async_trait]
+impl Handler for CachedProxy {
+    async fn handle(
+        &self,
+        req: &mut Request,
+        depot: &mut Depot,
+        res: &mut Response,
+        _ctrl: &mut FlowCtrl,
+    ) {
+        FlowCtrl::new(self.handlers.clone())
+            .call_next(req, depot, res)
+            .await;
+    }
+}
@@ -0,0 +1,257 @@
+//! Per upstream circuit breaker.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+
+/// State of the circuit of an upstream.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CircuitState {
+    /// Requests are sent to the upstream.
+    Closed,
+    /// Requests are not sent to the upstream.
+    Open,
+    /// One trial request is sent to the upstream to decide whether to close the circuit.
+    HalfOpen,
+}
+
+#[derive(Debug)]
+enum Circuit {
+    Closed { failures: u32 },
+    Open { until: Instant },
+    HalfOpen { trial: bool },
+}
+
+/// Permission to send a request to an upstream, given by [`CircuitBreaker::acquire`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum Permit {
+    /// The circuit is closed.
+    Closed,
+    /// The request is the trial of a half open circuit.
+    Trial,
+}
+
+/// Circuit breaker, which stops sending requests to an upstream after consecutive failures.
+///
+/// After `failure_threshold` consecutive failures, errors, timeouts or `5xx` responses, the circuit of the
+/// upstream opens and requests to it are answered with `503 Service Unavailable` or retried on other
+/// upstreams. After `open_duration`, one trial request is allowed: the circuit closes if it succeeds and
+/// opens again otherwise. Outcomes of other requests, sent before the circuit opened, are ignored until the
+/// circuit is closed.
+///
+/// Only circuits with failures are kept, and circuits of upstreams which are no longer members of the proxy,
+/// such as members removed by discovery, are dropped when a new circuit is kept. Clones share the same
+/// circuits, so a circuit breaker should only be shared by proxies with the same upstreams.
+#[derive(Clone, Debug)]
+pub struct CircuitBreaker {
+    failure_threshold: u32,
+    open_duration: Duration,
+    circuits: Arc<Mutex<HashMap<String, Circuit>>>,
+}
+impl Default for CircuitBreaker {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+impl CircuitBreaker {
+    /// Create new `CircuitBreaker` which opens after 5 consecutive failures for 30 seconds.
+    pub fn new() -> Self {
+        Self {
+            failure_threshold: 5,
+            open_duration: Duration::from_secs(30),
+            circuits: Default::default(),
+        }
+    }
+
+    /// Sets the number of consecutive failures to open the circuit.
+    pub fn failure_threshold(mut self, threshold: u32) -> Self {
+        self.failure_threshold = threshold.max(1);
+        self
+    }
+
+    /// Sets how long the circuit stays open before a trial request.
+    pub fn open_duration(mut self, duration: Duration) -> Self {
+        self.open_duration = duration;
+        self
+    }
+
+    fn lock(&self) -> MutexGuard<'_, HashMap<String, Circuit>> {
+        self.circuits.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Returns the state of the circuit of the upstream.
+    pub fn state(&self, upstream: &str) -> CircuitState {
+        match self.lock().get(upstream) {
+            None | Some(Circuit::Closed { .. }) => CircuitState::Closed,
+            Some(Circuit::Open { until }) if Instant::now() < *until => CircuitState::Open,
+            Some(Circuit::Open { .. }) | Some(Circuit::HalfOpen { .. }) => CircuitState::HalfOpen,
+        }
+    }
+
+    /// Returns whether a request can be sent to the upstream, and takes the trial if the circuit is half
+    /// open.
+    pub(crate) fn acquire(&self, upstream: &str) -> Option<Permit> {
+        let mut circuits = self.lock();
+        let Some(circuit) = circuits.get_mut(upstream) else {
+            return Some(Permit::Closed);
+        };
+        match circuit {
+            Circuit::Closed { .. } => Some(Permit::Closed),
+            Circuit::Open { until } if Instant::now() < *until => None,
+            Circuit::Open { .. } | Circuit::HalfOpen { trial: false } => {
+                *circuit = Circuit::HalfOpen { trial: true };
+                Some(Permit::Trial)
+            }
+            Circuit::HalfOpen { trial: true } => None,
+        }
+    }
+
+    /// Gives back the trial of a half open circuit when the request is not sent.
+    pub(crate) fn release(&self, upstream: &str, permit: Permit) {
+        if permit != Permit::Trial {
+            return;
+        }
+        if let Some(circuit @ Circuit::HalfOpen { .. }) = self.lock().get_mut(upstream) {
+            *circuit = Circuit::HalfOpen { trial: false };
+        }
+    }
+
+    /// Records the result of a request sent to the upstream, `members` returns the current upstreams of the
+    /// proxy.
+    pub(crate) fn record<S: AsRef<str>>(
+        &self,
+        upstream: &str,
+        permit: Permit,
+        success: bool,
+        members: impl FnOnce() -> Vec<S>,
+    ) {
+        let mut circuits = self.lock();
+        match (permit, circuits.get_mut(upstream)) {
+            // Only the trial decides whether a half open circuit closes.
+            (Permit::Trial, Some(circuit @ Circuit::HalfOpen { .. })) => {
+                if success {
+                    circuits.remove(upstream);
+                } else {
+                    tracing::warn!(upstream, "circuit opened");
+                    *circuit = Circuit::Open {
+                        until: Instant::now() + self.open_duration,
+                    };
+                }
+                return;
+            }
+            // Requests sent before the circuit opened.
+            (Permit::Trial, _) | (_, Some(Circuit::Open { .. } | Circuit::HalfOpen { .. })) => {
+                return
+            }
+            (Permit::Closed, _) => {}
+        }
+        if success {
+            circuits.remove(upstream);
+            return;
+        }
+        if !circuits.contains_key(upstream) {
+            // A new circuit, drop the circuits of removed members meanwhile.
+            let members = members();
+            let is_member =
+                |upstream: &str| members.iter().any(|member| member.as_ref() == upstream);
+            if !is_member(upstream) {
+                return;
+            }
+            circuits.retain(|upstream, _| is_member(upstream));
+        }
+        let circuit = circuits
+            .entry(upstream.to_owned())
+            .or_insert(Circuit::Closed { failures: 0 });
+        *circuit = match &*circuit {
+            Circuit::Closed { failures } if failures + 1 < self.failure_threshold => {
+                Circuit::Closed {
+                    failures: failures + 1,
+                }
+            }
+            _ => {
+                tracing::warn!(upstream, "circuit opened");
+                Circuit::Open {
+                    until: Instant::now() + self.open_duration,
+                }
+            }
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_circuit_breaker() {
+        let breaker = CircuitBreaker::new()
+            .failure_threshold(2)
+            .open_duration(Duration::from_millis(50));
+        let members = || vec!["http://a", "http://b"];
+        assert_eq!(breaker.acquire("http://a"), Some(Permit::Closed));
+        breaker.record("http://a", Permit::Closed, false, members);
+        assert_eq!(breaker.state("http://a"), CircuitState::Closed);
+        breaker.record("http://a", Permit::Closed, false, members);
+        assert_eq!(breaker.state("http://a"), CircuitState::Open);
+        assert_eq!(breaker.acquire("http://a"), None);
+        assert_eq!(breaker.acquire("http://b"), Some(Permit::Closed));
+
+        std::thread::sleep(Duration::from_millis(60));
+        assert_eq!(breaker.state("http://a"), CircuitState::HalfOpen);
+        assert_eq!(breaker.acquire("http://a"), Some(Permit::Trial));
+        // Only one trial request at a time.
+        assert_eq!(breaker.acquire("http://a"), None);
+        breaker.release("http://a", Permit::Trial);
+        assert_eq!(breaker.acquire("http://a"), Some(Permit::Trial));
+        breaker.record("http://a", Permit::Trial, false, members);
+        assert_eq!(breaker.state("http://a"), CircuitState::Open);
+
+        std::thread::sleep(Duration::from_millis(60));
+        assert_eq!(breaker.acquire("http://a"), Some(Permit::Trial));
+        breaker.record("http://a", Permit::Trial, true, members);
+        assert_eq!(breaker.state("http://a"), CircuitState::Closed);
+    }
+
+    #[test]
+    fn test_outcomes_before_open() {
+        let breaker = CircuitBreaker::new()
+            .failure_threshold(1)
+            .open_duration(Duration::from_millis(50));
+        let members = || vec!["http://a"];
+        breaker.record("http://a", Permit::Closed, false, members);
+        assert_eq!(breaker.state("http://a"), CircuitState::Open);
+        // Requests sent before the circuit opened do not close it.
+        breaker.record("http://a", Permit::Closed, true, members);
+        assert_eq!(breaker.state("http://a"), CircuitState::Open);
+
+        std::thread::sleep(Duration::from_millis(60));
+        assert_eq!(breaker.acquire("http://a"), Some(Permit::Trial));
+        breaker.record("http://a", Permit::Closed, true, members);
+        breaker.release("http://a", Permit::Closed);
+        assert_eq!(breaker.acquire("http://a"), None);
+        // Only the trial closes the circuit.
+        breaker.record("http://a", Permit::Trial, true, members);
+        assert_eq!(breaker.state("http://a"), CircuitState::Closed);
+    }
+
+    #[test]
+    fn test_removed_members() {
+        let breaker = CircuitBreaker::new().failure_threshold(1);
+        breaker.record("http://a", Permit::Closed, false, || {
+            vec!["http://a", "http://b"]
+        });
+        assert_eq!(breaker.state("http://a"), CircuitState::Open);
+
+        // Upstreams which are not members are not tracked.
+        breaker.record("http://c", Permit::Closed, false, || {
+            vec!["http://a", "http://b"]
+        });
+        assert_eq!(breaker.state("http://c"), CircuitState::Closed);
+
+        // The circuit of the removed member is dropped when a new circuit is kept.
+        breaker.record("http://b", Permit::Closed, false, || vec!["http://b"]);
+        assert_eq!(breaker.state("http://b"), CircuitState::Open);
+        assert_eq!(breaker.state("http://a"), CircuitState::Closed);
+        assert_eq!(breaker.lock().len(), 1);
+    }
+}
@@ -0,0 +1,570 @@
+//! Upstreams discovered from DNS, files or custom service registries.
+
+use std::collections::{HashMap, HashSet};
+use std::future::Future;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::{Duration, Instant, SystemTime};
+
+use hickory_resolver::config::{NameServerConfigGroup, ResolverConfig, ResolverOpts};
+use hickory_resolver::system_conf::read_system_conf;
+use hickory_resolver::TokioAsyncResolver;
+use salvo_core::{BoxedError, Depot, Error, Request};
+use tokio::task::JoinHandle;
+
+use crate::{Outcome, Upstreams};
+
+/// Source of the members of [`Discovered`] upstreams.
+///
+/// Implement it to discover upstreams from custom service registries.
+pub trait Discover: Send + Sync + 'static {
+    /// Returns current members, such as `http://10.0.0.1:8080`.
+    fn discover(&self) -> impl Future<Output = Result<Vec<String>, BoxedError>> + Send;
+}
+
+/// Upstreams which members are refreshed in the background from a [`Discover`] source.
+///
+/// Requests are distributed to the members in round robin. Removed members are drained: they are not
+/// elected anymore, and are reported by [`Discovered::draining`] until their requests in flight are
+/// finished or the drain timeout elapses.
+///
+/// If the source fails or returns no member, the current members are kept, as this more likely comes from
+/// a broken registry than from a removed service.
+///
+/// # Example
+///
+/// ```no_run
+/// use std::time::Duration;
+///
+/// use salvo_core::prelude::*;
+/// use salvo_proxy::{Discovered, FileDiscovery, Proxy};
+///
+/// #[tokio::main]
+/// async fn main() {
+///     // `upstreams.json` contains `["http://10.0.0.1:8080", "http://10.0.0.2:8080"]`.
+///     let upstreams = Discovered::new(FileDiscovery::new("upstreams.json"))
+///         .interval(Duration::from_secs(5))
+///         .drain_timeout(Duration::from_secs(60));
+///     upstreams.refresh().await.expect("discover upstreams failed");
+///     upstreams.spawn_refresh();
+///     let router = Router::with_path("{**rest}").goal(Proxy::use_hyper_client(upstreams));
+///
+///     let acceptor = TcpListener::new("0.0.0.0:5800").bind().await;
+///     Server::new(acceptor).serve(router).await;
+/// }
+/// ```
+pub struct Discovered<D> {
+    source: Arc<D>,
+    shared: Arc<Shared>,
+    interval: Duration,
+    drain_timeout: Duration,
+}
+
+#[derive(Debug, Default)]
+struct Shared {
+    state: Mutex<State>,
+    next: AtomicUsize,
+}
+
+#[derive(Debug, Default)]
+struct State {
+    active: Vec<Arc<str>>,
+    in_flight: HashMap<Arc<str>, usize>,
+    draining: HashMap<Arc<str>, Instant>,
+}
+
+impl<D: Discover> Discovered<D> {
+    /// Create new `Discovered` without members, call [`Discovered::refresh`] to discover them.
+    ///
+    /// Default refresh interval is 30 seconds, and removed members are drained for at most 30 seconds.
+    pub fn new(source: D) -> Self {
+        Self {
+            source: Arc::new(source),
+            shared: Default::default(),
+            interval: Duration::from_secs(30),
+            drain_timeout: Duration::from_secs(30),
+        }
+    }
+
+    /// Sets the interval between refreshes.
+    pub fn interval(mut self, interval: Duration) -> Self {
+        self.interval = interval;
+        self
+    }
+
+    /// Sets the maximum time a removed member is drained.
+    pub fn drain_timeout(mut self, timeout: Duration) -> Self {
+        self.drain_timeout = timeout;
+        self
+    }
+
+    /// Get the source.
+    pub fn source(&self) -> &D {
+        &self.source
+    }
+
+    /// Discover members from the source now.
+    pub async fn refresh(&self) -> Result<(), BoxedError> {
+        let members = self.source.discover().await?;
+        self.shared.apply(members, self.drain_timeout);
+        Ok(())
+    }
+
+    /// Spawn a task which refreshes members on the interval.
+    ///
+    /// The task stops when the `Discovered` is dropped.
+    pub fn spawn_refresh(&self) -> JoinHandle<()> {
+        let shared = Arc::downgrade(&self.shared);
+        let source = self.source.clone();
+        let (interval, drain_timeout) = (self.interval, self.drain_timeout);
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            loop {
+                ticker.tick().await;
+                if shared.strong_count() == 0 {
+                    break;
+                }
+                let discovered = source.discover().await;
+                let Some(shared) = shared.upgrade() else {
+                    break;
+                };
+                match discovered {
+                    Ok(members) => shared.apply(members, drain_timeout),
+                    Err(e) => tracing::warn!(error = ?e, "discover upstreams failed"),
+                }
+            }
+        })
+    }
+
+    /// Returns removed members which are being drained, sorted.
+    pub fn draining(&self) -> Vec<String> {
+        let mut draining = self
+            .shared
+            .lock()
+            .draining
+            .keys()
+            .map(|member| member.to_string())
+            .collect::<Vec<_>>();
+        draining.sort();
+        draining
+    }
+}
+
+impl Shared {
+    fn lock(&self) -> MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn apply(&self, members: Vec<String>, drain_timeout: Duration) {
+        let mut seen = HashSet::new();
+        let members = members
+            .iter()
+            .filter(|member| !member.is_empty() && seen.insert(member.as_str()))
+            .map(|member| Arc::<str>::from(member.as_str()))
+            .collect::<Vec<_>>();
+        if members.is_empty() {
+            tracing::warn!("no upstream is discovered, keep current members");
+            return;
+        }
+        let now = Instant::now();
+        let mut guard = self.lock();
+        let state = &mut *guard;
+        let removed = state
+            .active
+            .iter()
+            .filter(|member| !members.contains(member))
+            .cloned()
+            .collect::<Vec<_>>();
+        for upstream in removed {
+            if state.in_flight.contains_key(&upstream) {
+                tracing::info!(upstream = &*upstream, "upstream removed, draining");
+                state.draining.insert(upstream, now + drain_timeout);
+            } else {
+                tracing::info!(upstream = &*upstream, "upstream removed");
+            }
+        }
+        for upstream in &members {
+            if !state.active.contains(upstream) {
+                state.draining.remove(upstream);
+                tracing::info!(upstream = &**upstream, "upstream added");
+            }
+        }
+        state.draining.retain(|upstream, deadline| {
+            if *deadline > now {
+                return true;
+            }
+            let in_flight = state.in_flight.get(upstream).copied().unwrap_or_default();
+            tracing::warn!(upstream = &**upstream, in_flight, "upstream drain timed out");
+            false
+        });
+        state.active = members;
+    }
+}
+
+impl<D: Discover> Upstreams for Discovered<D> {
+    type Error = Error;
+
+    async fn elect(&self, req: &Request, depot: &Depot) -> Result<Arc<str>, Self::Error> {
+        self.elect_accepted(req, depot, &|_| true)
+            .await?
+            .ok_or_else(|| Error::other("no upstream is discovered"))
+    }
+
+    async fn elect_accepted(
+        &self,
+        _req: &Request,
+        _depot: &Depot,
+        accept: &(dyn Fn(&str) -> bool + Send + Sync),
+    ) -> Result<Option<Arc<str>>, Self::Error> {
+        let mut state = self.shared.lock();
+        let len = state.active.len();
+        if len == 0 {
+            return Ok(None);
+        }
+        let start = self.shared.next.fetch_add(1, Ordering::Relaxed) % len;
+        let Some(upstream) = (0..len)
+            .map(|i| &state.active[(start + i) % len])
+            .find(|upstream| accept(upstream))
+            .cloned()
+        else {
+            return Ok(None);
+        };
+        *state.in_flight.entry(upstream.clone()).or_default() += 1;
+        Ok(Some(upstream))
+    }
+
+    fn finish(&self, upstream: &str, _outcome: Outcome) {
+        let mut state = self.shared.lock();
+        let finished = match state.in_flight.get_mut(upstream) {
+            Some(count) if *count > 1 => {
+                *count -= 1;
+                false
+            }
+            Some(_) => state.in_flight.remove(upstream).is_some(),
+            None => false,
+        };
+        if finished && state.draining.remove(upstream).is_some() {
+            tracing::info!(upstream, "upstream drained");
+        }
+    }
+
+    fn members(&self) -> Vec<Arc<str>> {
+        self.shared.lock().active.clone()
+    }
+}
+
+/// [`Discover`] source reading members from a JSON or TOML file, which is read again when it is modified.
+///
+/// Files with the `toml` extension contain an `upstreams` array, such as
+/// `upstreams = ["http://10.0.0.1:8080"]`. Other files are JSON, containing either an array of members
+/// or an object with an `upstreams` array.
+#[derive(Debug)]
+pub struct FileDiscovery {
+    path: PathBuf,
+    cache: Mutex<Option<(SystemTime, Vec<String>)>>,
+}
+
+impl FileDiscovery {
+    /// Create new `FileDiscovery` reading `path`.
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: path.into(),
+            cache: Mutex::new(None),
+        }
+    }
+
+    fn parse(&self, content: &str) -> Result<Vec<String>, BoxedError> {
+        let is_toml = self
+            .path
+            .extension()
+            .is_some_and(|extension| extension.eq_ignore_ascii_case("toml"));
+        let members = if is_toml {
+            let document = toml_edit::Document::parse(content)?;
+            document
+                .as_table()
+                .get("upstreams")
+                .and_then(|upstreams| upstreams.as_array())
+                .ok_or("`upstreams` array is missing")?
+                .iter()
+                .map(|member| member.as_str().map(ToOwned::to_owned))
+                .collect::<Option<Vec<_>>>()
+        } else {
+            let value = serde_json::from_str::<serde_json::Value>(content)?;
+            let upstreams = match &value {
+                serde_json::Value::Object(object) => object.get("upstreams"),
+                _ => Some(&value),
+            };
+            upstreams
+                .and_then(|upstreams| upstreams.as_array())
+                .ok_or("`upstreams` array is missing")?
+                .iter()
+                .map(|member| member.as_str().map(ToOwned::to_owned))
+                .collect::<Option<Vec<_>>>()
+        };
+        members.ok_or_else(|| "upstreams should be strings".into())
+    }
+}
+
+impl Discover for FileDiscovery {
+    async fn discover(&self) -> Result<Vec<String>, BoxedError> {
+        let modified = tokio::fs::metadata(&self.path).await?.modified()?;
+        if let Some((cached, members)) = &*self.cache.lock().unwrap_or_else(|e| e.into_inner()) {
+            if *cached == modified {
+                return Ok(members.clone());
+            }
+        }
+        let content = tokio::fs::read_to_string(&self.path).await?;
+        let members = self.parse(&content)?;
+        *self.cache.lock().unwrap_or_else(|e| e.into_inner()) = Some((modified, members.clone()));
+        Ok(members)
+    }
+}
+
+#[derive(Clone, Debug)]
+enum Query {
+    Lookup(String),
+    Srv(String),
+}
+
+/// [`Discover`] source resolving members from DNS `A`/`AAAA` or `SRV` records.
+///
+/// # Example
+///
+/// ```
+/// use salvo_proxy::{DnsDiscovery, Discovered};
+///
+/// let upstreams = Discovered::new(DnsDiscovery::srv("_http._tcp.backend.internal"));
+/// let upstreams = Discovered::new(DnsDiscovery::lookup("backend.internal:8080").scheme("https"));
+/// ```
+#[derive(Clone, Debug)]
+pub struct DnsDiscovery {
+    query: Query,
+    scheme: String,
+    nameserver: Option<SocketAddr>,
+    timeout: Duration,
+}
+
+impl DnsDiscovery {
+    /// Create new `DnsDiscovery` resolving `host:port` with the system resolver, every address is a member.
+    pub fn lookup(host: impl Into<String>) -> Self {
+        Self::new(Query::Lookup(host.into()))
+    }
+
+    /// Create new `DnsDiscovery` querying `SRV` records of `name`, such as `_http._tcp.backend.internal`.
+    ///
+    /// The targets of the records with the lowest priority are members, weights are not used.
+    pub fn srv(name: impl Into<String>) -> Self {
+        Self::new(Query::Srv(name.into()))
+    }
+
+    fn new(query: Query) -> Self {
+        Self {
+            query,
+            scheme: "http".into(),
+            nameserver: None,
+            timeout: Duration::from_secs(5),
+        }
+    }
+
+    /// Sets the scheme of the members, default is `http`.
+    pub fn scheme(mut self, scheme: impl Into<String>) -> Self {
+        self.scheme = scheme.into();
+        self
+    }
+
+    /// Sets the nameserver of `SRV` queries, default is the system configuration, such as `/etc/resolv.conf`.
+    pub fn nameserver(mut self, nameserver: SocketAddr) -> Self {
+        self.nameserver = Some(nameserver);
+        self
+    }
+
+    /// Sets the timeout of a resolution, default is 5 seconds.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    fn resolver(&self) -> Result<TokioAsyncResolver, BoxedError> {
+        let (config, mut options) = match self.nameserver {
+            Some(nameserver) => {
+                let nameservers = NameServerConfigGroup::from_ips_clear(
+                    &[nameserver.ip()],
+                    nameserver.port(),
+                    true,
+                );
+                (
+                    ResolverConfig::from_parts(None, vec![], nameservers),
+                    ResolverOpts::default(),
+                )
+            }
+            None => read_system_conf()?,
+        };
+        options.timeout = self.timeout;
+        Ok(TokioAsyncResolver::tokio(config, options))
+    }
+}
+
+impl Discover for DnsDiscovery {
+    async fn discover(&self) -> Result<Vec<String>, BoxedError> {
+        let resolving = async {
+            match &self.query {
+                Query::Lookup(host) => Ok(tokio::net::lookup_host(host.as_str())
+                    .await?
+                    .map(|addr| format!("{}://{addr}", self.scheme))
+                    .collect()),
+                Query::Srv(name) => {
+                    let lookup = self.resolver()?.srv_lookup(name.as_str()).await?;
+                    // A `.` target means the service is not available.
+                    let records = lookup
+                        .iter()
+                        .filter(|record| !record.target().is_root())
+                        .collect::<Vec<_>>();
+                    let priority = records.iter().map(|record| record.priority()).min();
+                    let mut targets = records
+                        .into_iter()
+                        .filter(|record| Some(record.priority()) == priority)
+                        .map(|record| {
+                            let target = record.target().to_utf8();
+                            (target.trim_end_matches('.').to_owned(), record.port())
+                        })
+                        .collect::<Vec<_>>();
+                    targets.sort();
+                    Ok(targets
+                        .into_iter()
+                        .map(|(target, port)| format!("{}://{target}:{port}", self.scheme))
+                        .collect())
+                }
+            }
+        };
+        tokio::time::timeout(self.timeout, resolving)
+            .await
+            .map_err(|_| "dns resolution timed out")?
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use hickory_resolver::proto::op::{Message, MessageType};
+    use hickory_resolver::proto::rr::rdata::SRV;
+    use hickory_resolver::proto::rr::{Name, RData, Record};
+    use tokio::net::UdpSocket;
+
+    use super::*;
+
+    struct Registry(Mutex<Vec<String>>);
+    impl Discover for Registry {
+        async fn discover(&self) -> Result<Vec<String>, BoxedError> {
+            Ok(self.0.lock().unwrap().clone())
+        }
+    }
+
+    fn member_names<D: Discover>(upstreams: &Discovered<D>) -> Vec<String> {
+        upstreams.members().iter().map(|member| member.to_string()).collect()
+    }
+
+    #[tokio::test]
+    async fn test_draining() {
+        let members = vec!["http://a".to_owned(), "http://b".to_owned()];
+        let upstreams = Discovered::new(Registry(Mutex::new(members)));
+        let (req, depot) = (Request::new(), Depot::new());
+        assert!(upstreams.elect(&req, &depot).await.is_err());
+
+        upstreams.refresh().await.unwrap();
+        assert_eq!(member_names(&upstreams), ["http://a", "http://b"]);
+        let elected = upstreams.elect(&req, &depot).await.unwrap();
+        assert_eq!(&*elected, "http://a");
+
+        *upstreams.source().0.lock().unwrap() = vec!["http://b".into(), "http://c".into()];
+        upstreams.refresh().await.unwrap();
+        assert_eq!(member_names(&upstreams), ["http://b", "http://c"]);
+        assert_eq!(upstreams.draining(), ["http://a"]);
+        for _ in 0..4 {
+            let upstream = upstreams.elect(&req, &depot).await.unwrap();
+            assert_ne!(&*upstream, "http://a");
+            upstreams.finish(&upstream, Outcome::Failed);
+        }
+        upstreams.finish(&elected, Outcome::Failed);
+        assert!(upstreams.draining().is_empty());
+
+        // Empty results are ignored.
+        upstreams.source().0.lock().unwrap().clear();
+        upstreams.refresh().await.unwrap();
+        assert_eq!(member_names(&upstreams), ["http://b", "http://c"]);
+    }
+
+    #[tokio::test]
+    async fn test_file_discovery() {
+        let dir = std::env::temp_dir().join(format!("salvo-proxy-{}", fastrand::u64(..)));
+        std::fs::create_dir_all(&dir).unwrap();
+        let json = dir.join("upstreams.json");
+        std::fs::write(&json, r#"{"upstreams": ["http://a", "http://b"]}"#).unwrap();
+        let upstreams = Discovered::new(FileDiscovery::new(&json));
+        upstreams.refresh().await.unwrap();
+        assert_eq!(member_names(&upstreams), ["http://a", "http://b"]);
+
+        let toml = dir.join("upstreams.toml");
+        std::fs::write(&toml, "upstreams = [\"http://c\"]\n").unwrap();
+        let members = FileDiscovery::new(&toml).discover().await.unwrap();
+        assert_eq!(members, ["http://c"]);
+
+        std::fs::write(&json, "[1]").unwrap();
+        assert!(FileDiscovery::new(&json).discover().await.is_err());
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[tokio::test]
+    async fn test_dns_lookup() {
+        let members = DnsDiscovery::lookup("127.0.0.1:8080")
+            .discover()
+            .await
+            .unwrap();
+        assert_eq!(members, ["http://127.0.0.1:8080"]);
+    }
+
+    #[tokio::test]
+    async fn test_dns_srv() {
+        let stub = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let nameserver = stub.local_addr().unwrap();
+        tokio::spawn(async move {
+            let mut packet = vec![0; 512];
+            let (len, peer) = stub.recv_from(&mut packet).await.unwrap();
+            let query = Message::from_vec(&packet[..len]).unwrap();
+            let name = query.queries()[0].name().clone();
+            let mut response = Message::new();
+            response
+                .set_id(query.id())
+                .set_message_type(MessageType::Response)
+                .set_recursion_desired(true)
+                .set_recursion_available(true)
+                .add_queries(query.queries().to_vec());
+            for (priority, port, target) in [
+                (20, 8080, "backend-3.internal."),
+                (10, 8080, "backend-2.internal."),
+                (10, 8081, "backend-1.internal."),
+                // Not available.
+                (10, 8082, "."),
+            ] {
+                let srv = SRV::new(priority, 10, port, Name::from_ascii(target).unwrap());
+                response.add_answer(Record::from_rdata(name.clone(), 60, RData::SRV(srv)));
+            }
+            stub.send_to(&response.to_vec().unwrap(), peer)
+                .await
+                .unwrap();
+        });
+
+        let members = DnsDiscovery::srv("_http._tcp.backend.internal")
+            .nameserver(nameserver)
+            .discover()
+            .await
+            .unwrap();
+        assert_eq!(
+            members,
+            [
+                "http://backend-1.internal:8081",
+                "http://backend-2.internal:8080"
+            ]
+        );
+    }
+}
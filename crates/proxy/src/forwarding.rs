@@ -0,0 +1,192 @@
+//! Hop-by-hop and forwarding headers.
+
+use std::net::IpAddr;
+
+use salvo_core::http::header::{
+    HeaderMap, HeaderName, HeaderValue, CONNECTION, FORWARDED, HOST, TE, TRANSFER_ENCODING,
+    UPGRADE, VIA,
+};
+use salvo_core::http::Version;
+use salvo_core::Request;
+
+const KEEP_ALIVE: HeaderName = HeaderName::from_static("keep-alive");
+const X_FORWARDED_FOR: HeaderName = HeaderName::from_static("x-forwarded-for");
+const X_FORWARDED_HOST: HeaderName = HeaderName::from_static("x-forwarded-host");
+const X_FORWARDED_PROTO: HeaderName = HeaderName::from_static("x-forwarded-proto");
+
+/// Removes hop-by-hop headers defined in [RFC 9110](https://www.rfc-editor.org/rfc/rfc9110#section-7.6.1),
+/// the headers named in `Connection` and all `Proxy-*` headers.
+///
+/// `TE: trailers` is kept, since it is required by gRPC upstreams. `Trailer` is kept too, since trailers are
+/// forwarded end to end.
+pub fn strip_hop_by_hop_headers(headers: &mut HeaderMap) {
+    let named = headers
+        .get_all(CONNECTION)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .filter_map(|name| HeaderName::from_bytes(name.trim().as_bytes()).ok())
+        .collect::<Vec<_>>();
+    for name in named {
+        headers.remove(name);
+    }
+    let trailers = headers
+        .get_all(TE)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .any(|value| value.trim().eq_ignore_ascii_case("trailers"));
+    for name in [CONNECTION, KEEP_ALIVE, TE, TRANSFER_ENCODING, UPGRADE] {
+        headers.remove(name);
+    }
+    let proxy_headers = headers
+        .keys()
+        .filter(|name| name.as_str().starts_with("proxy-"))
+        .cloned()
+        .collect::<Vec<_>>();
+    for name in proxy_headers {
+        headers.remove(name);
+    }
+    if trailers {
+        headers.insert(TE, HeaderValue::from_static("trailers"));
+    }
+}
+
+/// Returns the host requested by the client.
+pub(crate) fn original_host(req: &Request) -> Option<HeaderValue> {
+    req.headers().get(HOST).cloned().or_else(|| {
+        req.uri()
+            .authority()
+            .and_then(|authority| HeaderValue::from_str(authority.as_str()).ok())
+    })
+}
+
+fn remote_ip(req: &Request) -> Option<IpAddr> {
+    req.remote_addr().clone().into_std().map(|addr| addr.ip())
+}
+
+/// Appends `value` to the list header `name`, joining every existing line of it into a single one.
+fn append(headers: &mut HeaderMap, name: HeaderName, value: &str) {
+    let mut values = headers
+        .get_all(&name)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .collect::<Vec<_>>();
+    values.push(value);
+    if let Ok(value) = HeaderValue::from_str(&values.join(", ")) {
+        headers.insert(name, value);
+    }
+}
+
+/// Adds `X-Forwarded-For`, `X-Forwarded-Proto` and `X-Forwarded-Host` headers.
+pub(crate) fn add_x_forwarded(headers: &mut HeaderMap, req: &Request) {
+    if let Some(ip) = remote_ip(req) {
+        append(headers, X_FORWARDED_FOR, &ip.to_string());
+    }
+    if let Ok(proto) = HeaderValue::from_str(req.scheme().as_str()) {
+        headers.insert(X_FORWARDED_PROTO, proto);
+    }
+    if let Some(host) = original_host(req) {
+        headers.insert(X_FORWARDED_HOST, host);
+    }
+}
+
+/// Adds an element to the `Forwarded` header defined in [RFC 7239](https://www.rfc-editor.org/rfc/rfc7239).
+pub(crate) fn add_forwarded(headers: &mut HeaderMap, req: &Request) {
+    let mut element = match remote_ip(req) {
+        Some(IpAddr::V4(ip)) => format!("for={ip}"),
+        Some(IpAddr::V6(ip)) => format!("for=\"[{ip}]\""),
+        None => "for=unknown".to_owned(),
+    };
+    if let Some(host) =
+        original_host(req).and_then(|host| host.to_str().ok().map(ToOwned::to_owned))
+    {
+        element.push_str(&format!(";host=\"{host}\""));
+    }
+    element.push_str(&format!(";proto={}", req.scheme().as_str()));
+    append(headers, FORWARDED, &element);
+}
+
+/// Adds this proxy to the `Via` header.
+pub(crate) fn add_via(headers: &mut HeaderMap, version: Version, pseudonym: &str) {
+    let protocol = match version {
+        Version::HTTP_09 => "0.9",
+        Version::HTTP_10 => "1.0",
+        Version::HTTP_2 => "2",
+        Version::HTTP_3 => "3",
+        _ => "1.1",
+    };
+    append(headers, VIA, &format!("{protocol} {pseudonym}"));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_strip_hop_by_hop_headers() {
+        let mut headers = HeaderMap::new();
+        headers.insert(CONNECTION, HeaderValue::from_static("keep-alive, x-custom"));
+        headers.insert(KEEP_ALIVE, HeaderValue::from_static("timeout=5"));
+        headers.insert("x-custom", HeaderValue::from_static("1"));
+        headers.insert(TE, HeaderValue::from_static("trailers, deflate"));
+        headers.insert(TRANSFER_ENCODING, HeaderValue::from_static("chunked"));
+        headers.insert("proxy-authorization", HeaderValue::from_static("Basic abc"));
+        headers.insert("x-kept", HeaderValue::from_static("1"));
+        headers.append("set-cookie", HeaderValue::from_static("a=1"));
+        headers.append("set-cookie", HeaderValue::from_static("b=2"));
+        strip_hop_by_hop_headers(&mut headers);
+
+        assert_eq!(headers.len(), 4);
+        assert_eq!(headers[TE], "trailers");
+        assert_eq!(headers["x-kept"], "1");
+        assert_eq!(headers.get_all("set-cookie").iter().count(), 2);
+    }
+
+    #[test]
+    fn test_forwarding_headers() {
+        let mut req = Request::new();
+        *req.remote_addr_mut() = "[2001:db8::1]:8080"
+            .parse::<std::net::SocketAddr>()
+            .unwrap()
+            .into();
+        req.headers_mut()
+            .insert(HOST, HeaderValue::from_static("example.com"));
+        let mut headers = HeaderMap::new();
+        headers.insert(X_FORWARDED_FOR, HeaderValue::from_static("10.0.0.1"));
+        add_x_forwarded(&mut headers, &req);
+        add_forwarded(&mut headers, &req);
+        add_via(&mut headers, Version::HTTP_11, "salvo");
+
+        assert_eq!(headers[X_FORWARDED_FOR], "10.0.0.1, 2001:db8::1");
+        assert_eq!(headers[X_FORWARDED_PROTO], "http");
+        assert_eq!(headers[X_FORWARDED_HOST], "example.com");
+        assert_eq!(
+            headers[FORWARDED],
+            "for=\"[2001:db8::1]\";host=\"example.com\";proto=http"
+        );
+        assert_eq!(headers[VIA], "1.1 salvo");
+    }
+
+    #[test]
+    fn test_append_multiple_lines() {
+        let mut req = Request::new();
+        *req.remote_addr_mut() = "192.0.2.1:8080"
+            .parse::<std::net::SocketAddr>()
+            .unwrap()
+            .into();
+        let mut headers = HeaderMap::new();
+        headers.append(X_FORWARDED_FOR, HeaderValue::from_static("10.0.0.1"));
+        headers.append(
+            X_FORWARDED_FOR,
+            HeaderValue::from_static("10.0.0.2, 10.0.0.3"),
+        );
+        add_x_forwarded(&mut headers, &req);
+
+        assert_eq!(headers.get_all(X_FORWARDED_FOR).iter().count(), 1);
+        assert_eq!(
+            headers[X_FORWARDED_FOR],
+            "10.0.0.1, 10.0.0.2, 10.0.0.3, 192.0.2.1"
+        );
+    }
+}
@@ -0,0 +1,300 @@
+//! Translation between [gRPC-Web](https://github.com/grpc/grpc/blob/master/doc/PROTOCOL-WEB.md) and gRPC.
+
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use salvo_core::http::header::{HeaderMap, HeaderValue, CONTENT_LENGTH, CONTENT_TYPE, TE};
+use salvo_core::http::{ReqBody, ResBody};
+use salvo_core::hyper::body::{Body, Bytes, Frame, SizeHint};
+use salvo_core::BoxedError;
+
+const GRPC: &str = "application/grpc";
+const GRPC_WEB: &str = "application/grpc-web";
+const GRPC_WEB_TEXT: &str = "application/grpc-web-text";
+
+/// Encoding of a gRPC-Web request.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum GrpcWeb {
+    /// Binary messages, `application/grpc-web`.
+    Binary,
+    /// Base64 encoded messages, `application/grpc-web-text`.
+    Text,
+}
+
+impl GrpcWeb {
+    /// Returns the encoding of a gRPC-Web request.
+    pub(crate) fn detect(headers: &HeaderMap) -> Option<Self> {
+        let content_type = headers.get(CONTENT_TYPE)?.to_str().ok()?;
+        if content_type.starts_with(GRPC_WEB_TEXT) {
+            Some(Self::Text)
+        } else if content_type.starts_with(GRPC_WEB) {
+            Some(Self::Binary)
+        } else {
+            None
+        }
+    }
+
+    fn content_type(self) -> &'static str {
+        match self {
+            Self::Binary => GRPC_WEB,
+            Self::Text => GRPC_WEB_TEXT,
+        }
+    }
+
+    /// Translates a gRPC-Web request to gRPC.
+    pub(crate) fn translate_request(self, headers: &mut HeaderMap, body: ReqBody) -> ReqBody {
+        replace_content_type(headers, self.content_type(), GRPC);
+        headers.remove(CONTENT_LENGTH);
+        headers.insert(TE, HeaderValue::from_static("trailers"));
+        match self {
+            Self::Binary => body,
+            Self::Text => ReqBody::Boxed {
+                inner: Box::pin(DecodeBody {
+                    inner: body,
+                    pending: Vec::new(),
+                }),
+                fusewire: None,
+            },
+        }
+    }
+
+    /// Translates a gRPC response to gRPC-Web, responses which are not gRPC are left unchanged.
+    pub(crate) fn translate_response(self, headers: &mut HeaderMap, body: ResBody) -> ResBody {
+        let is_grpc = headers
+            .get(CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .is_some_and(|value| value.starts_with(GRPC));
+        if !is_grpc {
+            return body;
+        }
+        replace_content_type(headers, GRPC, self.content_type());
+        headers.remove(CONTENT_LENGTH);
+        ResBody::Boxed(Box::pin(EncodeBody {
+            inner: body,
+            text: self == Self::Text,
+            pending: Vec::new(),
+            done: false,
+        }))
+    }
+}
+
+fn replace_content_type(headers: &mut HeaderMap, from: &str, to: &str) {
+    let content_type = headers
+        .get(CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix(from))
+        .and_then(|suffix| HeaderValue::from_str(&format!("{to}{suffix}")).ok());
+    if let Some(content_type) = content_type {
+        headers.insert(CONTENT_TYPE, content_type);
+    }
+}
+
+/// Encodes trailers as the last message of a gRPC-Web response body.
+fn encode_trailers(trailers: &HeaderMap) -> Vec<u8> {
+    let mut block = Vec::new();
+    for (name, value) in trailers {
+        block.extend_from_slice(name.as_str().as_bytes());
+        block.push(b':');
+        block.extend_from_slice(value.as_bytes());
+        block.extend_from_slice(b"\r\n");
+    }
+    let mut message = Vec::with_capacity(block.len() + 5);
+    message.push(0x80);
+    message.extend_from_slice(&(block.len() as u32).to_be_bytes());
+    message.extend_from_slice(&block);
+    message
+}
+
+/// Decodes complete base64 quanta from `pending`, padded quanta may occur in the middle of the body.
+fn decode_quanta(pending: &mut Vec<u8>) -> Result<Vec<u8>, base64::DecodeError> {
+    pending.retain(|byte| !byte.is_ascii_whitespace());
+    let mut decoded = Vec::new();
+    let mut start = 0;
+    let len = pending.len() / 4 * 4;
+    while start < len {
+        let end = pending[start..len]
+            .chunks(4)
+            .position(|quantum| quantum.contains(&b'='))
+            .map_or(len, |index| start + (index + 1) * 4);
+        STANDARD.decode_vec(&pending[start..end], &mut decoded)?;
+        start = end;
+    }
+    pending.drain(..len);
+    Ok(decoded)
+}
+
+/// Request body decoding `application/grpc-web-text` messages.
+struct DecodeBody {
+    inner: ReqBody,
+    pending: Vec<u8>,
+}
+
+impl Body for DecodeBody {
+    type Data = Bytes;
+    type Error = BoxedError;
+
+    fn poll_frame(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Frame<Self::Data>, Self::Error>>> {
+        let this = self.get_mut();
+        loop {
+            match Pin::new(&mut this.inner).poll_frame(cx) {
+                Poll::Ready(Some(Ok(frame))) => match frame.into_data() {
+                    Ok(data) => {
+                        this.pending.extend_from_slice(&data);
+                        let decoded = decode_quanta(&mut this.pending)?;
+                        if !decoded.is_empty() {
+                            return Poll::Ready(Some(Ok(Frame::data(decoded.into()))));
+                        }
+                    }
+                    Err(frame) => return Poll::Ready(Some(Ok(frame))),
+                },
+                Poll::Ready(Some(Err(e))) => return Poll::Ready(Some(Err(e.into()))),
+                Poll::Ready(None) if this.pending.is_empty() => return Poll::Ready(None),
+                Poll::Ready(None) => {
+                    this.pending.clear();
+                    return Poll::Ready(Some(Err("incomplete base64 body".into())));
+                }
+                Poll::Pending => return Poll::Pending,
+            }
+        }
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.pending.is_empty() && self.inner.is_end_stream()
+    }
+
+    fn size_hint(&self) -> SizeHint {
+        SizeHint::default()
+    }
+}
+
+/// Response body moving trailers into the body, and base64 encoding it in text mode.
+struct EncodeBody {
+    inner: ResBody,
+    text: bool,
+    /// Bytes not yet encoded in text mode, since base64 is encoded in groups of 3 bytes.
+    pending: Vec<u8>,
+    done: bool,
+}
+
+impl EncodeBody {
+    fn encode(&mut self, data: &[u8], last: bool) -> Option<Bytes> {
+        if !self.text {
+            return (!data.is_empty()).then(|| Bytes::copy_from_slice(data));
+        }
+        self.pending.extend_from_slice(data);
+        let len = if last {
+            self.pending.len()
+        } else {
+            self.pending.len() / 3 * 3
+        };
+        if len == 0 {
+            return None;
+        }
+        let encoded = STANDARD.encode(&self.pending[..len]);
+        self.pending.drain(..len);
+        Some(encoded.into())
+    }
+}
+
+impl Body for EncodeBody {
+    type Data = Bytes;
+    type Error = BoxedError;
+
+    fn poll_frame(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Frame<Self::Data>, Self::Error>>> {
+        let this = self.get_mut();
+        while !this.done {
+            let encoded = match Pin::new(&mut this.inner).poll_frame(cx) {
+                Poll::Ready(Some(Ok(frame))) => match frame.into_data() {
+                    Ok(data) => this.encode(&data, false),
+                    Err(frame) => match frame.into_trailers() {
+                        Ok(trailers) => {
+                            this.done = true;
+                            this.encode(&encode_trailers(&trailers), true)
+                        }
+                        Err(_) => None,
+                    },
+                },
+                Poll::Ready(Some(Err(e))) => return Poll::Ready(Some(Err(e.into()))),
+                Poll::Ready(None) => {
+                    this.done = true;
+                    this.encode(&[], true)
+                }
+                Poll::Pending => return Poll::Pending,
+            };
+            if let Some(encoded) = encoded {
+                return Poll::Ready(Some(Ok(Frame::data(encoded))));
+            }
+        }
+        Poll::Ready(None)
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.done
+    }
+
+    fn size_hint(&self) -> SizeHint {
+        SizeHint::default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures_util::TryStreamExt;
+
+    use super::*;
+
+    #[test]
+    fn test_decode_quanta() {
+        let mut pending = b"AAE=AAEC".to_vec();
+        assert_eq!(decode_quanta(&mut pending).unwrap(), [0, 1, 0, 1, 2]);
+        assert!(pending.is_empty());
+
+        let mut pending = b"AAECAw".to_vec();
+        assert_eq!(decode_quanta(&mut pending).unwrap(), [0, 1, 2]);
+        assert_eq!(pending, b"Aw");
+        pending.extend_from_slice(b"==");
+        assert_eq!(decode_quanta(&mut pending).unwrap(), [3]);
+    }
+
+    #[tokio::test]
+    async fn test_translate_response() {
+        let (mut sender, body) = ResBody::channel();
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            CONTENT_TYPE,
+            HeaderValue::from_static("application/grpc+proto"),
+        );
+        headers.insert(CONTENT_LENGTH, HeaderValue::from_static("5"));
+        let body = GrpcWeb::Text.translate_response(&mut headers, body);
+        assert_eq!(headers[CONTENT_TYPE], "application/grpc-web-text+proto");
+        assert!(!headers.contains_key(CONTENT_LENGTH));
+
+        tokio::spawn(async move {
+            sender.send_data(vec![0, 0, 0, 0, 1]).await.unwrap();
+            sender.send_data(vec![7]).await.unwrap();
+            let mut trailers = HeaderMap::new();
+            trailers.insert("grpc-status", HeaderValue::from_static("0"));
+            sender.send_trailers(trailers).await.unwrap();
+        });
+        let encoded = body
+            .try_fold(Vec::new(), |mut encoded, frame| async move {
+                encoded.extend_from_slice(&frame.into_data().unwrap());
+                Ok(encoded)
+            })
+            .await
+            .unwrap();
+        let mut encoded = encoded;
+        let mut decoded = decode_quanta(&mut encoded).unwrap();
+        assert_eq!(decoded.drain(..6).as_slice(), [0, 0, 0, 0, 1, 7]);
+        assert_eq!(decoded[..5], [0x80, 0, 0, 0, 15]);
+        assert_eq!(&decoded[5..], b"grpc-status:0\r\n");
+    }
+}
@@ -0,0 +1,697 @@
+//! Health-aware upstreams with active health probes and passive outlier ejection.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, Weak};
+use std::time::{Duration, Instant};
+
+use salvo_core::http::header::HOST;
+use salvo_core::http::{Method, ReqBody};
+use salvo_core::{Depot, Request};
+use tokio::task::JoinHandle;
+
+use crate::{encode_unix_upstream, Client, Outcome, Upstreams};
+
+/// Settings of active health checks, which send `GET` requests to every upstream on an interval.
+#[derive(Clone, Debug)]
+pub struct ActiveCheck {
+    path: String,
+    interval: Duration,
+    timeout: Duration,
+    healthy_threshold: u32,
+    unhealthy_threshold: u32,
+}
+impl ActiveCheck {
+    /// Create new `ActiveCheck` probing the path of every upstream.
+    ///
+    /// Default interval is 10 seconds, timeout is 2 seconds, and an upstream is marked healthy after 2
+    /// consecutive successful probes and unhealthy after 3 consecutive failed probes.
+    pub fn new(path: impl Into<String>) -> Self {
+        Self {
+            path: path.into(),
+            interval: Duration::from_secs(10),
+            timeout: Duration::from_secs(2),
+            healthy_threshold: 2,
+            unhealthy_threshold: 3,
+        }
+    }
+    /// Sets the interval between probes.
+    pub fn interval(mut self, interval: Duration) -> Self {
+        self.interval = interval;
+        self
+    }
+    /// Sets the timeout of a probe.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+    /// Sets the number of consecutive successful probes to mark an upstream healthy.
+    pub fn healthy_threshold(mut self, threshold: u32) -> Self {
+        self.healthy_threshold = threshold.max(1);
+        self
+    }
+    /// Sets the number of consecutive failed probes to mark an upstream unhealthy.
+    pub fn unhealthy_threshold(mut self, threshold: u32) -> Self {
+        self.unhealthy_threshold = threshold.max(1);
+        self
+    }
+
+    fn url(&self, upstream: &str) -> String {
+        format!(
+            "{}/{}",
+            upstream.trim_end_matches('/'),
+            self.path.trim_start_matches('/')
+        )
+    }
+}
+
+/// Settings of passive health checks, which eject upstreams failing proxied requests.
+#[derive(Clone, Debug)]
+pub struct PassiveCheck {
+    max_failures: u32,
+    server_errors: bool,
+    base_ejection: Duration,
+    max_ejection: Duration,
+}
+impl Default for PassiveCheck {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+impl PassiveCheck {
+    /// Create new `PassiveCheck`.
+    ///
+    /// By default, an upstream is ejected for 10 seconds after 5 consecutive failures or `5xx` responses.
+    /// The ejection time doubles every time the upstream is ejected again, up to 5 minutes.
+    pub fn new() -> Self {
+        Self {
+            max_failures: 5,
+            server_errors: true,
+            base_ejection: Duration::from_secs(10),
+            max_ejection: Duration::from_secs(300),
+        }
+    }
+    /// Sets the number of consecutive failures to eject an upstream.
+    pub fn max_failures(mut self, max_failures: u32) -> Self {
+        self.max_failures = max_failures.max(1);
+        self
+    }
+    /// Sets whether `5xx` responses are counted as failures, default is true.
+    pub fn server_errors(mut self, server_errors: bool) -> Self {
+        self.server_errors = server_errors;
+        self
+    }
+    /// Sets the time of the first ejection.
+    pub fn base_ejection(mut self, duration: Duration) -> Self {
+        self.base_ejection = duration;
+        self
+    }
+    /// Sets the maximum time of an ejection.
+    pub fn max_ejection(mut self, duration: Duration) -> Self {
+        self.max_ejection = duration;
+        self
+    }
+
+    fn ejection(&self, ejections: u32) -> Duration {
+        let factor = 2u32.saturating_pow(ejections.saturating_sub(1));
+        self.base_ejection
+            .saturating_mul(factor)
+            .min(self.max_ejection)
+    }
+}
+
+/// Health of an upstream.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum UpstreamHealth {
+    /// The upstream receives requests.
+    Healthy,
+    /// The upstream failed active health probes.
+    Unhealthy,
+    /// The upstream is ejected for failing proxied requests.
+    Ejected,
+}
+
+/// State of an upstream, returned by [`HealthMonitor::states`].
+#[derive(Clone, Debug)]
+#[non_exhaustive]
+pub struct UpstreamState {
+    /// The upstream.
+    pub upstream: String,
+    /// Current health.
+    pub health: UpstreamHealth,
+    /// Consecutive failures of proxied requests.
+    pub consecutive_failures: u32,
+    /// Times the upstream has been ejected in a row.
+    pub ejections: u32,
+    /// Remaining time of the ejection.
+    pub ejected_for: Option<Duration>,
+}
+
+#[derive(Debug)]
+struct State {
+    probed_healthy: bool,
+    probe_successes: u32,
+    probe_failures: u32,
+    failures: u32,
+    ejections: u32,
+    ejected_until: Option<Instant>,
+}
+impl Default for State {
+    fn default() -> Self {
+        Self {
+            probed_healthy: true,
+            probe_successes: 0,
+            probe_failures: 0,
+            failures: 0,
+            ejections: 0,
+            ejected_until: None,
+        }
+    }
+}
+impl State {
+    fn is_ejected(&self, now: Instant) -> bool {
+        matches!(self.ejected_until, Some(until) if now < until)
+    }
+}
+
+/// Shared health states of the upstreams of a [`HealthChecked`], which can be used by an admin endpoint.
+#[derive(Clone, Debug, Default)]
+pub struct HealthMonitor {
+    states: Arc<Mutex<HashMap<String, State>>>,
+}
+impl HealthMonitor {
+    fn lock(&self) -> std::sync::MutexGuard<'_, HashMap<String, State>> {
+        self.states.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Returns whether the upstream can receive requests. Unknown upstreams are available.
+    pub fn is_available(&self, upstream: &str) -> bool {
+        self.lock()
+            .get(upstream)
+            .map(|state| state.probed_healthy && !state.is_ejected(Instant::now()))
+            .unwrap_or(true)
+    }
+
+    /// Returns the states of all known upstreams, sorted by upstream.
+    pub fn states(&self) -> Vec<UpstreamState> {
+        let now = Instant::now();
+        let mut states = self
+            .lock()
+            .iter()
+            .map(|(upstream, state)| UpstreamState {
+                upstream: upstream.clone(),
+                health: if state.is_ejected(now) {
+                    UpstreamHealth::Ejected
+                } else if !state.probed_healthy {
+                    UpstreamHealth::Unhealthy
+                } else {
+                    UpstreamHealth::Healthy
+                },
+                consecutive_failures: state.failures,
+                ejections: state.ejections,
+                ejected_for: state
+                    .ejected_until
+                    .and_then(|until| until.checked_duration_since(now))
+                    .filter(|remaining| !remaining.is_zero()),
+            })
+            .collect::<Vec<_>>();
+        states.sort_by(|a, b| a.upstream.cmp(&b.upstream));
+        states
+    }
+
+    /// Tracks the members, and drops the states of upstreams which are no longer members.
+    fn track<S: AsRef<str>>(&self, members: &[S]) {
+        let mut states = self.lock();
+        states.retain(|upstream, _| members.iter().any(|member| member.as_ref() == upstream));
+        for member in members {
+            if !states.contains_key(member.as_ref()) {
+                states.insert(member.as_ref().to_owned(), State::default());
+            }
+        }
+    }
+
+    fn report<S: AsRef<str>>(
+        &self,
+        upstream: &str,
+        failed: bool,
+        check: &PassiveCheck,
+        members: impl FnOnce() -> Vec<S>,
+    ) {
+        let now = Instant::now();
+        let mut states = self.lock();
+        if !states.contains_key(upstream) {
+            // A new member, drop the states of removed members meanwhile. Upstreams which are no longer
+            // members, such as removed upstreams finishing their requests, are not tracked.
+            let members = members();
+            let is_member =
+                |upstream: &str| members.iter().any(|member| member.as_ref() == upstream);
+            if !is_member(upstream) {
+                return;
+            }
+            states.retain(|upstream, _| is_member(upstream));
+        }
+        let state = states.entry(upstream.to_owned()).or_default();
+        if !failed {
+            state.failures = 0;
+            // Forget previous ejections once the upstream has been working for a while.
+            if matches!(state.ejected_until, Some(until) if now >= until + check.max_ejection) {
+                state.ejections = 0;
+                state.ejected_until = None;
+            }
+            return;
+        }
+        if state.is_ejected(now) {
+            return;
+        }
+        state.failures += 1;
+        if state.failures >= check.max_failures {
+            state.failures = 0;
+            state.ejections = state.ejections.saturating_add(1);
+            let ejection = check.ejection(state.ejections);
+            state.ejected_until = Some(now + ejection);
+            tracing::warn!(upstream, ejection = ?ejection, "upstream ejected");
+        }
+    }
+
+    fn probed(&self, upstream: &str, healthy: bool, check: &ActiveCheck) {
+        let mut states = self.lock();
+        // The upstream is removed while it is probed.
+        let Some(state) = states.get_mut(upstream) else {
+            return;
+        };
+        if healthy {
+            state.probe_failures = 0;
+            state.probe_successes = state.probe_successes.saturating_add(1);
+            if !state.probed_healthy && state.probe_successes >= check.healthy_threshold {
+                state.probed_healthy = true;
+                tracing::info!(upstream, "upstream is healthy");
+            }
+        } else {
+            state.probe_successes = 0;
+            state.probe_failures = state.probe_failures.saturating_add(1);
+            if state.probed_healthy && state.probe_failures >= check.unhealthy_threshold {
+                state.probed_healthy = false;
+                tracing::warn!(upstream, "upstream is unhealthy");
+            }
+        }
+    }
+}
+
+/// Upstreams which skip unhealthy and ejected members of the inner [`Upstreams`].
+///
+/// The inner upstreams elect among the available members with [`Upstreams::elect_accepted`]. If no upstream
+/// is available, the inner upstreams elect as usual, so that requests are not rejected while every upstream
+/// is down.
+///
+/// States of upstreams which are no longer members of the inner upstreams, such as members removed by
+/// discovery, are dropped.
+///
+/// # Example
+///
+/// ```no_run
+/// use std::time::Duration;
+///
+/// use salvo_core::prelude::*;
+/// use salvo_proxy::{
+///     ActiveCheck, HealthChecked, HealthMonitor, HyperClient, PassiveCheck, Proxy, RoundRobin,
+/// };
+///
+/// struct UpstreamStates(HealthMonitor);
+/// #[async_trait]
+/// impl Handler for UpstreamStates {
+///     async fn handle(&self, _req: &mut Request, _depot: &mut Depot, res: &mut Response, _ctrl: &mut FlowCtrl) {
+///         res.render(format!("{:#?}", self.0.states()));
+///     }
+/// }
+///
+/// #[tokio::main]
+/// async fn main() {
+///     let upstreams = HealthChecked::new(RoundRobin::new(["http://10.0.0.1:8080", "http://10.0.0.2:8080"]))
+///         .passive_check(PassiveCheck::new().max_failures(3));
+///     upstreams.spawn_active_check(
+///         ActiveCheck::new("/health").interval(Duration::from_secs(5)),
+///         HyperClient::default(),
+///     );
+///     let router = Router::new()
+///         .push(Router::with_path("admin/upstreams").get(UpstreamStates(upstreams.monitor())))
+///         .push(Router::with_path("{**rest}").goal(Proxy::use_hyper_client(upstreams)));
+///
+///     let acceptor = TcpListener::new("0.0.0.0:5800").bind().await;
+///     Server::new(acceptor).serve(router).await;
+/// }
+/// ```
+pub struct HealthChecked<U> {
+    inner: Arc<U>,
+    monitor: HealthMonitor,
+    passive: Option<PassiveCheck>,
+}
+impl<U: Upstreams> HealthChecked<U> {
+    /// Create new `HealthChecked`, without passive check.
+    pub fn new(inner: U) -> Self {
+        let monitor = HealthMonitor::default();
+        monitor.track(&inner.members());
+        Self {
+            inner: Arc::new(inner),
+            monitor,
+            passive: None,
+        }
+    }
+
+    /// Sets the passive check and returns new `HealthChecked`.
+    pub fn passive_check(mut self, check: PassiveCheck) -> Self {
+        self.passive = Some(check);
+        self
+    }
+
+    /// Get the inner upstreams.
+    pub fn inner(&self) -> &U {
+        &self.inner
+    }
+
+    /// Get the health monitor.
+    pub fn monitor(&self) -> HealthMonitor {
+        self.monitor.clone()
+    }
+
+    /// Spawn a task which probes all members of the inner upstreams on the interval of the check.
+    ///
+    /// The task stops when the `HealthChecked` is dropped.
+    pub fn spawn_active_check<C: Client>(&self, check: ActiveCheck, client: C) -> JoinHandle<()> {
+        let inner: Weak<U> = Arc::downgrade(&self.inner);
+        let monitor = self.monitor.clone();
+        let client = Arc::new(client);
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(check.interval);
+            loop {
+                ticker.tick().await;
+                let Some(members) = inner.upgrade().map(|inner| inner.members()) else {
+                    break;
+                };
+                monitor.track(&members);
+                let probes = members
+                    .into_iter()
+                    .map(|upstream| {
+                        let client = client.clone();
+                        let check = check.clone();
+                        tokio::spawn(async move {
+                            let healthy = probe(&*client, &check, &upstream).await;
+                            (upstream, healthy)
+                        })
+                    })
+                    .collect::<Vec<_>>();
+                for probe in probes {
+                    if let Ok((upstream, healthy)) = probe.await {
+                        monitor.probed(&upstream, healthy, &check);
+                    }
+                }
+            }
+        })
+    }
+}
+
+async fn probe<C: Client>(client: &C, check: &ActiveCheck, upstream: &str) -> bool {
+    // Unix domain socket upstreams are probed like proxied requests are sent to them.
+    let unix_upstream = encode_unix_upstream(upstream);
+    let mut builder = hyper::Request::builder()
+        .method(Method::GET)
+        .uri(check.url(unix_upstream.as_deref().unwrap_or(upstream)));
+    if unix_upstream.is_some() {
+        builder = builder.header(HOST, "localhost");
+    }
+    let request = match builder.body(ReqBody::None) {
+        Ok(request) => request,
+        Err(e) => {
+            tracing::error!(error = ?e, upstream, "build health check request failed");
+            return false;
+        }
+    };
+    match tokio::time::timeout(check.timeout, client.execute(request, None)).await {
+        Ok(Ok(response)) => response.status().is_success(),
+        Ok(Err(e)) => {
+            tracing::debug!(error = ?e, upstream, "health check failed");
+            false
+        }
+        Err(_) => {
+            tracing::debug!(upstream, "health check timed out");
+            false
+        }
+    }
+}
+
+impl<U: Upstreams> Upstreams for HealthChecked<U> {
+    type Error = U::Error;
+
+    async fn elect(&self, req: &Request, depot: &Depot) -> Result<Arc<str>, Self::Error> {
+        let available = |upstream: &str| self.monitor.is_available(upstream);
+        if let Some(elected) = self.inner.elect_accepted(req, depot, &available).await? {
+            return Ok(elected);
+        }
+        let elected = self.inner.elect(req, depot).await?;
+        tracing::warn!(upstream = &*elected, "no upstream is available");
+        Ok(elected)
+    }
+
+    async fn elect_accepted(
+        &self,
+        req: &Request,
+        depot: &Depot,
+        accept: &(dyn Fn(&str) -> bool + Send + Sync),
+    ) -> Result<Option<Arc<str>>, Self::Error> {
+        let accept = |upstream: &str| self.monitor.is_available(upstream) && accept(upstream);
+        self.inner.elect_accepted(req, depot, &accept).await
+    }
+
+    fn finish(&self, upstream: &str, outcome: Outcome) {
+        if let Some(check) = &self.passive {
+            match outcome {
+                Outcome::Responded(status) => {
+                    let failed = check.server_errors && status.is_server_error();
+                    self.monitor
+                        .report(upstream, failed, check, || self.inner.members());
+                }
+                Outcome::Failed => self
+                    .monitor
+                    .report(upstream, true, check, || self.inner.members()),
+                Outcome::Skipped | Outcome::Cancelled => {}
+            }
+        }
+        self.inner.finish(upstream, outcome);
+    }
+
+    fn members(&self) -> Vec<Arc<str>> {
+        self.inner.members()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use salvo_core::http::{ResBody, StatusCode};
+
+    use super::*;
+    use crate::{HyperRequest, HyperResponse, LeastInFlight};
+
+    #[tokio::test]
+    async fn test_passive_check() {
+        let upstreams = HealthChecked::new(vec!["http://a", "http://b"]).passive_check(
+            PassiveCheck::new()
+                .max_failures(2)
+                .base_ejection(Duration::from_millis(100)),
+        );
+        let (req, depot) = (Request::new(), Depot::new());
+        upstreams.finish("http://a", Outcome::Responded(StatusCode::BAD_GATEWAY));
+        upstreams.finish("http://a", Outcome::Failed);
+
+        let states = upstreams.monitor().states();
+        assert_eq!(states[0].health, UpstreamHealth::Ejected);
+        assert_eq!(states[0].ejections, 1);
+        assert_eq!(states[1].health, UpstreamHealth::Healthy);
+        for _ in 0..20 {
+            assert_eq!(&*upstreams.elect(&req, &depot).await.unwrap(), "http://b");
+        }
+
+        tokio::time::sleep(Duration::from_millis(150)).await;
+        assert!(upstreams.monitor().is_available("http://a"));
+        // The second ejection is longer.
+        upstreams.finish("http://a", Outcome::Failed);
+        upstreams.finish("http://a", Outcome::Failed);
+        let states = upstreams.monitor().states();
+        assert_eq!(states[0].ejections, 2);
+        assert!(states[0].ejected_for.unwrap() > Duration::from_millis(150));
+    }
+
+    #[tokio::test]
+    async fn test_all_ejected() {
+        let upstreams =
+            HealthChecked::new("http://a").passive_check(PassiveCheck::new().max_failures(1));
+        upstreams.finish("http://a", Outcome::Failed);
+        assert!(!upstreams.monitor().is_available("http://a"));
+        let elected = upstreams
+            .elect(&Request::new(), &Depot::new())
+            .await
+            .unwrap();
+        assert_eq!(&*elected, "http://a");
+    }
+
+    #[tokio::test]
+    async fn test_elect_accepted() {
+        let upstreams = HealthChecked::new(LeastInFlight::new(["http://a", "http://b"]))
+            .passive_check(PassiveCheck::new().max_failures(1));
+        upstreams.finish("http://a", Outcome::Failed);
+        let (req, depot) = (Request::new(), Depot::new());
+        for _ in 0..2 {
+            assert_eq!(&*upstreams.elect(&req, &depot).await.unwrap(), "http://b");
+        }
+        // Only the elected upstream is counted by the inner upstreams.
+        assert_eq!(upstreams.inner().in_flight("http://a"), Some(0));
+        assert_eq!(upstreams.inner().in_flight("http://b"), Some(2));
+    }
+
+    #[tokio::test]
+    async fn test_removed_members() {
+        struct Members(std::sync::Mutex<Vec<&'static str>>);
+        impl Upstreams for Members {
+            type Error = salvo_core::Error;
+
+            async fn elect(&self, _req: &Request, _depot: &Depot) -> Result<Arc<str>, Self::Error> {
+                Ok(self.0.lock().unwrap()[0].into())
+            }
+
+            fn members(&self) -> Vec<Arc<str>> {
+                self.0
+                    .lock()
+                    .unwrap()
+                    .iter()
+                    .copied()
+                    .map(Arc::from)
+                    .collect()
+            }
+        }
+
+        let upstreams =
+            HealthChecked::new(Members(std::sync::Mutex::new(vec!["http://a", "http://b"])))
+                .passive_check(PassiveCheck::new().max_failures(1));
+        upstreams.finish("http://a", Outcome::Failed);
+        assert!(!upstreams.monitor().is_available("http://a"));
+
+        *upstreams.inner().0.lock().unwrap() = vec!["http://b", "http://c"];
+        upstreams.finish("http://c", Outcome::Failed);
+        let states = upstreams.monitor().states();
+        assert_eq!(
+            states
+                .iter()
+                .map(|state| &*state.upstream)
+                .collect::<Vec<_>>(),
+            ["http://b", "http://c"]
+        );
+        // Removed upstreams finishing their requests are not tracked again.
+        upstreams.finish("http://a", Outcome::Failed);
+        assert_eq!(upstreams.monitor().states().len(), 2);
+    }
+
+    struct FakeClient;
+    impl Client for FakeClient {
+        type Error = salvo_core::Error;
+
+        async fn execute(
+            &self,
+            req: HyperRequest,
+            _upgraded: Option<hyper::upgrade::OnUpgrade>,
+        ) -> Result<HyperResponse, Self::Error> {
+            let status = if req.uri() == "http://a/health" {
+                StatusCode::OK
+            } else {
+                StatusCode::SERVICE_UNAVAILABLE
+            };
+            Ok(hyper::Response::builder()
+                .status(status)
+                .body(ResBody::None)
+                .unwrap())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_active_check() {
+        let upstreams = HealthChecked::new(vec!["http://a/", "http://b"]);
+        let task = upstreams.spawn_active_check(
+            ActiveCheck::new("/health")
+                .interval(Duration::from_millis(10))
+                .unhealthy_threshold(2),
+            FakeClient,
+        );
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        let states = upstreams.monitor().states();
+        assert_eq!(states[0].health, UpstreamHealth::Healthy);
+        assert_eq!(states[1].health, UpstreamHealth::Unhealthy);
+        assert_eq!(
+            &*upstreams
+                .elect(&Request::new(), &Depot::new())
+                .await
+                .unwrap(),
+            "http://a/"
+        );
+
+        drop(upstreams);
+        tokio::time::timeout(Duration::from_secs(1), task)
+            .await
+            .unwrap()
+            .unwrap();
+    }
+
+    #[cfg(all(unix, feature = "hyper-client"))]
+    #[tokio::test]
+    async fn test_active_check_unix() {
+        use hyper::server::conn::http1;
+        use hyper::service::service_fn;
+        use hyper_util::rt::TokioIo;
+        use tokio::net::UnixListener;
+
+        let path = std::env::temp_dir().join(format!("salvo-health-{}.sock", fastrand::u64(..)));
+        let listener = UnixListener::bind(&path).unwrap();
+        let probed = Arc::new(Mutex::new(Vec::new()));
+        let received = probed.clone();
+        tokio::spawn(async move {
+            loop {
+                let (stream, _) = listener.accept().await.unwrap();
+                let received = received.clone();
+                let service = service_fn(move |req: hyper::Request<hyper::body::Incoming>| {
+                    let host = req.headers()["host"].to_str().unwrap().to_owned();
+                    received
+                        .lock()
+                        .unwrap()
+                        .push(format!("{host} {}", req.uri()));
+                    let status = if req.uri() == "/health" {
+                        StatusCode::OK
+                    } else {
+                        StatusCode::NOT_FOUND
+                    };
+                    async move {
+                        Ok::<_, std::convert::Infallible>(
+                            hyper::Response::builder()
+                                .status(status)
+                                .body(String::new())
+                                .unwrap(),
+                        )
+                    }
+                });
+                tokio::spawn(http1::Builder::new().serve_connection(TokioIo::new(stream), service));
+            }
+        });
+
+        let upstreams = HealthChecked::new(vec![format!("unix://{}", path.display())]);
+        let task = upstreams.spawn_active_check(
+            ActiveCheck::new("/health")
+                .interval(Duration::from_millis(10))
+                .unhealthy_threshold(1),
+            crate::HyperClient::default(),
+        );
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        task.abort();
+        assert_eq!(
+            upstreams.monitor().states()[0].health,
+            UpstreamHealth::Healthy
+        );
+        let probed = probed.lock().unwrap();
+        assert!(!probed.is_empty());
+        assert!(probed.iter().all(|probe| probe == "localhost /health"));
+        let _ = std::fs::remove_file(path);
+    }
+}
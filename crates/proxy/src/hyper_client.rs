@@ -1,31 +1,32 @@
+use std::time::Duration;
+
 use hyper::upgrade::OnUpgrade;
 use hyper_rustls::{HttpsConnector, HttpsConnectorBuilder};
-use hyper_util::client::legacy::{connect::HttpConnector, Client as HyperUtilClient};
-use hyper_util::rt::TokioExecutor;
+use hyper_util::client::legacy::connect::{Connect, HttpConnector};
+use hyper_util::client::legacy::Client as HyperUtilClient;
+use hyper_util::rt::{TokioExecutor, TokioTimer};
 use salvo_core::http::{ReqBody, ResBody, StatusCode};
 use salvo_core::rt::tokio::TokioIo;
 use salvo_core::Error;
 use tokio::io::copy_bidirectional;
 
-use crate::{Client, HyperRequest, Proxy, BoxedError, Upstreams, HyperResponse};
+use crate::{Client, HyperRequest, Proxy, BoxedError, Upstreams, HyperResponse, UnixConnector};
+
+/// Connector of the default [`HyperClient`].
+pub type DefaultConnector = UnixConnector<HttpsConnector<HttpConnector>>;
 
 /// A [`Client`] implementation based on [`hyper_util::client::legacy::Client`].
+///
+/// The default connector supports `https` upstreams, and Unix domain socket upstreams such as
+/// `unix:///var/run/app.sock`.
 #[derive(Clone, Debug)]
-pub struct HyperClient {
-    inner: HyperUtilClient<HttpsConnector<HttpConnector>, ReqBody>,
+pub struct HyperClient<C = DefaultConnector> {
+    inner: HyperUtilClient<C, ReqBody>,
 }
 
 impl Default for HyperClient {
     fn default() -> Self {
-        let https = HttpsConnectorBuilder::new()
-            .with_native_roots()
-            .expect("no native root CA certificates found")
-            .https_only()
-            .enable_http1()
-            .build();
-        Self {
-            inner: HyperUtilClient::builder(TokioExecutor::new()).build(https),
-        }
+        HyperClientBuilder::new().build()
     }
 }
 
@@ -40,14 +41,137 @@ where
     }
 }
 
-impl HyperClient {
+impl<C> HyperClient<C> {
     /// Create a new `HyperClient` with the given `HyperClient`.
-    pub fn new(inner: HyperUtilClient<HttpsConnector<HttpConnector>, ReqBody>) -> Self {
+    pub fn new(inner: HyperUtilClient<C, ReqBody>) -> Self {
         Self { inner }
     }
 }
 
-impl Client for HyperClient {
+impl HyperClient {
+    /// Create a new [`HyperClientBuilder`].
+    pub fn builder() -> HyperClientBuilder {
+        HyperClientBuilder::new()
+    }
+
+    /// Create a new default `HyperClient` which fails connecting to upstream after the timeout.
+    pub fn with_connect_timeout(timeout: Duration) -> Self {
+        HyperClientBuilder::new().connect_timeout(timeout).build()
+    }
+}
+
+/// Builder of [`HyperClient`].
+///
+/// # Example
+///
+/// Proxy gRPC services listening on plain HTTP/2:
+///
+/// ```
+/// use std::time::Duration;
+///
+/// use salvo_proxy::{HyperClient, Proxy};
+///
+/// let client = HyperClient::builder()
+///     .http2_only(true)
+///     .http2_keep_alive_interval(Duration::from_secs(30))
+///     .build();
+/// let proxy = Proxy::new("http://127.0.0.1:50051", client);
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct HyperClientBuilder {
+    connect_timeout: Option<Duration>,
+    http2_only: bool,
+    http2_keep_alive_interval: Option<Duration>,
+}
+
+impl HyperClientBuilder {
+    /// Create a new `HyperClientBuilder`.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set timeout for connecting to upstream.
+    pub fn connect_timeout(mut self, timeout: Duration) -> Self {
+        self.connect_timeout = Some(timeout);
+        self
+    }
+
+    /// Only use HTTP/2, as required by gRPC upstreams, default is `false`.
+    ///
+    /// `http` upstreams are connected with HTTP/2 prior knowledge (h2c), and `https` upstreams negotiate HTTP/2
+    /// with ALPN. By default, only `https` upstreams are allowed and HTTP/1 is used.
+    pub fn http2_only(mut self, http2_only: bool) -> Self {
+        self.http2_only = http2_only;
+        self
+    }
+
+    /// Set interval of HTTP/2 pings, which keep connections with long lived streams alive.
+    pub fn http2_keep_alive_interval(mut self, interval: Duration) -> Self {
+        self.http2_keep_alive_interval = Some(interval);
+        self
+    }
+
+    /// Build the `HyperClient`.
+    pub fn build(self) -> HyperClient {
+        let mut http = HttpConnector::new();
+        http.enforce_http(false);
+        http.set_connect_timeout(self.connect_timeout);
+        let https = HttpsConnectorBuilder::new()
+            .with_native_roots()
+            .expect("no native root CA certificates found");
+        let https = if self.http2_only {
+            https.https_or_http().enable_http2().wrap_connector(http)
+        } else {
+            https.https_only().enable_http1().wrap_connector(http)
+        };
+        self.build_with_connector(UnixConnector::new(https))
+    }
+
+    /// Build the `HyperClient` with a custom connector, for example to use client certificates or a custom DNS
+    /// resolver. The connect timeout is not applied, since it is set on the connector.
+    ///
+    /// Wrap the connector in [`UnixConnector`] to keep supporting Unix domain socket upstreams.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use hyper_rustls::HttpsConnectorBuilder;
+    /// use hyper_util::client::legacy::connect::HttpConnector;
+    /// use salvo_proxy::{HyperClient, Proxy, UnixConnector};
+    ///
+    /// let mut http = HttpConnector::new();
+    /// http.enforce_http(false);
+    /// http.set_nodelay(true);
+    /// let https = HttpsConnectorBuilder::new()
+    ///     .with_native_roots()
+    ///     .unwrap()
+    ///     .https_only()
+    ///     .enable_http1()
+    ///     .wrap_connector(http);
+    /// let client = HyperClient::builder().build_with_connector(UnixConnector::new(https));
+    /// let proxy = Proxy::new("https://www.example.com", client);
+    /// ```
+    pub fn build_with_connector<C>(self, connector: C) -> HyperClient<C>
+    where
+        C: Connect + Clone + Send + Sync + 'static,
+    {
+        let mut builder = HyperUtilClient::builder(TokioExecutor::new());
+        builder.timer(TokioTimer::new()).http2_only(self.http2_only);
+        if let Some(interval) = self.http2_keep_alive_interval {
+            builder
+                .http2_keep_alive_interval(interval)
+                .http2_keep_alive_while_idle(true);
+        }
+        HyperClient {
+            inner: builder.build(connector),
+        }
+    }
+}
+
+impl<C> Client for HyperClient<C>
+where
+    C: Connect + Clone + Send + Sync + 'static,
+{
     type Error = salvo_core::Error;
 
     async fn execute(
@@ -104,8 +228,8 @@ mod tests {
     async fn test_upstreams_elect() {
         let upstreams = vec!["https://www.example.com", "https://www.example2.com"];
         let proxy = Proxy::new(upstreams.clone(), HyperClient::default());
-        let elected_upstream = proxy.upstreams().elect().await.unwrap();
-        assert!(upstreams.contains(&elected_upstream));
+        let elected_upstream = proxy.upstreams().elect(&Request::new(), &Depot::new()).await.unwrap();
+        assert!(upstreams.contains(&&*elected_upstream));
     }
 
     #[tokio::test]
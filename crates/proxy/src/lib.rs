@@ -37,21 +37,54 @@
 use std::convert::Infallible;
 use std::error::Error as StdError;
 use std::future::Future;
+use std::sync::Arc;
+use std::time::Duration;
 
 use hyper::upgrade::OnUpgrade;
 use percent_encoding::{utf8_percent_encode, CONTROLS};
-use salvo_core::http::header::{HeaderMap, HeaderName, HeaderValue, CONNECTION, HOST, UPGRADE};
+#[cfg(feature = "timeout")]
+use salvo_core::http::header::HeaderName;
+use salvo_core::http::header::{HeaderMap, HeaderValue, CONNECTION, HOST, UPGRADE};
 use salvo_core::http::uri::Uri;
-use salvo_core::http::{ReqBody, ResBody, StatusCode};
+use salvo_core::http::{Method, ReqBody, ResBody, StatusCode};
+use salvo_core::hyper::body::{Body, Bytes};
 use salvo_core::{async_trait, BoxedError, Depot, Error, FlowCtrl, Handler, Request, Response};
 
 #[macro_use]
 mod cfg;
 
+mod buffering;
+mod circuit_breaker;
+pub use circuit_breaker::*;
+mod forwarding;
+pub use forwarding::strip_hop_by_hop_headers;
+mod grpc_web;
+use grpc_web::GrpcWeb;
+mod health;
+pub use health::*;
+mod mirror;
+pub use mirror::{Mirror, MirrorMetrics};
+mod rewrite;
+pub use rewrite::*;
+mod upstreams;
+pub use upstreams::*;
+
+cfg_feature! {
+    #![feature = "cache"]
+    mod cache;
+    pub use cache::CachedProxy;
+}
+cfg_feature! {
+    #![feature = "discovery"]
+    mod discovery;
+    pub use discovery::{Discover, Discovered, DnsDiscovery, FileDiscovery};
+}
 cfg_feature! {
     #![feature = "hyper-client"]
     mod hyper_client;
     pub use hyper_client::*;
+    mod unix_connector;
+    pub use unix_connector::{UnixConnector, UnixConnectorStream};
 }
 cfg_feature! {
     #![feature = "reqwest-client"]
@@ -71,6 +104,21 @@ pub(crate) fn encode_url_path(path: &str) -> String {
         .join("/")
 }
 
+/// Returns the uri of a Unix domain socket upstream to build requests with, or `None` for other upstreams.
+///
+/// Socket paths are not valid authorities, so they are encoded to reach the connector.
+fn encode_unix_upstream(upstream: &str) -> Option<String> {
+    #[cfg(feature = "hyper-client")]
+    {
+        unix_connector::encode_unix_upstream(upstream)
+    }
+    #[cfg(not(feature = "hyper-client"))]
+    {
+        let _ = upstream;
+        None
+    }
+}
+
 /// Client trait.
 pub trait Client: Send + Sync + 'static {
     /// Error type.
@@ -83,35 +131,129 @@ pub trait Client: Send + Sync + 'static {
     ) -> impl Future<Output = Result<HyperResponse, Self::Error>> + Send;
 }
 
+/// Outcome of a proxied request, reported to [`Upstreams::finish`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Outcome {
+    /// The upstream responded with the status code.
+    Responded(StatusCode),
+    /// The request to the upstream failed or timed out.
+    Failed,
+    /// The elected upstream was not used, for example because its circuit is open.
+    Skipped,
+    /// The request was dropped before the upstream responded, for example because the client disconnected.
+    Cancelled,
+}
+
 /// Upstreams trait.
 pub trait Upstreams: Send + Sync + 'static {
     /// Error type.
     type Error: StdError + Send + Sync + 'static;
     /// Elect a upstream to process current request.
-    fn elect(&self) -> impl Future<Output = Result<&str, Self::Error>> + Send;
+    ///
+    /// The upstream is owned, so it stays valid while the request is in flight even if it is removed from
+    /// the members meanwhile.
+    fn elect(
+        &self,
+        req: &Request,
+        depot: &Depot,
+    ) -> impl Future<Output = Result<Arc<str>, Self::Error>> + Send;
+
+    /// Elect a upstream among the members `accept` returns true for, used by [`HealthChecked`] to skip
+    /// unavailable upstreams. Returns `None` if no accepted upstream can be elected.
+    ///
+    /// The default implementation elects up to once per member and releases the upstreams which are not
+    /// accepted with [`Outcome::Skipped`]. Strategies override it to pick among the accepted members only.
+    fn elect_accepted(
+        &self,
+        req: &Request,
+        depot: &Depot,
+        accept: &(dyn Fn(&str) -> bool + Send + Sync),
+    ) -> impl Future<Output = Result<Option<Arc<str>>, Self::Error>> + Send {
+        async move {
+            for _ in 0..self.members().len().max(1) {
+                let elected = self.elect(req, depot).await?;
+                if accept(&elected) {
+                    return Ok(Some(elected));
+                }
+                self.finish(&elected, Outcome::Skipped);
+            }
+            Ok(None)
+        }
+    }
+
+    /// Called exactly once for every elected upstream when the proxied request to it is done.
+    ///
+    /// Strategies which track the requests in flight, such as [`LeastInFlight`], release the upstream here.
+    /// It is also called when the request is dropped, so it is not async and must not block.
+    fn finish(&self, _upstream: &str, _outcome: Outcome) {}
+
+    /// Returns all upstreams which can be elected, used by health checks.
+    fn members(&self) -> Vec<Arc<str>> {
+        vec![]
+    }
 }
 impl Upstreams for &'static str {
     type Error = Infallible;
 
-    async fn elect(&self) -> Result<&str, Self::Error> {
-        Ok(*self)
+    async fn elect(&self, _req: &Request, _depot: &Depot) -> Result<Arc<str>, Self::Error> {
+        Ok(Arc::from(*self))
+    }
+
+    async fn elect_accepted(
+        &self,
+        _req: &Request,
+        _depot: &Depot,
+        accept: &(dyn Fn(&str) -> bool + Send + Sync),
+    ) -> Result<Option<Arc<str>>, Self::Error> {
+        Ok(accept(self).then(|| Arc::from(*self)))
+    }
+
+    fn members(&self) -> Vec<Arc<str>> {
+        vec![Arc::from(*self)]
     }
 }
 impl Upstreams for String {
     type Error = Infallible;
-    async fn elect(&self) -> Result<&str, Self::Error> {
-        Ok(self.as_str())
+    async fn elect(&self, _req: &Request, _depot: &Depot) -> Result<Arc<str>, Self::Error> {
+        Ok(Arc::from(self.as_str()))
+    }
+
+    async fn elect_accepted(
+        &self,
+        _req: &Request,
+        _depot: &Depot,
+        accept: &(dyn Fn(&str) -> bool + Send + Sync),
+    ) -> Result<Option<Arc<str>>, Self::Error> {
+        Ok(accept(self).then(|| Arc::from(self.as_str())))
+    }
+
+    fn members(&self) -> Vec<Arc<str>> {
+        vec![Arc::from(self.as_str())]
     }
 }
 
 impl<const N: usize> Upstreams for [&'static str; N] {
     type Error = Error;
-    async fn elect(&self) -> Result<&str, Self::Error> {
+    async fn elect(&self, _req: &Request, _depot: &Depot) -> Result<Arc<str>, Self::Error> {
         if self.is_empty() {
             return Err(Error::other("upstreams is empty"));
         }
         let index = fastrand::usize(..self.len());
-        Ok(self[index])
+        Ok(Arc::from(self[index]))
+    }
+
+    async fn elect_accepted(
+        &self,
+        _req: &Request,
+        _depot: &Depot,
+        accept: &(dyn Fn(&str) -> bool + Send + Sync),
+    ) -> Result<Option<Arc<str>>, Self::Error> {
+        Ok(elect_random(self.iter().copied(), accept).map(Arc::from))
+    }
+
+    fn members(&self) -> Vec<Arc<str>> {
+        self.iter().copied().map(Arc::from).collect()
     }
 }
 
@@ -120,12 +262,84 @@ where
     T: AsRef<str> + Send + Sync + 'static,
 {
     type Error = Error;
-    async fn elect(&self) -> Result<&str, Self::Error> {
+    async fn elect(&self, _req: &Request, _depot: &Depot) -> Result<Arc<str>, Self::Error> {
         if self.is_empty() {
             return Err(Error::other("upstreams is empty"));
         }
         let index = fastrand::usize(..self.len());
-        Ok(self[index].as_ref())
+        Ok(Arc::from(self[index].as_ref()))
+    }
+
+    async fn elect_accepted(
+        &self,
+        _req: &Request,
+        _depot: &Depot,
+        accept: &(dyn Fn(&str) -> bool + Send + Sync),
+    ) -> Result<Option<Arc<str>>, Self::Error> {
+        Ok(elect_random(self.iter().map(AsRef::as_ref), accept).map(Arc::from))
+    }
+
+    fn members(&self) -> Vec<Arc<str>> {
+        self.iter()
+            .map(|upstream| Arc::from(upstream.as_ref()))
+            .collect()
+    }
+}
+
+/// Elects a random upstream among the accepted ones.
+fn elect_random<'a>(
+    upstreams: impl Iterator<Item = &'a str>,
+    accept: &(dyn Fn(&str) -> bool + Send + Sync),
+) -> Option<&'a str> {
+    let accepted = upstreams
+        .filter(|upstream| accept(upstream))
+        .collect::<Vec<_>>();
+    if accepted.is_empty() {
+        None
+    } else {
+        Some(accepted[fastrand::usize(..accepted.len())])
+    }
+}
+
+/// An elected upstream, reported to [`Upstreams::finish`] and the circuit breaker when it is dropped.
+///
+/// The outcome is [`Outcome::Cancelled`] unless it is set with [`finish`](Elected::finish), so the upstream and
+/// the trial of a half open circuit are released even if the request is dropped, such as when the client
+/// disconnects.
+struct Elected<'a, U: Upstreams> {
+    upstreams: &'a U,
+    upstream: Arc<str>,
+    /// The circuit breaker which allowed the request, and its permit.
+    breaker: Option<(&'a CircuitBreaker, Permit)>,
+    outcome: Outcome,
+}
+impl<'a, U: Upstreams> Elected<'a, U> {
+    fn new(upstreams: &'a U, upstream: Arc<str>) -> Self {
+        Self {
+            upstreams,
+            upstream,
+            breaker: None,
+            outcome: Outcome::Cancelled,
+        }
+    }
+
+    fn finish(mut self, outcome: Outcome) {
+        self.outcome = outcome;
+    }
+}
+impl<U: Upstreams> Drop for Elected<'_, U> {
+    fn drop(&mut self) {
+        if let Some((breaker, permit)) = self.breaker {
+            let members = || self.upstreams.members();
+            match self.outcome {
+                Outcome::Responded(status) => {
+                    breaker.record(&self.upstream, permit, !status.is_server_error(), members);
+                }
+                Outcome::Failed => breaker.record(&self.upstream, permit, false, members),
+                Outcome::Skipped | Outcome::Cancelled => breaker.release(&self.upstream, permit),
+            }
+        }
+        self.upstreams.finish(&self.upstream, self.outcome);
     }
 }
 
@@ -159,6 +373,35 @@ where
     pub url_path_getter: UrlPartGetter,
     /// Url query getter.
     pub url_query_getter: UrlPartGetter,
+    /// Header used to propagate the remaining deadline to upstream.
+    #[cfg(feature = "timeout")]
+    pub deadline_header: Option<HeaderName>,
+    /// Timeout for receiving the upstream response head.
+    pub response_timeout: Option<Duration>,
+    /// Times a failed idempotent request is retried.
+    pub retries: usize,
+    /// Maximum size of request bodies buffered for retries.
+    pub retry_body_limit: usize,
+    /// Circuit breaker for upstreams.
+    pub circuit_breaker: Option<CircuitBreaker>,
+    /// Whether to send the `Host` header of the client request to upstream.
+    pub preserve_host: bool,
+    /// Whether to add `X-Forwarded-For`, `X-Forwarded-Proto` and `X-Forwarded-Host` headers.
+    pub x_forwarded: bool,
+    /// Whether to add the `Forwarded` header.
+    pub forwarded: bool,
+    /// Pseudonym of this proxy added to the `Via` header.
+    pub via: Option<String>,
+    /// Hooks called before requests are sent to upstream.
+    pub request_hooks: Vec<Box<dyn RequestHook>>,
+    /// Hooks called after responses are received from upstream.
+    pub response_hooks: Vec<Box<dyn ResponseHook>>,
+    /// Whether to translate gRPC-Web requests to gRPC.
+    pub grpc_web: bool,
+    /// Size of the buffer reading upstream response bodies ahead of the client.
+    pub buffering: Option<usize>,
+    /// Shadow upstream requests are mirrored to.
+    pub mirror: Option<Mirror>,
 }
 
 impl<U, C> Proxy<U, C>
@@ -174,6 +417,21 @@ where
             client,
             url_path_getter: Box::new(default_url_path_getter),
             url_query_getter: Box::new(default_url_query_getter),
+            #[cfg(feature = "timeout")]
+            deadline_header: None,
+            response_timeout: None,
+            retries: 0,
+            retry_body_limit: 64 * 1024,
+            circuit_breaker: None,
+            preserve_host: false,
+            x_forwarded: false,
+            forwarded: false,
+            via: None,
+            request_hooks: vec![],
+            response_hooks: vec![],
+            grpc_web: false,
+            buffering: None,
+            mirror: None,
         }
     }
 
@@ -197,6 +455,145 @@ where
         self
     }
 
+    /// Propagate the remaining deadline set by [`salvo_extra::timeout::Timeout`] to upstream.
+    ///
+    /// If the header name is `grpc-timeout`, the value is written in gRPC timeout format, otherwise it is the
+    /// remaining milliseconds.
+    #[cfg(feature = "timeout")]
+    #[inline]
+    pub fn deadline_header(mut self, name: HeaderName) -> Self {
+        self.deadline_header = Some(name);
+        self
+    }
+
+    /// Set timeout for receiving the upstream response head, requests timed out are answered with
+    /// `504 Gateway Timeout`.
+    ///
+    /// With the `timeout` feature, the timeout is shortened to the remaining deadline of the request.
+    #[inline]
+    pub fn response_timeout(mut self, timeout: Duration) -> Self {
+        self.response_timeout = Some(timeout);
+        self
+    }
+
+    /// Set times a failed request is retried on another upstream, default is 0.
+    ///
+    /// Only requests with idempotent methods and bodies no larger than
+    /// [`retry_body_limit`](Self::retry_body_limit) are retried, when the upstream fails or times out.
+    #[inline]
+    pub fn retries(mut self, retries: usize) -> Self {
+        self.retries = retries;
+        self
+    }
+
+    /// Set maximum size of request bodies buffered for retries, default is 64 KiB.
+    ///
+    /// Requests with larger bodies, or bodies of unknown size, are streamed to the upstream and not retried.
+    #[inline]
+    pub fn retry_body_limit(mut self, limit: usize) -> Self {
+        self.retry_body_limit = limit;
+        self
+    }
+
+    /// Set circuit breaker for upstreams.
+    #[inline]
+    pub fn circuit_breaker(mut self, circuit_breaker: CircuitBreaker) -> Self {
+        self.circuit_breaker = Some(circuit_breaker);
+        self
+    }
+
+    /// Set whether to send the `Host` header of the client request to upstream, default is `false`.
+    ///
+    /// When `false`, `Host` is set to the authority of the upstream.
+    #[inline]
+    pub fn preserve_host(mut self, preserve_host: bool) -> Self {
+        self.preserve_host = preserve_host;
+        self
+    }
+
+    /// Set whether to add `X-Forwarded-For`, `X-Forwarded-Proto` and `X-Forwarded-Host` headers, default is
+    /// `false`.
+    ///
+    /// The client address is appended to an existing `X-Forwarded-For` header, so only enable this when
+    /// upstreams trust the headers sent by clients or another proxy in front sanitizes them.
+    #[inline]
+    pub fn x_forwarded(mut self, x_forwarded: bool) -> Self {
+        self.x_forwarded = x_forwarded;
+        self
+    }
+
+    /// Set whether to add the [RFC 7239](https://www.rfc-editor.org/rfc/rfc7239) `Forwarded` header, default
+    /// is `false`.
+    #[inline]
+    pub fn forwarded(mut self, forwarded: bool) -> Self {
+        self.forwarded = forwarded;
+        self
+    }
+
+    /// Set pseudonym of this proxy added to the `Via` header, no `Via` header is added by default.
+    #[inline]
+    pub fn via(mut self, pseudonym: impl Into<String>) -> Self {
+        self.via = Some(pseudonym.into());
+        self
+    }
+
+    /// Add hook called before requests are sent to upstream, hooks are called in the order they are added.
+    ///
+    /// The hook is called for every attempt when requests are retried.
+    #[inline]
+    pub fn before_request(mut self, hook: impl RequestHook) -> Self {
+        self.request_hooks.push(Box::new(hook));
+        self
+    }
+
+    /// Add hook called after responses are received from upstream, hooks are called in the order they are
+    /// added.
+    #[inline]
+    pub fn after_response(mut self, hook: impl ResponseHook) -> Self {
+        self.response_hooks.push(Box::new(hook));
+        self
+    }
+
+    /// Set whether to translate [gRPC-Web](https://github.com/grpc/grpc/blob/master/doc/PROTOCOL-WEB.md)
+    /// requests to gRPC, default is `false`.
+    ///
+    /// Requests with `application/grpc-web` or `application/grpc-web-text` content type are sent to upstream as
+    /// gRPC, and trailers of the gRPC responses are sent in the response body as gRPC-Web expects. The upstream
+    /// is usually connected with HTTP/2 only, see `HyperClientBuilder::http2_only`.
+    #[inline]
+    pub fn grpc_web(mut self, grpc_web: bool) -> Self {
+        self.grpc_web = grpc_web;
+        self
+    }
+
+    /// Read upstream response bodies ahead of the client into a buffer of at most `max_size` bytes, like the
+    /// `proxy_buffering` directive of nginx. Responses are streamed straight through by default.
+    ///
+    /// Responses no larger than the buffer are read completely, so the upstream connection is released without
+    /// waiting for slow clients.
+    #[inline]
+    pub fn buffering(mut self, max_size: usize) -> Self {
+        self.buffering = Some(max_size);
+        self
+    }
+
+    /// Mirror a percentage of requests to a shadow upstream.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use salvo_proxy::{HyperClient, Mirror, Proxy};
+    ///
+    /// let proxy = Proxy::use_hyper_client("https://www.example.com").mirror(
+    ///     Mirror::new("https://staging.example.com", HyperClient::default()).percentage(10.0),
+    /// );
+    /// ```
+    #[inline]
+    pub fn mirror(mut self, mirror: Mirror) -> Self {
+        self.mirror = Some(mirror);
+        self
+    }
+
     /// Get upstreams list.
     #[inline]
     pub fn upstreams(&self) -> &U {
@@ -219,16 +616,170 @@ where
         &mut self.client
     }
 
+    /// Buffers the request body if the request can be retried.
+    async fn replay_body(&self, req: &mut Request) -> Result<Option<Bytes>, Error> {
+        let idempotent = matches!(
+            *req.method(),
+            Method::GET
+                | Method::HEAD
+                | Method::OPTIONS
+                | Method::TRACE
+                | Method::PUT
+                | Method::DELETE
+        );
+        if self.retries == 0 || !idempotent || get_upgrade_type(req.headers()).is_some() {
+            return Ok(None);
+        }
+        match req.body().size_hint().upper() {
+            Some(size) if size <= self.retry_body_limit as u64 => {}
+            _ => return Ok(None),
+        }
+        let body = req
+            .payload_with_max_size(self.retry_body_limit)
+            .await
+            .map_err(Error::other)?;
+        Ok(Some(body.clone()))
+    }
+
+    /// Elects an upstream which is not tried yet and whose circuit allows requests.
+    async fn elect_upstream(
+        &self,
+        req: &Request,
+        depot: &Depot,
+        tried: &[String],
+    ) -> Result<Option<Elected<'_, U>>, Error> {
+        let attempts = self.upstreams.members().len().max(1);
+        let mut fallback: Option<Elected<'_, U>> = None;
+        for _ in 0..attempts {
+            let upstream = self
+                .upstreams
+                .elect(req, depot)
+                .await
+                .map_err(Error::other)?;
+            let mut elected = Elected::new(&self.upstreams, upstream);
+            if let Some(breaker) = &self.circuit_breaker {
+                let Some(permit) = breaker.acquire(&elected.upstream) else {
+                    elected.finish(Outcome::Skipped);
+                    continue;
+                };
+                elected.breaker = Some((breaker, permit));
+            }
+            if !tried.iter().any(|tried| **tried == *elected.upstream) {
+                if let Some(fallback) = fallback.take() {
+                    fallback.finish(Outcome::Skipped);
+                }
+                return Ok(Some(elected));
+            }
+            if fallback.is_none() {
+                // Retry on the same upstream if there is no other one.
+                fallback = Some(elected);
+            } else {
+                elected.finish(Outcome::Skipped);
+            }
+        }
+        Ok(fallback)
+    }
+
+    fn timeout_for(&self, _depot: &Depot) -> Option<Duration> {
+        #[cfg(feature = "timeout")]
+        {
+            use salvo_extra::timeout::TimeoutDepotExt;
+            if let Some(deadline) = _depot.deadline() {
+                let remaining = deadline.remaining();
+                return Some(
+                    self.response_timeout
+                        .map_or(remaining, |timeout| timeout.min(remaining)),
+                );
+            }
+        }
+        self.response_timeout
+    }
+
+    fn grpc_web_of(&self, req: &Request) -> Option<GrpcWeb> {
+        if self.grpc_web {
+            GrpcWeb::detect(req.headers())
+        } else {
+            None
+        }
+    }
+
+    /// Sends a copy of the request to the mirror, bodies of known size are read here and others are copied
+    /// while they are streamed to upstream.
+    ///
+    /// Mirroring failures are logged and never fail the proxied request.
+    async fn mirror_request(&self, req: &mut Request, depot: &Depot, replay: Option<&Bytes>) {
+        let Some(mirror) = &self.mirror else {
+            return;
+        };
+        if !mirror.sample() || get_upgrade_type(req.headers()).is_some() {
+            return;
+        }
+        let Some(permit) = mirror.reserve() else {
+            return;
+        };
+        let shadow = match self
+            .build_proxied_request(&mirror.upstream, ReqBody::None, req, depot)
+            .await
+        {
+            Ok(shadow) => shadow,
+            Err(e) => {
+                tracing::warn!(error = ?e, upstream = mirror.upstream, "build mirrored request failed");
+                return;
+            }
+        };
+        let body = match replay {
+            Some(body) if body.len() <= mirror.body_limit => {
+                mirror::ShadowBody::Ready(body.clone())
+            }
+            Some(_) => {
+                mirror.skip();
+                return;
+            }
+            None if req.body().is_end_stream() => mirror::ShadowBody::Ready(Bytes::new()),
+            None => match req.body().size_hint().upper() {
+                Some(size) if size <= mirror.body_limit as u64 => {
+                    match req.payload_with_max_size(mirror.body_limit).await {
+                        Ok(body) => {
+                            let body = body.clone();
+                            req.replace_body(ReqBody::Once(body.clone()));
+                            mirror::ShadowBody::Ready(body)
+                        }
+                        Err(e) => {
+                            // The body is lost, so the proxied request fails upstream like any other broken
+                            // request body.
+                            tracing::warn!(error = ?e, upstream = mirror.upstream, "read mirrored request body failed");
+                            mirror.skip();
+                            return;
+                        }
+                    }
+                }
+                Some(_) => {
+                    mirror.skip();
+                    return;
+                }
+                None => {
+                    let (body, shadow_body) = mirror::tee(req.take_body(), mirror.body_limit);
+                    req.replace_body(body);
+                    shadow_body
+                }
+            },
+        };
+        mirror.send(permit, shadow, body, self.grpc_web_of(req));
+    }
+
     async fn build_proxied_request(
         &self,
+        upstream: &str,
+        body: ReqBody,
         req: &mut Request,
         depot: &Depot,
     ) -> Result<HyperRequest, Error> {
-        let upstream = self.upstreams.elect().await.map_err(Error::other)?;
         if upstream.is_empty() {
             tracing::error!("upstreams is empty");
             return Err(Error::other("upstreams is empty"));
         }
+        let unix_upstream = encode_unix_upstream(upstream);
+        let upstream = unix_upstream.as_deref().unwrap_or(upstream);
 
         let path = encode_url_path(&(self.url_path_getter)(req, depot).unwrap_or_default());
         let query = (self.url_query_getter)(req, depot);
@@ -251,39 +802,63 @@ where
             format!("{}/{}", upstream, rest)
         };
         let forward_url: Uri = TryFrom::try_from(forward_url).map_err(Error::other)?;
+        let mut headers = req.headers().clone();
+        strip_hop_by_hop_headers(&mut headers);
+        if let Some(upgrade) =
+            get_upgrade_type(req.headers()).and_then(|upgrade| HeaderValue::from_str(upgrade).ok())
+        {
+            headers.insert(CONNECTION, HeaderValue::from_static("upgrade"));
+            headers.insert(UPGRADE, upgrade);
+        }
+        let host = if self.preserve_host {
+            forwarding::original_host(req)
+        } else if unix_upstream.is_some() {
+            Some(HeaderValue::from_static("localhost"))
+        } else {
+            forward_url
+                .authority()
+                .and_then(|authority| HeaderValue::from_str(authority.as_str()).ok())
+        };
+        match host {
+            Some(host) => headers.insert(HOST, host),
+            None => headers.remove(HOST),
+        };
+        if self.x_forwarded {
+            forwarding::add_x_forwarded(&mut headers, req);
+        }
+        if self.forwarded {
+            forwarding::add_forwarded(&mut headers, req);
+        }
+        if let Some(pseudonym) = &self.via {
+            forwarding::add_via(&mut headers, req.version(), pseudonym);
+        }
+        let body = match self.grpc_web_of(req) {
+            Some(grpc_web) => grpc_web.translate_request(&mut headers, body),
+            None => body,
+        };
         let mut build = hyper::Request::builder()
             .method(req.method())
             .uri(&forward_url);
-        for (key, value) in req.headers() {
-            if key != HOST {
-                build = build.header(key, value);
-            }
+        if let Some(build_headers) = build.headers_mut() {
+            *build_headers = headers;
         }
-        if let Some(host) = forward_url
-            .host()
-            .and_then(|host| HeaderValue::from_str(host).ok())
-        {
-            build = build.header(HeaderName::from_static("host"), host);
+        #[cfg(feature = "timeout")]
+        if let Some(name) = &self.deadline_header {
+            use salvo_extra::timeout::TimeoutDepotExt;
+            if let Some(deadline) = depot.deadline() {
+                let value = if name.as_str() == "grpc-timeout" {
+                    deadline.grpc_timeout()
+                } else {
+                    deadline.remaining_millis().to_string()
+                };
+                if let (Ok(value), Some(headers)) =
+                    (HeaderValue::from_str(&value), build.headers_mut())
+                {
+                    headers.insert(name.clone(), value);
+                }
+            }
         }
-        // let x_forwarded_for_header_name = "x-forwarded-for";
-        // // Add forwarding information in the headers
-        // match request.headers_mut().entry(x_forwarded_for_header_name) {
-        //     Ok(header_entry) => {
-        //         match header_entry {
-        //             hyper::header::Entry::Vacant(entry) => {
-        //                 let addr = format!("{}", client_ip);
-        //                 entry.insert(addr.parse().unwrap());
-        //             },
-        //             hyper::header::Entry::Occupied(mut entry) => {
-        //                 let addr = format!("{}, {}", entry.get().to_str().unwrap(), client_ip);
-        //                 entry.insert(addr.parse().unwrap());
-        //             }
-        //         }
-        //     }
-        //     // shouldn't happen...
-        //     Err(_) => panic!("Invalid header name: {}", x_forwarded_for_header_name),
-        // }
-        build.body(req.take_body()).map_err(Error::other)
+        build.body(body).map_err(Error::other)
     }
 }
 
@@ -301,46 +876,131 @@ where
         res: &mut Response,
         _ctrl: &mut FlowCtrl,
     ) {
-        match self.build_proxied_request(req, depot).await {
-            Ok(proxied_request) => {
-                match self
-                    .client
-                    .execute(proxied_request, req.extensions_mut().remove())
+        let replay = match self.replay_body(req).await {
+            Ok(replay) => replay,
+            Err(e) => {
+                tracing::error!(error = ?e, "read request body failed");
+                res.status_code(StatusCode::BAD_REQUEST);
+                return;
+            }
+        };
+        let attempts = if replay.is_some() {
+            self.retries + 1
+        } else {
+            1
+        };
+        self.mirror_request(req, depot, replay.as_ref()).await;
+        let mut upgraded = req.extensions_mut().remove::<OnUpgrade>();
+        let mut tried = Vec::new();
+        let mut failure = StatusCode::SERVICE_UNAVAILABLE;
+        for _ in 0..attempts {
+            let elected = match self.elect_upstream(req, depot, &tried).await {
+                Ok(Some(elected)) => elected,
+                Ok(None) => {
+                    tracing::warn!(uri = ?req.uri(), "no upstream is available");
+                    break;
+                }
+                Err(e) => {
+                    tracing::error!(error = ?e, "elect upstream failed");
+                    break;
+                }
+            };
+            let upstream = elected.upstream.clone();
+            let upstream = &*upstream;
+            let body = match &replay {
+                Some(body) => ReqBody::Once(body.clone()),
+                None => req.take_body(),
+            };
+            let mut proxied_request =
+                match self.build_proxied_request(upstream, body, req, depot).await {
+                    Ok(proxied_request) => proxied_request,
+                    Err(e) => {
+                        tracing::error!(error = ?e, "build proxied request failed");
+                        elected.finish(Outcome::Skipped);
+                        res.status_code(StatusCode::INTERNAL_SERVER_ERROR);
+                        return;
+                    }
+                };
+            for hook in &self.request_hooks {
+                if let Err(e) = hook
+                    .before_request(&mut proxied_request, upstream, depot)
                     .await
                 {
-                    Ok(response) => {
-                        let (
-                            salvo_core::http::response::Parts {
-                                status,
-                                // version,
-                                headers,
-                                // extensions,
-                                ..
-                            },
-                            body,
-                        ) = response.into_parts();
-                        res.status_code(status);
-                        for (name, value) in headers {
-                            if let Some(name) = name {
-                                res.headers.insert(name, value);
-                            }
+                    tracing::error!(error = ?e, upstream, "request hook failed");
+                    elected.finish(Outcome::Skipped);
+                    res.render(e);
+                    return;
+                }
+            }
+            let response = self.client.execute(proxied_request, upgraded.take());
+            let response = match self.timeout_for(depot) {
+                Some(timeout) => tokio::time::timeout(timeout, response).await.ok(),
+                None => Some(response.await),
+            };
+            match response {
+                Some(Ok(mut response)) => {
+                    let status = response.status();
+                    elected.finish(Outcome::Responded(status));
+                    for hook in &self.response_hooks {
+                        if let Err(e) = hook.after_response(&mut response, upstream, depot).await {
+                            tracing::error!(error = ?e, upstream, "response hook failed");
+                            res.render(e);
+                            return;
                         }
-                        res.body(body);
                     }
-                    Err(e) => {
-                        tracing::error!( error = ?e, uri = ?req.uri(), "get response data failed: {}", e);
-                        res.status_code(StatusCode::INTERNAL_SERVER_ERROR);
+                    let (
+                        salvo_core::http::response::Parts {
+                            status,
+                            // version,
+                            mut headers,
+                            // extensions,
+                            ..
+                        },
+                        body,
+                    ) = response.into_parts();
+                    let body = match self.grpc_web_of(req) {
+                        Some(grpc_web) => grpc_web.translate_response(&mut headers, body),
+                        None => body,
+                    };
+                    let body = match self.buffering {
+                        Some(max_size) => buffering::buffer(body, max_size),
+                        None => body,
+                    };
+                    res.status_code(status);
+                    if status != StatusCode::SWITCHING_PROTOCOLS {
+                        strip_hop_by_hop_headers(&mut headers);
+                    }
+                    // Consecutive values of the same header have no name, so they are appended to keep
+                    // multi-valued headers such as `Set-Cookie` intact.
+                    let mut last_name = None;
+                    for (name, value) in headers {
+                        if let Some(name) = name {
+                            res.headers.remove(&name);
+                            last_name = Some(name);
+                        }
+                        if let Some(name) = &last_name {
+                            res.headers.append(name.clone(), value);
+                        }
                     }
+                    res.body(body);
+                    return;
+                }
+                Some(Err(e)) => {
+                    tracing::error!(error = ?e, uri = ?req.uri(), upstream, "get response data failed: {}", e);
+                    failure = StatusCode::BAD_GATEWAY;
+                }
+                None => {
+                    tracing::error!(uri = ?req.uri(), upstream, "upstream response timed out");
+                    failure = StatusCode::GATEWAY_TIMEOUT;
                 }
             }
-            Err(e) => {
-                tracing::error!(error = ?e, "build proxied request failed");
-            }
+            elected.finish(Outcome::Failed);
+            tried.push(upstream.to_owned());
         }
+        res.status_code(failure);
     }
 }
 #[inline]
-#[allow(dead_code)]
 fn get_upgrade_type(headers: &HeaderMap) -> Option<&str> {
     if headers
         .get(&CONNECTION)
@@ -368,6 +1028,13 @@ fn get_upgrade_type(headers: &HeaderMap) -> Option<&str> {
 // Unit tests for Proxy
 #[cfg(test)]
 mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use base64::engine::general_purpose::STANDARD;
+    use base64::Engine;
+    use salvo_core::http::header::{HeaderName, CONTENT_TYPE, TE};
+    use salvo_core::test::{ResponseExt, TestClient};
+
     use super::*;
 
     #[test]
@@ -385,4 +1052,427 @@ mod tests {
         let upgrade_type = get_upgrade_type(&headers);
         assert_eq!(upgrade_type, Some("websocket"));
     }
+
+    struct FakeClient;
+    impl Client for FakeClient {
+        type Error = Error;
+
+        async fn execute(
+            &self,
+            req: HyperRequest,
+            _upgraded: Option<OnUpgrade>,
+        ) -> Result<HyperResponse, Self::Error> {
+            match req.uri().host() {
+                Some("down") => Err(Error::other("connection refused")),
+                Some("slow") => {
+                    tokio::time::sleep(Duration::from_secs(10)).await;
+                    Err(Error::other("too slow"))
+                }
+                Some("error") => Ok(hyper::Response::builder()
+                    .status(StatusCode::INTERNAL_SERVER_ERROR)
+                    .body(ResBody::None)
+                    .unwrap()),
+                Some("grpc") => {
+                    assert_eq!(req.headers()[CONTENT_TYPE], "application/grpc+proto");
+                    assert_eq!(req.headers()[TE], "trailers");
+                    let mut body = req.into_body();
+                    let (mut sender, res_body) = ResBody::channel();
+                    tokio::spawn(async move {
+                        while let Some(Ok(frame)) =
+                            std::future::poll_fn(|cx| std::pin::Pin::new(&mut body).poll_frame(cx))
+                                .await
+                        {
+                            if let Ok(data) = frame.into_data() {
+                                sender.send_data(data).await.unwrap();
+                            }
+                        }
+                        let mut trailers = HeaderMap::new();
+                        trailers.insert("grpc-status", HeaderValue::from_static("0"));
+                        sender.send_trailers(trailers).await.unwrap();
+                    });
+                    Ok(hyper::Response::builder()
+                        .header(CONTENT_TYPE, "application/grpc+proto")
+                        .body(res_body)
+                        .unwrap())
+                }
+                #[cfg(feature = "cache")]
+                Some("cacheable") => {
+                    static COUNT: std::sync::atomic::AtomicUsize =
+                        std::sync::atomic::AtomicUsize::new(0);
+                    let count = COUNT.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+                    Ok(hyper::Response::builder()
+                        .header("cache-control", "max-age=60")
+                        .body(ResBody::Once(Bytes::from(count.to_string())))
+                        .unwrap())
+                }
+                Some("headers") => {
+                    let mut response = hyper::Response::builder()
+                        .header(CONNECTION, "x-hop")
+                        .header("x-hop", "1")
+                        .header("set-cookie", "a=1")
+                        .header("set-cookie", "b=2")
+                        .body(ResBody::None)
+                        .unwrap();
+                    for (name, value) in req.headers() {
+                        let name = format!("x-echo-{name}");
+                        response.headers_mut().append(
+                            HeaderName::from_bytes(name.as_bytes()).unwrap(),
+                            value.clone(),
+                        );
+                    }
+                    Ok(response)
+                }
+                _ => Ok(hyper::Response::builder()
+                    .body(ResBody::Once(Bytes::from(req.uri().to_string())))
+                    .unwrap()),
+            }
+        }
+    }
+
+    fn proxy_service<U>(proxy: Proxy<U, FakeClient>) -> salvo_core::Service
+    where
+        U: Upstreams,
+        U::Error: Into<BoxedError>,
+    {
+        salvo_core::Service::new(salvo_core::Router::with_path("{**rest}").goal(proxy))
+    }
+
+    #[tokio::test]
+    async fn test_retries() {
+        let service = proxy_service(
+            Proxy::new(RoundRobin::new(["http://down", "http://up"]), FakeClient).retries(1),
+        );
+        for _ in 0..2 {
+            let mut res = TestClient::get("http://127.0.0.1:5800/hello")
+                .send(&service)
+                .await;
+            assert_eq!(res.status_code, Some(StatusCode::OK));
+            assert_eq!(res.take_string().await.unwrap(), "http://up/hello");
+        }
+
+        // Not idempotent requests are not retried.
+        let service = proxy_service(
+            Proxy::new(RoundRobin::new(["http://down", "http://up"]), FakeClient).retries(1),
+        );
+        let res = TestClient::post("http://127.0.0.1:5800/hello")
+            .text("hello")
+            .send(&service)
+            .await;
+        assert_eq!(res.status_code, Some(StatusCode::BAD_GATEWAY));
+
+        // Bodies larger than the limit are not retried.
+        let service = proxy_service(
+            Proxy::new(RoundRobin::new(["http://down", "http://up"]), FakeClient)
+                .retries(1)
+                .retry_body_limit(2),
+        );
+        let res = TestClient::put("http://127.0.0.1:5800/hello")
+            .text("hello")
+            .send(&service)
+            .await;
+        assert_eq!(res.status_code, Some(StatusCode::BAD_GATEWAY));
+    }
+
+    #[tokio::test]
+    async fn test_response_timeout() {
+        let service = proxy_service(
+            Proxy::new("http://slow", FakeClient).response_timeout(Duration::from_millis(50)),
+        );
+        let res = TestClient::get("http://127.0.0.1:5800/hello")
+            .send(&service)
+            .await;
+        assert_eq!(res.status_code, Some(StatusCode::GATEWAY_TIMEOUT));
+    }
+
+    #[tokio::test]
+    async fn test_cancelled() {
+        struct Recording(Arc<Mutex<Vec<Outcome>>>);
+        impl Upstreams for Recording {
+            type Error = Infallible;
+
+            async fn elect(&self, _req: &Request, _depot: &Depot) -> Result<Arc<str>, Self::Error> {
+                Ok("http://slow".into())
+            }
+
+            fn finish(&self, _upstream: &str, outcome: Outcome) {
+                self.0.lock().unwrap().push(outcome);
+            }
+        }
+
+        let outcomes = Arc::new(Mutex::new(Vec::new()));
+        let service = proxy_service(Proxy::new(Recording(outcomes.clone()), FakeClient));
+        let request = TestClient::get("http://127.0.0.1:5800/hello").send(&service);
+        assert!(tokio::time::timeout(Duration::from_millis(50), request)
+            .await
+            .is_err());
+        assert_eq!(*outcomes.lock().unwrap(), [Outcome::Cancelled]);
+    }
+
+    #[tokio::test]
+    async fn test_circuit_breaker() {
+        let breaker = CircuitBreaker::new().failure_threshold(2);
+        let service =
+            proxy_service(Proxy::new("http://error", FakeClient).circuit_breaker(breaker.clone()));
+        for _ in 0..2 {
+            let res = TestClient::get("http://127.0.0.1:5800/hello")
+                .send(&service)
+                .await;
+            assert_eq!(res.status_code, Some(StatusCode::INTERNAL_SERVER_ERROR));
+        }
+        assert_eq!(breaker.state("http://error"), CircuitState::Open);
+        let res = TestClient::get("http://127.0.0.1:5800/hello")
+            .send(&service)
+            .await;
+        assert_eq!(res.status_code, Some(StatusCode::SERVICE_UNAVAILABLE));
+
+        // The trial of a half open circuit is released when the request is dropped.
+        let breaker = CircuitBreaker::new()
+            .failure_threshold(1)
+            .open_duration(Duration::from_millis(50));
+        let service = proxy_service(
+            Proxy::new("http://slow", FakeClient)
+                .circuit_breaker(breaker.clone())
+                .response_timeout(Duration::from_millis(10)),
+        );
+        let res = TestClient::get("http://127.0.0.1:5800/hello")
+            .send(&service)
+            .await;
+        assert_eq!(res.status_code, Some(StatusCode::GATEWAY_TIMEOUT));
+        tokio::time::sleep(Duration::from_millis(60)).await;
+        let service =
+            proxy_service(Proxy::new("http://slow", FakeClient).circuit_breaker(breaker.clone()));
+        let request = TestClient::get("http://127.0.0.1:5800/hello").send(&service);
+        assert!(tokio::time::timeout(Duration::from_millis(50), request)
+            .await
+            .is_err());
+        assert!(breaker.acquire("http://slow").is_some());
+    }
+
+    #[tokio::test]
+    async fn test_forwarding_headers() {
+        let service = proxy_service(
+            Proxy::new("http://headers:8080", FakeClient)
+                .x_forwarded(true)
+                .via("salvo"),
+        );
+        let res = TestClient::get("http://127.0.0.1:5800/hello")
+            .add_header(CONNECTION, "keep-alive, x-private", true)
+            .add_header("x-private", "1", true)
+            .add_header("proxy-authorization", "Basic abc", true)
+            .add_header("x-forwarded-for", "10.0.0.1", true)
+            .add_header("x-kept", "1", true)
+            .send(&service)
+            .await;
+        let headers = &res.headers;
+        assert_eq!(headers["x-echo-host"], "headers:8080");
+        assert_eq!(headers["x-echo-x-kept"], "1");
+        assert!(!headers.contains_key("x-echo-connection"));
+        assert!(!headers.contains_key("x-echo-x-private"));
+        assert!(!headers.contains_key("x-echo-proxy-authorization"));
+        // The test client has no remote address to append.
+        assert_eq!(headers["x-echo-x-forwarded-for"], "10.0.0.1");
+        assert_eq!(headers["x-echo-x-forwarded-proto"], "http");
+        assert_eq!(headers["x-echo-x-forwarded-host"], "127.0.0.1:5800");
+        assert_eq!(headers["x-echo-via"], "1.1 salvo");
+        assert!(!headers.contains_key("x-echo-forwarded"));
+        assert!(!headers.contains_key(CONNECTION));
+        assert!(!headers.contains_key("x-hop"));
+        assert_eq!(headers.get_all("set-cookie").iter().count(), 2);
+
+        let service = proxy_service(
+            Proxy::new("http://headers:8080", FakeClient)
+                .preserve_host(true)
+                .x_forwarded(false)
+                .forwarded(true),
+        );
+        let res = TestClient::get("http://127.0.0.1:5800/hello")
+            .send(&service)
+            .await;
+        assert_eq!(res.headers["x-echo-host"], "127.0.0.1:5800");
+        assert!(!res.headers.contains_key("x-echo-x-forwarded-for"));
+        assert!(res.headers["x-echo-forwarded"]
+            .to_str()
+            .unwrap()
+            .ends_with(";host=\"127.0.0.1:5800\";proto=http"));
+    }
+
+    #[tokio::test]
+    async fn test_hooks() {
+        let service = proxy_service(
+            Proxy::new("http://headers", FakeClient)
+                .before_request(HeaderRewrite::new().insert("x-token", "secret"))
+                .before_request(
+                    |req: &mut HyperRequest, upstream: &str, _depot: &mut Depot| {
+                        let value = HeaderValue::from_str(upstream).map_err(Error::other)?;
+                        req.headers_mut().insert("x-upstream", value);
+                        Ok(())
+                    },
+                )
+                .after_response(HeaderRewrite::new().remove("set-cookie")),
+        );
+        let res = TestClient::get("http://127.0.0.1:5800/hello")
+            .send(&service)
+            .await;
+        assert_eq!(res.headers["x-echo-x-token"], "secret");
+        assert_eq!(res.headers["x-echo-x-upstream"], "http://headers");
+        assert!(!res.headers.contains_key("set-cookie"));
+
+        let service = proxy_service(Proxy::new("http://up", FakeClient).after_response(
+            ResponseBodyRewrite::new(|body: Bytes| Bytes::from(body.to_ascii_uppercase())),
+        ));
+        let mut res = TestClient::get("http://127.0.0.1:5800/hello")
+            .send(&service)
+            .await;
+        assert_eq!(res.take_string().await.unwrap(), "HTTP://UP/HELLO");
+
+        let service = proxy_service(Proxy::new("http://up", FakeClient).before_request(
+            |_req: &mut HyperRequest, _upstream: &str, _depot: &mut Depot| {
+                Err(salvo_core::http::StatusError::unauthorized().into())
+            },
+        ));
+        let res = TestClient::get("http://127.0.0.1:5800/hello")
+            .send(&service)
+            .await;
+        assert_eq!(res.status_code, Some(StatusCode::UNAUTHORIZED));
+    }
+
+    #[tokio::test]
+    async fn test_grpc_web() {
+        let service = proxy_service(Proxy::new("http://grpc", FakeClient).grpc_web(true));
+        let mut res = TestClient::post("http://127.0.0.1:5800/hello.Greeter/SayHello")
+            .add_header(CONTENT_TYPE, "application/grpc-web-text+proto", true)
+            .body(STANDARD.encode([0, 0, 0, 0, 1, 7]))
+            .send(&service)
+            .await;
+        assert_eq!(res.headers[CONTENT_TYPE], "application/grpc-web-text+proto");
+        let body = STANDARD.decode(res.take_string().await.unwrap()).unwrap();
+        assert_eq!(body[..6], [0, 0, 0, 0, 1, 7]);
+        assert_eq!(body[6..11], [0x80, 0, 0, 0, 15]);
+        assert_eq!(&body[11..], b"grpc-status:0\r\n");
+    }
+
+    #[tokio::test]
+    async fn test_buffering() {
+        let service = proxy_service(Proxy::new("http://up", FakeClient).buffering(4));
+        let mut res = TestClient::get("http://127.0.0.1:5800/hello")
+            .send(&service)
+            .await;
+        assert_eq!(res.take_string().await.unwrap(), "http://up/hello");
+    }
+
+    #[cfg(feature = "cache")]
+    #[tokio::test]
+    async fn test_cache() {
+        use salvo_cache::{Cache, MokaStore, RequestIssuer};
+
+        let proxy = Proxy::new("http://cacheable", FakeClient)
+            .cache(Cache::new(MokaStore::new(100), RequestIssuer::default()));
+        let service =
+            salvo_core::Service::new(salvo_core::Router::with_path("{**rest}").goal(proxy));
+        let mut bodies = vec![];
+        for _ in 0..2 {
+            let mut res = TestClient::get("http://127.0.0.1:5800/hello")
+                .send(&service)
+                .await;
+            bodies.push(res.take_string().await.unwrap());
+        }
+        assert_eq!(bodies[0], bodies[1]);
+        let mut res = TestClient::post("http://127.0.0.1:5800/hello")
+            .send(&service)
+            .await;
+        assert_ne!(res.take_string().await.unwrap(), bodies[0]);
+    }
+
+    #[derive(Clone, Default)]
+    #[allow(clippy::type_complexity)]
+    struct RecordingClient(Arc<Mutex<Vec<(String, Vec<u8>)>>>);
+    impl Client for RecordingClient {
+        type Error = Error;
+
+        async fn execute(
+            &self,
+            req: HyperRequest,
+            _upgraded: Option<OnUpgrade>,
+        ) -> Result<HyperResponse, Self::Error> {
+            let uri = req.uri().to_string();
+            let mut body = req.into_body();
+            let mut data = Vec::new();
+            while let Some(frame) =
+                std::future::poll_fn(|cx| std::pin::Pin::new(&mut body).poll_frame(cx)).await
+            {
+                if let Ok(chunk) = frame?.into_data() {
+                    data.extend_from_slice(&chunk);
+                }
+            }
+            self.0.lock().unwrap().push((uri, data));
+            Ok(hyper::Response::new(ResBody::None))
+        }
+    }
+
+    async fn mirrored(mirror: &Mirror) -> MirrorMetrics {
+        for _ in 0..100 {
+            let metrics = mirror.metrics();
+            if metrics.responded + metrics.failed + metrics.skipped > 0 {
+                return metrics;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        mirror.metrics()
+    }
+
+    #[tokio::test]
+    async fn test_mirror() {
+        let shadow = RecordingClient::default();
+        let mirror = Mirror::new("http://shadow", shadow.clone());
+        let service = proxy_service(Proxy::new("http://up", FakeClient).mirror(mirror.clone()));
+        let mut res = TestClient::post("http://127.0.0.1:5800/hello")
+            .text("hello")
+            .send(&service)
+            .await;
+        assert_eq!(res.take_string().await.unwrap(), "http://up/hello");
+        assert_eq!(mirrored(&mirror).await.responded, 1);
+        assert_eq!(
+            shadow.0.lock().unwrap().as_slice(),
+            [("http://shadow/hello".to_owned(), b"hello".to_vec())]
+        );
+
+        // Shadow failures do not affect the response.
+        let mirror = Mirror::new("http://down", FakeClient);
+        let service = proxy_service(Proxy::new("http://up", FakeClient).mirror(mirror.clone()));
+        let res = TestClient::get("http://127.0.0.1:5800/hello")
+            .send(&service)
+            .await;
+        assert_eq!(res.status_code, Some(StatusCode::OK));
+        assert_eq!(mirrored(&mirror).await.failed, 1);
+
+        // Bodies larger than the limit are not mirrored.
+        let shadow = RecordingClient::default();
+        let mirror = Mirror::new("http://shadow", shadow.clone()).body_limit(2);
+        let service = proxy_service(Proxy::new("http://up", FakeClient).mirror(mirror.clone()));
+        let res = TestClient::post("http://127.0.0.1:5800/hello")
+            .text("hello")
+            .send(&service)
+            .await;
+        assert_eq!(res.status_code, Some(StatusCode::OK));
+        assert_eq!(mirrored(&mirror).await.skipped, 1);
+        assert!(shadow.0.lock().unwrap().is_empty());
+
+        let mirror = Mirror::new("http://shadow", RecordingClient::default()).percentage(0.0);
+        let service = proxy_service(Proxy::new("http://up", FakeClient).mirror(mirror.clone()));
+        TestClient::get("http://127.0.0.1:5800/hello")
+            .send(&service)
+            .await;
+        assert_eq!(mirror.metrics(), MirrorMetrics::default());
+
+        // Requests are not mirrored while too many shadow requests are in flight.
+        let mirror = Mirror::new("http://slow", FakeClient).max_in_flight(1);
+        let service = proxy_service(Proxy::new("http://up", FakeClient).mirror(mirror.clone()));
+        for _ in 0..2 {
+            let res = TestClient::get("http://127.0.0.1:5800/hello")
+                .send(&service)
+                .await;
+            assert_eq!(res.status_code, Some(StatusCode::OK));
+        }
+        assert_eq!(mirror.metrics().dropped, 1);
+    }
 }
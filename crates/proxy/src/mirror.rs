@@ -0,0 +1,329 @@
+//! Mirroring of proxied requests to a shadow upstream.
+
+use std::fmt::{self, Debug, Formatter};
+use std::pin::Pin;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
+
+use futures_util::future::BoxFuture;
+use salvo_core::http::ReqBody;
+use salvo_core::hyper::body::{Body, Bytes, Frame, SizeHint};
+use salvo_core::BoxedError;
+use tokio::sync::{oneshot, OwnedSemaphorePermit, Semaphore};
+
+use crate::grpc_web::GrpcWeb;
+use crate::{Client, HyperRequest, HyperResponse};
+
+type SendFn = Arc<
+    dyn Fn(HyperRequest) -> BoxFuture<'static, Result<HyperResponse, BoxedError>> + Send + Sync,
+>;
+
+#[derive(Debug, Default)]
+struct Counters {
+    sent: AtomicU64,
+    responded: AtomicU64,
+    failed: AtomicU64,
+    skipped: AtomicU64,
+    dropped: AtomicU64,
+    latency_micros: AtomicU64,
+}
+
+/// Metrics of the requests mirrored by a [`Mirror`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct MirrorMetrics {
+    /// Requests sent to the shadow upstream.
+    pub sent: u64,
+    /// Requests answered by the shadow upstream, whatever the status.
+    pub responded: u64,
+    /// Requests failed or timed out.
+    pub failed: u64,
+    /// Sampled requests not mirrored, because their body is larger than the limit or is not read completely
+    /// by the proxied request.
+    pub skipped: u64,
+    /// Sampled requests not mirrored, because too many shadow requests are in flight.
+    pub dropped: u64,
+    /// Total latency of the sent requests.
+    pub total_latency: Duration,
+}
+
+/// Duplicates a percentage of proxied requests to a shadow upstream, set with [`Proxy::mirror`].
+///
+/// Shadow requests are sent in the background with the head of the proxied request and a copy of its body,
+/// and their responses are discarded. Shadow failures never affect the response of the proxied request.
+///
+/// Request hooks are not called for shadow requests. Clones share the same metrics and in flight limit.
+///
+/// [`Proxy::mirror`]: crate::Proxy::mirror
+#[derive(Clone)]
+pub struct Mirror {
+    pub(crate) upstream: String,
+    percentage: f64,
+    pub(crate) body_limit: usize,
+    timeout: Duration,
+    max_in_flight: usize,
+    in_flight: Arc<Semaphore>,
+    send: SendFn,
+    counters: Arc<Counters>,
+}
+
+impl Debug for Mirror {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Mirror")
+            .field("upstream", &self.upstream)
+            .field("percentage", &self.percentage)
+            .field("body_limit", &self.body_limit)
+            .field("timeout", &self.timeout)
+            .field("max_in_flight", &self.max_in_flight)
+            .finish()
+    }
+}
+
+impl Mirror {
+    /// Create new `Mirror` sending all requests to `upstream` with `client`.
+    ///
+    /// Bodies up to 64 KiB are copied, shadow requests time out after 30 seconds and at most 256 of them are
+    /// in flight.
+    pub fn new<C: Client>(upstream: impl Into<String>, client: C) -> Self {
+        let client = Arc::new(client);
+        Self {
+            upstream: upstream.into(),
+            percentage: 100.0,
+            body_limit: 64 * 1024,
+            timeout: Duration::from_secs(30),
+            max_in_flight: 256,
+            in_flight: Arc::new(Semaphore::new(256)),
+            send: Arc::new(move |req| {
+                let client = client.clone();
+                Box::pin(async move { client.execute(req, None).await.map_err(Into::into) })
+            }),
+            counters: Default::default(),
+        }
+    }
+
+    /// Set percentage of requests mirrored, from 0 to 100.
+    pub fn percentage(mut self, percentage: f64) -> Self {
+        self.percentage = percentage.clamp(0.0, 100.0);
+        self
+    }
+
+    /// Set maximum size of copied bodies, requests with larger bodies are not mirrored.
+    pub fn body_limit(mut self, limit: usize) -> Self {
+        self.body_limit = limit;
+        self
+    }
+
+    /// Set timeout of shadow requests.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Set maximum number of shadow requests in flight, sampled requests are not mirrored when it is reached.
+    pub fn max_in_flight(mut self, max: usize) -> Self {
+        let max = max.min(Semaphore::MAX_PERMITS);
+        self.max_in_flight = max;
+        self.in_flight = Arc::new(Semaphore::new(max));
+        self
+    }
+
+    /// Returns metrics of the mirrored requests.
+    pub fn metrics(&self) -> MirrorMetrics {
+        let counters = &self.counters;
+        MirrorMetrics {
+            sent: counters.sent.load(Ordering::Relaxed),
+            responded: counters.responded.load(Ordering::Relaxed),
+            failed: counters.failed.load(Ordering::Relaxed),
+            skipped: counters.skipped.load(Ordering::Relaxed),
+            dropped: counters.dropped.load(Ordering::Relaxed),
+            total_latency: Duration::from_micros(counters.latency_micros.load(Ordering::Relaxed)),
+        }
+    }
+
+    /// Returns whether the current request is mirrored.
+    pub(crate) fn sample(&self) -> bool {
+        self.percentage >= 100.0 || fastrand::f64() * 100.0 < self.percentage
+    }
+
+    /// Reserves a slot for a shadow request, returns `None` if too many of them are in flight.
+    pub(crate) fn reserve(&self) -> Option<OwnedSemaphorePermit> {
+        let permit = self.in_flight.clone().try_acquire_owned().ok();
+        if permit.is_none() {
+            self.counters.dropped.fetch_add(1, Ordering::Relaxed);
+            tracing::debug!(
+                upstream = self.upstream,
+                "too many mirrored requests in flight"
+            );
+        }
+        permit
+    }
+
+    /// Records a sampled request which is not mirrored.
+    pub(crate) fn skip(&self) {
+        self.counters.skipped.fetch_add(1, Ordering::Relaxed);
+        tracing::debug!(upstream = self.upstream, "request body not mirrored");
+    }
+
+    /// Sends `req` to the shadow upstream in the background once its body is available, `permit` is released
+    /// when it is done.
+    pub(crate) fn send(
+        &self,
+        permit: OwnedSemaphorePermit,
+        mut req: HyperRequest,
+        body: ShadowBody,
+        grpc_web: Option<GrpcWeb>,
+    ) {
+        let mirror = self.clone();
+        tokio::spawn(async move {
+            let _permit = permit;
+            let body = match body {
+                ShadowBody::Ready(body) => Some(body),
+                ShadowBody::Pending(rx) => rx.await.ok(),
+            };
+            let Some(body) = body else {
+                mirror.skip();
+                return;
+            };
+            let body = ReqBody::Once(body);
+            *req.body_mut() = match grpc_web {
+                Some(grpc_web) => grpc_web.translate_request(req.headers_mut(), body),
+                None => body,
+            };
+            mirror.counters.sent.fetch_add(1, Ordering::Relaxed);
+            let started = Instant::now();
+            let result = tokio::time::timeout(mirror.timeout, (mirror.send)(req)).await;
+            let latency = started.elapsed();
+            mirror
+                .counters
+                .latency_micros
+                .fetch_add(latency.as_micros() as u64, Ordering::Relaxed);
+            match result {
+                Ok(Ok(res)) => {
+                    mirror.counters.responded.fetch_add(1, Ordering::Relaxed);
+                    tracing::debug!(upstream = mirror.upstream, status = %res.status(), ?latency, "mirrored request responded");
+                }
+                Ok(Err(e)) => {
+                    mirror.counters.failed.fetch_add(1, Ordering::Relaxed);
+                    tracing::warn!(upstream = mirror.upstream, error = ?e, ?latency, "mirrored request failed");
+                }
+                Err(_) => {
+                    mirror.counters.failed.fetch_add(1, Ordering::Relaxed);
+                    tracing::warn!(
+                        upstream = mirror.upstream,
+                        ?latency,
+                        "mirrored request timed out"
+                    );
+                }
+            }
+        });
+    }
+}
+
+/// Body of a shadow request.
+pub(crate) enum ShadowBody {
+    /// The body is already buffered.
+    Ready(Bytes),
+    /// The body is copied while the proxied request streams it, the sender is dropped if it is too large.
+    Pending(oneshot::Receiver<Bytes>),
+}
+
+/// Wraps `body` to copy at most `limit` bytes of it for a shadow request.
+pub(crate) fn tee(body: ReqBody, limit: usize) -> (ReqBody, ShadowBody) {
+    let (tx, rx) = oneshot::channel();
+    let body = ReqBody::Boxed {
+        inner: Box::pin(TeeBody {
+            inner: body,
+            copy: Vec::new(),
+            limit,
+            tx: Some(tx),
+        }),
+        fusewire: None,
+    };
+    (body, ShadowBody::Pending(rx))
+}
+
+struct TeeBody {
+    inner: ReqBody,
+    copy: Vec<u8>,
+    limit: usize,
+    tx: Option<oneshot::Sender<Bytes>>,
+}
+
+impl TeeBody {
+    fn finish(&mut self) {
+        if let Some(tx) = self.tx.take() {
+            let _ = tx.send(std::mem::take(&mut self.copy).into());
+        }
+    }
+}
+
+impl Body for TeeBody {
+    type Data = Bytes;
+    type Error = BoxedError;
+
+    fn poll_frame(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Frame<Self::Data>, Self::Error>>> {
+        let this = &mut *self;
+        let poll = Pin::new(&mut this.inner).poll_frame(cx);
+        match &poll {
+            Poll::Ready(Some(Ok(frame))) => {
+                if let Some(data) = frame.data_ref() {
+                    if this.copy.len() + data.len() <= this.limit {
+                        this.copy.extend_from_slice(data);
+                    } else {
+                        // Dropping the sender tells the shadow request to give up.
+                        this.tx = None;
+                    }
+                }
+                if this.inner.is_end_stream() {
+                    this.finish();
+                }
+            }
+            Poll::Ready(None) => this.finish(),
+            Poll::Ready(Some(Err(_))) => this.tx = None,
+            Poll::Pending => {}
+        }
+        poll.map_err(Into::into)
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.inner.is_end_stream()
+    }
+
+    fn size_hint(&self) -> SizeHint {
+        self.inner.size_hint()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn drain(mut body: ReqBody) -> Vec<u8> {
+        let mut data = Vec::new();
+        while let Some(frame) = std::future::poll_fn(|cx| Pin::new(&mut body).poll_frame(cx)).await
+        {
+            data.extend_from_slice(&frame.unwrap().into_data().unwrap());
+        }
+        data
+    }
+
+    #[tokio::test]
+    async fn test_tee() {
+        let (body, ShadowBody::Pending(rx)) = tee(ReqBody::Once("hello".into()), 8) else {
+            panic!("body should be pending");
+        };
+        assert_eq!(drain(body).await, b"hello");
+        assert_eq!(rx.await.unwrap(), "hello");
+
+        let (body, ShadowBody::Pending(rx)) = tee(ReqBody::Once("hello".into()), 2) else {
+            panic!("body should be pending");
+        };
+        assert_eq!(drain(body).await, b"hello");
+        assert!(rx.await.is_err());
+    }
+}
@@ -108,8 +108,12 @@ mod tests {
     async fn test_upstreams_elect() {
         let upstreams = vec!["https://www.example.com", "https://www.example2.com"];
         let proxy = Proxy::new(upstreams.clone(), ReqwestClient::default());
-        let elected_upstream = proxy.upstreams().elect().await.unwrap();
-        assert!(upstreams.contains(&elected_upstream));
+        let elected_upstream = proxy
+            .upstreams()
+            .elect(&Request::new(), &Depot::new())
+            .await
+            .unwrap();
+        assert!(upstreams.contains(&&*elected_upstream));
     }
 
     #[tokio::test]
@@ -0,0 +1,531 @@
+//! Hooks to rewrite proxied requests and responses.
+
+use std::fmt::Debug;
+
+use futures_util::TryStreamExt;
+use salvo_core::http::header::{
+    HeaderMap, HeaderName, HeaderValue, CONTENT_ENCODING, CONTENT_LENGTH, CONTENT_LOCATION,
+    CONTENT_TYPE, LOCATION, SET_COOKIE,
+};
+use salvo_core::http::uri::Uri;
+use salvo_core::http::ResBody;
+use salvo_core::hyper::body::{Body, Bytes};
+use salvo_core::{async_trait, Depot, Error};
+
+use crate::{HyperRequest, HyperResponse};
+
+/// Hook called with the request before it is sent to the upstream.
+///
+/// Returning an error aborts the request, and the error is rendered to the client.
+///
+/// Sync closures with the same arguments implement this trait.
+#[async_trait]
+pub trait RequestHook: Send + Sync + 'static {
+    /// Rewrite the request sent to `upstream`.
+    async fn before_request(
+        &self,
+        req: &mut HyperRequest,
+        upstream: &str,
+        depot: &mut Depot,
+    ) -> Result<(), Error>;
+}
+
+#[async_trait]
+impl<F> RequestHook for F
+where
+    F: Fn(&mut HyperRequest, &str, &mut Depot) -> Result<(), Error> + Send + Sync + 'static,
+{
+    async fn before_request(
+        &self,
+        req: &mut HyperRequest,
+        upstream: &str,
+        depot: &mut Depot,
+    ) -> Result<(), Error> {
+        self(req, upstream, depot)
+    }
+}
+
+/// Hook called with the response received from the upstream before it is written to the client.
+///
+/// Returning an error drops the response, and the error is rendered to the client.
+///
+/// Sync closures with the same arguments implement this trait.
+#[async_trait]
+pub trait ResponseHook: Send + Sync + 'static {
+    /// Rewrite the response received from `upstream`.
+    async fn after_response(
+        &self,
+        res: &mut HyperResponse,
+        upstream: &str,
+        depot: &mut Depot,
+    ) -> Result<(), Error>;
+}
+
+#[async_trait]
+impl<F> ResponseHook for F
+where
+    F: Fn(&mut HyperResponse, &str, &mut Depot) -> Result<(), Error> + Send + Sync + 'static,
+{
+    async fn after_response(
+        &self,
+        res: &mut HyperResponse,
+        upstream: &str,
+        depot: &mut Depot,
+    ) -> Result<(), Error> {
+        self(res, upstream, depot)
+    }
+}
+
+#[derive(Clone, Debug)]
+enum HeaderOp {
+    Insert(HeaderName, HeaderValue),
+    Append(HeaderName, HeaderValue),
+    Remove(HeaderName),
+}
+
+/// Inserts, appends or removes headers, in the order the operations are added.
+///
+/// It can be used as both [`RequestHook`] and [`ResponseHook`].
+///
+/// # Example
+///
+/// ```
+/// use salvo_core::http::header::AUTHORIZATION;
+/// use salvo_proxy::{HeaderRewrite, Proxy};
+///
+/// let proxy = Proxy::use_hyper_client("http://127.0.0.1:8080")
+///     .before_request(
+///         HeaderRewrite::new()
+///             .insert(AUTHORIZATION, "Bearer token")
+///             .remove("cookie"),
+///     )
+///     .after_response(HeaderRewrite::new().remove("server"));
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct HeaderRewrite {
+    ops: Vec<HeaderOp>,
+}
+impl HeaderRewrite {
+    /// Create new `HeaderRewrite`.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Insert header, replacing existing values.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the name or the value is invalid.
+    pub fn insert<N, V>(mut self, name: N, value: V) -> Self
+    where
+        N: TryInto<HeaderName>,
+        N::Error: Debug,
+        V: TryInto<HeaderValue>,
+        V::Error: Debug,
+    {
+        self.ops.push(HeaderOp::Insert(
+            name.try_into().expect("invalid header name"),
+            value.try_into().expect("invalid header value"),
+        ));
+        self
+    }
+
+    /// Append header value.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the name or the value is invalid.
+    pub fn append<N, V>(mut self, name: N, value: V) -> Self
+    where
+        N: TryInto<HeaderName>,
+        N::Error: Debug,
+        V: TryInto<HeaderValue>,
+        V::Error: Debug,
+    {
+        self.ops.push(HeaderOp::Append(
+            name.try_into().expect("invalid header name"),
+            value.try_into().expect("invalid header value"),
+        ));
+        self
+    }
+
+    /// Remove all values of header.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the name is invalid.
+    pub fn remove<N>(mut self, name: N) -> Self
+    where
+        N: TryInto<HeaderName>,
+        N::Error: Debug,
+    {
+        self.ops.push(HeaderOp::Remove(
+            name.try_into().expect("invalid header name"),
+        ));
+        self
+    }
+
+    fn apply(&self, headers: &mut HeaderMap) {
+        for op in &self.ops {
+            match op {
+                HeaderOp::Insert(name, value) => {
+                    headers.insert(name.clone(), value.clone());
+                }
+                HeaderOp::Append(name, value) => {
+                    headers.append(name.clone(), value.clone());
+                }
+                HeaderOp::Remove(name) => {
+                    headers.remove(name);
+                }
+            }
+        }
+    }
+}
+#[async_trait]
+impl RequestHook for HeaderRewrite {
+    async fn before_request(
+        &self,
+        req: &mut HyperRequest,
+        _upstream: &str,
+        _depot: &mut Depot,
+    ) -> Result<(), Error> {
+        self.apply(req.headers_mut());
+        Ok(())
+    }
+}
+#[async_trait]
+impl ResponseHook for HeaderRewrite {
+    async fn after_response(
+        &self,
+        res: &mut HyperResponse,
+        _upstream: &str,
+        _depot: &mut Depot,
+    ) -> Result<(), Error> {
+        self.apply(res.headers_mut());
+        Ok(())
+    }
+}
+
+/// Rewrites `Location` and `Content-Location` response headers pointing to the upstream, so that redirects
+/// go through the proxy.
+///
+/// Absolute URLs starting with the upstream, and paths starting with the upstream path, have this prefix
+/// replaced with the one given to [`LocationRewrite::new`]. Other locations are left unchanged.
+///
+/// # Example
+///
+/// ```
+/// use salvo_proxy::{LocationRewrite, Proxy};
+///
+/// // `http://127.0.0.1:8080/login` is rewritten to `/api/login`.
+/// let proxy = Proxy::use_hyper_client("http://127.0.0.1:8080")
+///     .after_response(LocationRewrite::new("/api"));
+/// ```
+#[derive(Clone, Debug)]
+pub struct LocationRewrite {
+    prefix: String,
+}
+impl LocationRewrite {
+    /// Create new `LocationRewrite` replacing the upstream with `prefix`, which is usually the path the
+    /// proxy is mounted at.
+    pub fn new(prefix: impl Into<String>) -> Self {
+        Self {
+            prefix: prefix.into().trim_end_matches('/').to_owned(),
+        }
+    }
+
+    fn rewrite(&self, location: &str, upstream: &str) -> Option<String> {
+        let upstream = upstream.trim_end_matches('/');
+        let rest = if location.starts_with('/') && !location.starts_with("//") {
+            let uri = upstream.parse::<Uri>().ok()?;
+            location.strip_prefix(uri.path().trim_end_matches('/'))?
+        } else {
+            let prefix = location.get(..upstream.len())?;
+            if !prefix.eq_ignore_ascii_case(upstream) {
+                return None;
+            }
+            &location[upstream.len()..]
+        };
+        if !(rest.is_empty() || rest.starts_with(['/', '?', '#'])) {
+            return None;
+        }
+        let location = format!("{}{}", self.prefix, rest);
+        if location.starts_with('/') {
+            Some(location)
+        } else {
+            Some(format!("/{location}"))
+        }
+    }
+}
+#[async_trait]
+impl ResponseHook for LocationRewrite {
+    async fn after_response(
+        &self,
+        res: &mut HyperResponse,
+        upstream: &str,
+        _depot: &mut Depot,
+    ) -> Result<(), Error> {
+        for name in [LOCATION, CONTENT_LOCATION] {
+            let location = res
+                .headers()
+                .get(&name)
+                .and_then(|value| value.to_str().ok())
+                .and_then(|location| self.rewrite(location, upstream))
+                .and_then(|location| HeaderValue::from_str(&location).ok());
+            if let Some(location) = location {
+                res.headers_mut().insert(name, location);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Rewrites the `Domain` and `Path` attributes of `Set-Cookie` response headers.
+///
+/// # Example
+///
+/// ```
+/// use salvo_proxy::{CookieRewrite, Proxy};
+///
+/// let proxy = Proxy::use_hyper_client("http://127.0.0.1:8080").after_response(
+///     CookieRewrite::new()
+///         .domain("internal.example.com", "")
+///         .path("/", "/api/"),
+/// );
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct CookieRewrite {
+    domains: Vec<(String, String)>,
+    paths: Vec<(String, String)>,
+}
+impl CookieRewrite {
+    /// Create new `CookieRewrite`.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Replace `Domain` attribute equal to `from` with `to`, the attribute is removed if `to` is empty.
+    pub fn domain(mut self, from: impl Into<String>, to: impl Into<String>) -> Self {
+        self.domains.push((from.into(), to.into()));
+        self
+    }
+
+    /// Replace `from` prefix of `Path` attribute with `to`.
+    pub fn path(mut self, from: impl Into<String>, to: impl Into<String>) -> Self {
+        self.paths.push((from.into(), to.into()));
+        self
+    }
+
+    fn rewrite(&self, cookie: &str) -> String {
+        let mut parts = cookie.split(';');
+        let mut rewritten = parts.next().unwrap_or_default().to_owned();
+        for attribute in parts {
+            let (name, value) = attribute.split_once('=').unwrap_or((attribute, ""));
+            let (name, value) = (name.trim(), value.trim());
+            if name.eq_ignore_ascii_case("domain") {
+                let current = value.trim_start_matches('.');
+                if let Some((_, to)) = self
+                    .domains
+                    .iter()
+                    .find(|(from, _)| from.trim_start_matches('.').eq_ignore_ascii_case(current))
+                {
+                    if !to.is_empty() {
+                        rewritten.push_str(&format!("; {name}={to}"));
+                    }
+                    continue;
+                }
+            } else if name.eq_ignore_ascii_case("path") {
+                if let Some((from, to)) = self
+                    .paths
+                    .iter()
+                    .find(|(from, _)| value.starts_with(from.as_str()))
+                {
+                    rewritten.push_str(&format!("; {name}={to}{}", &value[from.len()..]));
+                    continue;
+                }
+            }
+            rewritten.push(';');
+            rewritten.push_str(attribute);
+        }
+        rewritten
+    }
+}
+#[async_trait]
+impl ResponseHook for CookieRewrite {
+    async fn after_response(
+        &self,
+        res: &mut HyperResponse,
+        _upstream: &str,
+        _depot: &mut Depot,
+    ) -> Result<(), Error> {
+        let cookies =
+            res.headers()
+                .get_all(SET_COOKIE)
+                .iter()
+                .map(|value| match value.to_str() {
+                    Ok(cookie) => HeaderValue::from_str(&self.rewrite(cookie))
+                        .unwrap_or_else(|_| value.clone()),
+                    Err(_) => value.clone(),
+                })
+                .collect::<Vec<_>>();
+        res.headers_mut().remove(SET_COOKIE);
+        for cookie in cookies {
+            res.headers_mut().append(SET_COOKIE, cookie);
+        }
+        Ok(())
+    }
+}
+
+/// Transforms response bodies.
+///
+/// The body is buffered, so only responses with a known size up to
+/// [`max_size`](ResponseBodyRewrite::max_size) are transformed. Encoded responses, such as compressed ones,
+/// are left unchanged.
+///
+/// # Example
+///
+/// ```
+/// use salvo_core::hyper::body::Bytes;
+/// use salvo_proxy::{Proxy, ResponseBodyRewrite};
+///
+/// let proxy = Proxy::use_hyper_client("http://127.0.0.1:8080").after_response(
+///     ResponseBodyRewrite::new(|body: Bytes| {
+///         let body = String::from_utf8_lossy(&body).replace("http://127.0.0.1:8080", "");
+///         Bytes::from(body)
+///     })
+///     .content_type("text/html"),
+/// );
+/// ```
+pub struct ResponseBodyRewrite<F> {
+    transform: F,
+    content_types: Vec<String>,
+    max_size: u64,
+}
+impl<F> ResponseBodyRewrite<F>
+where
+    F: Fn(Bytes) -> Bytes + Send + Sync + 'static,
+{
+    /// Create new `ResponseBodyRewrite`, which transforms bodies up to 1 MiB.
+    pub fn new(transform: F) -> Self {
+        Self {
+            transform,
+            content_types: vec![],
+            max_size: 1024 * 1024,
+        }
+    }
+
+    /// Only transform responses whose content type starts with `content_type`, can be called multiple
+    /// times. All responses are transformed by default.
+    pub fn content_type(mut self, content_type: impl Into<String>) -> Self {
+        self.content_types.push(content_type.into());
+        self
+    }
+
+    /// Set maximum size of transformed bodies.
+    pub fn max_size(mut self, max_size: u64) -> Self {
+        self.max_size = max_size;
+        self
+    }
+}
+#[async_trait]
+impl<F> ResponseHook for ResponseBodyRewrite<F>
+where
+    F: Fn(Bytes) -> Bytes + Send + Sync + 'static,
+{
+    async fn after_response(
+        &self,
+        res: &mut HyperResponse,
+        _upstream: &str,
+        _depot: &mut Depot,
+    ) -> Result<(), Error> {
+        if res.headers().contains_key(CONTENT_ENCODING)
+            || !matches!(res.body().size_hint().upper(), Some(size) if size <= self.max_size)
+        {
+            return Ok(());
+        }
+        if !self.content_types.is_empty() {
+            let content_type = res
+                .headers()
+                .get(CONTENT_TYPE)
+                .and_then(|value| value.to_str().ok())
+                .unwrap_or_default();
+            if !self
+                .content_types
+                .iter()
+                .any(|prefix| content_type.starts_with(prefix.as_str()))
+            {
+                return Ok(());
+            }
+        }
+        let body = res
+            .body_mut()
+            .take()
+            .try_fold(Vec::new(), |mut body, frame| async move {
+                if let Ok(data) = frame.into_data() {
+                    body.extend_from_slice(&data);
+                }
+                Ok(body)
+            })
+            .await?;
+        let body = (self.transform)(Bytes::from(body));
+        res.headers_mut()
+            .insert(CONTENT_LENGTH, HeaderValue::from(body.len()));
+        *res.body_mut() = ResBody::Once(body);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_location_rewrite() {
+        let rewrite = LocationRewrite::new("/api/");
+        assert_eq!(
+            rewrite.rewrite("http://backend:8080/login?next=1", "http://backend:8080/"),
+            Some("/api/login?next=1".into())
+        );
+        assert_eq!(
+            rewrite.rewrite("/base/login", "http://backend/base"),
+            Some("/api/login".into())
+        );
+        assert_eq!(
+            rewrite.rewrite("http://backend", "http://backend"),
+            Some("/api".into())
+        );
+        assert_eq!(rewrite.rewrite("/other", "http://backend/base"), None);
+        assert_eq!(
+            rewrite.rewrite("http://backend2/login", "http://backend"),
+            None
+        );
+        assert_eq!(
+            rewrite.rewrite("https://example.com/", "http://backend"),
+            None
+        );
+        assert_eq!(
+            LocationRewrite::new("").rewrite("http://backend", "http://backend"),
+            Some("/".into())
+        );
+    }
+
+    #[test]
+    fn test_cookie_rewrite() {
+        let rewrite = CookieRewrite::new()
+            .domain("internal.example.com", "example.com")
+            .domain("backend", "")
+            .path("/", "/api/");
+        assert_eq!(
+            rewrite.rewrite("id=1; Domain=.internal.example.com; Path=/app; HttpOnly"),
+            "id=1; Domain=example.com; Path=/api/app; HttpOnly"
+        );
+        assert_eq!(
+            rewrite.rewrite("id=1; domain=backend; Secure"),
+            "id=1; Secure"
+        );
+        assert_eq!(
+            rewrite.rewrite("id=1; Domain=other.com"),
+            "id=1; Domain=other.com"
+        );
+    }
+}
@@ -0,0 +1,227 @@
+//! Connector for Unix domain socket upstreams.
+
+use std::future::Future;
+use std::io::{Error as IoError, IoSlice};
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use hyper::rt::{Read, ReadBufCursor, Write};
+use hyper::Uri;
+use hyper_util::client::legacy::connect::{Connected, Connection};
+use salvo_core::BoxedError;
+use tower::Service;
+
+/// Scheme of Unix domain socket upstreams, such as `unix:///var/run/app.sock`.
+pub(crate) const UNIX_SCHEME: &str = "unix";
+
+/// Returns the uri of a Unix domain socket upstream, where the hex encoded socket path is the authority.
+pub(crate) fn encode_unix_upstream(upstream: &str) -> Option<String> {
+    let path = upstream.strip_prefix("unix://")?;
+    Some(format!("{UNIX_SCHEME}://{}", hex::encode(path)))
+}
+
+fn decode_socket_path(uri: &Uri) -> Result<String, BoxedError> {
+    let host = uri.host().ok_or("unix upstream has no socket path")?;
+    Ok(String::from_utf8(hex::decode(host)?)?)
+}
+
+/// Connector which connects `unix://` upstreams, such as `unix:///var/run/app.sock`, to Unix domain sockets,
+/// and other upstreams with the inner connector.
+///
+/// It is used by the default [`HyperClient`](crate::HyperClient), and can wrap custom connectors.
+#[derive(Clone, Debug)]
+pub struct UnixConnector<C> {
+    inner: C,
+}
+
+impl<C> UnixConnector<C> {
+    /// Create new `UnixConnector` using `inner` for upstreams which are not Unix domain sockets.
+    pub fn new(inner: C) -> Self {
+        Self { inner }
+    }
+
+    /// Get inner connector.
+    pub fn inner(&self) -> &C {
+        &self.inner
+    }
+}
+
+impl<C> Service<Uri> for UnixConnector<C>
+where
+    C: Service<Uri>,
+    C::Error: Into<BoxedError>,
+    C::Future: Send + 'static,
+{
+    type Response = UnixConnectorStream<C::Response>;
+    type Error = BoxedError;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx).map_err(Into::into)
+    }
+
+    fn call(&mut self, uri: Uri) -> Self::Future {
+        if uri.scheme_str() != Some(UNIX_SCHEME) {
+            let connecting = self.inner.call(uri);
+            return Box::pin(async move {
+                connecting
+                    .await
+                    .map(|stream| UnixConnectorStream(Stream::Other(stream)))
+                    .map_err(Into::into)
+            });
+        }
+        let path = decode_socket_path(&uri);
+        Box::pin(async move {
+            let path = path?;
+            #[cfg(unix)]
+            {
+                let stream = tokio::net::UnixStream::connect(path).await?;
+                Ok(UnixConnectorStream(Stream::Unix(
+                    hyper_util::rt::TokioIo::new(stream),
+                )))
+            }
+            #[cfg(not(unix))]
+            {
+                Err(format!("unix socket {path} is not supported on this platform").into())
+            }
+        })
+    }
+}
+
+/// Connection made by [`UnixConnector`].
+#[derive(Debug)]
+pub struct UnixConnectorStream<T>(Stream<T>);
+
+#[derive(Debug)]
+enum Stream<T> {
+    #[cfg(unix)]
+    Unix(hyper_util::rt::TokioIo<tokio::net::UnixStream>),
+    Other(T),
+}
+
+impl<T> Connection for UnixConnectorStream<T>
+where
+    T: Connection,
+{
+    fn connected(&self) -> Connected {
+        match &self.0 {
+            #[cfg(unix)]
+            Stream::Unix(_) => Connected::new(),
+            Stream::Other(stream) => stream.connected(),
+        }
+    }
+}
+
+impl<T> Read for UnixConnectorStream<T>
+where
+    T: Read + Unpin,
+{
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: ReadBufCursor<'_>,
+    ) -> Poll<Result<(), IoError>> {
+        match &mut self.get_mut().0 {
+            #[cfg(unix)]
+            Stream::Unix(stream) => Pin::new(stream).poll_read(cx, buf),
+            Stream::Other(stream) => Pin::new(stream).poll_read(cx, buf),
+        }
+    }
+}
+
+impl<T> Write for UnixConnectorStream<T>
+where
+    T: Write + Unpin,
+{
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<Result<usize, IoError>> {
+        match &mut self.get_mut().0 {
+            #[cfg(unix)]
+            Stream::Unix(stream) => Pin::new(stream).poll_write(cx, buf),
+            Stream::Other(stream) => Pin::new(stream).poll_write(cx, buf),
+        }
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), IoError>> {
+        match &mut self.get_mut().0 {
+            #[cfg(unix)]
+            Stream::Unix(stream) => Pin::new(stream).poll_flush(cx),
+            Stream::Other(stream) => Pin::new(stream).poll_flush(cx),
+        }
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), IoError>> {
+        match &mut self.get_mut().0 {
+            #[cfg(unix)]
+            Stream::Unix(stream) => Pin::new(stream).poll_shutdown(cx),
+            Stream::Other(stream) => Pin::new(stream).poll_shutdown(cx),
+        }
+    }
+
+    fn is_write_vectored(&self) -> bool {
+        match &self.0 {
+            #[cfg(unix)]
+            Stream::Unix(stream) => stream.is_write_vectored(),
+            Stream::Other(stream) => stream.is_write_vectored(),
+        }
+    }
+
+    fn poll_write_vectored(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        bufs: &[IoSlice<'_>],
+    ) -> Poll<Result<usize, IoError>> {
+        match &mut self.get_mut().0 {
+            #[cfg(unix)]
+            Stream::Unix(stream) => Pin::new(stream).poll_write_vectored(cx, bufs),
+            Stream::Other(stream) => Pin::new(stream).poll_write_vectored(cx, bufs),
+        }
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use hyper::server::conn::http1;
+    use hyper::service::service_fn;
+    use hyper_util::rt::TokioIo;
+    use salvo_core::prelude::*;
+    use salvo_core::test::{ResponseExt, TestClient};
+    use tokio::net::UnixListener;
+
+    use crate::{HyperClient, Proxy};
+
+    #[tokio::test]
+    async fn test_unix_upstream() {
+        let path = std::env::temp_dir().join(format!("salvo-proxy-{}.sock", fastrand::u64(..)));
+        let listener = UnixListener::bind(&path).unwrap();
+        tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            let service = service_fn(|req: hyper::Request<hyper::body::Incoming>| async move {
+                let host = req.headers()["host"].to_str().unwrap().to_owned();
+                Ok::<_, std::convert::Infallible>(hyper::Response::new(format!(
+                    "{host} {}",
+                    req.uri()
+                )))
+            });
+            http1::Builder::new()
+                .serve_connection(TokioIo::new(stream), service)
+                .await
+                .unwrap();
+        });
+
+        let upstream = format!("unix://{}", path.display());
+        let router =
+            Router::with_path("{**rest}").goal(Proxy::new(upstream, HyperClient::default()));
+        let content = TestClient::get("http://127.0.0.1:5800/hello?name=salvo")
+            .send(router)
+            .await
+            .take_string()
+            .await
+            .unwrap();
+        assert_eq!(content, "localhost /hello?name=salvo");
+        let _ = std::fs::remove_file(path);
+    }
+}
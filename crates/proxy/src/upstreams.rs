@@ -0,0 +1,645 @@
+//! Ready-made [`Upstreams`] implementations with load-balancing strategies.
+
+use std::hash::Hasher;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+
+use salvo_core::http::header::{HeaderName, COOKIE};
+use salvo_core::{Depot, Error, Request};
+
+use crate::{elect_random, Outcome, Upstreams};
+
+fn empty_error() -> Error {
+    Error::other("upstreams is empty")
+}
+
+/// Picks upstreams in turn.
+#[derive(Debug)]
+pub struct RoundRobin<T> {
+    upstreams: Vec<T>,
+    next: AtomicUsize,
+}
+impl<T> RoundRobin<T>
+where
+    T: AsRef<str> + Send + Sync + 'static,
+{
+    /// Create new `RoundRobin`.
+    pub fn new(upstreams: impl IntoIterator<Item = T>) -> Self {
+        Self {
+            upstreams: upstreams.into_iter().collect(),
+            next: AtomicUsize::new(0),
+        }
+    }
+
+    /// Picks the next accepted upstream in turn.
+    fn pick(&self, accept: &(dyn Fn(&str) -> bool + Send + Sync)) -> Option<&str> {
+        let len = self.upstreams.len();
+        if len == 0 {
+            return None;
+        }
+        let start = self.next.fetch_add(1, Ordering::Relaxed) % len;
+        (0..len)
+            .map(|i| self.upstreams[(start + i) % len].as_ref())
+            .find(|upstream| accept(upstream))
+    }
+}
+impl<T> Upstreams for RoundRobin<T>
+where
+    T: AsRef<str> + Send + Sync + 'static,
+{
+    type Error = Error;
+
+    async fn elect(&self, _req: &Request, _depot: &Depot) -> Result<Arc<str>, Self::Error> {
+        self.pick(&|_| true).map(Arc::from).ok_or_else(empty_error)
+    }
+
+    async fn elect_accepted(
+        &self,
+        _req: &Request,
+        _depot: &Depot,
+        accept: &(dyn Fn(&str) -> bool + Send + Sync),
+    ) -> Result<Option<Arc<str>>, Self::Error> {
+        Ok(self.pick(accept).map(Arc::from))
+    }
+
+    fn members(&self) -> Vec<Arc<str>> {
+        self.upstreams
+            .iter()
+            .map(|upstream| Arc::from(upstream.as_ref()))
+            .collect()
+    }
+}
+
+/// Picks upstreams in turn in proportion to their weights.
+///
+/// It uses the smooth weighted round-robin algorithm of nginx, so that upstreams with big weights are not
+/// picked many times in a row. Upstreams with weight `0` are never picked.
+#[derive(Debug)]
+pub struct WeightedRoundRobin<T> {
+    upstreams: Vec<(T, i64)>,
+    current: Mutex<Vec<i64>>,
+}
+impl<T> WeightedRoundRobin<T>
+where
+    T: AsRef<str> + Send + Sync + 'static,
+{
+    /// Create new `WeightedRoundRobin` with upstreams and their weights.
+    pub fn new(upstreams: impl IntoIterator<Item = (T, usize)>) -> Self {
+        let upstreams = upstreams
+            .into_iter()
+            .map(|(upstream, weight)| (upstream, weight as i64))
+            .collect::<Vec<_>>();
+        Self {
+            current: Mutex::new(vec![0; upstreams.len()]),
+            upstreams,
+        }
+    }
+
+    /// Picks among the accepted upstreams with the smooth weighted round-robin algorithm.
+    fn pick(&self, accept: &(dyn Fn(&str) -> bool + Send + Sync)) -> Option<&str> {
+        let mut current = self
+            .current
+            .lock()
+            .expect("failed to lock weighted round-robin state");
+        let mut total = 0;
+        let mut best: Option<usize> = None;
+        for (i, (upstream, weight)) in self.upstreams.iter().enumerate() {
+            if *weight == 0 || !accept(upstream.as_ref()) {
+                continue;
+            }
+            current[i] += weight;
+            total += weight;
+            if best.map_or(true, |best| current[i] > current[best]) {
+                best = Some(i);
+            }
+        }
+        let best = best?;
+        current[best] -= total;
+        Some(self.upstreams[best].0.as_ref())
+    }
+}
+impl<T> Upstreams for WeightedRoundRobin<T>
+where
+    T: AsRef<str> + Send + Sync + 'static,
+{
+    type Error = Error;
+
+    async fn elect(&self, _req: &Request, _depot: &Depot) -> Result<Arc<str>, Self::Error> {
+        self.pick(&|_| true).map(Arc::from).ok_or_else(empty_error)
+    }
+
+    async fn elect_accepted(
+        &self,
+        _req: &Request,
+        _depot: &Depot,
+        accept: &(dyn Fn(&str) -> bool + Send + Sync),
+    ) -> Result<Option<Arc<str>>, Self::Error> {
+        Ok(self.pick(accept).map(Arc::from))
+    }
+
+    fn members(&self) -> Vec<Arc<str>> {
+        self.upstreams
+            .iter()
+            .filter(|(_, weight)| *weight > 0)
+            .map(|(upstream, _)| Arc::from(upstream.as_ref()))
+            .collect()
+    }
+}
+
+#[derive(Debug)]
+struct Counted<T> {
+    upstream: T,
+    in_flight: AtomicUsize,
+}
+
+fn counted<T>(upstreams: impl IntoIterator<Item = T>) -> Vec<Counted<T>> {
+    upstreams
+        .into_iter()
+        .map(|upstream| Counted {
+            upstream,
+            in_flight: AtomicUsize::new(0),
+        })
+        .collect()
+}
+
+fn release<T: AsRef<str>>(upstreams: &[Counted<T>], upstream: &str) {
+    if let Some(member) = upstreams.iter().find(|m| m.upstream.as_ref() == upstream) {
+        // Never wraps below zero, even if `finish` is called for a request not counted.
+        let _ = member
+            .in_flight
+            .fetch_update(Ordering::AcqRel, Ordering::Acquire, |n| n.checked_sub(1));
+    }
+}
+
+fn in_flight<T: AsRef<str>>(upstreams: &[Counted<T>], upstream: &str) -> Option<usize> {
+    upstreams
+        .iter()
+        .find(|m| m.upstream.as_ref() == upstream)
+        .map(|m| m.in_flight.load(Ordering::Acquire))
+}
+
+/// Picks the upstream with the least requests in flight.
+///
+/// A request is in flight from the election until the upstream response head is received, so long
+/// streamed bodies and upgraded connections are not counted.
+#[derive(Debug)]
+pub struct LeastInFlight<T> {
+    upstreams: Vec<Counted<T>>,
+}
+impl<T> LeastInFlight<T>
+where
+    T: AsRef<str> + Send + Sync + 'static,
+{
+    /// Create new `LeastInFlight`.
+    pub fn new(upstreams: impl IntoIterator<Item = T>) -> Self {
+        Self {
+            upstreams: counted(upstreams),
+        }
+    }
+
+    /// Returns the number of requests in flight to the upstream.
+    pub fn in_flight(&self, upstream: &str) -> Option<usize> {
+        in_flight(&self.upstreams, upstream)
+    }
+
+    /// Picks the accepted upstream with the least requests in flight and counts the request.
+    fn pick(&self, accept: &(dyn Fn(&str) -> bool + Send + Sync)) -> Option<&str> {
+        let len = self.upstreams.len();
+        if len == 0 {
+            return None;
+        }
+        // Start from a random position, so that ties are not always won by the first upstream.
+        let offset = fastrand::usize(..len);
+        let member = (0..len)
+            .map(|i| &self.upstreams[(i + offset) % len])
+            .filter(|m| accept(m.upstream.as_ref()))
+            .min_by_key(|m| m.in_flight.load(Ordering::Acquire))?;
+        member.in_flight.fetch_add(1, Ordering::AcqRel);
+        Some(member.upstream.as_ref())
+    }
+}
+impl<T> Upstreams for LeastInFlight<T>
+where
+    T: AsRef<str> + Send + Sync + 'static,
+{
+    type Error = Error;
+
+    async fn elect(&self, _req: &Request, _depot: &Depot) -> Result<Arc<str>, Self::Error> {
+        self.pick(&|_| true).map(Arc::from).ok_or_else(empty_error)
+    }
+
+    async fn elect_accepted(
+        &self,
+        _req: &Request,
+        _depot: &Depot,
+        accept: &(dyn Fn(&str) -> bool + Send + Sync),
+    ) -> Result<Option<Arc<str>>, Self::Error> {
+        Ok(self.pick(accept).map(Arc::from))
+    }
+
+    fn finish(&self, upstream: &str, _outcome: Outcome) {
+        release(&self.upstreams, upstream);
+    }
+
+    fn members(&self) -> Vec<Arc<str>> {
+        self.upstreams
+            .iter()
+            .map(|m| Arc::from(m.upstream.as_ref()))
+            .collect()
+    }
+}
+
+/// Picks two random upstreams and uses the one with less requests in flight.
+///
+/// It is nearly as good as [`LeastInFlight`] and avoids all proxies sending requests to the same least
+/// loaded upstream at once.
+#[derive(Debug)]
+pub struct PowerOfTwoChoices<T> {
+    upstreams: Vec<Counted<T>>,
+}
+impl<T> PowerOfTwoChoices<T>
+where
+    T: AsRef<str> + Send + Sync + 'static,
+{
+    /// Create new `PowerOfTwoChoices`.
+    pub fn new(upstreams: impl IntoIterator<Item = T>) -> Self {
+        Self {
+            upstreams: counted(upstreams),
+        }
+    }
+
+    /// Returns the number of requests in flight to the upstream.
+    pub fn in_flight(&self, upstream: &str) -> Option<usize> {
+        in_flight(&self.upstreams, upstream)
+    }
+
+    /// Picks the less loaded of two random accepted upstreams and counts the request.
+    fn pick(&self, accept: &(dyn Fn(&str) -> bool + Send + Sync)) -> Option<&str> {
+        let accepted = self
+            .upstreams
+            .iter()
+            .filter(|m| accept(m.upstream.as_ref()))
+            .collect::<Vec<_>>();
+        let len = accepted.len();
+        let member = match len {
+            0 => return None,
+            1 => accepted[0],
+            _ => {
+                let first = fastrand::usize(..len);
+                let second = (first + fastrand::usize(1..len)) % len;
+                let (first, second) = (accepted[first], accepted[second]);
+                if second.in_flight.load(Ordering::Acquire)
+                    < first.in_flight.load(Ordering::Acquire)
+                {
+                    second
+                } else {
+                    first
+                }
+            }
+        };
+        member.in_flight.fetch_add(1, Ordering::AcqRel);
+        Some(member.upstream.as_ref())
+    }
+}
+impl<T> Upstreams for PowerOfTwoChoices<T>
+where
+    T: AsRef<str> + Send + Sync + 'static,
+{
+    type Error = Error;
+
+    async fn elect(&self, _req: &Request, _depot: &Depot) -> Result<Arc<str>, Self::Error> {
+        self.pick(&|_| true).map(Arc::from).ok_or_else(empty_error)
+    }
+
+    async fn elect_accepted(
+        &self,
+        _req: &Request,
+        _depot: &Depot,
+        accept: &(dyn Fn(&str) -> bool + Send + Sync),
+    ) -> Result<Option<Arc<str>>, Self::Error> {
+        Ok(self.pick(accept).map(Arc::from))
+    }
+
+    fn finish(&self, upstream: &str, _outcome: Outcome) {
+        release(&self.upstreams, upstream);
+    }
+
+    fn members(&self) -> Vec<Arc<str>> {
+        self.upstreams
+            .iter()
+            .map(|m| Arc::from(m.upstream.as_ref()))
+            .collect()
+    }
+}
+
+/// The part of the request used by [`ConsistentHash`] to pick the upstream.
+#[derive(Clone, Debug)]
+#[non_exhaustive]
+pub enum HashKey {
+    /// Value of the request header.
+    Header(HeaderName),
+    /// Value of the request cookie.
+    Cookie(String),
+    /// IP address of the client.
+    RemoteIp,
+}
+impl HashKey {
+    /// Hash on the value of the request header.
+    pub fn header(name: HeaderName) -> Self {
+        Self::Header(name)
+    }
+    /// Hash on the value of the request cookie.
+    pub fn cookie(name: impl Into<String>) -> Self {
+        Self::Cookie(name.into())
+    }
+
+    fn get(&self, req: &Request) -> Option<String> {
+        match self {
+            Self::Header(name) => req
+                .headers()
+                .get(name)
+                .and_then(|value| value.to_str().ok())
+                .map(ToOwned::to_owned),
+            Self::Cookie(name) => req
+                .headers()
+                .get_all(COOKIE)
+                .iter()
+                .filter_map(|value| value.to_str().ok())
+                .flat_map(|value| value.split(';'))
+                .filter_map(|pair| pair.trim().split_once('='))
+                .find(|(key, _)| key == name)
+                .map(|(_, value)| value.to_owned()),
+            Self::RemoteIp => req
+                .remote_addr()
+                .clone()
+                .into_std()
+                .map(|addr| addr.ip().to_string()),
+        }
+    }
+}
+
+// FNV-1a with a final mix, stable across processes and builds.
+#[derive(Default)]
+struct StableHasher(u64);
+impl Hasher for StableHasher {
+    fn write(&mut self, bytes: &[u8]) {
+        if self.0 == 0 {
+            self.0 = 0xcbf2_9ce4_8422_2325;
+        }
+        for byte in bytes {
+            self.0 = (self.0 ^ *byte as u64).wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+    fn finish(&self) -> u64 {
+        let mut hash = self.0;
+        hash ^= hash >> 33;
+        hash = hash.wrapping_mul(0xff51_afd7_ed55_8ccd);
+        hash ^= hash >> 33;
+        hash
+    }
+}
+
+/// Picks the upstream by the hash of a part of the request, so that requests with the same key, such as a
+/// session cookie, go to the same upstream.
+///
+/// It uses rendezvous hashing: when an upstream is added or removed, only the keys of that upstream move.
+/// Requests without the key are sent to a random upstream.
+#[derive(Debug)]
+pub struct ConsistentHash<T> {
+    upstreams: Vec<T>,
+    key: HashKey,
+}
+impl<T> ConsistentHash<T>
+where
+    T: AsRef<str> + Send + Sync + 'static,
+{
+    /// Create new `ConsistentHash`.
+    pub fn new(upstreams: impl IntoIterator<Item = T>, key: HashKey) -> Self {
+        Self {
+            upstreams: upstreams.into_iter().collect(),
+            key,
+        }
+    }
+
+    /// Returns the upstream for the key.
+    pub fn pick(&self, key: &str) -> Option<&str> {
+        self.pick_accepted(key, &|_| true)
+    }
+
+    /// Returns the accepted upstream for the key, which is the upstream for the key if it is accepted.
+    fn pick_accepted(
+        &self,
+        key: &str,
+        accept: &(dyn Fn(&str) -> bool + Send + Sync),
+    ) -> Option<&str> {
+        self.upstreams
+            .iter()
+            .filter(|upstream| accept(upstream.as_ref()))
+            .max_by_key(|upstream| {
+                let mut hasher = StableHasher::default();
+                hasher.write(upstream.as_ref().as_bytes());
+                hasher.write(&[0xff]);
+                hasher.write(key.as_bytes());
+                hasher.finish()
+            })
+            .map(AsRef::as_ref)
+    }
+}
+impl<T> Upstreams for ConsistentHash<T>
+where
+    T: AsRef<str> + Send + Sync + 'static,
+{
+    type Error = Error;
+
+    async fn elect(&self, req: &Request, depot: &Depot) -> Result<Arc<str>, Self::Error> {
+        self.elect_accepted(req, depot, &|_| true)
+            .await?
+            .ok_or_else(empty_error)
+    }
+
+    async fn elect_accepted(
+        &self,
+        req: &Request,
+        _depot: &Depot,
+        accept: &(dyn Fn(&str) -> bool + Send + Sync),
+    ) -> Result<Option<Arc<str>>, Self::Error> {
+        let elected = match self.key.get(req) {
+            Some(key) => self.pick_accepted(&key, accept),
+            None => elect_random(self.upstreams.iter().map(AsRef::as_ref), accept),
+        };
+        Ok(elected.map(Arc::from))
+    }
+
+    fn members(&self) -> Vec<Arc<str>> {
+        self.upstreams
+            .iter()
+            .map(|upstream| Arc::from(upstream.as_ref()))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use salvo_core::http::StatusCode;
+
+    use super::*;
+
+    async fn elect_many<U: Upstreams>(upstreams: &U, count: usize) -> HashMap<String, usize> {
+        let (req, depot) = (Request::new(), Depot::new());
+        let mut counts = HashMap::new();
+        for _ in 0..count {
+            let upstream = upstreams
+                .elect(&req, &depot)
+                .await
+                .ok()
+                .unwrap()
+                .to_string();
+            *counts.entry(upstream).or_default() += 1;
+        }
+        counts
+    }
+
+    #[tokio::test]
+    async fn test_round_robin() {
+        let upstreams = RoundRobin::new(["http://a", "http://b", "http://c"]);
+        let counts = elect_many(&upstreams, 30).await;
+        assert!(counts.values().all(|count| *count == 10));
+
+        let upstreams = RoundRobin::new(Vec::<String>::new());
+        assert!(upstreams
+            .elect(&Request::new(), &Depot::new())
+            .await
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn test_weighted_round_robin() {
+        let upstreams =
+            WeightedRoundRobin::new([("http://a", 5), ("http://b", 1), ("http://c", 0)]);
+        let (req, depot) = (Request::new(), Depot::new());
+        let mut elected = vec![];
+        for _ in 0..6 {
+            elected.push(upstreams.elect(&req, &depot).await.unwrap().to_string());
+        }
+        // Smooth: the light upstream is not picked at the start or the end of the cycle.
+        assert_eq!(
+            elected,
+            ["http://a", "http://a", "http://a", "http://b", "http://a", "http://a"]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_least_in_flight() {
+        let upstreams = LeastInFlight::new(["http://a", "http://b"]);
+        let (req, depot) = (Request::new(), Depot::new());
+        let first = upstreams.elect(&req, &depot).await.unwrap();
+        let second = upstreams.elect(&req, &depot).await.unwrap();
+        assert_ne!(first, second);
+        upstreams.finish(&first, Outcome::Responded(StatusCode::OK));
+        assert_eq!(upstreams.in_flight(&first), Some(0));
+        assert_eq!(upstreams.elect(&req, &depot).await.unwrap(), first);
+    }
+
+    #[tokio::test]
+    async fn test_power_of_two_choices() {
+        let upstreams = PowerOfTwoChoices::new(["http://a", "http://b"]);
+        let (req, depot) = (Request::new(), Depot::new());
+        let first = upstreams.elect(&req, &depot).await.unwrap();
+        assert_ne!(upstreams.elect(&req, &depot).await.unwrap(), first);
+        upstreams.finish(&first, Outcome::Failed);
+        assert_eq!(upstreams.in_flight(&first), Some(0));
+    }
+
+    #[tokio::test]
+    async fn test_consistent_hash() {
+        let upstreams = ConsistentHash::new(
+            ["http://a", "http://b", "http://c"],
+            HashKey::cookie("session"),
+        );
+        let mut req = Request::new();
+        req.headers_mut()
+            .insert(COOKIE, "theme=dark; session=abc123".parse().unwrap());
+        let depot = Depot::new();
+        let elected = upstreams.elect(&req, &depot).await.unwrap();
+        for _ in 0..10 {
+            assert_eq!(upstreams.elect(&req, &depot).await.unwrap(), elected);
+        }
+        assert_eq!(upstreams.pick("abc123"), Some(&*elected));
+
+        // Only keys of the removed upstream move.
+        let keys = (0..100).map(|i| format!("key{i}")).collect::<Vec<_>>();
+        let fewer = ConsistentHash::new(["http://a", "http://b"], HashKey::RemoteIp);
+        for key in &keys {
+            let before = upstreams.pick(key).unwrap();
+            if before != "http://c" {
+                assert_eq!(fewer.pick(key), Some(before));
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_elect_accepted() {
+        let (req, depot) = (Request::new(), Depot::new());
+        let accept = |upstream: &str| upstream != "http://a";
+
+        let upstreams = RoundRobin::new(["http://a", "http://b", "http://c"]);
+        for _ in 0..6 {
+            let elected = upstreams
+                .elect_accepted(&req, &depot, &accept)
+                .await
+                .unwrap();
+            assert_ne!(elected.as_deref(), Some("http://a"));
+        }
+        let upstreams = WeightedRoundRobin::new([("http://a", 5), ("http://b", 1)]);
+        let elected = upstreams
+            .elect_accepted(&req, &depot, &accept)
+            .await
+            .unwrap();
+        assert_eq!(elected.as_deref(), Some("http://b"));
+        let upstreams = LeastInFlight::new(["http://a", "http://b"]);
+        for _ in 0..2 {
+            let elected = upstreams
+                .elect_accepted(&req, &depot, &accept)
+                .await
+                .unwrap();
+            assert_eq!(elected.as_deref(), Some("http://b"));
+        }
+        assert_eq!(upstreams.in_flight("http://a"), Some(0));
+        let upstreams = PowerOfTwoChoices::new(["http://a", "http://b"]);
+        let elected = upstreams
+            .elect_accepted(&req, &depot, &accept)
+            .await
+            .unwrap();
+        assert_eq!(elected.as_deref(), Some("http://b"));
+        assert_eq!(upstreams.in_flight("http://a"), Some(0));
+
+        // The next upstream on the hash ring.
+        let upstreams = ConsistentHash::new(
+            ["http://a", "http://b", "http://c"],
+            HashKey::header(HeaderName::from_static("x-session")),
+        );
+        let key = (0..)
+            .map(|i| format!("key{i}"))
+            .find(|key| upstreams.pick(key) == Some("http://a"))
+            .unwrap();
+        let mut req = Request::new();
+        req.headers_mut().insert("x-session", key.parse().unwrap());
+        let elected = upstreams
+            .elect_accepted(&req, &depot, &accept)
+            .await
+            .unwrap();
+        assert_eq!(
+            elected.as_deref(),
+            ConsistentHash::new(["http://b", "http://c"], HashKey::RemoteIp).pick(&key)
+        );
+
+        let upstreams = RoundRobin::new(["http://a"]);
+        let elected = upstreams
+            .elect_accepted(&req, &depot, &accept)
+            .await
+            .unwrap();
+        assert_eq!(elected, None);
+    }
+}
@@ -0,0 +1,226 @@
+use std::error::Error as StdError;
+use std::fmt::{self, Display, Formatter};
+use std::future::Future;
+use std::hash::Hash;
+
+use super::{RateGuard, RateStore};
+
+/// A store which saves guards with compare-and-swap, such as Redis with `WATCH`/`MULTI` or a SQL table with
+/// a version column.
+///
+/// Wrap it in [`CasStore`] to use it with [`RateLimiter`](crate::RateLimiter).
+pub trait CasRateStore: Send + Sync + 'static {
+    /// Error type for the store.
+    type Error: StdError;
+    /// Key
+    type Key: Hash + Eq + Send + Sync + Clone + 'static;
+    /// Saved guard.
+    type Guard: RateGuard;
+    /// Version of a saved guard, such as a counter or the serialized guard itself.
+    type Version: Send;
+
+    /// Load the guard and its version, returns `None` if there is no guard for the key.
+    #[allow(clippy::type_complexity)]
+    fn load_guard(
+        &self,
+        key: &Self::Key,
+    ) -> impl Future<Output = Result<Option<(Self::Guard, Self::Version)>, Self::Error>> + Send;
+
+    /// Save the guard only if the saved version is still `version`, `None` means there was no guard.
+    ///
+    /// Returns `false` if the guard was changed by others in the meantime.
+    fn swap_guard(
+        &self,
+        key: &Self::Key,
+        version: Option<Self::Version>,
+        guard: &Self::Guard,
+    ) -> impl Future<Output = Result<bool, Self::Error>> + Send;
+}
+
+/// Error of [`CasStore`].
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum CasError<E> {
+    /// Error of the inner store.
+    Store(E),
+    /// The guard was changed by others on every try.
+    Conflict,
+}
+impl<E: Display> Display for CasError<E> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Store(e) => e.fmt(f),
+            Self::Conflict => f.write_str("rate guard swap retries exhausted"),
+        }
+    }
+}
+impl<E: StdError> StdError for CasError<E> {}
+impl<E> From<E> for CasError<E> {
+    fn from(e: E) -> Self {
+        Self::Store(e)
+    }
+}
+
+/// Makes a [`RateStore`] from a [`CasRateStore`].
+///
+/// The guard is verified again with the latest saved state whenever the swap fails, up to `max_retries`
+/// times, then [`CasError::Conflict`] is returned.
+#[derive(Clone, Debug)]
+pub struct CasStore<S> {
+    inner: S,
+    max_retries: usize,
+}
+impl<S: CasRateStore> CasStore<S> {
+    /// Create a new `CasStore`.
+    #[inline]
+    pub fn new(inner: S) -> Self {
+        Self {
+            inner,
+            max_retries: 16,
+        }
+    }
+
+    /// Sets the max number of retries when the swap fails and returns new `CasStore`, default is 16.
+    #[inline]
+    pub fn max_retries(mut self, max_retries: usize) -> Self {
+        self.max_retries = max_retries;
+        self
+    }
+
+    /// Get the inner store.
+    #[inline]
+    pub fn inner(&self) -> &S {
+        &self.inner
+    }
+}
+
+impl<S: CasRateStore> RateStore for CasStore<S> {
+    type Error = CasError<S::Error>;
+    type Key = S::Key;
+    type Guard = S::Guard;
+
+    async fn consume(
+        &self,
+        key: Self::Key,
+        refer: &Self::Guard,
+        quota: &<Self::Guard as RateGuard>::Quota,
+        cost: usize,
+    ) -> Result<(bool, Self::Guard), Self::Error> {
+        for _ in 0..=self.max_retries {
+            let (mut guard, version) = match self.inner.load_guard(&key).await? {
+                Some((guard, version)) => (guard, Some(version)),
+                None => (refer.clone(), None),
+            };
+            let verified = guard.verify_cost(quota, cost).await;
+            if self.inner.swap_guard(&key, version, &guard).await? {
+                return Ok((verified, guard));
+            }
+        }
+        Err(CasError::Conflict)
+    }
+
+    async fn peek(
+        &self,
+        key: Self::Key,
+        refer: &Self::Guard,
+        quota: &<Self::Guard as RateGuard>::Quota,
+        cost: usize,
+    ) -> Result<(bool, Self::Guard), Self::Error> {
+        let mut guard = match self.inner.load_guard(&key).await? {
+            Some((guard, _)) => guard,
+            None => refer.clone(),
+        };
+        let verified = guard.verify_cost(quota, cost).await;
+        Ok((verified, guard))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::convert::Infallible;
+    use std::sync::{Arc, Mutex};
+
+    use super::*;
+    use crate::{BasicQuota, FixedGuard};
+
+    #[derive(Default)]
+    struct VersionedStore {
+        guards: Mutex<HashMap<String, (FixedGuard, u64)>>,
+        conflict: bool,
+    }
+    impl CasRateStore for VersionedStore {
+        type Error = Infallible;
+        type Key = String;
+        type Guard = FixedGuard;
+        type Version = u64;
+
+        async fn load_guard(&self, key: &String) -> Result<Option<(FixedGuard, u64)>, Infallible> {
+            let loaded = self.guards.lock().unwrap().get(key).cloned();
+            // Give other tasks a chance to change the guard.
+            tokio::task::yield_now().await;
+            Ok(loaded)
+        }
+
+        async fn swap_guard(
+            &self,
+            key: &String,
+            version: Option<u64>,
+            guard: &FixedGuard,
+        ) -> Result<bool, Infallible> {
+            let mut guards = self.guards.lock().unwrap();
+            let current = guards.get(key).map(|(_, version)| *version);
+            if current != version || self.conflict {
+                return Ok(false);
+            }
+            guards.insert(
+                key.clone(),
+                (guard.clone(), version.unwrap_or_default() + 1),
+            );
+            Ok(true)
+        }
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_cas_store_concurrency() {
+        let store = Arc::new(CasStore::new(VersionedStore::default()).max_retries(usize::MAX));
+        let quota = BasicQuota::per_minute(10);
+        let tasks = (0..100)
+            .map(|_| {
+                let store = store.clone();
+                let quota = quota.clone();
+                tokio::spawn(async move {
+                    let (verified, _) = store
+                        .consume("user".to_owned(), &FixedGuard::new(), &quota, 1)
+                        .await
+                        .unwrap();
+                    verified
+                })
+            })
+            .collect::<Vec<_>>();
+        let mut passed = 0;
+        for task in tasks {
+            if task.await.unwrap() {
+                passed += 1;
+            }
+        }
+        assert_eq!(passed, 10);
+    }
+
+    #[tokio::test]
+    async fn test_cas_store_conflict() {
+        let store = CasStore::new(VersionedStore {
+            conflict: true,
+            ..Default::default()
+        });
+        let result = store
+            .consume(
+                "user".to_owned(),
+                &FixedGuard::new(),
+                &BasicQuota::per_minute(10),
+                1,
+            )
+            .await;
+        assert!(matches!(result, Err(CasError::Conflict)));
+    }
+}
@@ -0,0 +1,501 @@
+use salvo_core::handler::{none_skipper, Skipper};
+use salvo_core::http::{Request, Response, StatusCode, StatusError};
+use salvo_core::{async_trait, Depot, FlowCtrl, Handler};
+use tokio::sync::Mutex;
+
+use super::{
+    add_legacy_headers, insert_header, limit_item, policy_item, QuotaGetter, RateGuard, RateIssuer,
+    RateStore, Rejection,
+};
+
+/// A rule of [`CompositeLimiter`], made of an issuer and a quota getter.
+///
+/// The name of the rule is used as the policy name in the `RateLimit-Policy` and `RateLimit` headers, and
+/// to separate keys of different rules in the store.
+pub struct RateRule<I, P> {
+    name: String,
+    issuer: I,
+    quota_getter: P,
+}
+impl<I, P> RateRule<I, P>
+where
+    I: RateIssuer<Key = String>,
+    P: QuotaGetter<String>,
+{
+    /// Create a new `RateRule`.
+    #[inline]
+    pub fn new(name: impl Into<String>, issuer: I, quota_getter: P) -> Self {
+        Self {
+            name: name.into(),
+            issuer,
+            quota_getter,
+        }
+    }
+}
+
+enum Resolved<Q> {
+    Skipped,
+    Quota(String, Q),
+    Failed,
+}
+
+#[async_trait]
+trait DynRule<Q>: Send + Sync + 'static {
+    fn name(&self) -> &str;
+    async fn resolve(&self, req: &mut Request, depot: &Depot) -> Resolved<Q>;
+}
+
+#[async_trait]
+impl<I, P> DynRule<P::Quota> for RateRule<I, P>
+where
+    I: RateIssuer<Key = String>,
+    P: QuotaGetter<String>,
+{
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    async fn resolve(&self, req: &mut Request, depot: &Depot) -> Resolved<P::Quota> {
+        let Some(key) = self.issuer.issue(req, depot).await else {
+            return Resolved::Skipped;
+        };
+        match self.quota_getter.get(&key).await {
+            Ok(quota) => Resolved::Quota(format!("{}:{key}", self.name), quota),
+            Err(e) => {
+                tracing::error!(error = ?e, rule = %self.name, "RateLimiter error: {}", e);
+                Resolved::Failed
+            }
+        }
+    }
+}
+
+/// `CompositeLimiter` applies several [`RateRule`]s to every request in one pass.
+///
+/// A request is allowed only if all rules allow it, and quota of the rules is consumed only when the request
+/// is allowed. Requests are checked and consumed one at a time, so the quota of all rules is consumed or none
+/// is. This holds as long as the store is not shared with other limiters.
+/// Rules whose issuer returns `None`, like a per-user rule for anonymous requests, are skipped.
+///
+/// The rate limit headers report the most restrictive rule: the rule which rejects the request with the
+/// longest retry time, or the rule with the least remaining quota.
+///
+/// # Example
+///
+/// ```
+/// use salvo_core::prelude::*;
+/// use salvo_rate_limiter::{BasicQuota, CompositeLimiter, FixedGuard, MokaStore, RateRule, RemoteIpIssuer};
+///
+/// let limiter = CompositeLimiter::new(FixedGuard::new(), MokaStore::new())
+///     .rule(RateRule::new("ip", RemoteIpIssuer, BasicQuota::per_second(10)))
+///     .rule(RateRule::new(
+///         "global",
+///         |_: &mut Request, _: &Depot| Some("all".to_owned()),
+///         BasicQuota::per_second(1000),
+///     ))
+///     .standard_headers(true);
+/// let router = Router::new().hoop(limiter);
+/// ```
+pub struct CompositeLimiter<G: RateGuard, S> {
+    guard: G,
+    store: S,
+    rules: Vec<Box<dyn DynRule<G::Quota>>>,
+    cost: usize,
+    add_headers: bool,
+    standard_headers: bool,
+    rejection: Rejection,
+    shadow: bool,
+    skipper: Box<dyn Skipper>,
+    lock: Mutex<()>,
+}
+
+impl<G: RateGuard, S: RateStore<Key = String, Guard = G>> CompositeLimiter<G, S> {
+    /// Create a new `CompositeLimiter` without rules.
+    #[inline]
+    pub fn new(guard: G, store: S) -> Self {
+        Self {
+            guard,
+            store,
+            rules: vec![],
+            cost: 1,
+            add_headers: false,
+            standard_headers: false,
+            rejection: Rejection::Error(StatusError::too_many_requests()),
+            shadow: false,
+            skipper: Box::new(none_skipper),
+            lock: Mutex::new(()),
+        }
+    }
+
+    /// Adds a rule and returns new `CompositeLimiter`.
+    #[inline]
+    pub fn rule<I, P>(mut self, rule: RateRule<I, P>) -> Self
+    where
+        I: RateIssuer<Key = String>,
+        P: QuotaGetter<String, Quota = G::Quota>,
+    {
+        self.rules.push(Box::new(rule));
+        self
+    }
+
+    /// Sets skipper and returns new `CompositeLimiter`.
+    #[inline]
+    pub fn with_skipper(mut self, skipper: impl Skipper) -> Self {
+        self.skipper = Box::new(skipper);
+        self
+    }
+
+    /// Sets the units of quota consumed by every request and returns new `CompositeLimiter`, default is 1.
+    #[inline]
+    pub fn cost(mut self, cost: usize) -> Self {
+        self.cost = cost;
+        self
+    }
+
+    /// Sets `add_headers` and returns new `CompositeLimiter`.
+    /// If `add_headers` is true, the `X-RateLimit-*` headers of the most restrictive rule will be added to
+    /// the response.
+    #[inline]
+    pub fn add_headers(mut self, add_headers: bool) -> Self {
+        self.add_headers = add_headers;
+        self
+    }
+
+    /// Sets `standard_headers` and returns new `CompositeLimiter`.
+    /// If `standard_headers` is true, the `RateLimit-Policy` header listing all applied rules and the
+    /// `RateLimit` header of the most restrictive rule will be added to the response.
+    #[inline]
+    pub fn standard_headers(mut self, standard_headers: bool) -> Self {
+        self.standard_headers = standard_headers;
+        self
+    }
+
+    /// Sets the error rendered when a request is rejected and returns new `CompositeLimiter`.
+    ///
+    /// Default is `StatusError::too_many_requests()`. The `cause` of the error is not rendered.
+    #[inline]
+    pub fn rejection_error(mut self, error: StatusError) -> Self {
+        self.rejection = Rejection::Error(error);
+        self
+    }
+
+    /// Sets the handler called when a request is rejected and returns new `CompositeLimiter`.
+    #[inline]
+    pub fn rejection(mut self, handler: impl Handler) -> Self {
+        self.rejection = Rejection::Handler(Box::new(handler));
+        self
+    }
+
+    /// Sets `shadow` and returns new `CompositeLimiter`.
+    /// If `shadow` is true, requests consume the quota as usual, but requests exceeding it are only logged and
+    /// not rejected.
+    ///
+    /// A shadow limiter should have its own store, otherwise its consumption is counted by the other limiters
+    /// sharing the store.
+    #[inline]
+    pub fn shadow(mut self, shadow: bool) -> Self {
+        self.shadow = shadow;
+        self
+    }
+}
+
+#[async_trait]
+impl<G, S> Handler for CompositeLimiter<G, S>
+where
+    G: RateGuard,
+    S: RateStore<Key = String, Guard = G>,
+{
+    async fn handle(
+        &self,
+        req: &mut Request,
+        depot: &mut Depot,
+        res: &mut Response,
+        ctrl: &mut FlowCtrl,
+    ) {
+        if self.skipper.skipped(req, depot) {
+            return;
+        }
+        let mut applied = Vec::with_capacity(self.rules.len());
+        for rule in &self.rules {
+            match rule.resolve(req, depot).await {
+                Resolved::Skipped => {}
+                Resolved::Quota(key, quota) => applied.push((rule.name(), key, quota)),
+                Resolved::Failed => {
+                    res.status_code(StatusCode::INTERNAL_SERVER_ERROR);
+                    ctrl.skip_rest();
+                    return;
+                }
+            }
+        }
+        if applied.is_empty() {
+            return;
+        }
+
+        // Check all rules first, so that quota is not consumed by requests rejected by other rules. The lock
+        // keeps concurrent requests from taking the quota between the check and the consumption.
+        let lock = self.lock.lock().await;
+        let mut results = Vec::with_capacity(applied.len());
+        for (_, key, quota) in &applied {
+            match self
+                .store
+                .peek(key.clone(), &self.guard, quota, self.cost)
+                .await
+            {
+                Ok(result) => results.push(result),
+                Err(e) => {
+                    tracing::error!(error = ?e, "RateLimiter error: {}", e);
+                    res.status_code(StatusCode::INTERNAL_SERVER_ERROR);
+                    ctrl.skip_rest();
+                    return;
+                }
+            }
+        }
+        if results.iter().all(|(verified, _)| *verified) {
+            for ((_, key, quota), result) in applied.iter().zip(results.iter_mut()) {
+                match self
+                    .store
+                    .consume(key.clone(), &self.guard, quota, self.cost)
+                    .await
+                {
+                    Ok(consumed) => *result = consumed,
+                    Err(e) => {
+                        tracing::error!(error = ?e, "RateLimiter error: {}", e);
+                        res.status_code(StatusCode::INTERNAL_SERVER_ERROR);
+                        ctrl.skip_rest();
+                        return;
+                    }
+                }
+                // A limiter sharing the store took the quota after the check.
+                if !result.0 {
+                    break;
+                }
+            }
+        }
+        drop(lock);
+        let verified = results.iter().all(|(verified, _)| *verified);
+
+        // The most restrictive rule.
+        let mut index = 0;
+        let mut strictest = None;
+        for (i, ((_, _, quota), (passed, guard))) in applied.iter().zip(&results).enumerate() {
+            let rank = if verified {
+                -(guard.remaining(quota).await as i64)
+            } else if !passed {
+                guard.retry_after(quota, self.cost).await
+            } else {
+                continue;
+            };
+            if strictest < Some(rank) {
+                strictest = Some(rank);
+                index = i;
+            }
+        }
+        let (name, _, quota) = &applied[index];
+        let guard = &results[index].1;
+
+        if self.shadow {
+            if !verified {
+                tracing::warn!(
+                    path = req.uri().path(),
+                    rule = %name,
+                    "RateLimiter would reject the request"
+                );
+            }
+            return;
+        }
+
+        if self.add_headers {
+            add_legacy_headers(res, guard, quota).await;
+        }
+        if self.standard_headers {
+            let mut policies = Vec::with_capacity(applied.len());
+            for ((name, _, quota), (_, guard)) in applied.iter().zip(&results) {
+                policies.push(policy_item(name, guard, quota).await);
+            }
+            let limit = limit_item(name, guard, quota).await;
+            insert_header(res, "RateLimit-Policy", &policies.join(", "));
+            insert_header(res, "RateLimit", &limit);
+        }
+        if !verified {
+            let retry_after = guard.retry_after(quota, self.cost).await;
+            self.rejection
+                .reject(retry_after, req, depot, res, ctrl)
+                .await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::convert::Infallible;
+    use std::sync::Arc;
+
+    use salvo_core::prelude::*;
+    use salvo_core::test::TestClient;
+    use tracing_test::traced_test;
+
+    use super::*;
+    use crate::{BasicQuota, FixedGuard, MokaStore};
+
+    #[handler]
+    async fn limited() -> &'static str {
+        "Limited page"
+    }
+
+    #[tokio::test]
+    async fn test_composite_limiter() {
+        let limiter = CompositeLimiter::new(FixedGuard::new(), MokaStore::new())
+            .rule(RateRule::new(
+                "user",
+                |req: &mut Request, _: &Depot| req.query::<String>("user"),
+                BasicQuota::per_minute(2),
+            ))
+            .rule(RateRule::new(
+                "global",
+                |_: &mut Request, _: &Depot| Some("all".to_owned()),
+                BasicQuota::per_minute(3),
+            ))
+            .standard_headers(true);
+        let router = Router::new().push(Router::with_path("limited").hoop(limiter).get(limited));
+        let service = Service::new(router);
+
+        for _ in 0..2 {
+            let respone = TestClient::get("http://127.0.0.1:5800/limited?user=user1")
+                .send(&service)
+                .await;
+            assert_eq!(respone.status_code, Some(StatusCode::OK));
+        }
+        let respone = TestClient::get("http://127.0.0.1:5800/limited?user=user1")
+            .send(&service)
+            .await;
+        assert_eq!(respone.status_code, Some(StatusCode::TOO_MANY_REQUESTS));
+        assert_eq!(
+            respone.headers()["ratelimit-policy"],
+            "\"user\";q=2;w=60, \"global\";q=3;w=60"
+        );
+        assert!(respone.headers()["ratelimit"]
+            .to_str()
+            .unwrap()
+            .starts_with("\"user\";r=0;"));
+
+        // The rejected request does not consume the global quota.
+        let respone = TestClient::get("http://127.0.0.1:5800/limited?user=user2")
+            .send(&service)
+            .await;
+        assert_eq!(respone.status_code, Some(StatusCode::OK));
+        assert!(respone.headers()["ratelimit"]
+            .to_str()
+            .unwrap()
+            .starts_with("\"global\";r=0;"));
+
+        let respone = TestClient::get("http://127.0.0.1:5800/limited")
+            .send(&service)
+            .await;
+        assert_eq!(respone.status_code, Some(StatusCode::TOO_MANY_REQUESTS));
+        assert_eq!(respone.headers()["ratelimit-policy"], "\"global\";q=3;w=60");
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_composite_concurrent() {
+        // Gives concurrent requests the chance to take the quota between the check and the consumption.
+        struct SlowStore(MokaStore<String, FixedGuard>);
+        impl RateStore for SlowStore {
+            type Error = Infallible;
+            type Key = String;
+            type Guard = FixedGuard;
+
+            async fn consume(
+                &self,
+                key: String,
+                refer: &FixedGuard,
+                quota: &BasicQuota,
+                cost: usize,
+            ) -> Result<(bool, FixedGuard), Infallible> {
+                self.0.consume(key, refer, quota, cost).await
+            }
+
+            async fn peek(
+                &self,
+                key: String,
+                refer: &FixedGuard,
+                quota: &BasicQuota,
+                cost: usize,
+            ) -> Result<(bool, FixedGuard), Infallible> {
+                let result = self.0.peek(key, refer, quota, cost).await;
+                tokio::time::sleep(tokio::time::Duration::from_millis(20)).await;
+                result
+            }
+        }
+
+        let limiter = CompositeLimiter::new(FixedGuard::new(), SlowStore(MokaStore::new()))
+            .rule(RateRule::new(
+                "global",
+                |_: &mut Request, _: &Depot| Some("all".to_owned()),
+                BasicQuota::per_minute(10),
+            ))
+            .rule(RateRule::new(
+                "user",
+                |req: &mut Request, _: &Depot| req.query::<String>("user"),
+                BasicQuota::per_minute(1),
+            ));
+        let router = Router::new().push(Router::with_path("limited").hoop(limiter).get(limited));
+        let service = Arc::new(Service::new(router));
+
+        let tasks = (0..20)
+            .map(|i| {
+                let service = service.clone();
+                tokio::spawn(async move {
+                    TestClient::get(format!("http://127.0.0.1:5800/limited?user=user{}", i % 5))
+                        .send(&*service)
+                        .await
+                        .status_code
+                })
+            })
+            .collect::<Vec<_>>();
+        let mut passed = 0;
+        for task in tasks {
+            if task.await.unwrap() == Some(StatusCode::OK) {
+                passed += 1;
+            }
+        }
+        assert_eq!(passed, 5);
+
+        // Requests rejected by the user rule do not consume the global quota.
+        let mut passed = 0;
+        for i in 5..15 {
+            let respone = TestClient::get(format!("http://127.0.0.1:5800/limited?user=user{i}"))
+                .send(&*service)
+                .await;
+            if respone.status_code == Some(StatusCode::OK) {
+                passed += 1;
+            }
+        }
+        assert_eq!(passed, 5);
+    }
+
+    #[tokio::test]
+    #[traced_test]
+    async fn test_composite_shadow() {
+        let shadow = CompositeLimiter::new(FixedGuard::new(), MokaStore::new())
+            .rule(RateRule::new(
+                "user",
+                |req: &mut Request, _: &Depot| req.query::<String>("user"),
+                BasicQuota::per_minute(1),
+            ))
+            .shadow(true);
+        let router = Router::new().push(Router::with_path("shadow").hoop(shadow).get(limited));
+        let service = Service::new(router);
+
+        let respone = TestClient::get("http://127.0.0.1:5800/shadow?user=user1")
+            .send(&service)
+            .await;
+        assert_eq!(respone.status_code, Some(StatusCode::OK));
+        assert!(!logs_contain("RateLimiter would reject the request"));
+
+        // Shadow mode consumes the quota and only logs the rejection.
+        let respone = TestClient::get("http://127.0.0.1:5800/shadow?user=user1")
+            .send(&service)
+            .await;
+        assert_eq!(respone.status_code, Some(StatusCode::OK));
+        assert!(logs_contain("RateLimiter would reject the request"));
+    }
+}
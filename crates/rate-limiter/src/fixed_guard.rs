@@ -31,6 +31,10 @@ impl FixedGuard {
 impl RateGuard for FixedGuard {
     type Quota = BasicQuota;
     async fn verify(&mut self, quota: &Self::Quota) -> bool {
+        self.verify_cost(quota, 1).await
+    }
+
+    async fn verify_cost(&mut self, quota: &Self::Quota, cost: usize) -> bool {
         if self.quota.is_none() || OffsetDateTime::now_utc() > self.reset || self.quota.as_ref() != Some(quota) {
             if self.quota.as_ref() != Some(quota) {
                 let mut quota = quota.clone();
@@ -40,10 +44,10 @@ impl RateGuard for FixedGuard {
                 self.quota = Some(quota);
             }
             self.reset = OffsetDateTime::now_utc() + quota.period;
-            self.count = 1;
-            true
-        } else if self.count < quota.limit {
-            self.count += 1;
+            self.count = 0;
+        }
+        if self.count + cost <= quota.limit.max(1) {
+            self.count += cost;
             true
         } else {
             false
@@ -51,7 +55,7 @@ impl RateGuard for FixedGuard {
     }
 
     async fn remaining(&self, quota: &Self::Quota) -> usize {
-        quota.limit - self.count
+        quota.limit.saturating_sub(self.count)
     }
 
     async fn reset(&self, _: &Self::Quota) -> i64 {
@@ -61,4 +65,8 @@ impl RateGuard for FixedGuard {
     async fn limit(&self, quota: &Self::Quota) -> usize {
         quota.limit
     }
+
+    async fn window(&self, quota: &Self::Quota) -> Option<i64> {
+        Some(quota.period.whole_seconds())
+    }
 }
@@ -0,0 +1,74 @@
+use serde::{Deserialize, Serialize};
+use time::OffsetDateTime;
+
+use super::{BurstQuota, RateGuard};
+
+/// Generic cell rate algorithm (GCRA) implement.
+///
+/// Only the theoretical arrival time of the next request is kept, which makes the guard cheap to save in
+/// external stores. It allows the same bursts as a token bucket with the same quota.
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct GcraGuard {
+    tat: OffsetDateTime,
+}
+
+impl Default for GcraGuard {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl GcraGuard {
+    /// Create a new `GcraGuard`.
+    pub fn new() -> Self {
+        Self {
+            tat: OffsetDateTime::UNIX_EPOCH,
+        }
+    }
+}
+
+impl RateGuard for GcraGuard {
+    type Quota = BurstQuota;
+    async fn verify(&mut self, quota: &Self::Quota) -> bool {
+        self.verify_cost(quota, 1).await
+    }
+
+    async fn verify_cost(&mut self, quota: &Self::Quota, cost: usize) -> bool {
+        let now = OffsetDateTime::now_utc();
+        let interval = quota.interval();
+        let tat = self.tat.max(now) + interval * cost as f64;
+        if tat - now > interval * quota.burst.max(1) as f64 {
+            false
+        } else {
+            self.tat = tat;
+            true
+        }
+    }
+
+    async fn remaining(&self, quota: &Self::Quota) -> usize {
+        let now = OffsetDateTime::now_utc();
+        let used = (self.tat.max(now) - now) / quota.interval();
+        (quota.burst.max(1) as f64 - used).max(0.0) as usize
+    }
+
+    async fn reset(&self, _: &Self::Quota) -> i64 {
+        self.tat.max(OffsetDateTime::now_utc()).unix_timestamp()
+    }
+
+    async fn limit(&self, quota: &Self::Quota) -> usize {
+        quota.burst
+    }
+
+    async fn window(&self, quota: &Self::Quota) -> Option<i64> {
+        Some((quota.interval() * quota.burst.max(1) as f64).whole_seconds())
+    }
+
+    async fn retry_after(&self, quota: &Self::Quota, cost: usize) -> i64 {
+        let interval = quota.interval();
+        let ready = self.tat - interval * (quota.burst.max(1) as f64 - cost as f64);
+        (ready - OffsetDateTime::now_utc())
+            .as_seconds_f64()
+            .ceil()
+            .max(0.0) as i64
+    }
+}
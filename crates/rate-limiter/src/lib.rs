@@ -10,23 +10,34 @@
 //!
 //! [`RateGuard`] is strategy to verify is the request exceeded quota.
 //!
+//! [`CompositeLimiter`] applies several [`RateRule`]s, such as per-IP, per-user and global limits, in one
+//! middleware.
+//!
+//! [`RateStore`] saves guards and verifies requests atomically, [`CasStore`] builds one on top of
+//! any store supporting compare-and-swap.
+//!
 //! Read more: <https://salvo.rs>
 #![doc(html_favicon_url = "https://salvo.rs/favicon-32x32.png")]
 #![doc(html_logo_url = "https://salvo.rs/images/logo.svg")]
 #![cfg_attr(docsrs, feature(doc_cfg))]
 
-use std::borrow::Borrow;
 use std::error::Error as StdError;
 use std::future::Future;
 use std::hash::Hash;
 
 use salvo_core::conn::SocketAddr;
 use salvo_core::handler::{none_skipper, Skipper};
+use salvo_core::http::header::RETRY_AFTER;
 use salvo_core::http::{HeaderValue, Request, Response, StatusCode, StatusError};
 use salvo_core::{async_trait, Depot, FlowCtrl, Handler};
+use time::OffsetDateTime;
 
+mod cas_store;
+mod composite;
 mod quota;
-pub use quota::{BasicQuota, CelledQuota, QuotaGetter};
+pub use cas_store::{CasError, CasRateStore, CasStore};
+pub use composite::{CompositeLimiter, RateRule};
+pub use quota::{BasicQuota, BurstQuota, CelledQuota, QuotaGetter};
 #[macro_use]
 mod cfg;
 
@@ -51,6 +62,20 @@ cfg_feature! {
     pub use sliding_guard::SlidingGuard;
 }
 
+cfg_feature! {
+    #![feature = "token-bucket-guard"]
+
+    mod token_bucket_guard;
+    pub use token_bucket_guard::TokenBucketGuard;
+}
+
+cfg_feature! {
+    #![feature = "gcra-guard"]
+
+    mod gcra_guard;
+    pub use gcra_guard::GcraGuard;
+}
+
 /// Issuer is used to identify every request.
 pub trait RateIssuer: Send + Sync + 'static {
     /// The key is used to identify the rate limit.
@@ -93,6 +118,18 @@ pub trait RateGuard: Clone + Send + Sync + 'static {
     /// Verify is current request exceed the quota.
     fn verify(&mut self, quota: &Self::Quota) -> impl Future<Output = bool> + Send;
 
+    /// Verify is current request exceed the quota, the request consumes `cost` units of the quota.
+    ///
+    /// The default implementation ignores `cost` and calls [`verify`](RateGuard::verify).
+    fn verify_cost(
+        &mut self,
+        quota: &Self::Quota,
+        cost: usize,
+    ) -> impl Future<Output = bool> + Send {
+        let _ = cost;
+        self.verify(quota)
+    }
+
     /// Returns the remaining quota.
     fn remaining(&self, quota: &Self::Quota) -> impl Future<Output = usize> + Send;
 
@@ -101,31 +138,67 @@ pub trait RateGuard: Clone + Send + Sync + 'static {
 
     /// Returns the limit.
     fn limit(&self, quota: &Self::Quota) -> impl Future<Output = usize> + Send;
+
+    /// Returns the time window of the limit in seconds, used by the `RateLimit-Policy` header.
+    ///
+    /// The default implementation returns `None`, and the window is omitted from the header.
+    fn window(&self, quota: &Self::Quota) -> impl Future<Output = Option<i64>> + Send {
+        let _ = quota;
+        async { None }
+    }
+
+    /// Returns the seconds to wait before a request consuming `cost` units of the quota may be verified again,
+    /// used by the `Retry-After` header.
+    ///
+    /// The default implementation ignores `cost` and returns the seconds until [`reset`](RateGuard::reset).
+    fn retry_after(&self, quota: &Self::Quota, cost: usize) -> impl Future<Output = i64> + Send {
+        let _ = cost;
+        async move { (self.reset(quota).await - OffsetDateTime::now_utc().unix_timestamp()).max(0) }
+    }
 }
 
 /// `RateStore` is used to store rate limit data.
+///
+/// The guard of a key must be loaded, verified and saved as one atomic operation, otherwise concurrent
+/// requests with the same key overwrite each other's updates and exceed the quota. Stores which only support
+/// compare-and-swap can implement [`CasRateStore`] and be wrapped in [`CasStore`].
 pub trait RateStore: Send + Sync + 'static {
     /// Error type for RateStore.
     type Error: StdError;
     /// Key
     type Key: Hash + Eq + Send + Clone + 'static;
     /// Saved guard.
-    type Guard;
-    /// Get the guard from the store.
-    fn load_guard<Q>(
+    type Guard: RateGuard;
+    /// Atomically verify the request with the guard of the key and save the updated guard.
+    ///
+    /// `refer` is used when there is no guard for the key yet and `cost` is passed to
+    /// [`RateGuard::verify_cost`]. Returns whether the request is verified and the updated guard.
+    fn consume(
         &self,
-        key: &Q,
+        key: Self::Key,
         refer: &Self::Guard,
-    ) -> impl Future<Output = Result<Self::Guard, Self::Error>> + Send
-    where
-        Self::Key: Borrow<Q>,
-        Q: Hash + Eq + Sync;
-    /// Save the guard from the store.
-    fn save_guard(
+        quota: &<Self::Guard as RateGuard>::Quota,
+        cost: usize,
+    ) -> impl Future<Output = Result<(bool, Self::Guard), Self::Error>> + Send;
+
+    /// Verify the request like [`consume`](RateStore::consume), but without saving the updated guard.
+    ///
+    /// The default implementation verifies with `refer` as if there were no guard for the key, stores
+    /// should override it to check the saved guard.
+    fn peek(
         &self,
         key: Self::Key,
-        guard: Self::Guard,
-    ) -> impl Future<Output = Result<(), Self::Error>> + Send;
+        refer: &Self::Guard,
+        quota: &<Self::Guard as RateGuard>::Quota,
+        cost: usize,
+    ) -> impl Future<Output = Result<(bool, Self::Guard), Self::Error>> + Send {
+        let _ = key;
+        let mut guard = refer.clone();
+        async move {
+            let verified = guard.verify_cost(quota, cost).await;
+            Ok((verified, guard))
+        }
+    }
 }
 
 /// `RateLimiter` is the main struct to used limit user request.
@@ -134,10 +207,20 @@ pub struct RateLimiter<G, S, I, Q> {
     store: S,
     issuer: I,
     quota_getter: Q,
+    cost: usize,
     add_headers: bool,
+    standard_headers: bool,
+    policy_name: String,
+    rejection: Rejection,
+    shadow: bool,
     skipper: Box<dyn Skipper>,
 }
 
+enum Rejection {
+    Error(StatusError),
+    Handler(Box<dyn Handler>),
+}
+
 impl<G: RateGuard, S: RateStore, I: RateIssuer, P: QuotaGetter<I::Key>> RateLimiter<G, S, I, P> {
     /// Create a new `RateLimiter`
     #[inline]
@@ -147,7 +230,12 @@ impl<G: RateGuard, S: RateStore, I: RateIssuer, P: QuotaGetter<I::Key>> RateLimi
             store,
             issuer,
             quota_getter,
+            cost: 1,
             add_headers: false,
+            standard_headers: false,
+            policy_name: "default".into(),
+            rejection: Rejection::Error(StatusError::too_many_requests()),
+            shadow: false,
             skipper: Box::new(none_skipper),
         }
     }
@@ -159,6 +247,16 @@ impl<G: RateGuard, S: RateStore, I: RateIssuer, P: QuotaGetter<I::Key>> RateLimi
         self
     }
 
+    /// Sets the units of quota consumed by every request and returns new `RateLimiter`, default is 1.
+    ///
+    /// Limiters sharing the same store and issuer consume the same quota, so expensive endpoints can
+    /// use a limiter with a higher cost.
+    #[inline]
+    pub fn cost(mut self, cost: usize) -> Self {
+        self.cost = cost;
+        self
+    }
+
     /// Sets `add_headers` and returns new `RateLimiter`.
     /// If `add_headers` is true, the rate limit headers will be added to the response.
     #[inline]
@@ -166,6 +264,56 @@ impl<G: RateGuard, S: RateStore, I: RateIssuer, P: QuotaGetter<I::Key>> RateLimi
         self.add_headers = add_headers;
         self
     }
+
+    /// Sets `standard_headers` and returns new `RateLimiter`.
+    /// If `standard_headers` is true, the IETF `RateLimit-Policy` and `RateLimit` headers will be added to
+    /// the response.
+    #[inline]
+    pub fn standard_headers(mut self, standard_headers: bool) -> Self {
+        self.standard_headers = standard_headers;
+        self
+    }
+
+    /// Sets the policy name used in the `RateLimit-Policy` and `RateLimit` headers and returns new
+    /// `RateLimiter`, default is `default`.
+    ///
+    /// The headers are not added if the name is not a valid header value.
+    #[inline]
+    pub fn policy_name(mut self, policy_name: impl Into<String>) -> Self {
+        self.policy_name = policy_name.into();
+        self
+    }
+
+    /// Sets the error rendered when a request is rejected and returns new `RateLimiter`.
+    ///
+    /// Default is `StatusError::too_many_requests()`. The `cause` of the error is not rendered.
+    #[inline]
+    pub fn rejection_error(mut self, error: StatusError) -> Self {
+        self.rejection = Rejection::Error(error);
+        self
+    }
+
+    /// Sets the handler called when a request is rejected and returns new `RateLimiter`.
+    ///
+    /// The status code is already set to `429 Too Many Requests` and the rate limit headers are already
+    /// added when the handler is called.
+    #[inline]
+    pub fn rejection(mut self, handler: impl Handler) -> Self {
+        self.rejection = Rejection::Handler(Box::new(handler));
+        self
+    }
+
+    /// Sets `shadow` and returns new `RateLimiter`.
+    /// If `shadow` is true, requests consume the quota as usual, but requests exceeding it are only logged and
+    /// not rejected, and no headers are added to the response. It is useful to try out new quotas.
+    ///
+    /// A shadow limiter should have its own store, otherwise its consumption is counted by the other limiters
+    /// sharing the store.
+    #[inline]
+    pub fn shadow(mut self, shadow: bool) -> Self {
+        self.shadow = shadow;
+        self
+    }
 }
 
 #[async_trait]
@@ -203,8 +351,12 @@ where
                 return;
             }
         };
-        let mut guard = match self.store.load_guard(&key, &self.guard).await {
-            Ok(guard) => guard,
+        let (verified, guard) = match self
+            .store
+            .consume(key, &self.guard, &quota, self.cost)
+            .await
+        {
+            Ok(consumed) => consumed,
             Err(e) => {
                 tracing::error!(error = ?e, "RateLimiter error: {}", e);
                 res.status_code(StatusCode::INTERNAL_SERVER_ERROR);
@@ -212,43 +364,119 @@ where
                 return;
             }
         };
-        let verified = guard.verify(&quota).await;
+
+        if self.shadow {
+            if !verified {
+                tracing::warn!(
+                    path = req.uri().path(),
+                    "RateLimiter would reject the request"
+                );
+            }
+            return;
+        }
 
         if self.add_headers {
-            res.headers_mut().insert(
-                "X-RateLimit-Limit",
-                HeaderValue::from_str(&guard.limit(&quota).await.to_string())
-                    .expect("Invalid header value"),
-            );
-            res.headers_mut().insert(
-                "X-RateLimit-Remaining",
-                HeaderValue::from_str(&(guard.remaining(&quota).await).to_string())
-                    .expect("Invalid header value"),
-            );
-            res.headers_mut().insert(
-                "X-RateLimit-Reset",
-                HeaderValue::from_str(&guard.reset(&quota).await.to_string())
-                    .expect("Invalid header value"),
-            );
+            add_legacy_headers(res, &guard, &quota).await;
+        }
+        if self.standard_headers {
+            let policy = policy_item(&self.policy_name, &guard, &quota).await;
+            let limit = limit_item(&self.policy_name, &guard, &quota).await;
+            insert_header(res, "RateLimit-Policy", &policy);
+            insert_header(res, "RateLimit", &limit);
         }
         if !verified {
-            res.status_code(StatusCode::TOO_MANY_REQUESTS);
-            ctrl.skip_rest();
+            let retry_after = guard.retry_after(&quota, self.cost).await;
+            self.rejection
+                .reject(retry_after, req, depot, res, ctrl)
+                .await;
         }
-        if let Err(e) = self.store.save_guard(key, guard).await {
-            tracing::error!(error = ?e, "RateLimiter save guard failed");
+    }
+}
+
+impl Rejection {
+    async fn reject(
+        &self,
+        retry_after: i64,
+        req: &mut Request,
+        depot: &mut Depot,
+        res: &mut Response,
+        ctrl: &mut FlowCtrl,
+    ) {
+        res.headers_mut()
+            .insert(RETRY_AFTER, HeaderValue::from(retry_after.max(0)));
+        res.status_code(StatusCode::TOO_MANY_REQUESTS);
+        match self {
+            Rejection::Error(e) => {
+                let mut error = StatusError::too_many_requests();
+                error.code = e.code;
+                error.name.clone_from(&e.name);
+                error.brief.clone_from(&e.brief);
+                error.detail.clone_from(&e.detail);
+                res.render(error);
+            }
+            Rejection::Handler(handler) => handler.handle(req, depot, res, ctrl).await,
         }
+        ctrl.skip_rest();
     }
 }
 
+fn insert_header(res: &mut Response, name: &'static str, value: &str) {
+    match HeaderValue::from_str(value) {
+        Ok(value) => {
+            res.headers_mut().insert(name, value);
+        }
+        Err(_) => tracing::warn!(header = name, value, "invalid rate limit header value"),
+    }
+}
+
+async fn add_legacy_headers<G: RateGuard>(res: &mut Response, guard: &G, quota: &G::Quota) {
+    insert_header(
+        res,
+        "X-RateLimit-Limit",
+        &guard.limit(quota).await.to_string(),
+    );
+    insert_header(
+        res,
+        "X-RateLimit-Remaining",
+        &guard.remaining(quota).await.to_string(),
+    );
+    insert_header(
+        res,
+        "X-RateLimit-Reset",
+        &guard.reset(quota).await.to_string(),
+    );
+}
+
+/// Item of the `RateLimit-Policy` header.
+async fn policy_item<G: RateGuard>(name: &str, guard: &G, quota: &G::Quota) -> String {
+    let mut item = format!("\"{name}\";q={}", guard.limit(quota).await);
+    if let Some(window) = guard.window(quota).await {
+        item.push_str(&format!(";w={window}"));
+    }
+    item
+}
+
+/// Item of the `RateLimit` header.
+async fn limit_item<G: RateGuard>(name: &str, guard: &G, quota: &G::Quota) -> String {
+    let reset = guard.reset(quota).await - OffsetDateTime::now_utc().unix_timestamp();
+    format!(
+        "\"{name}\";r={};t={}",
+        guard.remaining(quota).await,
+        reset.max(0)
+    )
+}
+
 #[cfg(test)]
 mod tests {
+    use std::borrow::Borrow;
     use std::collections::HashMap;
-    use std::sync::LazyLock;
+    use std::convert::Infallible;
+    use std::sync::{Arc, LazyLock};
 
     use salvo_core::prelude::*;
     use salvo_core::test::{ResponseExt, TestClient};
     use salvo_core::Error;
+    use tracing_test::traced_test;
 
     use super::*;
 
@@ -424,4 +652,225 @@ mod tests {
         assert_eq!(respone.status_code, Some(StatusCode::OK));
         assert_eq!(respone.take_string().await.unwrap(), "Limited page");
     }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_concurrent_requests() {
+        let limiter = RateLimiter::new(
+            FixedGuard::default(),
+            MokaStore::default(),
+            UserIssuer,
+            BasicQuota::per_minute(10),
+        );
+        let router = Router::new().push(Router::with_path("limited").hoop(limiter).get(limited));
+        let service = Arc::new(Service::new(router));
+
+        let tasks = (0..100)
+            .map(|_| {
+                let service = service.clone();
+                tokio::spawn(async move {
+                    TestClient::get("http://127.0.0.1:5800/limited?user=user1")
+                        .send(&*service)
+                        .await
+                        .status_code
+                })
+            })
+            .collect::<Vec<_>>();
+        let mut passed = 0;
+        for task in tasks {
+            if task.await.unwrap() == Some(StatusCode::OK) {
+                passed += 1;
+            }
+        }
+        assert_eq!(passed, 10);
+    }
+
+    async fn check_burst<G>(guard: G)
+    where
+        G: RateGuard<Quota = BurstQuota>,
+    {
+        let store = MokaStore::default();
+        let quota = BurstQuota::per_minute(60, 5);
+        let cheap = RateLimiter::new(guard.clone(), store.clone(), UserIssuer, quota.clone());
+        let expensive = RateLimiter::new(guard, store, UserIssuer, quota).cost(5);
+        let router = Router::new()
+            .push(Router::with_path("limited").hoop(cheap).get(limited))
+            .push(Router::with_path("expensive").hoop(expensive).get(limited));
+        let service = Service::new(router);
+
+        for _ in 0..5 {
+            let respone = TestClient::get("http://127.0.0.1:5800/limited?user=user1")
+                .send(&service)
+                .await;
+            assert_eq!(respone.status_code, Some(StatusCode::OK));
+        }
+        let respone = TestClient::get("http://127.0.0.1:5800/limited?user=user1")
+            .send(&service)
+            .await;
+        assert_eq!(respone.status_code, Some(StatusCode::TOO_MANY_REQUESTS));
+
+        let respone = TestClient::get("http://127.0.0.1:5800/expensive?user=user2")
+            .send(&service)
+            .await;
+        assert_eq!(respone.status_code, Some(StatusCode::OK));
+        let respone = TestClient::get("http://127.0.0.1:5800/limited?user=user2")
+            .send(&service)
+            .await;
+        assert_eq!(respone.status_code, Some(StatusCode::TOO_MANY_REQUESTS));
+
+        tokio::time::sleep(tokio::time::Duration::from_millis(1100)).await;
+        let respone = TestClient::get("http://127.0.0.1:5800/limited?user=user2")
+            .send(&service)
+            .await;
+        assert_eq!(respone.status_code, Some(StatusCode::OK));
+        let respone = TestClient::get("http://127.0.0.1:5800/expensive?user=user2")
+            .send(&service)
+            .await;
+        assert_eq!(respone.status_code, Some(StatusCode::TOO_MANY_REQUESTS));
+        // Five units are needed, about one second of refill is not enough.
+        let retry_after: i64 = respone.headers()[RETRY_AFTER]
+            .to_str()
+            .unwrap()
+            .parse()
+            .unwrap();
+        assert!(retry_after >= 4);
+    }
+
+    #[tokio::test]
+    async fn test_token_bucket_guard() {
+        check_burst(TokenBucketGuard::default()).await;
+    }
+
+    #[tokio::test]
+    async fn test_gcra_guard() {
+        check_burst(GcraGuard::default()).await;
+    }
+
+    #[tokio::test]
+    async fn test_default_peek() {
+        struct ConsumeOnly(MokaStore<String, FixedGuard>);
+        impl RateStore for ConsumeOnly {
+            type Error = Infallible;
+            type Key = String;
+            type Guard = FixedGuard;
+
+            async fn consume(
+                &self,
+                key: String,
+                refer: &FixedGuard,
+                quota: &BasicQuota,
+                cost: usize,
+            ) -> Result<(bool, FixedGuard), Infallible> {
+                self.0.consume(key, refer, quota, cost).await
+            }
+        }
+
+        let store = ConsumeOnly(MokaStore::default());
+        let quota = BasicQuota::per_minute(1);
+        let refer = FixedGuard::default();
+        for _ in 0..2 {
+            let (verified, _) = store.peek("user1".into(), &refer, &quota, 1).await.unwrap();
+            assert!(verified);
+        }
+        let (verified, _) = store
+            .consume("user1".into(), &refer, &quota, 1)
+            .await
+            .unwrap();
+        assert!(verified);
+    }
+
+    #[tokio::test]
+    async fn test_headers_and_rejection() {
+        let limiter = RateLimiter::new(
+            FixedGuard::default(),
+            MokaStore::default(),
+            UserIssuer,
+            BasicQuota::per_minute(1),
+        )
+        .standard_headers(true)
+        .policy_name("minute")
+        .rejection_error(StatusError::too_many_requests().brief("Slow down."));
+        let shadow = RateLimiter::new(
+            FixedGuard::default(),
+            MokaStore::default(),
+            UserIssuer,
+            BasicQuota::per_minute(1),
+        )
+        .standard_headers(true)
+        .shadow(true);
+        let strict = RateLimiter::new(
+            FixedGuard::default(),
+            MokaStore::default(),
+            UserIssuer,
+            BasicQuota::per_minute(1),
+        )
+        .standard_headers(true)
+        .policy_name("bad\nname");
+        let router = Router::new()
+            .push(Router::with_path("limited").hoop(limiter).get(limited))
+            .push(Router::with_path("shadow").hoop(shadow).get(limited))
+            .push(Router::with_path("strict").hoop(strict).get(limited));
+        let service = Service::new(router);
+
+        let respone = TestClient::get("http://127.0.0.1:5800/limited?user=user1")
+            .send(&service)
+            .await;
+        assert_eq!(respone.status_code, Some(StatusCode::OK));
+        assert_eq!(respone.headers()["ratelimit-policy"], "\"minute\";q=1;w=60");
+        let limit = respone.headers()["ratelimit"].to_str().unwrap();
+        assert!(limit.starts_with("\"minute\";r=0;t="));
+
+        let mut respone = TestClient::get("http://127.0.0.1:5800/limited?user=user1")
+            .send(&service)
+            .await;
+        assert_eq!(respone.status_code, Some(StatusCode::TOO_MANY_REQUESTS));
+        let retry_after: i64 = respone.headers()[RETRY_AFTER]
+            .to_str()
+            .unwrap()
+            .parse()
+            .unwrap();
+        assert!(retry_after > 0 && retry_after <= 60);
+        assert!(respone.take_string().await.unwrap().contains("Slow down."));
+
+        for _ in 0..3 {
+            let respone = TestClient::get("http://127.0.0.1:5800/shadow?user=user1")
+                .send(&service)
+                .await;
+            assert_eq!(respone.status_code, Some(StatusCode::OK));
+            assert!(!respone.headers().contains_key("ratelimit"));
+        }
+        // Invalid policy names only skip the headers.
+        let respone = TestClient::get("http://127.0.0.1:5800/strict?user=user1")
+            .send(&service)
+            .await;
+        assert_eq!(respone.status_code, Some(StatusCode::OK));
+        assert!(!respone.headers().contains_key("ratelimit"));
+    }
+
+    #[tokio::test]
+    #[traced_test]
+    async fn test_shadow() {
+        let limiter = RateLimiter::new(
+            FixedGuard::default(),
+            MokaStore::default(),
+            UserIssuer,
+            BasicQuota::per_minute(1),
+        )
+        .shadow(true);
+        let router = Router::new().push(Router::with_path("limited").hoop(limiter).get(limited));
+        let service = Service::new(router);
+
+        let respone = TestClient::get("http://127.0.0.1:5800/limited?user=user1")
+            .send(&service)
+            .await;
+        assert_eq!(respone.status_code, Some(StatusCode::OK));
+        assert!(!logs_contain("RateLimiter would reject the request"));
+
+        for _ in 0..2 {
+            let respone = TestClient::get("http://127.0.0.1:5800/limited?user=user1")
+                .send(&service)
+                .await;
+            assert_eq!(respone.status_code, Some(StatusCode::OK));
+        }
+        assert!(logs_contain("RateLimiter would reject the request"));
+    }
 }
@@ -1,13 +1,16 @@
-use std::borrow::Borrow;
 use std::convert::Infallible;
 use std::hash::Hash;
 
 use moka::future::Cache as MokaCache;
+use moka::ops::compute::Op;
 
 use super::{RateGuard, RateStore};
 
 /// A simple in-memory store for rate limiter.
-#[derive(Debug)]
+///
+/// Guards are verified and updated while holding a per-key lock, so concurrent requests with the same key
+/// never lose updates. Clones share the same guards.
+#[derive(Clone, Debug)]
 pub struct MokaStore<K, G>
 where
     K: Hash + Eq + Send + Sync + Clone + 'static,
@@ -46,21 +49,44 @@ where
     type Key = K;
     type Guard = G;
 
-    async fn load_guard<Q>(&self, key: &Q, refer: &Self::Guard) -> Result<Self::Guard, Self::Error>
-    where
-        Self::Key: Borrow<Q>,
-        Q: Hash + Eq + Sync,
-    {
-        let guard = self.inner.get(key).await;
-        if let Some(guard) = guard {
-            Ok(guard)
-        } else {
-            Ok(refer.clone())
-        }
+    async fn consume(
+        &self,
+        key: Self::Key,
+        refer: &Self::Guard,
+        quota: &G::Quota,
+        cost: usize,
+    ) -> Result<(bool, Self::Guard), Self::Error> {
+        let mut verified = false;
+        let result = self
+            .inner
+            .entry(key)
+            .and_compute_with(|entry| {
+                let mut guard = entry
+                    .map(|e| e.into_value())
+                    .unwrap_or_else(|| refer.clone());
+                let verified = &mut verified;
+                async move {
+                    *verified = guard.verify_cost(quota, cost).await;
+                    Op::Put(guard)
+                }
+            })
+            .await;
+        let guard = result
+            .into_entry()
+            .expect("guard is always put")
+            .into_value();
+        Ok((verified, guard))
     }
 
-    async fn save_guard(&self, key: Self::Key, guard: Self::Guard) -> Result<(), Self::Error> {
-        self.inner.insert(key, guard).await;
-        Ok(())
+    async fn peek(
+        &self,
+        key: Self::Key,
+        refer: &Self::Guard,
+        quota: &G::Quota,
+        cost: usize,
+    ) -> Result<(bool, Self::Guard), Self::Error> {
+        let mut guard = self.inner.get(&key).await.unwrap_or_else(|| refer.clone());
+        let verified = guard.verify_cost(quota, cost).await;
+        Ok((verified, guard))
     }
 }
@@ -113,6 +113,64 @@ impl CelledQuota {
     }
 }
 
+/// A quota allows bursts, used by token bucket and GCRA guards.
+///
+/// Requests are replenished evenly at the rate of `limit` per `period`, and up to `burst` requests are
+/// allowed at once.
+#[non_exhaustive]
+#[derive(Serialize, Deserialize, Eq, PartialEq, Clone, Debug)]
+pub struct BurstQuota {
+    /// The limit of requests.
+    pub limit: usize,
+    /// The period of requests.
+    pub period: Duration,
+    /// The max requests allowed at once.
+    pub burst: usize,
+}
+impl BurstQuota {
+    /// Create new `BurstQuota`.
+    pub const fn new(limit: usize, burst: usize, period: Duration) -> Self {
+        Self {
+            limit,
+            burst,
+            period,
+        }
+    }
+
+    /// Sets the limit of the quota per second.
+    pub const fn per_second(limit: usize, burst: usize) -> Self {
+        Self::new(limit, burst, Duration::seconds(1))
+    }
+    /// Sets the limit of the quota seconds.
+    pub const fn set_seconds(limit: usize, burst: usize, seconds: i64) -> Self {
+        Self::new(limit, burst, Duration::seconds(seconds))
+    }
+
+    /// Sets the limit of the quota per minute.
+    pub const fn per_minute(limit: usize, burst: usize) -> Self {
+        Self::new(limit, burst, Duration::seconds(60))
+    }
+    /// Sets the limit of the quota minutes.
+    pub const fn set_minutes(limit: usize, burst: usize, minutes: i64) -> Self {
+        Self::new(limit, burst, Duration::seconds(60 * minutes))
+    }
+
+    /// Sets the limit of the quota per hour.
+    pub const fn per_hour(limit: usize, burst: usize) -> Self {
+        Self::new(limit, burst, Duration::seconds(3600))
+    }
+    /// Sets the limit of the quota hours.
+    pub const fn set_hours(limit: usize, burst: usize, hours: i64) -> Self {
+        Self::new(limit, burst, Duration::seconds(3600 * hours))
+    }
+
+    /// Time to replenish one request, the quota is treated as one request per period if it is empty.
+    #[cfg(any(feature = "token-bucket-guard", feature = "gcra-guard"))]
+    pub(crate) fn interval(&self) -> Duration {
+        self.period / self.limit.max(1) as f64
+    }
+}
+
 impl<Key, T> QuotaGetter<Key> for T
 where
     Key: Hash + Eq + Send + Sync + 'static,
@@ -193,4 +251,39 @@ mod tests {
         assert_eq!(quota.cells, 6);
         assert_eq!(quota.period, Duration::seconds(7200));
     }
+
+    #[test]
+    fn test_burst_quota() {
+        let quota = BurstQuota::per_second(10, 3);
+        assert_eq!(quota.limit, 10);
+        assert_eq!(quota.burst, 3);
+        assert_eq!(quota.period, Duration::seconds(1));
+
+        let quota = BurstQuota::set_minutes(15, 7, 2);
+        assert_eq!(quota.limit, 15);
+        assert_eq!(quota.burst, 7);
+        assert_eq!(quota.period, Duration::seconds(120));
+
+        let quota = BurstQuota::set_hours(15, 6, 2);
+        assert_eq!(quota.limit, 15);
+        assert_eq!(quota.burst, 6);
+        assert_eq!(quota.period, Duration::seconds(7200));
+    }
+
+    #[cfg(any(feature = "token-bucket-guard", feature = "gcra-guard"))]
+    #[test]
+    fn test_burst_quota_interval() {
+        assert_eq!(
+            BurstQuota::per_second(10, 3).interval(),
+            Duration::milliseconds(100)
+        );
+        assert_eq!(
+            BurstQuota::set_minutes(15, 7, 2).interval(),
+            Duration::seconds(8)
+        );
+        assert_eq!(
+            BurstQuota::per_second(0, 3).interval(),
+            Duration::seconds(1)
+        );
+    }
 }
@@ -34,6 +34,10 @@ impl SlidingGuard {
 impl RateGuard for SlidingGuard {
     type Quota = CelledQuota;
     async fn verify(&mut self, quota: &Self::Quota) -> bool {
+        self.verify_cost(quota, 1).await
+    }
+
+    async fn verify_cost(&mut self, quota: &Self::Quota, cost: usize) -> bool {
         if self.quota.is_none() || self.quota.as_ref() != Some(quota) {
             let mut quota = quota.clone();
             if quota.limit == 0 {
@@ -49,17 +53,18 @@ impl RateGuard for SlidingGuard {
             self.cell_span = quota.period / (quota.cells as u32);
             self.counts = vec![0; quota.cells];
             self.head = 0;
-            self.counts[0] = 1;
+            self.counts[0] = cost;
+            let limit = quota.limit;
             self.quota = Some(quota);
-            return true;
+            return cost <= limit;
         }
         let mut delta = OffsetDateTime::now_utc() - self.cell_inst;
         if delta > quota.period {
             self.counts = vec![0; quota.cells];
             self.head = 0;
-            self.counts[0] = 1;
+            self.counts[0] = cost;
             self.cell_inst = OffsetDateTime::now_utc();
-            return true;
+            return cost <= quota.limit.max(1);
         } else {
             while delta > self.cell_span {
                 delta -= self.cell_span;
@@ -67,7 +72,7 @@ impl RateGuard for SlidingGuard {
                 self.counts[self.head] = 0;
             }
             self.head = (self.head + 1) % self.counts.len();
-            self.counts[self.head] += 1;
+            self.counts[self.head] += cost;
             self.cell_inst = OffsetDateTime::now_utc();
         }
         self.counts.iter().cloned().sum::<usize>() <= quota.limit
@@ -87,4 +92,8 @@ impl RateGuard for SlidingGuard {
     async fn limit(&self, quota: &Self::Quota) -> usize {
         quota.limit
     }
+
+    async fn window(&self, quota: &Self::Quota) -> Option<i64> {
+        Some(quota.period.whole_seconds())
+    }
 }
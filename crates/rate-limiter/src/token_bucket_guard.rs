@@ -0,0 +1,86 @@
+use serde::{Deserialize, Serialize};
+use time::OffsetDateTime;
+
+use super::{BurstQuota, RateGuard};
+
+/// Token bucket implement.
+///
+/// The bucket holds up to `burst` tokens and is refilled at the rate of `limit` tokens per `period`, every
+/// request takes tokens from the bucket.
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct TokenBucketGuard {
+    tokens: f64,
+    updated: OffsetDateTime,
+    quota: Option<BurstQuota>,
+}
+
+impl Default for TokenBucketGuard {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl TokenBucketGuard {
+    /// Create a new `TokenBucketGuard`.
+    pub fn new() -> Self {
+        Self {
+            tokens: 0.0,
+            updated: OffsetDateTime::now_utc(),
+            quota: None,
+        }
+    }
+
+    fn refill(&mut self, quota: &BurstQuota, now: OffsetDateTime) {
+        let capacity = quota.burst.max(1) as f64;
+        if self.quota.as_ref() != Some(quota) {
+            self.tokens = capacity;
+            self.quota = Some(quota.clone());
+        } else if now > self.updated {
+            let refilled = (now - self.updated) / quota.interval();
+            self.tokens = (self.tokens + refilled).min(capacity);
+        }
+        self.updated = now;
+    }
+}
+
+impl RateGuard for TokenBucketGuard {
+    type Quota = BurstQuota;
+    async fn verify(&mut self, quota: &Self::Quota) -> bool {
+        self.verify_cost(quota, 1).await
+    }
+
+    async fn verify_cost(&mut self, quota: &Self::Quota, cost: usize) -> bool {
+        self.refill(quota, OffsetDateTime::now_utc());
+        if self.tokens >= cost as f64 {
+            self.tokens -= cost as f64;
+            true
+        } else {
+            false
+        }
+    }
+
+    async fn remaining(&self, _: &Self::Quota) -> usize {
+        self.tokens as usize
+    }
+
+    async fn reset(&self, quota: &Self::Quota) -> i64 {
+        let missing = quota.burst.max(1) as f64 - self.tokens;
+        (self.updated + quota.interval() * missing.max(0.0)).unix_timestamp()
+    }
+
+    async fn limit(&self, quota: &Self::Quota) -> usize {
+        quota.burst
+    }
+
+    async fn window(&self, quota: &Self::Quota) -> Option<i64> {
+        Some((quota.interval() * quota.burst.max(1) as f64).whole_seconds())
+    }
+
+    async fn retry_after(&self, quota: &Self::Quota, cost: usize) -> i64 {
+        let ready = self.updated + quota.interval() * (cost as f64 - self.tokens).max(0.0);
+        (ready - OffsetDateTime::now_utc())
+            .as_seconds_f64()
+            .ceil()
+            .max(0.0) as i64
+    }
+}
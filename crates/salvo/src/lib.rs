@@ -24,13 +24,20 @@
 //! | `eyre` | Integrate with the [`eyre`](https://crates.io/crates/eyre) crate | ❌ |
 //! | `affix-state` | Middleware for adding prefix and suffix to the request path | ❌ |
 //! | `craft` | Generate handlers or endpoints with shared data | ❌ |
+//! | `api-key` | Middleware for API key authentication | ❌ |
 //! | `basic-auth` | Middleware for basic authentication | ❌ |
+//! | `body-capture` | Middleware for capturing request and response bodies | ❌ |
+//! | `digest-auth` | Middleware for HTTP Digest authentication | ❌ |
+//! | `http-signature` | Middleware for verifying HTTP message signatures | ❌ |
+//! | `ip-filter` | Middleware for restricting access by client IP address | ❌ |
 //! | `caching-headers` | Middleware for setting caching headers | ❌ |
 //! | `catch-panic` | Middleware for catching panics | ❌ |
 //! | `concurrency-limiter` | Middleware for limiting concurrency | ❌ |
 //! | `force-https` | Middleware for forcing HTTPS | ❌ |
 //! | `logging` | Middleware for logging requests and responses | ❌ |
+//! | `precondition` | Middleware for conditional requests of unsafe methods | ❌ |
 //! | `request-id` | Middleware for setting a request ID | ❌ |
+//! | `security-headers` | Middleware for setting security related headers | ❌ |
 //! | `size-limiter` | Middleware for limiting request size | ❌ |
 //! | `sse` | Server-Sent Events (SSE) middleware | ❌ |
 //! | `timeout` | Middleware for setting a timeout | ❌ |
@@ -53,16 +60,46 @@ cfg_feature! {
     // #[doc(no_inline)]
     pub use salvo_extra::affix_state;
 }
+cfg_feature! {
+    #![feature ="api-key"]
+    // #[doc(no_inline)]
+    pub use salvo_extra::api_key;
+}
 cfg_feature! {
     #![feature ="basic-auth"]
     // #[doc(no_inline)]
     pub use salvo_extra::basic_auth;
 }
+cfg_feature! {
+    #![feature ="body-capture"]
+    // #[doc(no_inline)]
+    pub use salvo_extra::body_capture;
+}
+cfg_feature! {
+    #![feature ="digest-auth"]
+    // #[doc(no_inline)]
+    pub use salvo_extra::digest_auth;
+}
+cfg_feature! {
+    #![feature ="http-signature"]
+    // #[doc(no_inline)]
+    pub use salvo_extra::http_signature;
+}
+cfg_feature! {
+    #![feature ="ip-filter"]
+    // #[doc(no_inline)]
+    pub use salvo_extra::ip_filter;
+}
 cfg_feature! {
     #![feature ="caching-headers"]
     // #[doc(no_inline)]
     pub use salvo_extra::caching_headers;
 }
+cfg_feature! {
+    #![feature ="precondition"]
+    // #[doc(no_inline)]
+    pub use salvo_extra::precondition;
+}
 cfg_feature! {
     #![feature ="catch-panic"]
     // #[doc(no_inline)]
@@ -113,6 +150,11 @@ cfg_feature! {
     // #[doc(no_inline)]
     pub use salvo_extra::request_id;
 }
+cfg_feature! {
+    #![feature ="security-headers"]
+    // #[doc(no_inline)]
+    pub use salvo_extra::security_headers;
+}
 cfg_feature! {
     #![feature ="cache"]
     #[doc(no_inline)]
@@ -190,9 +232,33 @@ pub mod prelude {
         #![feature ="basic-auth"]
         pub use salvo_extra::basic_auth::{BasicAuth, BasicAuthDepotExt, BasicAuthValidator};
     }
+    cfg_feature! {
+        #![feature ="api-key"]
+        pub use salvo_extra::api_key::{ApiKeyAuth, ApiKeyDepotExt, ApiKeySource, ApiKeyValidator};
+    }
+    cfg_feature! {
+        #![feature ="body-capture"]
+        pub use salvo_extra::body_capture::BodyCapture;
+    }
+    cfg_feature! {
+        #![feature ="digest-auth"]
+        pub use salvo_extra::digest_auth::{DigestAuth, DigestAuthDepotExt, DigestAuthValidator};
+    }
+    cfg_feature! {
+        #![feature ="http-signature"]
+        pub use salvo_extra::http_signature::{HttpSignature, HttpSignatureDepotExt, HttpSignatureKeyResolver};
+    }
+    cfg_feature! {
+        #![feature ="ip-filter"]
+        pub use salvo_extra::ip_filter::{IpFilter, IpFilterDepotExt, IpRules};
+    }
     cfg_feature! {
         #![feature ="caching-headers"]
-        pub use salvo_extra::caching_headers::CachingHeaders;
+        pub use salvo_extra::caching_headers::{CachingHeaders, CachingHeadersDepotExt};
+    }
+    cfg_feature! {
+        #![feature ="precondition"]
+        pub use salvo_extra::precondition::{Preconditions, ValidatorResolver, Validators};
     }
     cfg_feature! {
         #![feature ="catch-panic"]
@@ -227,6 +293,10 @@ pub mod prelude {
         #![feature ="proxy"]
         pub use salvo_proxy::Proxy;
     }
+    cfg_feature! {
+        #![feature ="security-headers"]
+        pub use salvo_extra::security_headers::{SecurityHeaders, SecurityHeadersDepotExt};
+    }
     cfg_feature! {
         #![feature ="session"]
         pub use salvo_session::{SessionDepotExt, SessionHandler, SessionStore};
@@ -249,7 +319,7 @@ pub mod prelude {
     }
     cfg_feature! {
         #![feature ="timeout"]
-        pub use salvo_extra::timeout::Timeout;
+        pub use salvo_extra::timeout::{Timeout, TimeoutDepotExt};
     }
     cfg_feature! {
         #![feature ="tower-compat"]